@@ -0,0 +1,59 @@
+use anyhow::Result;
+use pocketflow_rs::Context;
+use pocketflow_rs::utils::text_chunking::count_tokens;
+use serde::{Deserialize, Serialize};
+
+/// Context key the chat history of a session is stored under.
+pub const CHAT_HISTORY_KEY: &str = "chat_history";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatTurn {
+    /// `user`, `assistant`, or `system` for summaries of earlier turns
+    pub role: String,
+    pub content: String,
+}
+
+impl ChatTurn {
+    pub fn new(role: &str, content: impl Into<String>) -> Self {
+        Self {
+            role: role.to_string(),
+            content: content.into(),
+        }
+    }
+
+    pub fn token_count(&self) -> usize {
+        count_tokens(&self.role) + count_tokens(&self.content)
+    }
+}
+
+/// The turns of a multi-turn chat, oldest first.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChatHistory {
+    pub turns: Vec<ChatTurn>,
+}
+
+impl ChatHistory {
+    pub fn push(&mut self, role: &str, content: impl Into<String>) {
+        self.turns.push(ChatTurn::new(role, content));
+    }
+
+    /// Approximate token count of all turns.
+    pub fn token_count(&self) -> usize {
+        self.turns.iter().map(ChatTurn::token_count).sum()
+    }
+
+    /// The history stored in `context`, or an empty one if there is none.
+    pub fn from_context(context: &Context) -> Result<Self> {
+        match context.get(CHAT_HISTORY_KEY) {
+            Some(value) => Ok(Self::deserialize(value)?),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn to_context(&self, context: &mut Context) {
+        context.set(
+            CHAT_HISTORY_KEY,
+            serde_json::to_value(self).unwrap_or_default(),
+        );
+    }
+}
@@ -1,5 +1,13 @@
+pub mod history;
+pub mod limits;
 pub mod nodes;
+pub mod pipeline;
 pub mod state;
+#[cfg(test)]
+mod test_utils;
 
+pub use history::*;
+pub use limits::*;
 pub use nodes::*;
+pub use pipeline::*;
 pub use state::*;
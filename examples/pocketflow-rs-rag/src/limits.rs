@@ -0,0 +1,50 @@
+use anyhow::Result;
+use tracing::warn;
+
+/// What to do when a [`Limit`] is exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LimitPolicy {
+    /// Fail the node, so nothing downstream (e.g. embedding calls) runs.
+    #[default]
+    Error,
+    /// Log a warning and keep only the first `max` items.
+    Truncate,
+}
+
+/// A safety cap on the number of items a node will pass downstream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limit {
+    pub max: usize,
+    pub policy: LimitPolicy,
+}
+
+impl Limit {
+    pub fn new(max: usize, policy: LimitPolicy) -> Self {
+        Self { max, policy }
+    }
+
+    pub fn apply<T>(&self, mut items: Vec<T>, what: &str) -> Result<Vec<T>> {
+        if items.len() <= self.max {
+            return Ok(items);
+        }
+
+        match self.policy {
+            LimitPolicy::Error => Err(anyhow::anyhow!(
+                "Too many {}: {} exceeds the limit of {}",
+                what,
+                items.len(),
+                self.max
+            )),
+            LimitPolicy::Truncate => {
+                warn!(
+                    "Too many {}: {} exceeds the limit of {}, truncating",
+                    what,
+                    items.len(),
+                    self.max
+                );
+                items.truncate(self.max);
+                Ok(items)
+            }
+        }
+    }
+}
@@ -1,16 +1,20 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
-use pocketflow_rs::utils::{text_chunking::ChunkingStrategy, vector_db::DistanceMetric};
-use pocketflow_rs::{Context as FlowContext, build_flow};
+use clap::{Parser, Subcommand, ValueEnum};
+#[cfg(feature = "local-embeddings")]
+use pocketflow_rs::utils::embedding::FastEmbedGenerator;
+use pocketflow_rs::utils::{
+    content_fetcher::FetchOptions,
+    embedding::{EmbeddingGenerator, EmbeddingOptions, OpenAIEmbeddingGenerator},
+    llm_wrapper::OpenAIClient,
+    prompt_template::PromptTemplate,
+    vector_db::{DistanceMetric, QdrantDB, VectorDBOptions},
+};
+use pocketflow_rs::{Context as FlowContext, ProgressReporter, build_flow};
 use pocketflow_rs_rag::{
-    QueryRewriteNode,
-    nodes::{
-        ChunkDocumentsNode, CreateIndexNode, EmbedDocumentsNode, EmbedQueryNode, FileLoaderNode,
-        GenerateAnswerNode, RetrieveDocumentNode,
-    },
-    state::RagState,
+    LimitPolicy, OfflineConfig, OnlineConfig, nodes::ReembedCollectionNode, rag_offline, rag_online,
 };
-use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
 use tracing::Level;
 use tracing_subscriber::FmtSubscriber;
 
@@ -21,6 +25,58 @@ struct Cli {
     command: Commands,
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum EmbeddingProvider {
+    /// The OpenAI embeddings API, needs an API key
+    Openai,
+    /// A fastembed model run offline, needs the `local-embeddings` feature
+    Local,
+}
+
+/// The embedder for `provider` and the dimension of its embeddings. `model` and
+/// `dimension` default to the provider's own.
+fn embedder(
+    provider: EmbeddingProvider,
+    api_key: Option<&str>,
+    endpoint: &str,
+    model: Option<String>,
+    dimension: Option<usize>,
+) -> Result<(Arc<dyn EmbeddingGenerator>, usize)> {
+    match provider {
+        EmbeddingProvider::Openai => {
+            let api_key = api_key.ok_or_else(|| {
+                anyhow::anyhow!("--api-key is required for the openai embedding provider")
+            })?;
+            let dimension = dimension.unwrap_or(1024);
+            let generator = OpenAIEmbeddingGenerator::new(
+                api_key,
+                endpoint,
+                EmbeddingOptions {
+                    model: model.unwrap_or_else(|| "text-embedding-ada-002".to_string()),
+                    dimensions: Some(dimension),
+                },
+            );
+            Ok((Arc::new(generator), dimension))
+        }
+        #[cfg(feature = "local-embeddings")]
+        EmbeddingProvider::Local => {
+            let generator = FastEmbedGenerator::new(EmbeddingOptions {
+                model: model.unwrap_or_else(|| FastEmbedGenerator::DEFAULT_MODEL.to_string()),
+                dimensions: dimension,
+            })?;
+            let dimension = generator.dimension();
+            Ok((Arc::new(generator), dimension))
+        }
+        #[cfg(not(feature = "local-embeddings"))]
+        EmbeddingProvider::Local => {
+            let _ = (model, dimension);
+            Err(anyhow::anyhow!(
+                "The local embedding provider needs the example built with --features local-embeddings"
+            ))
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Process documents offline
@@ -33,9 +89,9 @@ enum Commands {
         #[arg(long, default_value = "documents")]
         collection: String,
 
-        /// OpenAI API key
+        /// OpenAI API key, required for the openai embedding provider
         #[arg(long)]
-        api_key: String,
+        api_key: Option<String>,
 
         /// Qdrant API key
         #[arg(long)]
@@ -45,6 +101,10 @@ enum Commands {
         #[arg(short, long, default_value = "https://api.openai.com/v1")]
         endpoint: String,
 
+        /// Where document embeddings are computed
+        #[arg(long, value_enum, default_value = "openai")]
+        embedding_provider: EmbeddingProvider,
+
         /// Chunk size for document splitting
         #[arg(long, default_value = "1000")]
         chunk_size: usize,
@@ -53,12 +113,54 @@ enum Commands {
         #[arg(long, default_value = "200")]
         overlap: usize,
 
-        /// OpenAI model to use
-        #[arg(long, default_value = "text-embedding-ada-002")]
-        model: String,
+        /// Embedding model, text-embedding-ada-002 for openai and BGESmallENV15Q for
+        /// local by default
+        #[arg(long)]
+        model: Option<String>,
 
-        #[arg(long, default_value = "1024")]
-        dimension: usize,
+        /// Embedding dimension, 1024 for openai and the model's own for local by default
+        #[arg(long)]
+        dimension: Option<usize>,
+
+        /// Maximum number of documents to load
+        #[arg(long)]
+        max_documents: Option<usize>,
+
+        /// Maximum number of chunks to embed
+        #[arg(long)]
+        max_chunks: Option<usize>,
+
+        /// Truncate with a warning instead of failing when a limit is exceeded
+        #[arg(long)]
+        truncate_on_limit: bool,
+
+        /// Store the full text of documents up to this many bytes alongside their chunks
+        #[arg(long)]
+        max_document_text: Option<usize>,
+
+        /// User-Agent sent when fetching web urls
+        #[arg(long, default_value = "pocketflow_rs")]
+        user_agent: String,
+
+        /// Minimum delay in milliseconds between requests to the same host
+        #[arg(long, default_value = "1000")]
+        per_host_delay_ms: u64,
+
+        /// Also build a BM25 keyword index over the chunks and save it to this path
+        #[arg(long)]
+        bm25_index: Option<String>,
+
+        /// Fetch web urls even if robots.txt disallows them
+        #[arg(long)]
+        ignore_robots_txt: bool,
+
+        /// Keep going with the documents that loaded when some files fail
+        #[arg(long)]
+        skip_failed_files: bool,
+
+        /// Print the progress of each indexing stage to stderr
+        #[arg(long)]
+        progress: bool,
 
         /// Paths to document files
         #[arg(required = true)]
@@ -86,26 +188,100 @@ enum Commands {
         #[arg(short, long, default_value = "3")]
         k: usize,
 
+        /// Drop retrieved documents with a lower similarity score
+        #[arg(long)]
+        min_score: Option<f32>,
+
+        /// Combine vector and keyword search, weighting the vector ranking by this
+        /// value between 0 and 1
+        #[arg(long)]
+        hybrid_alpha: Option<f32>,
+
+        /// Drop retrieved documents duplicating a better ranked one, by text or by
+        /// an embedding cosine similarity of at least this value
+        #[arg(long)]
+        dedup_threshold: Option<f32>,
+
+        /// File with a query rewrite prompt, using {{query}}
+        #[arg(long)]
+        rewrite_prompt: Option<String>,
+
+        /// File with an answer prompt, using {{sources}} and {{question}}
+        #[arg(long)]
+        answer_prompt: Option<String>,
+
         /// chat mode
         #[arg(long, default_value = "chat")]
         chat_mode: String,
 
-        /// embedding dimension
-        #[arg(long, default_value = "1024")]
-        dimension: usize,
+        /// Embedding dimension, 1024 for openai and the model's own for local by default
+        #[arg(long)]
+        dimension: Option<usize>,
 
         /// Qdrant API key
         #[arg(long)]
         qdrant_api_key: Option<String>,
 
-        /// Embedding model
-        #[arg(long, default_value = "text-embedding-ada-002")]
-        embedding_model: String,
+        /// Where query embeddings are computed, as when the documents were indexed
+        #[arg(long, value_enum, default_value = "openai")]
+        embedding_provider: EmbeddingProvider,
+
+        /// Embedding model, text-embedding-ada-002 for openai and BGESmallENV15Q for
+        /// local by default
+        #[arg(long)]
+        embedding_model: Option<String>,
+
+        /// Answer with the retrieved passages if no answer is generated within this
+        /// many milliseconds
+        #[arg(long)]
+        deadline_ms: Option<u64>,
+
+        /// Times to run the flow while no answer is generated before the deadline
+        #[arg(long, default_value = "1")]
+        attempts: usize,
 
         /// Question to answer
         #[arg(required = true)]
         query: String,
     },
+    /// Re-embed an indexed collection with a new embedding model
+    Reembed {
+        /// Qdrant database URL
+        #[arg(long, default_value = "http://localhost:6333")]
+        db_url: String,
+
+        /// Qdrant API key
+        #[arg(long)]
+        qdrant_api_key: Option<String>,
+
+        /// Collection to read chunks from
+        #[arg(long, default_value = "documents")]
+        source_collection: String,
+
+        /// Collection to write re-embedded chunks to
+        #[arg(long)]
+        target_collection: String,
+
+        /// Embedding dimension of the source collection
+        #[arg(long, default_value = "1024")]
+        source_dimension: usize,
+
+        /// OpenAI API key
+        #[arg(long)]
+        api_key: String,
+
+        /// OpenAI API endpoint
+        #[arg(long, default_value = "https://api.openai.com/v1")]
+        endpoint: String,
+
+        /// New embedding model
+        #[arg(long)]
+        model: String,
+
+        /// New embedding dimension
+        #[arg(long)]
+        dimension: usize,
+    },
 }
 
 #[tokio::main]
@@ -121,44 +297,62 @@ async fn main() -> Result<()> {
             api_key,
             qdrant_api_key,
             endpoint,
+            embedding_provider,
             chunk_size,
             overlap,
             model,
             dimension,
+            max_documents,
+            max_chunks,
+            truncate_on_limit,
+            max_document_text,
+            user_agent,
+            per_host_delay_ms,
+            ignore_robots_txt,
+            skip_failed_files,
+            bm25_index,
+            progress,
         } => {
-            let file_loader = FileLoaderNode::new(files);
-            let chunk_documents =
-                ChunkDocumentsNode::new(chunk_size, overlap, ChunkingStrategy::Sentence);
-            let embed_documents = EmbedDocumentsNode::new(
-                api_key.clone(),
-                endpoint.clone(),
-                model.clone(),
-                Some(dimension),
-            );
-            let create_index = CreateIndexNode::new(
+            let (embedder, dimension) = embedder(
+                embedding_provider,
+                api_key.as_deref(),
+                &endpoint,
+                model,
+                dimension,
+            )?;
+            let db = QdrantDB::new(
                 db_url,
                 qdrant_api_key,
-                collection,
-                dimension,
-                DistanceMetric::Cosine,
+                VectorDBOptions {
+                    collection_name: collection,
+                    dimension,
+                    distance_metric: DistanceMetric::Cosine,
+                    payload_indexes: Vec::new(),
+                    sparse_vector_name: None,
+                },
             )
             .await?;
 
-            let flow = build_flow!(
-                start: ("file_loader", file_loader),
-                nodes: [
-                    ("chunk_documents", chunk_documents),
-                    ("embed_documents", embed_documents),
-                    ("create_index", create_index)
-                ],
-                edges: [
-                    ("file_loader", "chunk_documents", RagState::Default),
-                    ("chunk_documents", "embed_documents", RagState::Default),
-                    ("embed_documents", "create_index", RagState::Default)
-                ]
-            );
-
-            flow.run(FlowContext::new()).await?;
+            let mut config = OfflineConfig::new(files, embedder, Arc::new(db));
+            config.chunk_size = chunk_size;
+            config.overlap = overlap;
+            config.max_documents = max_documents;
+            config.max_chunks = max_chunks;
+            if truncate_on_limit {
+                config.limit_policy = LimitPolicy::Truncate;
+            }
+            config.max_document_text = max_document_text;
+            config.fetch_options = FetchOptions {
+                user_agent,
+                per_host_delay: Duration::from_millis(per_host_delay_ms),
+                respect_robots_txt: !ignore_robots_txt,
+            };
+            config.skip_failed_files = skip_failed_files;
+            config.bm25_index = bm25_index;
+            if progress {
+                config.progress = ProgressReporter::new(|event| eprintln!("{}", event));
+            }
+            rag_offline(config).await?;
         }
         Commands::Online {
             query,
@@ -167,55 +361,107 @@ async fn main() -> Result<()> {
             api_key,
             endpoint,
             k,
+            min_score,
+            hybrid_alpha,
+            dedup_threshold,
+            rewrite_prompt,
+            answer_prompt,
             chat_mode,
             dimension,
             qdrant_api_key,
+            embedding_provider,
             embedding_model,
+            deadline_ms,
+            attempts,
         } => {
-            let mut context = FlowContext::new();
-            context.set("user_query", json!(query.clone()));
-
-            let query_rewrite_node =
-                QueryRewriteNode::new(api_key.clone(), chat_mode.clone(), endpoint.clone());
-
-            let embed_query_node = EmbedQueryNode::new(
+            let llm = Arc::new(OpenAIClient::new(
                 api_key.clone(),
+                chat_mode,
                 endpoint.clone(),
-                embedding_model.clone(),
-                Some(dimension),
-            );
-
-            let retrieve_node = RetrieveDocumentNode::new(
+            ));
+            let (embedder, dimension) = embedder(
+                embedding_provider,
+                Some(&api_key),
+                &endpoint,
+                embedding_model,
+                dimension,
+            )?;
+            let db = QdrantDB::new(
                 db_url,
                 qdrant_api_key,
-                collection,
-                dimension,
-                DistanceMetric::Cosine,
-                k,
+                VectorDBOptions {
+                    collection_name: collection,
+                    dimension,
+                    distance_metric: DistanceMetric::Cosine,
+                    payload_indexes: Vec::new(),
+                    sparse_vector_name: None,
+                },
             )
             .await?;
 
-            let generate_node = GenerateAnswerNode::new(api_key, chat_mode, endpoint, query);
-
-            // Build and execute online flow
-            let flow = build_flow!(
-                start: ("query_rewrite", query_rewrite_node),
-                nodes: [
-                    ("embed_query", embed_query_node),
-                    ("retrieve", retrieve_node),
-                    ("generate", generate_node)
-                ],
-                edges: [
-                    ("query_rewrite", "embed_query", RagState::Default),
-                    ("embed_query", "retrieve", RagState::Default),
-                    ("retrieve", "generate", RagState::Default)
-                ]
-            );
-
-            let result = flow.run(context).await?;
+            let mut config = OnlineConfig::new(query, llm.clone(), embedder, Arc::new(db));
+            config.k = k;
+            config.min_score = min_score;
+            config.hybrid_alpha = hybrid_alpha;
+            config.dedup_threshold = dedup_threshold;
+            config.rewrite_prompt = rewrite_prompt.map(PromptTemplate::from_file).transpose()?;
+            config.answer_prompt = answer_prompt.map(PromptTemplate::from_file).transpose()?;
+            config.streaming_llm = Some(llm);
+            config.deadline = deadline_ms.map(Duration::from_millis);
+            config.attempts = attempts;
+            let result = rag_online(config).await?;
 
             termimad::print_text(result.as_str().unwrap());
         }
+        Commands::Reembed {
+            db_url,
+            qdrant_api_key,
+            source_collection,
+            target_collection,
+            source_dimension,
+            api_key,
+            endpoint,
+            model,
+            dimension,
+        } => {
+            let source = QdrantDB::new(
+                db_url.clone(),
+                qdrant_api_key.clone(),
+                VectorDBOptions {
+                    collection_name: source_collection,
+                    dimension: source_dimension,
+                    distance_metric: DistanceMetric::Cosine,
+                    payload_indexes: Vec::new(),
+                    sparse_vector_name: None,
+                },
+            )
+            .await?;
+            let target = QdrantDB::new(
+                db_url,
+                qdrant_api_key,
+                VectorDBOptions {
+                    collection_name: target_collection,
+                    dimension,
+                    distance_metric: DistanceMetric::Cosine,
+                    payload_indexes: Vec::new(),
+                    sparse_vector_name: None,
+                },
+            )
+            .await?;
+            let generator = OpenAIEmbeddingGenerator::new(
+                &api_key,
+                &endpoint,
+                EmbeddingOptions {
+                    model,
+                    dimensions: Some(dimension),
+                },
+            );
+
+            let reembed =
+                ReembedCollectionNode::new(Arc::new(source), Arc::new(target), Arc::new(generator));
+            let flow = build_flow!(start: ("reembed", reembed));
+            flow.run(FlowContext::new()).await?;
+        }
     }
 
     Ok(())
@@ -0,0 +1,187 @@
+use crate::state::RagState;
+use anyhow::Result;
+use async_trait::async_trait;
+use pocketflow_rs::utils::llm_wrapper::{LLMOptions, LLMWrapper};
+use pocketflow_rs::{Context, Node, ProcessResult};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use tracing::info;
+
+const DEFAULT_REFUSAL_PHRASES: &[&str] = &[
+    "i don't know",
+    "i do not know",
+    "i'm not sure",
+    "i am not sure",
+    "cannot be found in the context",
+    "not mentioned in the context",
+    "no information",
+    "unable to answer",
+];
+
+/// Checks the generated answer in `result` and routes to [`RagState::NoAnswer`] when
+/// it is a refusal, so the flow can fall back to e.g. web search. Answers are matched
+/// case-insensitively against refusal phrases and, if set, judged by an LLM.
+pub struct AnswerQualityNode {
+    phrases: Vec<String>,
+    judge: Option<Arc<dyn LLMWrapper>>,
+}
+
+impl Default for AnswerQualityNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AnswerQualityNode {
+    pub fn new() -> Self {
+        Self {
+            phrases: DEFAULT_REFUSAL_PHRASES
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+            judge: None,
+        }
+    }
+
+    /// Replace the default refusal phrases.
+    pub fn with_phrases(mut self, phrases: Vec<String>) -> Self {
+        self.phrases = phrases.into_iter().map(|p| p.to_lowercase()).collect();
+        self
+    }
+
+    /// Also ask `judge` whether answers that pass the phrase check answer the question.
+    pub fn with_judge(mut self, judge: Arc<dyn LLMWrapper>) -> Self {
+        self.judge = Some(judge);
+        self
+    }
+
+    fn matched_phrase(&self, answer: &str) -> Option<&str> {
+        let answer = answer.to_lowercase().replace('’', "'");
+        self.phrases
+            .iter()
+            .find(|phrase| answer.contains(phrase.as_str()))
+            .map(|phrase| phrase.as_str())
+    }
+
+    async fn judge_answers(
+        &self,
+        judge: &dyn LLMWrapper,
+        question: &str,
+        answer: &str,
+    ) -> Result<bool> {
+        let prompt = format!(
+            "Does the answer below actually answer the question, rather than refusing or \
+             saying the information is unavailable? Reply with only YES or NO.\n\n\
+             Question: {}\n\nAnswer: {}",
+            question, answer
+        );
+        let options = LLMOptions::deterministic();
+        let response = judge.generate_with_options(&prompt, options).await?;
+        Ok(!response.content.trim().to_uppercase().starts_with("NO"))
+    }
+}
+
+#[async_trait]
+impl Node for AnswerQualityNode {
+    type State = RagState;
+
+    fn name(&self) -> &str {
+        "AnswerQuality"
+    }
+
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let answer = context.get_str("result")?;
+
+        if answer.trim().is_empty() {
+            return Ok(json!({"answered": false, "reason": "empty answer"}));
+        }
+        if let Some(phrase) = self.matched_phrase(answer) {
+            return Ok(json!({"answered": false, "reason": format!("matched '{}'", phrase)}));
+        }
+        if let Some(judge) = &self.judge {
+            let question = context
+                .get("user_query")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default();
+            if !self.judge_answers(judge.as_ref(), question, answer).await? {
+                return Ok(json!({"answered": false, "reason": "rejected by judge"}));
+            }
+        }
+        Ok(json!({"answered": true, "reason": null}))
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<RagState>> {
+        match result {
+            Ok(value) => {
+                context.set("answer_quality", value.clone());
+                if value["answered"].as_bool() == Some(true) {
+                    Ok(ProcessResult::new(
+                        RagState::Default,
+                        "answered".to_string(),
+                    ))
+                } else {
+                    info!("No answer: {}", value["reason"]);
+                    Ok(ProcessResult::new(
+                        RagState::NoAnswer,
+                        "no_answer".to_string(),
+                    ))
+                }
+            }
+            Err(e) => Ok(ProcessResult::new(
+                RagState::GenerationError,
+                format!("answer_quality_error: {}", e),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::CannedLLM;
+
+    async fn route(node: &AnswerQualityNode, answer: &str) -> RagState {
+        let mut context = Context::new();
+        context.set("user_query", json!("What is Pangu?"));
+        context.set("result", json!(answer));
+        let result = node.execute(&context).await;
+        node.post_process(&mut context, &result)
+            .await
+            .unwrap()
+            .state
+    }
+
+    #[tokio::test]
+    async fn test_refusal_routes_to_no_answer() {
+        let node = AnswerQualityNode::new();
+
+        assert_eq!(route(&node, "I don't know.").await, RagState::NoAnswer);
+        assert_eq!(
+            route(&node, "Sorry, I Don’t Know the answer to that.").await,
+            RagState::NoAnswer
+        );
+        assert_eq!(
+            route(&node, "Pangu is Alibaba's distributed storage system.").await,
+            RagState::Default
+        );
+    }
+
+    #[tokio::test]
+    async fn test_judge_rejects_evasive_answer() {
+        let node = AnswerQualityNode::new().with_judge(Arc::new(CannedLLM::new("NO")));
+        assert_eq!(
+            route(&node, "That is an interesting question about storage.").await,
+            RagState::NoAnswer
+        );
+
+        let node = AnswerQualityNode::new().with_judge(Arc::new(CannedLLM::new("YES")));
+        assert_eq!(
+            route(&node, "Pangu is Alibaba's distributed storage system.").await,
+            RagState::Default
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use crate::state::RagState;
+use anyhow::Result;
+use async_trait::async_trait;
+use pocketflow_rs::utils::bm25::Bm25Index;
+use pocketflow_rs::{Context, Node, ProcessResult};
+use serde_json::Value;
+use std::path::PathBuf;
+use tracing::info;
+
+/// Builds a BM25 keyword index over the chunks in `documents_chunked`, keyed by
+/// chunk id so keyword hits line up with the vector records. The index is stored
+/// in the context as `bm25_index` and, with a path set, saved next to the vectors.
+pub struct BuildBm25IndexNode {
+    path: Option<PathBuf>,
+}
+
+impl BuildBm25IndexNode {
+    pub fn new() -> Self {
+        Self { path: None }
+    }
+
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+}
+
+impl Default for BuildBm25IndexNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Node for BuildBm25IndexNode {
+    type State = RagState;
+
+    fn name(&self) -> &str {
+        "BuildBm25Index"
+    }
+
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let chunks = context.get_array("documents_chunked")?;
+
+        let mut index = Bm25Index::new();
+        for chunk in chunks {
+            let id = chunk
+                .get("id")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| anyhow::anyhow!("No id found in chunk"))?;
+            let text = chunk
+                .get("text")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| anyhow::anyhow!("No text found in chunk"))?;
+            index.add_document(id, text);
+        }
+        info!("Built BM25 index over {} chunks", index.len());
+
+        if let Some(path) = &self.path {
+            index.save(path)?;
+            info!("Saved BM25 index to {:?}", path);
+        }
+
+        Ok(serde_json::to_value(&index)?)
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<RagState>> {
+        match result {
+            Ok(value) => {
+                context.set("bm25_index", value.clone());
+                Ok(ProcessResult::new(
+                    RagState::Default,
+                    "bm25_index_built".to_string(),
+                ))
+            }
+            Err(e) => Ok(ProcessResult::new(
+                RagState::IndexCreationError,
+                format!("index_creation_error: {}", e),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_build_and_persist_bm25_index() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("bm25.json");
+        let mut context = Context::new();
+        context.set(
+            "documents_chunked",
+            json!([
+                {"id": "chunk-0", "text": "Pangu is a distributed storage system"},
+                {"id": "chunk-1", "text": "The storage system is fast"},
+                {"id": "chunk-2", "text": "The weather is nice"}
+            ]),
+        );
+
+        let node = BuildBm25IndexNode::new().with_path(&path);
+        let result = node.execute(&context).await;
+        node.post_process(&mut context, &result).await.unwrap();
+
+        let index: Bm25Index =
+            serde_json::from_value(context.get("bm25_index").unwrap().clone()).unwrap();
+        assert_eq!(index.search("pangu storage", 1)[0].0, "chunk-0");
+
+        let loaded = Bm25Index::load(&path).unwrap();
+        assert_eq!(loaded.len(), 3);
+    }
+}
@@ -1,14 +1,22 @@
+use crate::limits::Limit;
 use crate::state::RagState;
 use anyhow::Result;
 use async_trait::async_trait;
-use pocketflow_rs::utils::text_chunking::{ChunkingOptions, ChunkingStrategy, TextChunker};
-use pocketflow_rs::{Context, Node, ProcessResult};
+use pocketflow_rs::utils::text_chunking::{
+    ChunkingOptions, ChunkingStrategy, TextChunker, count_tokens,
+};
+use pocketflow_rs::{Context, Document, Node, ProcessResult, ProgressReporter};
 use serde_json::{Value, json};
-use tracing::info;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+use uuid::Uuid;
 
 pub struct ChunkDocumentsNode {
     chunker: TextChunker,
     options: ChunkingOptions,
+    max_chunks: Option<Limit>,
+    enrich: bool,
+    progress: ProgressReporter,
 }
 
 impl ChunkDocumentsNode {
@@ -19,40 +27,101 @@ impl ChunkDocumentsNode {
                 chunk_size,
                 overlap,
                 strategy,
+                ..ChunkingOptions::default()
             },
+            max_chunks: None,
+            enrich: false,
+            progress: ProgressReporter::default(),
         }
     }
+
+    pub fn with_max_chunks(mut self, limit: Limit) -> Self {
+        self.max_chunks = Some(limit);
+        self
+    }
+
+    /// Sentence separators, e.g. [`SeparatorSet::cjk`](pocketflow_rs::utils::text_chunking::SeparatorSet::cjk)
+    /// for Chinese or Japanese text.
+    pub fn with_separators(mut self, separators: Vec<String>) -> Self {
+        self.options.separators = separators;
+        self
+    }
+
+    /// Add `char_count`, `token_count` and a SHA-256 content `hash` to each chunk record.
+    pub fn with_enrichment(mut self) -> Self {
+        self.enrich = true;
+        self
+    }
+
+    /// Report the chunks produced so far as `chunks` progress, once per document.
+    pub fn with_progress(mut self, progress: ProgressReporter) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Derive a chunk id from its source document and position, so re-chunking the
+    /// same document yields the same ids and re-indexing upserts instead of duplicating.
+    fn chunk_id(source: &str, chunk_index: usize) -> String {
+        Uuid::new_v5(
+            &Uuid::NAMESPACE_URL,
+            format!("{}#{}", source, chunk_index).as_bytes(),
+        )
+        .to_string()
+    }
 }
 
 #[async_trait]
 impl Node for ChunkDocumentsNode {
     type State = RagState;
 
+    fn name(&self) -> &str {
+        "ChunkDocuments"
+    }
+
     async fn execute(&self, context: &Context) -> Result<Value> {
-        let documents = context
-            .get("documents")
-            .and_then(|v| v.as_array())
-            .ok_or_else(|| anyhow::anyhow!("No documents found in context"))?;
-
-        let mut chunks_meta = Vec::new();
-        for doc_map in documents {
-            let content = doc_map
-                .get("content")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| anyhow::anyhow!("No content found in document"))?;
-            let chunks = self.chunker.chunk_text(content, &self.options);
-            info!(
-                "Process: {:?}, Chunks lens: {:?}",
-                doc_map.get("metadata").unwrap(),
-                chunks.len()
-            );
-            chunks_meta.push(json!({
-                "chunks": chunks,
-                "metadata": doc_map.get("metadata").unwrap_or(&Value::Null),
-            }));
+        let documents = Document::list_from_context(context, "documents")?;
+
+        let mut chunk_records = Vec::new();
+        for (doc_index, doc) in documents.iter().enumerate() {
+            let metadata = &doc.metadata;
+            let mut source = doc
+                .url()
+                .map(|url| url.to_string())
+                .unwrap_or_else(|| format!("document-{}", doc_index));
+            // Documents loaded per CSV row share their file's url
+            if let Some(row) = metadata.get("row").and_then(Value::as_u64) {
+                source = format!("{}#row={}", source, row);
+            }
+
+            let chunks = self.chunker.chunk_text(&doc.content, &self.options);
+            if chunks.is_empty() {
+                warn!("Skipping document '{}': no chunks produced", source);
+                continue;
+            }
+            info!("Process: {:?}, Chunks lens: {:?}", metadata, chunks.len());
+            for (chunk_index, text) in chunks.into_iter().enumerate() {
+                let mut record = json!({
+                    "id": Self::chunk_id(&source, chunk_index),
+                    "text": text,
+                    "chunk_index": chunk_index,
+                    "metadata": metadata,
+                });
+                if self.enrich {
+                    record["char_count"] = json!(text.chars().count());
+                    record["token_count"] = json!(count_tokens(&text));
+                    record["hash"] = json!(format!("{:x}", Sha256::digest(text.as_bytes())));
+                }
+                chunk_records.push(record);
+            }
+            self.progress
+                .report(self.name(), "chunks", chunk_records.len(), None);
         }
 
-        Ok(Value::Array(chunks_meta))
+        if let Some(limit) = &self.max_chunks {
+            chunk_records = limit.apply(chunk_records, "chunks")?;
+        }
+
+        Ok(Value::Array(chunk_records))
     }
 
     async fn post_process(
@@ -75,3 +144,140 @@ impl Node for ChunkDocumentsNode {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::LimitPolicy;
+
+    fn context_with_documents() -> Context {
+        let mut context = Context::new();
+        context.set(
+            "documents",
+            json!([
+                {
+                    "content": "First sentence. Second sentence. Third sentence.",
+                    "metadata": {"url": "docs/a.txt"}
+                },
+                {
+                    "content": "Another document. With two sentences.",
+                    "metadata": {"url": "docs/b.txt"}
+                }
+            ]),
+        );
+        context
+    }
+
+    #[tokio::test]
+    async fn test_chunk_ids_are_stable() {
+        let node = ChunkDocumentsNode::new(20, 0, ChunkingStrategy::Sentence);
+
+        let first = node.execute(&context_with_documents()).await.unwrap();
+        let second = node.execute(&context_with_documents()).await.unwrap();
+
+        let ids = |value: &Value| -> Vec<String> {
+            value
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|chunk| chunk["id"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(ids(&first), ids(&second));
+
+        let mut unique = ids(&first);
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), first.as_array().unwrap().len());
+
+        let chunk = &first.as_array().unwrap()[1];
+        assert_eq!(chunk["chunk_index"], json!(1));
+        assert_eq!(chunk["metadata"]["url"], json!("docs/a.txt"));
+        assert!(chunk["text"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_csv_rows_get_distinct_chunk_ids() {
+        let node = ChunkDocumentsNode::new(100, 0, ChunkingStrategy::Sentence);
+        let mut context = Context::new();
+        context.set(
+            "documents",
+            json!([
+                {"content": "name: Ada", "metadata": {"url": "people.csv", "row": 0}},
+                {"content": "name: Linus", "metadata": {"url": "people.csv", "row": 1}}
+            ]),
+        );
+
+        let chunks = node.execute(&context).await.unwrap();
+
+        let chunks = chunks.as_array().unwrap();
+        assert_ne!(chunks[0]["id"], chunks[1]["id"]);
+        assert_eq!(chunks[1]["metadata"]["url"], json!("people.csv"));
+    }
+
+    #[tokio::test]
+    async fn test_max_chunks() {
+        // Five chunks in total: three from the first document, two from the second
+        let error_node = ChunkDocumentsNode::new(20, 0, ChunkingStrategy::Sentence)
+            .with_max_chunks(Limit::new(4, LimitPolicy::Error));
+        let result = error_node.execute(&context_with_documents()).await;
+        assert!(result.unwrap_err().to_string().contains("Too many chunks"));
+
+        let truncate_node = ChunkDocumentsNode::new(20, 0, ChunkingStrategy::Sentence)
+            .with_max_chunks(Limit::new(4, LimitPolicy::Truncate));
+        let result = truncate_node
+            .execute(&context_with_documents())
+            .await
+            .unwrap();
+        assert_eq!(result.as_array().unwrap().len(), 4);
+
+        let inert_node = ChunkDocumentsNode::new(20, 0, ChunkingStrategy::Sentence)
+            .with_max_chunks(Limit::new(5, LimitPolicy::Error));
+        let result = inert_node.execute(&context_with_documents()).await.unwrap();
+        assert_eq!(result.as_array().unwrap().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_enrichment() {
+        let plain = ChunkDocumentsNode::new(20, 0, ChunkingStrategy::Sentence);
+        let result = plain.execute(&context_with_documents()).await.unwrap();
+        assert!(result[0].get("hash").is_none());
+
+        let node = ChunkDocumentsNode::new(20, 0, ChunkingStrategy::Sentence).with_enrichment();
+        let result = node.execute(&context_with_documents()).await.unwrap();
+        let chunks = result.as_array().unwrap();
+
+        let chunk = &chunks[0];
+        assert_eq!(chunk["text"], json!("First sentence"));
+        assert_eq!(chunk["char_count"], json!(14));
+        assert_eq!(chunk["token_count"], json!(2));
+        assert_eq!(
+            chunk["hash"],
+            json!("fa66b2c06fe7809e2440611558b59fc6290bd116f5887aa55c2b5c7d3b92f0b0")
+        );
+        for chunk in chunks {
+            assert_eq!(chunk["hash"].as_str().unwrap().len(), 64);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_empty_documents_are_skipped() {
+        let mut context = Context::new();
+        context.set(
+            "documents",
+            json!([
+                {"content": "", "metadata": {"url": "docs/empty.txt"}},
+                {"content": "  \n\t ", "metadata": {"url": "docs/blank.txt"}},
+                {"content": "Hello", "metadata": {"url": "docs/hello.txt"}}
+            ]),
+        );
+
+        let node = ChunkDocumentsNode::new(20, 0, ChunkingStrategy::FixedSize);
+        let result = node.execute(&context).await.unwrap();
+        let chunks = result.as_array().unwrap();
+
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0]["text"], json!("Hello"));
+        assert_eq!(chunks[0]["metadata"]["url"], json!("docs/hello.txt"));
+    }
+}
@@ -0,0 +1,251 @@
+use super::dedup_chunks::cosine_similarity;
+use crate::state::RagState;
+use anyhow::Result;
+use async_trait::async_trait;
+use pocketflow_rs::utils::embedding::EmbeddingGenerator;
+use pocketflow_rs::utils::text_chunking::TextChunker;
+use pocketflow_rs::{Context, Node, ProcessResult};
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::info;
+
+const DEFAULT_MAX_SENTENCES: usize = 3;
+
+/// Trims each of the `retrieved_documents` down to the `max_sentences` sentences
+/// most similar to `query_embedding`, kept in their original order, so the answer
+/// prompt only carries the parts of a chunk relevant to the query. Chunks with no
+/// more sentences than that are left as they are.
+pub struct ContextCompressionNode {
+    generator: Arc<dyn EmbeddingGenerator>,
+    max_sentences: usize,
+    separators: Vec<String>,
+    chunker: TextChunker,
+}
+
+impl ContextCompressionNode {
+    pub fn new(generator: Arc<dyn EmbeddingGenerator>) -> Self {
+        Self {
+            generator,
+            max_sentences: DEFAULT_MAX_SENTENCES,
+            separators: Vec::new(),
+            chunker: TextChunker::new(),
+        }
+    }
+
+    pub fn with_max_sentences(mut self, max_sentences: usize) -> Self {
+        self.max_sentences = max_sentences.max(1);
+        self
+    }
+
+    /// Sentence separators, see `SeparatorSet`; English by default.
+    pub fn with_separators(mut self, separators: Vec<String>) -> Self {
+        self.separators = separators;
+        self
+    }
+
+    fn text(document: &Value) -> &str {
+        document["metadata"]["text"].as_str().unwrap_or("")
+    }
+}
+
+#[async_trait]
+impl Node for ContextCompressionNode {
+    type State = RagState;
+
+    fn name(&self) -> &str {
+        "ContextCompression"
+    }
+
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let mut documents = context.get_array("retrieved_documents")?.clone();
+        let query_embedding: Vec<f32> = context.get_as("query_embedding")?;
+
+        // Embed the sentences of every chunk that needs trimming in one batch
+        let sentences: Vec<Vec<String>> = documents
+            .iter()
+            .map(|document| {
+                self.chunker
+                    .split_sentences(Self::text(document), &self.separators)
+            })
+            .collect();
+        let to_score: Vec<String> = sentences
+            .iter()
+            .filter(|sentences| sentences.len() > self.max_sentences)
+            .flatten()
+            .cloned()
+            .collect();
+        if to_score.is_empty() {
+            return Ok(Value::Array(documents));
+        }
+        let embeddings = self.generator.generate_embeddings(&to_score).await?;
+        if embeddings.len() != to_score.len() {
+            return Err(anyhow::anyhow!(
+                "Expected {} embeddings, got {}",
+                to_score.len(),
+                embeddings.len()
+            ));
+        }
+        let mut embeddings = embeddings.into_iter();
+
+        let mut dropped = 0;
+        for (document, sentences) in documents.iter_mut().zip(&sentences) {
+            if sentences.len() <= self.max_sentences {
+                continue;
+            }
+            let mut scored: Vec<(usize, f32)> = embeddings
+                .by_ref()
+                .take(sentences.len())
+                .enumerate()
+                .map(|(index, embedding)| {
+                    let embedding: Vec<f32> = embedding.into_iter().map(|f| f as f32).collect();
+                    (index, cosine_similarity(&embedding, &query_embedding))
+                })
+                .collect();
+            scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+            let mut kept: Vec<usize> = scored
+                .into_iter()
+                .take(self.max_sentences)
+                .map(|(index, _)| index)
+                .collect();
+            kept.sort_unstable();
+
+            dropped += sentences.len() - kept.len();
+            let compressed = kept
+                .into_iter()
+                .map(|index| sentences[index].as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            document["metadata"]["text"] = Value::String(compressed);
+        }
+        info!(
+            "Dropped {} of {} sentences from retrieved documents",
+            dropped,
+            sentences.iter().map(Vec::len).sum::<usize>()
+        );
+        Ok(Value::Array(documents))
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<RagState>> {
+        match result {
+            Ok(value) => {
+                context.set("retrieved_documents", value.clone());
+                Ok(ProcessResult::new(
+                    RagState::Default,
+                    "context_compressed".to_string(),
+                ))
+            }
+            Err(e) => Ok(ProcessResult::new(
+                RagState::CompressionError,
+                format!("compression_error: {}", e),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Embeds sentences mentioning Pangu along the first axis, anything else along
+    /// the second.
+    struct KeywordEmbeddingGenerator;
+
+    #[async_trait]
+    impl EmbeddingGenerator for KeywordEmbeddingGenerator {
+        async fn generate_embedding(&self, text: &str) -> Result<Vec<f64>> {
+            if text.contains("Pangu") {
+                Ok(vec![1.0, 0.1])
+            } else {
+                Ok(vec![0.1, 1.0])
+            }
+        }
+
+        async fn generate_embeddings(&self, texts: &[String]) -> Result<Vec<Vec<f64>>> {
+            let mut embeddings = Vec::new();
+            for text in texts {
+                embeddings.push(self.generate_embedding(text).await?);
+            }
+            Ok(embeddings)
+        }
+    }
+
+    fn document(text: &str) -> Value {
+        json!({
+            "id": "1",
+            "vector": [],
+            "metadata": {"text": text, "file_metadata": {"url": "pangu.md"}},
+            "score": 0.9
+        })
+    }
+
+    #[tokio::test]
+    async fn test_irrelevant_sentences_are_dropped() {
+        let node =
+            ContextCompressionNode::new(Arc::new(KeywordEmbeddingGenerator)).with_max_sentences(2);
+        let mut context = Context::new();
+        context.set("query_embedding", json!([1.0, 0.0]));
+        context.set(
+            "retrieved_documents",
+            json!([
+                document(
+                    "Pangu keeps three replicas of every chunk. The weather was sunny that day. \
+                     Pangu masters track where chunks live. Lunch was served at noon."
+                ),
+                document("Short chunks stay whole. Even without Pangu."),
+            ]),
+        );
+
+        let result = node.execute(&context).await;
+        let outcome = node.post_process(&mut context, &result).await.unwrap();
+
+        assert_eq!(outcome.state, RagState::Default);
+        let documents = context.get("retrieved_documents").unwrap();
+        assert_eq!(
+            documents[0]["metadata"]["text"],
+            "Pangu keeps three replicas of every chunk. Pangu masters track where chunks live."
+        );
+        assert_eq!(documents[0]["metadata"]["file_metadata"]["url"], "pangu.md");
+        assert_eq!(
+            documents[1]["metadata"]["text"],
+            "Short chunks stay whole. Even without Pangu."
+        );
+    }
+
+    /// Returns one embedding fewer than it was asked for.
+    struct ShortBatchEmbeddingGenerator;
+
+    #[async_trait]
+    impl EmbeddingGenerator for ShortBatchEmbeddingGenerator {
+        async fn generate_embedding(&self, text: &str) -> Result<Vec<f64>> {
+            KeywordEmbeddingGenerator.generate_embedding(text).await
+        }
+
+        async fn generate_embeddings(&self, texts: &[String]) -> Result<Vec<Vec<f64>>> {
+            let mut embeddings = KeywordEmbeddingGenerator.generate_embeddings(texts).await?;
+            embeddings.pop();
+            Ok(embeddings)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_short_embedding_batch_is_an_error() {
+        let node = ContextCompressionNode::new(Arc::new(ShortBatchEmbeddingGenerator))
+            .with_max_sentences(1);
+        let mut context = Context::new();
+        context.set("query_embedding", json!([1.0, 0.0]));
+        context.set(
+            "retrieved_documents",
+            json!([document(
+                "Pangu keeps replicas. Lunch was at noon. Pangu tracks chunks."
+            )]),
+        );
+
+        let error = node.execute(&context).await.unwrap_err();
+        assert_eq!(error.to_string(), "Expected 3 embeddings, got 2");
+    }
+}
@@ -4,12 +4,19 @@ use async_trait::async_trait;
 use pocketflow_rs::utils::vector_db::{
     DistanceMetric, QdrantDB, VectorDB, VectorDBOptions, VectorRecord,
 };
-use pocketflow_rs::{Context, Node, ProcessResult};
+use pocketflow_rs::{Context, Document, Node, ProcessResult, ProgressReporter};
 use serde_json::Value;
+use std::collections::HashMap;
 use std::sync::Arc;
+use tracing::warn;
+
+/// Records upserted per insert call, and so per progress event.
+const BATCH_SIZE: usize = 100;
 
 pub struct CreateIndexNode {
-    db: Arc<QdrantDB>,
+    db: Arc<dyn VectorDB>,
+    max_document_text_len: Option<usize>,
+    progress: ProgressReporter,
 }
 
 impl CreateIndexNode {
@@ -24,9 +31,73 @@ impl CreateIndexNode {
             collection_name: collection,
             dimension,
             distance_metric,
+            payload_indexes: Vec::new(),
+            sparse_vector_name: None,
         };
+        Self::from_options(db_url, api_key, options).await
+    }
+
+    /// Open the collection described by `options`, e.g. with payload indexes on the
+    /// fields retrieval filters on.
+    pub async fn from_options(
+        db_url: String,
+        api_key: Option<String>,
+        options: VectorDBOptions,
+    ) -> Result<Self> {
         let db = QdrantDB::new(db_url, api_key, options).await?;
-        Ok(Self { db: Arc::new(db) })
+        Ok(Self::from_db(Arc::new(db)))
+    }
+
+    pub fn from_db(db: Arc<dyn VectorDB>) -> Self {
+        Self {
+            db,
+            max_document_text_len: None,
+            progress: ProgressReporter::default(),
+        }
+    }
+
+    /// Store the full text of each chunk's source document in its payload, so a cited
+    /// chunk can be expanded to the whole document. Documents longer than `max_len`
+    /// bytes are not stored.
+    pub fn with_document_text(mut self, max_len: usize) -> Self {
+        self.max_document_text_len = Some(max_len);
+        self
+    }
+
+    /// Report the records upserted so far as `records` progress, once per batch of
+    /// 100.
+    pub fn with_progress(mut self, progress: ProgressReporter) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    fn document_texts(&self, context: &Context, max_len: usize) -> HashMap<String, String> {
+        let documents = match context.get("documents").and_then(|v| v.as_array()) {
+            Some(documents) => documents,
+            None => {
+                warn!("No documents found in context, skipping document text");
+                return HashMap::new();
+            }
+        };
+
+        documents
+            .iter()
+            .filter_map(|doc| {
+                let doc = Document::from_value(doc).ok()?;
+                let url = doc.url()?;
+                let content = &doc.content;
+                if content.len() > max_len {
+                    warn!(
+                        "Document {} is {} bytes, over the {} byte limit, not storing its text",
+                        url,
+                        content.len(),
+                        max_len
+                    );
+                    return None;
+                }
+                Some((url.to_string(), content.to_string()))
+            })
+            .collect()
     }
 }
 
@@ -34,52 +105,100 @@ impl CreateIndexNode {
 impl Node for CreateIndexNode {
     type State = RagState;
 
+    fn name(&self) -> &str {
+        "CreateIndex"
+    }
+
     async fn execute(&self, context: &Context) -> Result<Value> {
-        let chunks_embeddings = context
-            .get("chunk_embeddings")
-            .and_then(|v| v.as_array())
-            .ok_or_else(|| anyhow::anyhow!("No embeddings found in context"))?;
+        let chunks_embeddings = context.get_array("chunk_embeddings")?;
+
+        let document_texts = self
+            .max_document_text_len
+            .map(|max_len| self.document_texts(context, max_len))
+            .unwrap_or_default();
 
+        let dimension = self.db.dimension();
         let mut records = Vec::new();
-        for chunk_embedding in chunks_embeddings {
-            let chunks = chunk_embedding
-                .get("chunks")
+        for (index, chunk_embedding) in chunks_embeddings.iter().enumerate() {
+            let id = chunk_embedding
+                .get("id")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| anyhow::anyhow!("No id found in chunk"))?;
+            let text = chunk_embedding
+                .get("text")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| anyhow::anyhow!("No text found in chunk"))?;
+            let embedding_vec: Vec<f32> = chunk_embedding
+                .get("embedding")
                 .and_then(|v| v.as_array())
-                .ok_or_else(|| anyhow::anyhow!("No chunks found in document"))?;
-            let embeddings = chunk_embedding
-                .get("embeddings")
-                .and_then(|v| v.as_array())
-                .ok_or_else(|| anyhow::anyhow!("No embeddings found in document"))?;
+                .ok_or_else(|| anyhow::anyhow!("No embedding found in chunk"))?
+                .iter()
+                .filter_map(|v| v.as_f64().map(|x| x as f32))
+                .collect();
+            // Checked here, as the db rejects a whole batch over one bad vector
+            if let Some(dimension) = dimension
+                && embedding_vec.len() != dimension
+            {
+                return Err(anyhow::anyhow!(
+                    "Embedding dimension mismatch at chunk {} ({}): expected {}, got {}",
+                    index,
+                    id,
+                    dimension,
+                    embedding_vec.len()
+                ));
+            }
+            let chunk_index = chunk_embedding
+                .get("chunk_index")
+                .cloned()
+                .unwrap_or(Value::Null);
             let metadata = chunk_embedding.get("metadata").unwrap_or(&Value::Null);
 
-            let chunks_size = chunks.len();
-            for i in 0..chunks_size {
-                let chunk = chunks[i].to_string();
-                let default_embedding = Vec::new();
-                let embedding = embeddings[i].as_array().unwrap_or(&default_embedding);
-                let embedding_vec: Vec<f32> = embedding
-                    .iter()
-                    .filter_map(|v| v.as_f64().map(|x| x as f32))
-                    .collect();
-                records.push(VectorRecord {
-                    id: uuid::Uuid::new_v4().to_string(),
-                    vector: embedding_vec,
-                    metadata: serde_json::Map::from_iter(vec![
-                        ("text".to_string(), serde_json::Value::String(chunk)),
-                        ("file_metadata".to_string(), metadata.clone()),
-                    ]),
-                });
+            let mut payload = serde_json::Map::from_iter(vec![
+                ("text".to_string(), Value::String(text.to_string())),
+                ("chunk_index".to_string(), chunk_index),
+                ("file_metadata".to_string(), metadata.clone()),
+            ]);
+            // Chunk stats, present when chunking ran with enrichment
+            for key in ["char_count", "token_count", "hash"] {
+                if let Some(value) = chunk_embedding.get(key) {
+                    payload.insert(key.to_string(), value.clone());
+                }
+            }
+            if let Some(document_text) = metadata
+                .get("url")
+                .and_then(|v| v.as_str())
+                .and_then(|url| document_texts.get(url))
+            {
+                payload.insert(
+                    "document_text".to_string(),
+                    Value::String(document_text.clone()),
+                );
             }
+
+            records.push(VectorRecord {
+                id: id.to_string(),
+                vector: embedding_vec,
+                sparse_vector: None,
+                metadata: payload,
+                score: None,
+            });
         }
 
         if records.is_empty() {
             return Err(anyhow::anyhow!("No valid records to insert"));
         }
 
-        self.db
-            .insert(records)
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to insert records: {}", e))?;
+        let total = records.len();
+        let mut upserted = 0;
+        for batch in records.chunks(BATCH_SIZE) {
+            self.db
+                .insert(batch.to_vec())
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to insert records: {}", e))?;
+            upserted += batch.len();
+            self.progress
+                .report(self.name(), "records", upserted, Some(total));
+        }
         Ok(Value::Null)
     }
 
@@ -101,3 +220,85 @@ impl Node for CreateIndexNode {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::memory_db;
+    use serde_json::json;
+
+    fn context_with_chunks() -> Context {
+        let mut context = Context::new();
+        context.set(
+            "documents",
+            json!([
+                {"content": "Short document. With two chunks.", "metadata": {"url": "a.txt"}},
+                {"content": "A much longer document that exceeds the limit.", "metadata": {"url": "b.txt"}}
+            ]),
+        );
+        context.set(
+            "chunk_embeddings",
+            json!([
+                {"id": "a-0", "text": "Short document", "chunk_index": 0, "metadata": {"url": "a.txt"}, "embedding": [0.1, 0.2]},
+                {"id": "a-1", "text": "With two chunks.", "chunk_index": 1, "metadata": {"url": "a.txt"}, "embedding": [0.3, 0.4]},
+                {"id": "b-0", "text": "A much longer document", "chunk_index": 0, "metadata": {"url": "b.txt"}, "embedding": [0.5, 0.6]}
+            ]),
+        );
+        context
+    }
+
+    #[tokio::test]
+    async fn test_document_text_retrievable_for_chunk() {
+        let db = memory_db(2);
+        let node = CreateIndexNode::from_db(db.clone()).with_document_text(40);
+        node.execute(&context_with_chunks()).await.unwrap();
+
+        let records = db.search(vec![0.3, 0.4], 10).await.unwrap();
+        let cited = records.iter().find(|r| r.id == "a-1").unwrap();
+        assert_eq!(
+            cited.metadata.get("document_text"),
+            Some(&json!("Short document. With two chunks."))
+        );
+
+        // Over the size limit, so only the chunk text is stored
+        let too_long = records.iter().find(|r| r.id == "b-0").unwrap();
+        assert!(!too_long.metadata.contains_key("document_text"));
+    }
+
+    #[tokio::test]
+    async fn test_document_text_disabled_by_default() {
+        let db = memory_db(2);
+        let node = CreateIndexNode::from_db(db.clone());
+        node.execute(&context_with_chunks()).await.unwrap();
+
+        let records = db.search(vec![0.3, 0.4], 10).await.unwrap();
+        assert_eq!(records.len(), 3);
+        assert!(
+            records
+                .iter()
+                .all(|r| !r.metadata.contains_key("document_text"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dimension_mismatch_names_chunk() {
+        let db = memory_db(2);
+        let mut context = context_with_chunks();
+        context.set(
+            "chunk_embeddings",
+            json!([
+                {"id": "a-0", "text": "Short document", "embedding": [0.1, 0.2]},
+                {"id": "a-1", "text": "With two chunks.", "embedding": [0.3, 0.4, 0.5]}
+            ]),
+        );
+
+        let node = CreateIndexNode::from_db(db.clone());
+        let error = node.execute(&context).await.unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Embedding dimension mismatch at chunk 1 (a-1): expected 2, got 3"
+        );
+        assert!(db.is_empty());
+    }
+}
@@ -0,0 +1,274 @@
+use crate::state::RagState;
+use anyhow::Result;
+use async_trait::async_trait;
+use pocketflow_rs::{Context, Node, ProcessResult};
+use serde_json::{Value, json};
+use std::collections::HashSet;
+use tracing::info;
+
+/// Context metadata key recording how many chunks [`DedupChunksNode`] removed.
+pub const DEDUP_STATS_KEY: &str = "dedup_stats";
+
+/// Number of consecutive words in a shingle.
+const SHINGLE_SIZE: usize = 3;
+
+/// When two chunks count as duplicates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DedupStrategy {
+    /// Same text, ignoring case and whitespace.
+    Exact,
+    /// Cosine similarity of the chunk embeddings at or above the threshold.
+    CosineThreshold(f32),
+    /// Jaccard similarity of word shingles at or above the threshold.
+    Shingle(f32),
+}
+
+/// Removes duplicate chunks, keeping the first of each group. Reads and writes
+/// `chunk_embeddings` by default so cosine mode reuses the computed embeddings.
+pub struct DedupChunksNode {
+    strategy: DedupStrategy,
+    key: String,
+}
+
+impl DedupChunksNode {
+    pub fn new(strategy: DedupStrategy) -> Self {
+        Self {
+            strategy,
+            key: "chunk_embeddings".to_string(),
+        }
+    }
+
+    /// Deduplicate the chunks under `key` instead, e.g. `documents_chunked` to drop
+    /// duplicates before embedding them (not possible with cosine mode).
+    pub fn with_key(mut self, key: &str) -> Self {
+        self.key = key.to_string();
+        self
+    }
+
+    fn text(chunk: &Value) -> Result<&str> {
+        chunk
+            .get("text")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("No text found in chunk"))
+    }
+
+    fn normalize(text: &str) -> String {
+        text.split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase()
+    }
+
+    fn embedding(chunk: &Value) -> Result<Vec<f32>> {
+        chunk
+            .get("embedding")
+            .and_then(|v| v.as_array())
+            .map(|values| {
+                values
+                    .iter()
+                    .filter_map(|v| v.as_f64().map(|f| f as f32))
+                    .collect()
+            })
+            .ok_or_else(|| {
+                anyhow::anyhow!("No embedding found in chunk, run dedup after embedding")
+            })
+    }
+
+    fn shingles(text: &str) -> HashSet<String> {
+        let words: Vec<String> = text
+            .split_whitespace()
+            .map(|w| {
+                w.trim_matches(|c: char| !c.is_alphanumeric())
+                    .to_lowercase()
+            })
+            .filter(|w| !w.is_empty())
+            .collect();
+        if words.len() < SHINGLE_SIZE {
+            return HashSet::from([words.join(" ")]);
+        }
+        words.windows(SHINGLE_SIZE).map(|w| w.join(" ")).collect()
+    }
+
+    fn keep_distinct<T>(
+        chunks: &[Value],
+        features: Vec<T>,
+        is_duplicate: impl Fn(&T, &T) -> bool,
+    ) -> Vec<Value> {
+        let mut kept: Vec<usize> = Vec::new();
+        for (i, feature) in features.iter().enumerate() {
+            if !kept.iter().any(|&j| is_duplicate(&features[j], feature)) {
+                kept.push(i);
+            }
+        }
+        kept.into_iter().map(|i| chunks[i].clone()).collect()
+    }
+}
+
+pub(crate) fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+fn jaccard_similarity(a: &HashSet<String>, b: &HashSet<String>) -> f32 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 1.0;
+    }
+    a.intersection(b).count() as f32 / union as f32
+}
+
+#[async_trait]
+impl Node for DedupChunksNode {
+    type State = RagState;
+
+    fn name(&self) -> &str {
+        "DedupChunks"
+    }
+
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let chunks = context.get_array(&self.key)?;
+
+        let kept = match self.strategy {
+            DedupStrategy::Exact => {
+                let mut seen = HashSet::new();
+                let mut kept = Vec::new();
+                for chunk in chunks {
+                    if seen.insert(Self::normalize(Self::text(chunk)?)) {
+                        kept.push(chunk.clone());
+                    }
+                }
+                kept
+            }
+            DedupStrategy::CosineThreshold(threshold) => {
+                let embeddings = chunks
+                    .iter()
+                    .map(Self::embedding)
+                    .collect::<Result<Vec<_>>>()?;
+                Self::keep_distinct(chunks, embeddings, |a, b| {
+                    cosine_similarity(a, b) >= threshold
+                })
+            }
+            DedupStrategy::Shingle(threshold) => {
+                let shingles = chunks
+                    .iter()
+                    .map(|chunk| Self::text(chunk).map(Self::shingles))
+                    .collect::<Result<Vec<_>>>()?;
+                Self::keep_distinct(chunks, shingles, |a, b| {
+                    jaccard_similarity(a, b) >= threshold
+                })
+            }
+        };
+
+        let removed = chunks.len() - kept.len();
+        info!(
+            "Removed {} duplicate chunks with {:?}",
+            removed, self.strategy
+        );
+        Ok(json!({"chunks": kept, "removed": removed}))
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<RagState>> {
+        match result {
+            Ok(value) => {
+                context.set(&self.key, value["chunks"].clone());
+                context.set_metadata(DEDUP_STATS_KEY, json!({"removed": value["removed"]}));
+                Ok(ProcessResult::new(
+                    RagState::Default,
+                    "chunks_deduplicated".to_string(),
+                ))
+            }
+            Err(e) => Ok(ProcessResult::new(
+                RagState::DedupError,
+                format!("dedup_error: {}", e),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two paraphrases with nearly identical embeddings, a verbatim copy of the
+    /// first, and an unrelated chunk.
+    fn context_with_chunks() -> Context {
+        let mut context = Context::new();
+        context.set(
+            "chunk_embeddings",
+            json!([
+                {"id": "a", "text": "Pangu is a distributed storage system.", "embedding": [0.9, 0.1, 0.0]},
+                {"id": "b", "text": "Pangu is a storage system that is distributed.", "embedding": [0.88, 0.12, 0.01]},
+                {"id": "c", "text": "  pangu is a distributed   storage system. ", "embedding": [0.9, 0.1, 0.0]},
+                {"id": "d", "text": "Cooking pasta takes ten minutes.", "embedding": [0.0, 0.2, 0.95]}
+            ]),
+        );
+        context
+    }
+
+    async fn kept_ids(node: &DedupChunksNode) -> (Vec<String>, Context) {
+        let mut context = context_with_chunks();
+        let result = node.execute(&context).await;
+        node.post_process(&mut context, &result).await.unwrap();
+        let ids = context
+            .get("chunk_embeddings")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|chunk| chunk["id"].as_str().unwrap().to_string())
+            .collect();
+        (ids, context)
+    }
+
+    #[tokio::test]
+    async fn test_exact_keeps_paraphrases() {
+        let (ids, context) = kept_ids(&DedupChunksNode::new(DedupStrategy::Exact)).await;
+
+        assert_eq!(ids, vec!["a", "b", "d"]);
+        assert_eq!(
+            context.get_metadata(DEDUP_STATS_KEY),
+            Some(&json!({"removed": 1}))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cosine_removes_paraphrases_above_threshold() {
+        let (ids, _) = kept_ids(&DedupChunksNode::new(DedupStrategy::CosineThreshold(0.95))).await;
+        assert_eq!(ids, vec!["a", "d"]);
+
+        // A threshold above the paraphrase similarity only removes the exact copy
+        let (ids, _) = kept_ids(&DedupChunksNode::new(DedupStrategy::CosineThreshold(
+            0.9999,
+        )))
+        .await;
+        assert_eq!(ids, vec!["a", "b", "d"]);
+    }
+
+    #[tokio::test]
+    async fn test_shingle_threshold() {
+        let (ids, _) = kept_ids(&DedupChunksNode::new(DedupStrategy::Shingle(0.8))).await;
+        assert_eq!(ids, vec!["a", "b", "d"]);
+
+        let (ids, _) = kept_ids(&DedupChunksNode::new(DedupStrategy::Shingle(0.1))).await;
+        assert_eq!(ids, vec!["a", "d"]);
+    }
+
+    #[tokio::test]
+    async fn test_cosine_requires_embeddings() {
+        let mut context = Context::new();
+        context.set("documents_chunked", json!([{"id": "a", "text": "Hello"}]));
+        let node =
+            DedupChunksNode::new(DedupStrategy::CosineThreshold(0.9)).with_key("documents_chunked");
+
+        assert!(node.execute(&context).await.is_err());
+    }
+}
@@ -0,0 +1,73 @@
+use crate::state::RagState;
+use anyhow::Result;
+use async_trait::async_trait;
+use pocketflow_rs::{Context, Node, ProcessResult};
+use serde_json::{Value, json};
+use tracing::warn;
+
+/// Set to `true` in the context once `DegradedAnswerNode` has answered.
+pub const DEGRADED_KEY: &str = "degraded";
+
+/// The fallback when no answer can be generated: lists the retrieved snippets
+/// as they are, without LLM synthesis, as the `result`. Meant to follow
+/// `GenerateAnswerNode` on `RagState::GenerationError`, or to be run by hand on
+/// the context of a flow given up on; see `rag_online`.
+pub struct DegradedAnswerNode;
+
+#[async_trait]
+impl Node for DegradedAnswerNode {
+    type State = RagState;
+
+    fn name(&self) -> &str {
+        "DegradedAnswer"
+    }
+
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let snippets: Vec<String> = context
+            .get_array("retrieved_documents")?
+            .iter()
+            .enumerate()
+            .map(|(i, record)| {
+                let metadata = &record["metadata"];
+                format!(
+                    "{}. {}: {}",
+                    i + 1,
+                    metadata["file_metadata"]["url"]
+                        .as_str()
+                        .unwrap_or("unknown source"),
+                    metadata["text"].as_str().unwrap_or_default()
+                )
+            })
+            .collect();
+        if snippets.is_empty() {
+            return Err(anyhow::anyhow!("No retrieved documents to answer from"));
+        }
+
+        Ok(json!(format!(
+            "No answer could be generated. The most relevant passages found:\n\n{}",
+            snippets.join("\n")
+        )))
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<RagState>> {
+        match result {
+            Ok(value) => {
+                warn!("Answering with the retrieved snippets only");
+                context.set("result", value.clone());
+                context.set(DEGRADED_KEY, json!(true));
+                Ok(ProcessResult::new(
+                    RagState::Default,
+                    "answer_degraded".to_string(),
+                ))
+            }
+            Err(e) => Ok(ProcessResult::new(
+                RagState::GenerationError,
+                format!("generation_error: {}", e),
+            )),
+        }
+    }
+}
@@ -3,27 +3,71 @@ use anyhow::Result;
 use async_trait::async_trait;
 use pocketflow_rs::embedding::EmbeddingGenerator;
 use pocketflow_rs::utils::embedding::{EmbeddingOptions, OpenAIEmbeddingGenerator};
-use pocketflow_rs::{Context, Node, ProcessResult};
+use pocketflow_rs::{Context, Node, ProcessResult, ProgressReporter, RetryPolicy};
 use serde_json::{Value, json};
 use std::sync::Arc;
 use tracing::{debug, info};
 
+/// Embeddings with a smaller L2 norm than this are treated as degenerate.
+const MIN_EMBEDDING_NORM: f64 = 1e-6;
+
 pub struct EmbedDocumentsNode {
-    generator: Arc<OpenAIEmbeddingGenerator>,
+    generator: Arc<dyn EmbeddingGenerator>,
+    retry_policy: RetryPolicy,
+    progress: ProgressReporter,
+    batch_size: usize,
 }
 
 impl EmbedDocumentsNode {
     pub fn new(api_key: String, endpoint: String, model: String, dimension: Option<usize>) -> Self {
+        Self::from_generator(Arc::new(OpenAIEmbeddingGenerator::new(
+            &api_key,
+            &endpoint,
+            EmbeddingOptions {
+                model,
+                dimensions: dimension,
+            },
+        )))
+    }
+
+    pub fn from_generator(generator: Arc<dyn EmbeddingGenerator>) -> Self {
         Self {
-            generator: Arc::new(OpenAIEmbeddingGenerator::new(
-                &api_key,
-                &endpoint,
-                EmbeddingOptions {
-                    model,
-                    dimensions: dimension,
-                },
-            )),
+            generator,
+            retry_policy: RetryPolicy::default(),
+            progress: ProgressReporter::default(),
+            batch_size: 100,
+        }
+    }
+
+    /// Chunks sent to the generator per call, and so per progress event.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Retry transient embedding API failures such as rate limits.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Report the chunks embedded so far as `embeddings` progress, once per batch.
+    pub fn with_progress(mut self, progress: ProgressReporter) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Reject vectors that would silently poison the index: NaN/Inf components or an
+    /// all-zero (near-zero norm) vector, as returned by some providers on failure.
+    fn validate_embedding(embedding: &[f64]) -> Result<()> {
+        if embedding.iter().any(|x| !x.is_finite()) {
+            return Err(anyhow::anyhow!("embedding contains NaN or Inf"));
+        }
+        let norm = embedding.iter().map(|x| x * x).sum::<f64>().sqrt();
+        if norm < MIN_EMBEDDING_NORM {
+            return Err(anyhow::anyhow!("embedding has near-zero norm {}", norm));
         }
+        Ok(())
     }
 }
 
@@ -31,40 +75,84 @@ impl EmbedDocumentsNode {
 impl Node for EmbedDocumentsNode {
     type State = RagState;
 
+    fn name(&self) -> &str {
+        "EmbedDocuments"
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy.clone()
+    }
+
     async fn execute(&self, context: &Context) -> Result<Value> {
-        let documents_chunked = context
-            .get("documents_chunked")
-            .and_then(|v| v.as_array())
-            .ok_or_else(|| anyhow::anyhow!("No chunks found in context"))?;
+        let documents_chunked = context.get_array("documents_chunked")?;
         info!("Documents chunked: {:?}", documents_chunked.len());
 
-        let mut embed_result = Vec::new();
-        for chunk in documents_chunked {
-            let chunks = chunk
-                .get("chunks")
-                .and_then(|v| v.as_array())
-                .ok_or_else(|| anyhow::anyhow!("No chunks found in document"))?;
-            let chunk_text: Vec<String> = chunks
-                .iter()
-                .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                .collect();
-            debug!("Chunk text: {:?}", chunk_text);
-            info!("Chunk text len: {:?}", chunk_text.len());
-            let embeddings = self.generator.generate_embeddings(&chunk_text).await?;
-            info!("Embeddings len: {:?}", embeddings.len());
-            if embeddings.is_empty() {
-                return Err(anyhow::anyhow!("Embeddings array is empty"));
-            }
-            info!("First Embeddings: {:?}", embeddings[0]);
-
-            embed_result.push(json!(
-                {
-                    "chunks": chunk_text,
-                    "embeddings": embeddings,
-                    "metadata": chunk.get("metadata").unwrap_or(&Value::Null),
-                }
+        let chunk_text = documents_chunked
+            .iter()
+            .map(|chunk| {
+                chunk
+                    .get("text")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string())
+                    .ok_or_else(|| anyhow::anyhow!("No text found in chunk"))
+            })
+            .collect::<Result<Vec<String>>>()?;
+        debug!("Chunk text: {:?}", chunk_text);
+        info!("Chunk text len: {:?}", chunk_text.len());
+
+        let mut embeddings = Vec::with_capacity(chunk_text.len());
+        for batch in chunk_text.chunks(self.batch_size) {
+            embeddings.extend(self.generator.generate_embeddings(batch).await?);
+            self.progress.report(
+                self.name(),
+                "embeddings",
+                embeddings.len(),
+                Some(chunk_text.len()),
+            );
+        }
+        info!("Embeddings len: {:?}", embeddings.len());
+        if embeddings.is_empty() {
+            return Err(anyhow::anyhow!("Embeddings array is empty"));
+        }
+        if embeddings.len() != documents_chunked.len() {
+            return Err(anyhow::anyhow!(
+                "Expected {} embeddings, got {}",
+                documents_chunked.len(),
+                embeddings.len()
             ));
         }
+        info!("First Embeddings: {:?}", embeddings[0]);
+
+        let invalid: Vec<String> = documents_chunked
+            .iter()
+            .zip(&embeddings)
+            .filter_map(|(chunk, embedding)| {
+                Self::validate_embedding(embedding).err().map(|e| {
+                    let id = chunk
+                        .get("id")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("unknown");
+                    format!("{} ({})", id, e)
+                })
+            })
+            .collect();
+        if !invalid.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Degenerate embeddings for chunks: {}",
+                invalid.join(", ")
+            ));
+        }
+
+        // Keep the chunk record (id, text, chunk_index, metadata) and attach its embedding
+        let embed_result = documents_chunked
+            .iter()
+            .zip(embeddings)
+            .map(|(chunk, embedding)| {
+                let mut record = chunk.clone();
+                record["embedding"] = json!(embedding);
+                record
+            })
+            .collect();
 
         Ok(Value::Array(embed_result))
     }
@@ -89,3 +177,89 @@ impl Node for EmbedDocumentsNode {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MockEmbeddingGenerator {
+        embeddings: Vec<Vec<f64>>,
+        batch_sizes: Mutex<Vec<usize>>,
+    }
+
+    #[async_trait]
+    impl EmbeddingGenerator for MockEmbeddingGenerator {
+        async fn generate_embedding(&self, text: &str) -> Result<Vec<f64>> {
+            let embeds = self.generate_embeddings(&[text.to_string()]).await?;
+            Ok(embeds[0].clone())
+        }
+
+        async fn generate_embeddings(&self, texts: &[String]) -> Result<Vec<Vec<f64>>> {
+            let mut batch_sizes = self.batch_sizes.lock().unwrap();
+            let done: usize = batch_sizes.iter().sum();
+            batch_sizes.push(texts.len());
+            Ok(self.embeddings[done..done + texts.len()].to_vec())
+        }
+    }
+
+    fn context_with_chunks() -> Context {
+        let mut context = Context::new();
+        context.set(
+            "documents_chunked",
+            json!([
+                {"id": "good", "text": "fine", "chunk_index": 0, "metadata": null},
+                {"id": "zero", "text": "zero", "chunk_index": 1, "metadata": null},
+                {"id": "nan", "text": "nan", "chunk_index": 2, "metadata": null}
+            ]),
+        );
+        context
+    }
+
+    #[tokio::test]
+    async fn test_degenerate_embeddings_rejected() {
+        let node = EmbedDocumentsNode::from_generator(Arc::new(MockEmbeddingGenerator {
+            embeddings: vec![vec![0.6, 0.8], vec![0.0, 0.0], vec![f64::NAN, 1.0]],
+            ..Default::default()
+        }));
+        let mut context = context_with_chunks();
+
+        let result = node.execute(&context).await;
+        let error = result.as_ref().unwrap_err().to_string();
+        assert!(error.contains("zero (embedding has near-zero norm"));
+        assert!(error.contains("nan (embedding contains NaN or Inf)"));
+        assert!(!error.contains("good"));
+
+        let process_result = node.post_process(&mut context, &result).await.unwrap();
+        assert_eq!(process_result.state, RagState::EmbeddingError);
+        assert!(!context.contains_key("chunk_embeddings"));
+    }
+
+    #[tokio::test]
+    async fn test_valid_embeddings_attached_to_chunks() {
+        let node = EmbedDocumentsNode::from_generator(Arc::new(MockEmbeddingGenerator {
+            embeddings: vec![vec![0.6, 0.8], vec![1.0, 0.0], vec![0.0, -1.0]],
+            ..Default::default()
+        }));
+
+        let result = node.execute(&context_with_chunks()).await.unwrap();
+        let records = result.as_array().unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1]["id"], json!("zero"));
+        assert_eq!(records[1]["embedding"], json!([1.0, 0.0]));
+    }
+
+    #[tokio::test]
+    async fn test_chunks_embedded_in_batches() {
+        let generator = Arc::new(MockEmbeddingGenerator {
+            embeddings: vec![vec![0.6, 0.8], vec![1.0, 0.0], vec![0.0, -1.0]],
+            ..Default::default()
+        });
+        let node = EmbedDocumentsNode::from_generator(generator.clone()).with_batch_size(2);
+
+        let result = node.execute(&context_with_chunks()).await.unwrap();
+        assert_eq!(*generator.batch_sizes.lock().unwrap(), vec![2, 1]);
+        assert_eq!(result[2]["embedding"], json!([0.0, -1.0]));
+    }
+}
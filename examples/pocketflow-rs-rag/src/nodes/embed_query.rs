@@ -7,23 +7,38 @@ use pocketflow_rs::utils::embedding::{
 use pocketflow_rs::{Context, Node, ProcessResult};
 use serde_json::{Value, json};
 use std::sync::Arc;
+use tracing::info;
 
+/// Context key holding the query text `query_embedding` was computed from.
+const QUERY_EMBEDDING_TEXT_KEY: &str = "query_embedding_text";
+
+/// Embeds `rewritten_query` into `query_embedding`. If the context already holds
+/// the embedding of the same query text, it is reused instead of calling the API.
 pub struct EmbedQueryNode {
-    generator: Arc<OpenAIEmbeddingGenerator>,
+    generator: Arc<dyn EmbeddingGenerator>,
 }
 
 impl EmbedQueryNode {
     pub fn new(api_key: String, endpoint: String, model: String, dimension: Option<usize>) -> Self {
-        Self {
-            generator: Arc::new(OpenAIEmbeddingGenerator::new(
-                &api_key,
-                &endpoint,
-                EmbeddingOptions {
-                    model,
-                    dimensions: dimension,
-                },
-            )),
-        }
+        Self::from_generator(Arc::new(OpenAIEmbeddingGenerator::new(
+            &api_key,
+            &endpoint,
+            EmbeddingOptions {
+                model,
+                dimensions: dimension,
+            },
+        )))
+    }
+
+    pub fn from_generator(generator: Arc<dyn EmbeddingGenerator>) -> Self {
+        Self { generator }
+    }
+
+    fn cached_embedding<'a>(context: &'a Context, query: &str) -> Option<&'a Value> {
+        let cached_text = context.get(QUERY_EMBEDDING_TEXT_KEY)?.as_str()?;
+        context
+            .get("query_embedding")
+            .filter(|embedding| cached_text == query && embedding.is_array())
     }
 }
 
@@ -31,20 +46,22 @@ impl EmbedQueryNode {
 impl Node for EmbedQueryNode {
     type State = RagState;
 
-    #[allow(unused_variables)]
+    fn name(&self) -> &str {
+        "EmbedQuery"
+    }
+
     async fn execute(&self, context: &Context) -> Result<Value> {
-        let query = context
-            .get("rewritten_query")
-            .and_then(|v| v.as_str())
-            .unwrap_or("")
-            .to_string();
+        let query = context.get_str("rewritten_query")?.to_string();
+        if let Some(embedding) = Self::cached_embedding(context, &query) {
+            info!("Reusing query embedding from context");
+            return Ok(json!({"query": query, "embedding": embedding}));
+        }
+
         let embedding = self.generator.generate_embedding(&query).await?;
         if embedding.is_empty() {
             return Err(anyhow::anyhow!("No embedding generated for query"));
         }
-        Ok(Value::Array(
-            embedding.into_iter().map(|x| json!(x)).collect(),
-        ))
+        Ok(json!({"query": query, "embedding": embedding}))
     }
 
     async fn post_process(
@@ -54,7 +71,8 @@ impl Node for EmbedQueryNode {
     ) -> Result<ProcessResult<RagState>> {
         match result {
             Ok(value) => {
-                context.set("query_embedding", value.clone());
+                context.set("query_embedding", value["embedding"].clone());
+                context.set(QUERY_EMBEDDING_TEXT_KEY, value["query"].clone());
                 Ok(ProcessResult::new(
                     RagState::Default,
                     "query_embedded".to_string(),
@@ -67,3 +85,53 @@ impl Node for EmbedQueryNode {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct CountingEmbeddingGenerator {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl EmbeddingGenerator for CountingEmbeddingGenerator {
+        async fn generate_embedding(&self, text: &str) -> Result<Vec<f64>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![text.len() as f64, 1.0])
+        }
+
+        async fn generate_embeddings(&self, texts: &[String]) -> Result<Vec<Vec<f64>>> {
+            let mut embeddings = Vec::new();
+            for text in texts {
+                embeddings.push(self.generate_embedding(text).await?);
+            }
+            Ok(embeddings)
+        }
+    }
+
+    async fn run_node(node: &EmbedQueryNode, context: &mut Context) {
+        let result = node.execute(context).await;
+        node.post_process(context, &result).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_same_query_is_embedded_once() {
+        let generator = Arc::new(CountingEmbeddingGenerator::default());
+        let node = EmbedQueryNode::from_generator(generator.clone());
+        let mut context = Context::new();
+        context.set("rewritten_query", json!("what is pangu"));
+
+        run_node(&node, &mut context).await;
+        run_node(&node, &mut context).await;
+        assert_eq!(generator.calls.load(Ordering::SeqCst), 1);
+        assert_eq!(context.get("query_embedding").unwrap(), &json!([13.0, 1.0]));
+
+        context.set("rewritten_query", json!("pangu design"));
+        run_node(&node, &mut context).await;
+        assert_eq!(generator.calls.load(Ordering::SeqCst), 2);
+        assert_eq!(context.get("query_embedding").unwrap(), &json!([12.0, 1.0]));
+    }
+}
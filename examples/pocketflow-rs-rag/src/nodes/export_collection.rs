@@ -0,0 +1,96 @@
+use crate::state::RagState;
+use anyhow::Result;
+use async_trait::async_trait;
+use pocketflow_rs::utils::vector_db::VectorDB;
+use pocketflow_rs::{Context, Node, ProcessResult};
+use serde_json::{Value, json};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::info;
+
+/// Dumps every record of a collection to a JSONL file, one `{id, vector, metadata}`
+/// object per line, for backups or moving to another backend with
+/// `ImportCollectionNode`.
+pub struct ExportCollectionNode {
+    db: Arc<dyn VectorDB>,
+    path: PathBuf,
+    batch_size: usize,
+}
+
+impl ExportCollectionNode {
+    pub fn new(db: Arc<dyn VectorDB>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            db,
+            path: path.into(),
+            batch_size: 100,
+        }
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+}
+
+#[async_trait]
+impl Node for ExportCollectionNode {
+    type State = RagState;
+
+    fn name(&self) -> &str {
+        "ExportCollection"
+    }
+
+    #[allow(unused_variables)]
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let mut writer = BufWriter::new(File::create(&self.path)?);
+        let mut offset = None;
+        let mut exported = 0;
+
+        loop {
+            let page = self.db.scroll(offset, self.batch_size).await?;
+            for record in &page.records {
+                let line = json!({
+                    "id": record.id,
+                    "vector": record.vector,
+                    "metadata": record.metadata,
+                });
+                writeln!(writer, "{}", line)?;
+            }
+            exported += page.records.len();
+
+            match page.next_offset {
+                Some(next) => offset = Some(next),
+                None => break,
+            }
+        }
+        writer.flush()?;
+        info!("Exported {} records to {:?}", exported, self.path);
+
+        Ok(json!({
+            "exported": exported,
+            "path": self.path,
+        }))
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<RagState>> {
+        match result {
+            Ok(value) => {
+                context.set("exported", value.clone());
+                Ok(ProcessResult::new(
+                    RagState::Default,
+                    "collection_exported".to_string(),
+                ))
+            }
+            Err(e) => Ok(ProcessResult::new(
+                RagState::ExportError,
+                format!("export_error: {}", e),
+            )),
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use crate::state::RagState;
+use anyhow::Result;
+use async_trait::async_trait;
+use pocketflow_rs::utils::llm_wrapper::{LLMOptions, LLMWrapper};
+use pocketflow_rs::{Context, Node, ProcessResult};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use tracing::warn;
+
+const DEFAULT_THRESHOLD: f64 = 0.8;
+
+/// Context key the faithfulness report is written under.
+pub const FAITHFULNESS_KEY: &str = "faithfulness";
+
+/// Scores how well the answer in `result` is supported by `retrieved_documents`.
+/// An LLM judge checks each claim of the answer against the context; the score is
+/// the fraction of supported claims. Answers scoring below the threshold route to
+/// [`RagState::Unfaithful`] with the unsupported claims listed in the report.
+pub struct FaithfulnessNode {
+    judge: Arc<dyn LLMWrapper>,
+    threshold: f64,
+}
+
+impl FaithfulnessNode {
+    pub fn new(judge: Arc<dyn LLMWrapper>) -> Self {
+        Self {
+            judge,
+            threshold: DEFAULT_THRESHOLD,
+        }
+    }
+
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    fn assemble_context(context: &Context) -> String {
+        context
+            .get("retrieved_documents")
+            .and_then(|v| v.as_array())
+            .map(|docs| {
+                docs.iter()
+                    .filter_map(|doc| doc["metadata"]["text"].as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n\n")
+            })
+            .unwrap_or_default()
+    }
+
+    /// The judged claims as `(claim, supported)` pairs. Fails on a claim missing its
+    /// text or verdict, rather than leaving it out of the score.
+    fn parse_verdicts(reply: &str) -> Result<Vec<(String, bool)>> {
+        let start = reply.find('{');
+        let end = reply.rfind('}');
+        let json = match (start, end) {
+            (Some(start), Some(end)) if start < end => &reply[start..=end],
+            _ => return Err(anyhow::anyhow!("Judge reply is not JSON: {}", reply)),
+        };
+        let value: Value = serde_json::from_str(json)?;
+        let claims = value["claims"]
+            .as_array()
+            .ok_or_else(|| anyhow::anyhow!("Judge reply has no claims"))?;
+        claims
+            .iter()
+            .map(|c| match (c["claim"].as_str(), c["supported"].as_bool()) {
+                (Some(claim), Some(supported)) => Ok((claim.to_string(), supported)),
+                _ => Err(anyhow::anyhow!("Malformed claim in judge reply: {}", c)),
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Node for FaithfulnessNode {
+    type State = RagState;
+
+    fn name(&self) -> &str {
+        "Faithfulness"
+    }
+
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let answer = context.get_str("result")?;
+        let retrieved_context = Self::assemble_context(context);
+
+        let prompt = format!(
+            "Split the answer below into its factual claims and decide for each claim \
+             whether it is supported by the context.\n\n\
+             Context:\n{}\n\nAnswer:\n{}\n\n\
+             Respond with ONLY JSON of the form \
+             {{\"claims\": [{{\"claim\": \"...\", \"supported\": true}}]}}.",
+            retrieved_context, answer
+        );
+        let options = LLMOptions::for_extraction();
+        let response = self.judge.generate_with_options(&prompt, options).await?;
+        let verdicts = Self::parse_verdicts(&response.content)?;
+
+        // Only an empty answer has no claims to contradict the context
+        let supported = verdicts.iter().filter(|(_, supported)| *supported).count();
+        let score = if !verdicts.is_empty() {
+            supported as f64 / verdicts.len() as f64
+        } else if answer.trim().is_empty() {
+            1.0
+        } else {
+            return Err(anyhow::anyhow!("Judge found no claims in the answer"));
+        };
+        let unsupported: Vec<&str> = verdicts
+            .iter()
+            .filter(|(_, supported)| !supported)
+            .map(|(claim, _)| claim.as_str())
+            .collect();
+        Ok(json!({"score": score, "unsupported_claims": unsupported}))
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<RagState>> {
+        match result {
+            Ok(value) => {
+                context.set(FAITHFULNESS_KEY, value.clone());
+                let score = value["score"].as_f64().unwrap_or(0.0);
+                if score >= self.threshold {
+                    Ok(ProcessResult::new(
+                        RagState::Default,
+                        "faithful".to_string(),
+                    ))
+                } else {
+                    warn!(
+                        "Answer faithfulness {:.2} is below {:.2}, unsupported claims: {}",
+                        score, self.threshold, value["unsupported_claims"]
+                    );
+                    Ok(ProcessResult::new(
+                        RagState::Unfaithful,
+                        "unfaithful".to_string(),
+                    ))
+                }
+            }
+            Err(e) => Ok(ProcessResult::new(
+                RagState::GenerationError,
+                format!("faithfulness_error: {}", e),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::CannedLLM;
+
+    async fn run_node(node: &FaithfulnessNode, answer: &str) -> (RagState, Context) {
+        let mut context = Context::new();
+        context.set(
+            "retrieved_documents",
+            json!([
+                {"id": "a", "metadata": {"text": "Pangu is Alibaba's distributed storage system."}},
+                {"id": "b", "metadata": {"text": "Pangu keeps three replicas of every chunk."}}
+            ]),
+        );
+        context.set("result", json!(answer));
+        let result = node.execute(&context).await;
+        let state = node
+            .post_process(&mut context, &result)
+            .await
+            .unwrap()
+            .state;
+        (state, context)
+    }
+
+    #[tokio::test]
+    async fn test_supported_answer_passes() {
+        let judge = Arc::new(CannedLLM::new(
+            r#"{"claims": [
+                {"claim": "Pangu is Alibaba's storage system", "supported": true},
+                {"claim": "Pangu keeps three replicas", "supported": true}
+            ]}"#,
+        ));
+        let node = FaithfulnessNode::new(judge.clone());
+        let (state, context) = run_node(
+            &node,
+            "Pangu is Alibaba's storage system and keeps three replicas.",
+        )
+        .await;
+
+        assert_eq!(state, RagState::Default);
+        let report = context.get(FAITHFULNESS_KEY).unwrap();
+        assert_eq!(report["score"], json!(1.0));
+        assert_eq!(report["unsupported_claims"], json!([]));
+        assert!(judge.prompts()[0].contains("three replicas of every chunk"));
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_answer_routes_to_unfaithful() {
+        let judge = Arc::new(CannedLLM::new(
+            "```json\n{\"claims\": [\
+                {\"claim\": \"Pangu is Alibaba's storage system\", \"supported\": true},\
+                {\"claim\": \"Pangu was released in 1998\", \"supported\": false}\
+            ]}\n```",
+        ));
+        let node = FaithfulnessNode::new(judge);
+        let (state, context) = run_node(
+            &node,
+            "Pangu is Alibaba's storage system, released in 1998.",
+        )
+        .await;
+
+        assert_eq!(state, RagState::Unfaithful);
+        let report = context.get(FAITHFULNESS_KEY).unwrap();
+        assert_eq!(report["score"], json!(0.5));
+        assert_eq!(
+            report["unsupported_claims"],
+            json!(["Pangu was released in 1998"])
+        );
+
+        // The same answer passes a lenient threshold
+        let judge = Arc::new(CannedLLM::new(
+            r#"{"claims": [{"claim": "a", "supported": true}, {"claim": "b", "supported": false}]}"#,
+        ));
+        let node = FaithfulnessNode::new(judge).with_threshold(0.5);
+        let (state, _) = run_node(&node, "Pangu is Alibaba's storage system.").await;
+        assert_eq!(state, RagState::Default);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_judge_reply_is_an_error() {
+        let answer = "Pangu is Alibaba's storage system.";
+        for reply in [
+            r#"{"claims": [{"text": "Pangu is a storage system", "verdict": "yes"}]}"#,
+            r#"{"claims": [{"claim": "a", "supported": true}, {"claim": "b"}]}"#,
+            r#"{"claims": []}"#,
+        ] {
+            let node = FaithfulnessNode::new(Arc::new(CannedLLM::new(reply)));
+            let (state, context) = run_node(&node, answer).await;
+
+            assert_eq!(state, RagState::GenerationError, "reply {}", reply);
+            assert!(context.get(FAITHFULNESS_KEY).is_none());
+        }
+    }
+}
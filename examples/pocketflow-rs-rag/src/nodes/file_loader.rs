@@ -1,51 +1,184 @@
+use super::validate_sources::VALIDATED_URLS_KEY;
+use crate::limits::Limit;
 use crate::state::RagState;
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use docx_rs::{DocumentChild, Table, TableCellContent, TableChild, TableRowChild};
 use pdf_extract::extract_text;
-use pocketflow_rs::{Context as FlowContext, Node, ProcessResult};
-use reqwest::Client;
+use pocketflow_rs::utils::content_fetcher::{ContentFetcher, FetchOptions};
+use pocketflow_rs::{Context as FlowContext, Document, Node, ProcessResult, ProgressReporter};
 use serde_json::{Value, json};
 use std::fs;
 use std::path::Path;
 use std::sync::Arc;
 use std::time::SystemTime;
-use tracing::info;
+use tracing::{info, warn};
 
-#[derive(Debug)]
-struct Document {
-    content: String,
-    metadata: Value,
+fn loaded_document(content: String, url: &str, file_type: &str) -> Document {
+    let metadata = json!({
+        "url": url,
+        "file_type": file_type,
+        "timestamp": SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        "content_length": content.len(),
+    });
+    Document::new(content, metadata)
 }
 
-impl Document {
-    fn new(content: String, url: &str, file_type: &str) -> Self {
-        let metadata = json!({
-            "url": url,
-            "file_type": file_type,
-            "timestamp": SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_secs(),
-            "content_length": content.len(),
-        });
-        Self { content, metadata }
+/// Context metadata key listing the urls a lenient loader failed to load.
+pub const FAILED_URLS_KEY: &str = "failed_urls";
+
+const DOCX_CONTENT_TYPE: &str =
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+
+/// How `FileLoaderNode` turns a CSV file into documents. Each row is written as
+/// `header: value` pairs, empty values left out, and the metadata gets the file's
+/// number of `rows`, its header excluded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CsvMode {
+    /// One document per file, with a line per row.
+    #[default]
+    Flatten,
+    /// One document per row, with its `row` index, starting at 0, in the metadata.
+    PerRow,
+}
+
+/// The rows of a CSV file with a header line, as text.
+fn csv_rows(bytes: &[u8]) -> Result<Vec<String>> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(bytes);
+    let headers = reader.headers()?.clone();
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let fields: Vec<String> = record
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| !value.trim().is_empty())
+            .map(|(i, value)| match headers.get(i) {
+                Some(header) if !header.is_empty() => format!("{}: {}", header, value.trim()),
+                _ => format!("column {}: {}", i + 1, value.trim()),
+            })
+            .collect();
+        rows.push(fields.join(", "));
+    }
+    Ok(rows)
+}
+
+/// The text of a `.docx` file: a line per paragraph, and a line per table row with
+/// its cells separated by ` | `.
+fn docx_text(bytes: &[u8]) -> Result<String> {
+    let docx =
+        docx_rs::read_docx(bytes).map_err(|e| anyhow::anyhow!("Invalid DOCX file: {}", e))?;
+    let mut lines = Vec::new();
+    for child in &docx.document.children {
+        match child {
+            DocumentChild::Paragraph(paragraph) => lines.push(paragraph.raw_text()),
+            DocumentChild::Table(table) => table_lines(table, &mut lines),
+            _ => {}
+        }
     }
+    Ok(lines.join("\n"))
 }
 
+fn table_lines(table: &Table, lines: &mut Vec<String>) {
+    for TableChild::TableRow(row) in &table.rows {
+        let mut cells = Vec::new();
+        for TableRowChild::TableCell(cell) in &row.cells {
+            let mut text = Vec::new();
+            for content in &cell.children {
+                match content {
+                    TableCellContent::Paragraph(paragraph) => text.push(paragraph.raw_text()),
+                    TableCellContent::Table(table) => table_lines(table, lines),
+                    _ => {}
+                }
+            }
+            cells.push(text.join(" "));
+        }
+        lines.push(cells.join(" | "));
+    }
+}
+
+/// Loads documents from local paths and web urls. Outputs
+/// `{"documents": [...], "failed_urls": [{"url", "error"}]}`. If a
+/// `ValidateSourcesNode` ran first, the normalized urls it passed are loaded instead
+/// of the ones given here.
 pub struct FileLoaderNode {
     urls: Vec<String>,
-    client: Arc<Client>,
+    fetcher: Arc<ContentFetcher>,
+    fetch_options: FetchOptions,
+    client: Option<Arc<reqwest::Client>>,
+    max_documents: Option<Limit>,
+    lenient: bool,
+    progress: ProgressReporter,
+    csv_mode: CsvMode,
 }
 
 impl FileLoaderNode {
+    /// A strict loader: any url failing to load fails the node.
     pub fn new(urls: Vec<String>) -> Self {
         Self {
             urls,
-            client: Arc::new(Client::new()),
+            fetcher: Arc::new(ContentFetcher::new(FetchOptions::default())),
+            fetch_options: FetchOptions::default(),
+            client: None,
+            max_documents: None,
+            lenient: false,
+            progress: ProgressReporter::default(),
+            csv_mode: CsvMode::default(),
+        }
+    }
+
+    /// A lenient loader: failed urls are recorded under [`FAILED_URLS_KEY`] in the
+    /// context metadata, and the node only fails if no url loads.
+    pub fn lenient(urls: Vec<String>) -> Self {
+        Self {
+            lenient: true,
+            ..Self::new(urls)
+        }
+    }
+
+    pub fn with_max_documents(mut self, limit: Limit) -> Self {
+        self.max_documents = Some(limit);
+        self
+    }
+
+    pub fn with_csv_mode(mut self, csv_mode: CsvMode) -> Self {
+        self.csv_mode = csv_mode;
+        self
+    }
+
+    /// Report each url handled, loaded or not, as `files` progress.
+    pub fn with_progress(mut self, progress: ProgressReporter) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Set the user agent, per-host delay and robots.txt handling for web urls.
+    pub fn with_fetch_options(mut self, options: FetchOptions) -> Self {
+        self.fetch_options = options;
+        self.rebuild_fetcher();
+        self
+    }
+
+    /// Fetch web urls through `client`, e.g. one shared across the app or set up
+    /// with a proxy.
+    pub fn with_client(mut self, client: Arc<reqwest::Client>) -> Self {
+        self.client = Some(client);
+        self.rebuild_fetcher();
+        self
+    }
+
+    fn rebuild_fetcher(&mut self) {
+        let mut fetcher = ContentFetcher::new(self.fetch_options.clone());
+        if let Some(client) = &self.client {
+            fetcher = fetcher.with_client(client.clone());
         }
+        self.fetcher = Arc::new(fetcher);
     }
 
-    fn detect_file_type(path: &Path) -> Result<&'static str> {
+    pub(crate) fn detect_file_type(path: &Path) -> Result<&'static str> {
         let extension = path
             .extension()
             .and_then(|ext| ext.to_str())
@@ -54,18 +187,50 @@ impl FileLoaderNode {
         match extension.to_lowercase().as_str() {
             "pdf" => Ok("pdf"),
             "txt" => Ok("text"),
+            "csv" => Ok("csv"),
+            "docx" => Ok("docx"),
             _ => Err(anyhow::anyhow!("Unsupported file type: {}", extension)),
         }
     }
 
-    async fn load_from_url(&self, url: &str) -> Result<Document> {
+    fn csv_documents(&self, bytes: &[u8], url: &str) -> Result<Vec<Document>> {
+        let rows = csv_rows(bytes)?;
+        let total = rows.len();
+        let documents = match self.csv_mode {
+            CsvMode::Flatten => vec![loaded_document(rows.join("\n"), url, "csv")],
+            CsvMode::PerRow => rows
+                .into_iter()
+                .enumerate()
+                .map(|(row, content)| {
+                    let mut doc = loaded_document(content, url, "csv");
+                    doc.metadata["row"] = json!(row);
+                    doc
+                })
+                .collect(),
+        };
+        Ok(documents
+            .into_iter()
+            .map(|mut doc| {
+                doc.metadata["rows"] = json!(total);
+                doc
+            })
+            .collect())
+    }
+
+    /// Returns no documents for web urls skipped because robots.txt disallows
+    /// them, and one per row for CSV files loaded with [`CsvMode::PerRow`].
+    async fn load_from_url(&self, url: &str) -> Result<Vec<Document>> {
         info!("Loading content from URL: {}", url);
         if url.starts_with("http://") || url.starts_with("https://") {
-            let response = self.client.get(url).send().await?;
+            let Some(response) = self.fetcher.fetch_content(url).await? else {
+                return Ok(Vec::new());
+            };
+            // Without parameters such as the charset
             let content_type = response
                 .headers()
                 .get("content-type")
-                .map(|header| header.to_str().unwrap_or("text/plain"));
+                .map(|header| header.to_str().unwrap_or("text/plain"))
+                .map(|content_type| content_type.split(';').next().unwrap_or_default().trim());
 
             let mut file_type = "web";
             let content = match content_type {
@@ -75,10 +240,19 @@ impl FileLoaderNode {
                     file_type = "pdf";
                     pdf_extract::extract_text_from_mem(&bytes)?
                 }
+                Some("text/csv") => {
+                    let bytes = response.bytes().await?;
+                    return self.csv_documents(&bytes, url);
+                }
+                Some(DOCX_CONTENT_TYPE) => {
+                    let bytes = response.bytes().await?;
+                    file_type = "docx";
+                    docx_text(&bytes)?
+                }
                 _ => response.text().await?,
             };
 
-            Ok(Document::new(content, url, file_type))
+            Ok(vec![loaded_document(content, url, file_type)])
         } else {
             info!("Loading content from local file: {}", url);
             let path = Path::new(url);
@@ -88,9 +262,20 @@ impl FileLoaderNode {
                     .with_context(|| format!("Failed to extract text from PDF: {:?}", path))?,
                 "text" => fs::read_to_string(path)
                     .with_context(|| format!("Failed to read text file: {:?}", path))?,
+                "csv" => {
+                    let bytes = fs::read(path)
+                        .with_context(|| format!("Failed to read CSV file: {:?}", path))?;
+                    return self
+                        .csv_documents(&bytes, url)
+                        .with_context(|| format!("Failed to parse CSV file: {:?}", path));
+                }
+                "docx" => fs::read(path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|bytes| docx_text(&bytes))
+                    .with_context(|| format!("Failed to extract text from DOCX: {:?}", path))?,
                 _ => unreachable!(),
             };
-            Ok(Document::new(content, url, file_type))
+            Ok(vec![loaded_document(content, url, file_type)])
         }
     }
 }
@@ -99,27 +284,60 @@ impl FileLoaderNode {
 impl Node for FileLoaderNode {
     type State = RagState;
 
-    #[allow(unused_variables)]
+    fn name(&self) -> &str {
+        "FileLoader"
+    }
+
     async fn execute(&self, context: &FlowContext) -> Result<Value> {
         let mut documents = Vec::new();
+        let mut failed_urls = Vec::new();
 
-        for url in &self.urls {
-            let doc = self
+        // Check the cap before fetching anything
+        let validated: Option<Vec<String>> = context.get_as(VALIDATED_URLS_KEY).ok();
+        let urls: Vec<&String> = validated.as_ref().unwrap_or(&self.urls).iter().collect();
+        let urls = match &self.max_documents {
+            Some(limit) => limit.apply(urls, "documents")?,
+            None => urls,
+        };
+
+        let total = urls.len();
+        for (index, url) in urls.into_iter().enumerate() {
+            let loaded = self
                 .load_from_url(url)
                 .await
-                .with_context(|| format!("Failed to load content from URL: {}", url))?;
-            info!("Document loaded: {:?}", doc.metadata);
-            documents.push(json!({
-                "content": doc.content,
-                "metadata": doc.metadata
-            }));
+                .with_context(|| format!("Failed to load content from URL: {}", url));
+            self.progress
+                .report(self.name(), "files", index + 1, Some(total));
+            let loaded = match loaded {
+                Ok(loaded) => loaded,
+                Err(e) if self.lenient => {
+                    warn!("{:#}", e);
+                    failed_urls.push(json!({"url": url, "error": format!("{:#}", e)}));
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            for doc in loaded {
+                info!("Document loaded: {:?}", doc.metadata);
+                documents.push(doc);
+            }
         }
 
         if documents.is_empty() {
-            return Err(anyhow::anyhow!("No documents loaded from any URL"));
+            return Err(anyhow::anyhow!(
+                "No documents loaded from any URL{}",
+                if failed_urls.is_empty() {
+                    String::new()
+                } else {
+                    format!(": {}", Value::Array(failed_urls))
+                }
+            ));
         }
 
-        Ok(Value::Array(documents))
+        Ok(json!({
+            "documents": documents,
+            "failed_urls": failed_urls,
+        }))
     }
 
     async fn post_process(
@@ -129,7 +347,11 @@ impl Node for FileLoaderNode {
     ) -> Result<ProcessResult<RagState>> {
         match result {
             Ok(value) => {
-                context.set("documents", value.clone());
+                context.set("documents", value["documents"].clone());
+                let failed_urls = &value["failed_urls"];
+                if failed_urls.as_array().is_some_and(|urls| !urls.is_empty()) {
+                    context.set_metadata(FAILED_URLS_KEY, failed_urls.clone());
+                }
                 Ok(ProcessResult::new(
                     RagState::Default,
                     "documents_loaded".to_string(),
@@ -146,6 +368,7 @@ impl Node for FileLoaderNode {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::limits::LimitPolicy;
     use std::fs::File;
     use std::io::Write;
     use tempfile::tempdir;
@@ -165,7 +388,7 @@ mod tests {
         let result = loader.execute(&FlowContext::new()).await.unwrap();
 
         // Verify the result
-        let documents = result.as_array().unwrap();
+        let documents = result["documents"].as_array().unwrap();
         assert_eq!(documents.len(), 1);
 
         let doc = &documents[0];
@@ -190,8 +413,8 @@ mod tests {
         let result = loader.execute(&FlowContext::new()).await;
 
         if let Ok(result) = result {
-            let documents = result.as_array().unwrap();
-            assert!(documents.len() > 0);
+            let documents = result["documents"].as_array().unwrap();
+            assert!(!documents.is_empty());
 
             for doc in documents {
                 assert!(doc["content"].is_string());
@@ -222,4 +445,162 @@ mod tests {
                 .contains("Failed to load content from URL")
         );
     }
+
+    fn write_text_files(dir: &Path, count: usize) -> Vec<String> {
+        (0..count)
+            .map(|i| {
+                let file_path = dir.join(format!("test_{}.txt", i));
+                let mut file = File::create(&file_path).unwrap();
+                writeln!(file, "Content {}", i).unwrap();
+                file_path.to_str().unwrap().to_string()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_max_documents_error() {
+        let dir = tempdir().unwrap();
+        let urls = write_text_files(dir.path(), 3);
+
+        let loader =
+            FileLoaderNode::new(urls).with_max_documents(Limit::new(2, LimitPolicy::Error));
+        let result = loader.execute(&FlowContext::new()).await;
+
+        assert!(result.is_err());
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Too many documents")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_max_documents_truncate() {
+        let dir = tempdir().unwrap();
+        let urls = write_text_files(dir.path(), 3);
+
+        let loader =
+            FileLoaderNode::new(urls).with_max_documents(Limit::new(2, LimitPolicy::Truncate));
+        let result = loader.execute(&FlowContext::new()).await.unwrap();
+
+        let documents = result["documents"].as_array().unwrap();
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[0]["content"].as_str().unwrap(), "Content 0\n");
+    }
+
+    #[tokio::test]
+    async fn test_max_documents_below_limit() {
+        let dir = tempdir().unwrap();
+        let urls = write_text_files(dir.path(), 3);
+
+        let loader =
+            FileLoaderNode::new(urls).with_max_documents(Limit::new(3, LimitPolicy::Error));
+        let result = loader.execute(&FlowContext::new()).await.unwrap();
+
+        assert_eq!(result["documents"].as_array().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_lenient_records_failed_urls() {
+        let dir = tempdir().unwrap();
+        let mut urls = write_text_files(dir.path(), 1);
+        let missing = dir.path().join("missing.txt").to_str().unwrap().to_string();
+        urls.push(missing.clone());
+
+        let loader = FileLoaderNode::lenient(urls);
+        let mut context = FlowContext::new();
+        let result = loader.execute(&context).await;
+        loader.post_process(&mut context, &result).await.unwrap();
+
+        let documents = context.get("documents").unwrap().as_array().unwrap();
+        assert_eq!(documents.len(), 1);
+        assert_eq!(documents[0]["content"].as_str().unwrap(), "Content 0\n");
+
+        let failed = context
+            .get_metadata(FAILED_URLS_KEY)
+            .unwrap()
+            .as_array()
+            .unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0]["url"].as_str().unwrap(), missing);
+    }
+
+    #[tokio::test]
+    async fn test_lenient_errors_when_all_fail() {
+        let dir = tempdir().unwrap();
+        let missing = dir.path().join("missing.txt").to_str().unwrap().to_string();
+
+        let loader = FileLoaderNode::lenient(vec![missing]);
+        let result = loader.execute(&FlowContext::new()).await;
+
+        assert!(result.is_err());
+    }
+
+    fn write_csv(dir: &std::path::Path) -> String {
+        let path = dir.join("people.csv");
+        let mut file = File::create(&path).unwrap();
+        write!(file, "name,city,\nAda,London,extra\nLinus,,\n").unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_load_csv_flattened() {
+        let dir = tempdir().unwrap();
+        let loader = FileLoaderNode::new(vec![write_csv(dir.path())]);
+
+        let result = loader.execute(&FlowContext::new()).await.unwrap();
+
+        let documents = result["documents"].as_array().unwrap();
+        assert_eq!(documents.len(), 1);
+        assert_eq!(
+            documents[0]["content"],
+            json!("name: Ada, city: London, column 3: extra\nname: Linus")
+        );
+        assert_eq!(documents[0]["metadata"]["file_type"], json!("csv"));
+        assert_eq!(documents[0]["metadata"]["rows"], json!(2));
+    }
+
+    #[tokio::test]
+    async fn test_load_csv_per_row() {
+        let dir = tempdir().unwrap();
+        let url = write_csv(dir.path());
+        let loader = FileLoaderNode::new(vec![url.clone()]).with_csv_mode(CsvMode::PerRow);
+
+        let result = loader.execute(&FlowContext::new()).await.unwrap();
+
+        let documents = result["documents"].as_array().unwrap();
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[1]["content"], json!("name: Linus"));
+        assert_eq!(documents[1]["metadata"]["row"], json!(1));
+        assert_eq!(documents[1]["metadata"]["rows"], json!(2));
+        assert_eq!(documents[1]["metadata"]["url"], json!(url));
+    }
+
+    #[tokio::test]
+    async fn test_load_docx_file() {
+        use docx_rs::{Docx, Paragraph, Run, Table, TableCell, TableRow};
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("report.docx");
+        let cell = |text: &str| {
+            TableCell::new().add_paragraph(Paragraph::new().add_run(Run::new().add_text(text)))
+        };
+        Docx::new()
+            .add_paragraph(Paragraph::new().add_run(Run::new().add_text("Quarterly report")))
+            .add_table(Table::new(vec![TableRow::new(vec![
+                cell("Revenue"),
+                cell("42"),
+            ])]))
+            .build()
+            .pack(File::create(&path).unwrap())
+            .unwrap();
+
+        let loader = FileLoaderNode::new(vec![path.to_str().unwrap().to_string()]);
+        let result = loader.execute(&FlowContext::new()).await.unwrap();
+
+        let doc = &result["documents"][0];
+        assert_eq!(doc["content"], json!("Quarterly report\nRevenue | 42"));
+        assert_eq!(doc["metadata"]["file_type"], json!("docx"));
+    }
 }
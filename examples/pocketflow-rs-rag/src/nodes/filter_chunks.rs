@@ -0,0 +1,224 @@
+use crate::state::RagState;
+use anyhow::Result;
+use async_trait::async_trait;
+use pocketflow_rs::utils::text_chunking::{
+    ChunkingOptions, ChunkingStrategy, TextChunker, count_tokens,
+};
+use pocketflow_rs::{Context, Node, ProcessResult};
+use serde_json::{Value, json};
+use tracing::info;
+use uuid::Uuid;
+
+/// Context metadata key recording how many chunks [`FilterChunksNode`] dropped and split.
+pub const CHUNK_FILTER_STATS_KEY: &str = "chunk_filter_stats";
+
+/// Drops chunks in `documents_chunked` shorter than a minimum length and splits ones
+/// over a maximum into smaller parts.
+pub struct FilterChunksNode {
+    chunker: TextChunker,
+    min_chars: usize,
+    min_tokens: usize,
+    max_chars: Option<usize>,
+}
+
+impl Default for FilterChunksNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FilterChunksNode {
+    pub fn new() -> Self {
+        Self {
+            chunker: TextChunker::new(),
+            min_chars: 0,
+            min_tokens: 0,
+            max_chars: None,
+        }
+    }
+
+    pub fn with_min_chars(mut self, min_chars: usize) -> Self {
+        self.min_chars = min_chars;
+        self
+    }
+
+    pub fn with_min_tokens(mut self, min_tokens: usize) -> Self {
+        self.min_tokens = min_tokens;
+        self
+    }
+
+    /// Split chunks longer than `max_chars` at whitespace into parts of at most that size.
+    pub fn with_max_chars(mut self, max_chars: usize) -> Self {
+        self.max_chars = Some(max_chars);
+        self
+    }
+
+    fn is_too_short(&self, text: &str) -> bool {
+        text.chars().count() < self.min_chars || count_tokens(text) < self.min_tokens
+    }
+
+    fn split(&self, chunk: &Value, text: &str, max_chars: usize) -> Vec<Value> {
+        let options = ChunkingOptions {
+            chunk_size: max_chars,
+            overlap: 0,
+            strategy: ChunkingStrategy::FixedSize,
+            ..ChunkingOptions::default()
+        };
+        let id = chunk["id"].as_str().unwrap_or_default();
+        self.chunker
+            .chunk_text(text, &options)
+            .into_iter()
+            .enumerate()
+            .map(|(part, part_text)| {
+                let mut record = chunk.clone();
+                record["id"] = json!(
+                    Uuid::new_v5(&Uuid::NAMESPACE_URL, format!("{}#{}", id, part).as_bytes())
+                        .to_string()
+                );
+                record["text"] = json!(part_text);
+                record["part"] = json!(part);
+                record
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Node for FilterChunksNode {
+    type State = RagState;
+
+    fn name(&self) -> &str {
+        "FilterChunks"
+    }
+
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let chunks = context.get_array("documents_chunked")?;
+
+        let mut kept = Vec::with_capacity(chunks.len());
+        let mut dropped_short = 0;
+        let mut split_long = 0;
+        for chunk in chunks {
+            let text = chunk
+                .get("text")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| anyhow::anyhow!("No text found in chunk"))?;
+            if self.is_too_short(text) {
+                dropped_short += 1;
+                continue;
+            }
+            match self.max_chars {
+                Some(max_chars) if text.len() > max_chars => {
+                    split_long += 1;
+                    kept.extend(self.split(chunk, text, max_chars));
+                }
+                _ => kept.push(chunk.clone()),
+            }
+        }
+        info!(
+            "Kept {} chunks, dropped {} short, split {} long",
+            kept.len(),
+            dropped_short,
+            split_long
+        );
+
+        Ok(json!({
+            "chunks": kept,
+            "dropped_short": dropped_short,
+            "split_long": split_long,
+        }))
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<RagState>> {
+        match result {
+            Ok(value) => {
+                context.set("documents_chunked", value["chunks"].clone());
+                context.set_metadata(
+                    CHUNK_FILTER_STATS_KEY,
+                    json!({
+                        "dropped_short": value["dropped_short"],
+                        "split_long": value["split_long"],
+                    }),
+                );
+                Ok(ProcessResult::new(
+                    RagState::Default,
+                    "documents_chunked".to_string(),
+                ))
+            }
+            Err(e) => Ok(ProcessResult::new(
+                RagState::ChunkingError,
+                format!("chunking_error: {}", e),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context_with_chunks(texts: &[&str]) -> Context {
+        let chunks: Vec<Value> = texts
+            .iter()
+            .enumerate()
+            .map(|(i, text)| json!({"id": format!("chunk-{}", i), "text": text, "chunk_index": i}))
+            .collect();
+        let mut context = Context::new();
+        context.set("documents_chunked", Value::Array(chunks));
+        context
+    }
+
+    #[tokio::test]
+    async fn test_drops_tiny_chunks_and_records_counts() {
+        let mut context = context_with_chunks(&[
+            "Introduction",
+            "Pangu is a distributed storage system built for scale.",
+            "---",
+            "Chunks are replicated across three storage nodes.",
+        ]);
+        let node = FilterChunksNode::new().with_min_chars(5).with_min_tokens(3);
+
+        let result = node.execute(&context).await;
+        node.post_process(&mut context, &result).await.unwrap();
+
+        let texts: Vec<&str> = context
+            .get("documents_chunked")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|chunk| chunk["text"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                "Pangu is a distributed storage system built for scale.",
+                "Chunks are replicated across three storage nodes.",
+            ]
+        );
+        assert_eq!(
+            context.get_metadata(CHUNK_FILTER_STATS_KEY),
+            Some(&json!({"dropped_short": 2, "split_long": 0}))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_splits_long_chunks() {
+        let context = context_with_chunks(&["one two three four five six", "short"]);
+        let node = FilterChunksNode::new().with_max_chars(10);
+
+        let result = node.execute(&context).await.unwrap();
+        let chunks = result["chunks"].as_array().unwrap();
+
+        assert_eq!(result["split_long"], json!(1));
+        assert!(chunks.len() > 2);
+        for chunk in chunks {
+            assert!(chunk["text"].as_str().unwrap().len() <= 10);
+        }
+        assert_ne!(chunks[0]["id"], chunks[1]["id"]);
+        assert_eq!(chunks.last().unwrap()["text"], json!("short"));
+    }
+}
@@ -0,0 +1,199 @@
+use crate::state::RagState;
+use anyhow::Result;
+use async_trait::async_trait;
+use pocketflow_rs::vector_db::VectorRecord;
+use pocketflow_rs::{Context, Node, ProcessResult};
+use serde_json::Value;
+use std::collections::HashMap;
+use tracing::info;
+
+/// Context key holding one list of retrieved records per sub-query, e.g. from
+/// multi-query expansion or HyDE.
+pub const SUB_QUERY_RESULTS_KEY: &str = "sub_query_results";
+
+/// How the scores of a chunk retrieved by several sub-queries are combined.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FusionStrategy {
+    /// The best score any sub-query gave the chunk.
+    MaxScore,
+    /// Reciprocal rank fusion: the sum of `1 / (k + rank)` over the sub-queries that
+    /// retrieved the chunk, with ranks starting at 1. Ignores the raw scores, so it
+    /// also fuses lists whose scores are not comparable.
+    Rrf(f32),
+}
+
+impl Default for FusionStrategy {
+    fn default() -> Self {
+        FusionStrategy::Rrf(60.0)
+    }
+}
+
+/// Unions the result lists under [`SUB_QUERY_RESULTS_KEY`] by record id, so a chunk
+/// found by several sub-queries appears once, with its fused score. Writes the
+/// fused records, best first, to `retrieved_documents` for reranking or answering.
+pub struct FuseResultsNode {
+    strategy: FusionStrategy,
+    top_k: Option<usize>,
+}
+
+impl FuseResultsNode {
+    pub fn new(strategy: FusionStrategy) -> Self {
+        Self {
+            strategy,
+            top_k: None,
+        }
+    }
+
+    /// Keep only the `top_k` best fused records.
+    pub fn with_top_k(mut self, top_k: usize) -> Self {
+        self.top_k = Some(top_k);
+        self
+    }
+
+    fn fuse(&self, result_sets: Vec<Vec<VectorRecord>>) -> Vec<VectorRecord> {
+        // Records in first-seen order, so ties keep the earlier sub-queries' order
+        let mut fused: Vec<VectorRecord> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for results in result_sets {
+            for (rank, mut record) in results.into_iter().enumerate() {
+                let score = match self.strategy {
+                    FusionStrategy::MaxScore => record.score.unwrap_or(f32::NEG_INFINITY),
+                    FusionStrategy::Rrf(k) => 1.0 / (k + rank as f32 + 1.0),
+                };
+                match positions.get(&record.id) {
+                    Some(&position) => {
+                        let existing = &mut fused[position];
+                        let current = existing.score.unwrap_or(f32::NEG_INFINITY);
+                        existing.score = Some(match self.strategy {
+                            FusionStrategy::MaxScore => current.max(score),
+                            FusionStrategy::Rrf(_) => current + score,
+                        });
+                    }
+                    None => {
+                        record.score = Some(score);
+                        positions.insert(record.id.clone(), fused.len());
+                        fused.push(record);
+                    }
+                }
+            }
+        }
+
+        fused.sort_by(|a, b| b.score.unwrap().total_cmp(&a.score.unwrap()));
+        if let Some(top_k) = self.top_k {
+            fused.truncate(top_k);
+        }
+        fused
+    }
+}
+
+#[async_trait]
+impl Node for FuseResultsNode {
+    type State = RagState;
+
+    fn name(&self) -> &str {
+        "FuseResults"
+    }
+
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let result_sets = context
+            .get_array(SUB_QUERY_RESULTS_KEY)?
+            .iter()
+            .map(|results| {
+                Ok(serde_json::from_value::<Vec<VectorRecord>>(
+                    results.clone(),
+                )?)
+            })
+            .collect::<Result<Vec<Vec<VectorRecord>>>>()?;
+        let total: usize = result_sets.iter().map(Vec::len).sum();
+
+        let fused = self.fuse(result_sets);
+        info!("Fused {} results into {}", total, fused.len());
+        Ok(Value::Array(
+            fused.iter().map(VectorRecord::to_value).collect(),
+        ))
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<RagState>> {
+        match result {
+            Ok(value) => {
+                context.set("retrieved_documents", value.clone());
+                Ok(ProcessResult::new(
+                    RagState::Default,
+                    "results_fused".to_string(),
+                ))
+            }
+            Err(e) => Ok(ProcessResult::new(
+                RagState::RetrievalError,
+                format!("retrieval_error: {}", e),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn record(id: &str, score: f32) -> Value {
+        json!({"id": id, "vector": [], "metadata": {"text": id}, "score": score})
+    }
+
+    async fn run_node(node: &FuseResultsNode) -> Vec<(String, f32)> {
+        let mut context = Context::new();
+        context.set(
+            SUB_QUERY_RESULTS_KEY,
+            json!([
+                [record("a", 0.9), record("b", 0.8), record("c", 0.7)],
+                [record("b", 0.95), record("d", 0.6), record("a", 0.5)]
+            ]),
+        );
+        let result = node.execute(&context).await;
+        node.post_process(&mut context, &result).await.unwrap();
+        context
+            .get("retrieved_documents")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| {
+                (
+                    r["id"].as_str().unwrap().to_string(),
+                    r["score"].as_f64().unwrap() as f32,
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_max_score_fusion() {
+        let fused = run_node(&FuseResultsNode::new(FusionStrategy::MaxScore)).await;
+
+        assert_eq!(
+            fused,
+            vec![
+                ("b".to_string(), 0.95),
+                ("a".to_string(), 0.9),
+                ("c".to_string(), 0.7),
+                ("d".to_string(), 0.6),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rrf_fusion() {
+        let node = FuseResultsNode::new(FusionStrategy::Rrf(60.0)).with_top_k(3);
+        let fused = run_node(&node).await;
+
+        let ids: Vec<&str> = fused.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, vec!["b", "a", "d"]);
+        let rrf = |ranks: &[f32]| ranks.iter().map(|rank| 1.0 / (60.0 + rank)).sum::<f32>();
+        assert!((fused[0].1 - rrf(&[2.0, 1.0])).abs() < 1e-6);
+        assert!((fused[1].1 - rrf(&[1.0, 3.0])).abs() < 1e-6);
+        assert!((fused[2].1 - rrf(&[2.0])).abs() < 1e-6);
+    }
+}
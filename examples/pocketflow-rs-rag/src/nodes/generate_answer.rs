@@ -1,46 +1,146 @@
 use crate::state::RagState;
 use anyhow::Result;
 use async_trait::async_trait;
-use pocketflow_rs::utils::llm_wrapper::{LLMWrapper, OpenAIClient};
+use pocketflow_rs::utils::llm_wrapper::{LLMOptions, LLMWrapper, OpenAIClient};
+use pocketflow_rs::utils::prompt_template::PromptTemplate;
 use pocketflow_rs::vector_db::VectorRecord;
-use pocketflow_rs::{Context, Node, ProcessResult};
-use serde_json::Value;
+use pocketflow_rs::{Context, Node, NodeConfig, ProcessResult, RetryPolicy};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::io::Write;
 use std::sync::Arc;
+use tracing::warn;
 
+/// The default answer prompt, filled in with the retrieved `{{sources}}` and the
+/// `{{question}}`.
+pub const DEFAULT_ANSWER_PROMPT: &str = "
+You are a helpful assistant. Based on the following context, please answer the question. If the answer cannot be found in the context, say 'I don't know'.\n\n
+Output format using markdown and add reference links to the source documents. \n\n
+You can use the following context to answer the question: \n{{sources}}\n\n
+Question: {{question}}\n\n
+Answer:";
+
+/// The default answer prompt with citations, filled in with the numbered
+/// `{{sources}}` and the `{{question}}`.
+pub const CITATIONS_ANSWER_PROMPT: &str = "
+You are a helpful assistant. Based on the following numbered sources, please answer the question. If the answer cannot be found in the sources, say 'I don't know'.\n\n
+Cite the sources you use inline by their number in square brackets, e.g. [1] or [1][2], right after the statement they support. Do not add a list of sources at the end.\n\n
+Sources: \n{{sources}}\n\n
+Question: {{question}}\n\n
+Answer:";
+
+/// Answers the query from the retrieved documents. Set `RAG_STREAM=1` (or the
+/// `stream` param) to print the answer to stdout as it is generated; only an
+/// OpenAI client streams, others answer in one piece.
+///
+/// With [`GenerateAnswerNode::with_citations`] the sources are numbered, the model
+/// cites them inline like `[1]`, and the result is `{"answer", "citations": [{"n",
+/// "url", "text"}], "invalid_citations": [...]}` listing the cited sources and any
+/// cited numbers that match none.
 pub struct GenerateAnswerNode {
-    client: Arc<OpenAIClient>,
+    client: Arc<dyn LLMWrapper>,
+    streaming_client: Option<Arc<OpenAIClient>>,
     query: String,
+    citations: bool,
+    prompt: Option<PromptTemplate>,
+    config: NodeConfig,
+    retry_policy: RetryPolicy,
 }
 
 impl GenerateAnswerNode {
     pub fn new(api_key: String, model: String, endpoint: String, query: String) -> Self {
+        Self::from_openai(Arc::new(OpenAIClient::new(api_key, model, endpoint)), query)
+    }
+
+    pub fn from_openai(client: Arc<OpenAIClient>, query: String) -> Self {
         Self {
-            client: Arc::new(OpenAIClient::new(api_key, model, endpoint)),
+            streaming_client: Some(client.clone()),
+            ..Self::from_client(client, query)
+        }
+    }
+
+    pub fn from_client(client: Arc<dyn LLMWrapper>, query: String) -> Self {
+        Self {
+            client,
+            streaming_client: None,
             query,
+            citations: false,
+            prompt: None,
+            config: NodeConfig::new("RAG_"),
+            retry_policy: RetryPolicy::default(),
         }
     }
+
+    pub fn with_config(mut self, config: NodeConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Answer with inline numbered citations, see [`GenerateAnswerNode`].
+    pub fn with_citations(mut self) -> Self {
+        self.citations = true;
+        self
+    }
+
+    /// Answer with `prompt` instead of [`DEFAULT_ANSWER_PROMPT`] or, with citations,
+    /// [`CITATIONS_ANSWER_PROMPT`]. It is filled in with the `{{sources}}`, numbered
+    /// like `[1] url: text` with citations, and the `{{question}}`.
+    pub fn with_prompt(mut self, prompt: PromptTemplate) -> Self {
+        self.prompt = Some(prompt);
+        self
+    }
+
+    /// Retry transient LLM failures such as rate limits.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+}
+
+/// The numbers cited in `answer` as `[1]` or `[1, 2]`, in order of first citation.
+fn cited_numbers(answer: &str) -> Vec<usize> {
+    let mut numbers: Vec<usize> = Vec::new();
+    for (start, _) in answer.match_indices('[') {
+        let Some(end) = answer[start..].find(']') else {
+            break;
+        };
+        let cited: Option<Vec<usize>> = answer[start + 1..start + end]
+            .split(',')
+            .map(|n| n.trim().parse().ok())
+            .collect();
+        for n in cited.into_iter().flatten() {
+            if !numbers.contains(&n) {
+                numbers.push(n);
+            }
+        }
+    }
+    numbers
 }
 
 #[async_trait]
 impl Node for GenerateAnswerNode {
     type State = RagState;
 
+    fn name(&self) -> &str {
+        "GenerateAnswer"
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy.clone()
+    }
+
     async fn execute(&self, context: &Context) -> Result<Value> {
-        let retrieved_docs = context
-            .get("retrieved_documents")
-            .and_then(|v| v.as_array())
-            .ok_or_else(|| anyhow::anyhow!("No retrieved documents found in context"))?;
+        let retrieved_docs = context.get_array("retrieved_documents")?;
 
         let retrieved_docs_array: Vec<VectorRecord> = retrieved_docs
             .iter()
             .map(VectorRecord::parse_by_value)
             .collect();
 
-        let retrieved_text_with_meta = retrieved_docs_array
+        let sources = retrieved_docs_array
             .iter()
             .map(|v| {
-                format!(
-                    "{}: {}",
+                (
                     v.metadata
                         .get("file_metadata")
                         .unwrap()
@@ -48,32 +148,91 @@ impl Node for GenerateAnswerNode {
                         .unwrap()
                         .as_str()
                         .unwrap(),
-                    v.metadata.get("text").unwrap()
+                    v.metadata.get("text").unwrap(),
                 )
             })
-            .collect::<Vec<_>>()
-            .join("\n\n");
+            .collect::<Vec<_>>();
 
-        if retrieved_text_with_meta.is_empty() {
-            return Ok(Value::String("I don't know.".to_string()));
+        if sources.is_empty() {
+            let answer = Value::String("I don't know.".to_string());
+            if self.citations {
+                return Ok(json!({"answer": answer, "citations": [], "invalid_citations": []}));
+            }
+            return Ok(answer);
         }
 
-        let prompt = format!("
-You are a helpful assistant. Based on the following context, please answer the question. If the answer cannot be found in the context, say 'I don't know'.\n\n
-Output format using markdown and add reference links to the source documents. \n\n
-You can use the following context to answer the question: \n{}\n\n
-Question: {}\n\n
-Answer:",
-        retrieved_text_with_meta,
-            self.query
-        );
+        let (default_prompt, sources_text) = if self.citations {
+            let numbered_sources = sources
+                .iter()
+                .enumerate()
+                .map(|(i, (url, text))| {
+                    format!("[{}] {}: {}", i + 1, url, text.as_str().unwrap_or_default())
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            (CITATIONS_ANSWER_PROMPT, numbered_sources)
+        } else {
+            let retrieved_text_with_meta = sources
+                .iter()
+                .map(|(url, text)| format!("{}: {}", url, text))
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            (DEFAULT_ANSWER_PROMPT, retrieved_text_with_meta)
+        };
+        let vars = HashMap::from([
+            ("sources".to_string(), sources_text),
+            ("question".to_string(), self.query.clone()),
+        ]);
+        let prompt = match &self.prompt {
+            Some(prompt) => prompt.render(&vars)?,
+            None => PromptTemplate::new(default_prompt).render(&vars)?,
+        };
 
-        let response = self.client.generate(&prompt).await?;
+        let streaming_client = self
+            .streaming_client
+            .as_ref()
+            .filter(|_| self.config.get_bool("stream", false));
+        let response = if let Some(client) = streaming_client {
+            let response = client
+                .generate_with_callback(&prompt, LLMOptions::default(), |token| {
+                    print!("{}", token);
+                    std::io::stdout().flush().ok();
+                })
+                .await?;
+            println!();
+            response
+        } else {
+            self.client.generate(&prompt).await?
+        };
         if response.content.is_empty() {
             return Err(anyhow::anyhow!("Empty response from LLM"));
         }
 
-        Ok(Value::String(response.content.trim().to_string()))
+        let answer = response.content.trim().to_string();
+        if !self.citations {
+            return Ok(Value::String(answer));
+        }
+
+        let mut citations = Vec::new();
+        let mut invalid_citations = Vec::new();
+        for n in cited_numbers(&answer) {
+            match n.checked_sub(1).and_then(|i| sources.get(i)) {
+                Some((url, text)) => citations.push(json!({"n": n, "url": url, "text": text})),
+                None => invalid_citations.push(n),
+            }
+        }
+        if !invalid_citations.is_empty() {
+            warn!(
+                "Answer cites {:?}, but there are only {} sources",
+                invalid_citations,
+                sources.len()
+            );
+        }
+        Ok(json!({
+            "answer": answer,
+            "citations": citations,
+            "invalid_citations": invalid_citations,
+        }))
     }
 
     async fn post_process(
@@ -96,3 +255,83 @@ Answer:",
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::CannedLLM;
+
+    /// Answers every prompt with `answer`, checking the prompt lists the sources.
+    fn answering(answer: &'static str) -> Arc<CannedLLM> {
+        Arc::new(CannedLLM::replying(move |prompt| {
+            assert!(prompt.contains("[2] fuxi.pdf: Fuxi schedules the jobs."));
+            Ok(answer.to_string())
+        }))
+    }
+
+    fn retrieved_context() -> Context {
+        let mut context = Context::new();
+        context.set(
+            "retrieved_documents",
+            json!([
+                {"id": "1", "vector": [], "metadata": {
+                    "text": "Pangu keeps three replicas.",
+                    "file_metadata": {"url": "pangu.pdf"}}},
+                {"id": "2", "vector": [], "metadata": {
+                    "text": "Fuxi schedules the jobs.",
+                    "file_metadata": {"url": "fuxi.pdf"}}},
+            ]),
+        );
+        context
+    }
+
+    #[tokio::test]
+    async fn test_citations_map_numbers_to_sources() {
+        let llm =
+            answering("Pangu keeps three copies [1], Fuxi runs the jobs [2][1] and more [3, 2].");
+        let node = GenerateAnswerNode::from_client(llm, "What keeps data safe?".to_string())
+            .with_citations();
+        let mut context = retrieved_context();
+
+        let result = node.execute(&context).await;
+        node.post_process(&mut context, &result).await.unwrap();
+
+        assert_eq!(
+            context.get("result").unwrap(),
+            &json!({
+                "answer": "Pangu keeps three copies [1], Fuxi runs the jobs [2][1] and more [3, 2].",
+                "citations": [
+                    {"n": 1, "url": "pangu.pdf", "text": "Pangu keeps three replicas."},
+                    {"n": 2, "url": "fuxi.pdf", "text": "Fuxi schedules the jobs."},
+                ],
+                "invalid_citations": [3],
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_custom_prompt() {
+        let llm = answering("Fuxi [2].");
+        let node = GenerateAnswerNode::from_client(llm.clone(), "Who schedules?".to_string())
+            .with_citations()
+            .with_prompt(PromptTemplate::new("{{sources}}\n\nQ: {{question}}"));
+
+        let result = node.execute(&retrieved_context()).await.unwrap();
+        assert_eq!(result["answer"], json!("Fuxi [2]."));
+
+        // A variable the node doesn't provide fails before calling the LLM
+        let node = GenerateAnswerNode::from_client(llm, "Who schedules?".to_string())
+            .with_prompt(PromptTemplate::new("{{context}}\n\nQ: {{question}}"));
+        let error = node.execute(&retrieved_context()).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Missing prompt template variables: context"
+        );
+    }
+
+    #[test]
+    fn test_cited_numbers() {
+        assert_eq!(cited_numbers("a [2] b [0][2, 1] [x] [1"), vec![2, 0, 1]);
+        assert!(cited_numbers("no citations").is_empty());
+    }
+}
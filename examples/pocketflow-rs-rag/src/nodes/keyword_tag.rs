@@ -0,0 +1,268 @@
+use crate::state::RagState;
+use anyhow::Result;
+use async_trait::async_trait;
+use pocketflow_rs::utils::bm25::tokenize;
+use pocketflow_rs::utils::llm_wrapper::LLMWrapper;
+use pocketflow_rs::{Context, Document, Node, ProcessResult};
+use serde_json::{Value, json};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tracing::info;
+
+/// Words too common to be keywords.
+const STOP_WORDS: &[&str] = &[
+    "about", "after", "all", "also", "and", "any", "are", "because", "been", "but", "can", "could",
+    "does", "each", "for", "from", "had", "has", "have", "her", "his", "how", "into", "its",
+    "more", "most", "not", "one", "only", "other", "our", "out", "over", "she", "should", "some",
+    "such", "than", "that", "the", "their", "them", "then", "there", "these", "they", "this",
+    "those", "through", "too", "very", "was", "way", "were", "what", "when", "where", "which",
+    "while", "who", "why", "will", "with", "would", "you", "your",
+];
+
+/// The longest document text sent to the LLM, in bytes.
+const MAX_LLM_INPUT: usize = 8000;
+
+/// How `KeywordTagNode` picks keywords.
+#[derive(Clone)]
+pub enum KeywordMethod {
+    /// The terms with the highest TF-IDF over the loaded documents: frequent in the
+    /// document, rare across the others.
+    TfIdf,
+    /// Keywords and key phrases chosen by an LLM, one call per document.
+    Llm(Arc<dyn LLMWrapper>),
+}
+
+/// Tags each loaded document with up to `max_keywords` keywords, stored as a
+/// `keywords` array in its metadata. Placed between `FileLoaderNode` and
+/// `ChunkDocumentsNode`, the keywords end up in every chunk's `file_metadata`,
+/// where searches can filter on them.
+pub struct KeywordTagNode {
+    method: KeywordMethod,
+    max_keywords: usize,
+}
+
+impl KeywordTagNode {
+    pub fn new(method: KeywordMethod) -> Self {
+        Self {
+            method,
+            max_keywords: 5,
+        }
+    }
+
+    pub fn with_max_keywords(mut self, max_keywords: usize) -> Self {
+        self.max_keywords = max_keywords;
+        self
+    }
+
+    fn tf_idf_keywords(&self, documents: &[Document]) -> Vec<Vec<String>> {
+        let terms: Vec<Vec<String>> = documents
+            .iter()
+            .map(|doc| {
+                tokenize(&doc.content)
+                    .into_iter()
+                    .filter(|term| {
+                        term.chars().count() > 2
+                            && !term.chars().all(|c| c.is_numeric())
+                            && !STOP_WORDS.contains(&term.as_str())
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut document_frequency: HashMap<&str, usize> = HashMap::new();
+        for doc_terms in &terms {
+            for term in doc_terms.iter().collect::<HashSet<_>>() {
+                *document_frequency.entry(term).or_default() += 1;
+            }
+        }
+
+        let total = documents.len() as f64;
+        terms
+            .iter()
+            .map(|doc_terms| {
+                let mut counts: HashMap<&str, usize> = HashMap::new();
+                for term in doc_terms {
+                    *counts.entry(term).or_default() += 1;
+                }
+                let mut scored: Vec<(&str, f64)> = counts
+                    .into_iter()
+                    .map(|(term, count)| {
+                        let tf = count as f64 / doc_terms.len() as f64;
+                        let idf =
+                            ((total + 1.0) / (document_frequency[term] as f64 + 1.0)).ln() + 1.0;
+                        (term, tf * idf)
+                    })
+                    .collect();
+                scored.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(b.0)));
+                scored
+                    .into_iter()
+                    .take(self.max_keywords)
+                    .map(|(term, _)| term.to_string())
+                    .collect()
+            })
+            .collect()
+    }
+
+    async fn llm_keywords(&self, client: &dyn LLMWrapper, doc: &Document) -> Result<Vec<String>> {
+        let mut end = MAX_LLM_INPUT.min(doc.content.len());
+        while !doc.content.is_char_boundary(end) {
+            end -= 1;
+        }
+        let prompt = format!(
+            "Extract the {} most important keywords or short key phrases from the following document, \
+             the terms someone would search for to find it.\n\
+             Respond with a comma-separated list only, most important first.\n\n\
+             Document:\n{}\n\nKeywords:",
+            self.max_keywords,
+            &doc.content[..end]
+        );
+        let response = client.generate(&prompt).await?;
+
+        let mut keywords: Vec<String> = Vec::new();
+        for keyword in response.content.split([',', '\n']) {
+            let keyword = keyword
+                .trim()
+                .trim_start_matches(['-', '*'])
+                .trim_matches(|c: char| c.is_whitespace() || c == '"' || c == '\'' || c == '.')
+                .to_lowercase();
+            if !keyword.is_empty() && !keywords.contains(&keyword) {
+                keywords.push(keyword);
+            }
+        }
+        keywords.truncate(self.max_keywords);
+        Ok(keywords)
+    }
+}
+
+#[async_trait]
+impl Node for KeywordTagNode {
+    type State = RagState;
+
+    fn name(&self) -> &str {
+        "KeywordTag"
+    }
+
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let mut documents = Document::list_from_context(context, "documents")?;
+
+        let keywords = match &self.method {
+            KeywordMethod::TfIdf => self.tf_idf_keywords(&documents),
+            KeywordMethod::Llm(client) => {
+                let mut keywords = Vec::with_capacity(documents.len());
+                for doc in &documents {
+                    keywords.push(self.llm_keywords(client.as_ref(), doc).await?);
+                }
+                keywords
+            }
+        };
+
+        for (doc, keywords) in documents.iter_mut().zip(keywords) {
+            info!("Keywords of {:?}: {:?}", doc.url(), keywords);
+            if !doc.metadata.is_object() {
+                doc.metadata = json!({});
+            }
+            doc.metadata["keywords"] = json!(keywords);
+        }
+        Ok(Value::Array(
+            documents.iter().map(Document::to_value).collect(),
+        ))
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<RagState>> {
+        match result {
+            Ok(value) => {
+                context.set("documents", value.clone());
+                Ok(ProcessResult::new(
+                    RagState::Default,
+                    "documents_tagged".to_string(),
+                ))
+            }
+            Err(e) => Ok(ProcessResult::new(
+                RagState::KeywordTagError,
+                format!("keyword_tag_error: {}", e),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::CannedLLM;
+
+    fn corpus_context() -> Context {
+        let mut context = Context::new();
+        let documents = [
+            (
+                "pangu.txt",
+                "Pangu is the distributed storage system. Pangu stores every chunk on three \
+                 replicas, and Pangu keeps the replicas of a chunk on different racks.",
+            ),
+            (
+                "fuxi.txt",
+                "Fuxi is the scheduling system. Fuxi schedules the jobs of the cluster and \
+                 restarts the jobs that fail.",
+            ),
+            (
+                "nuwa.txt",
+                "Nuwa is the coordination system. Nuwa elects the masters of the cluster.",
+            ),
+        ]
+        .map(|(url, content)| Document::new(content, json!({"url": url})));
+        Document::list_to_context(&mut context, "documents", &documents);
+        context
+    }
+
+    fn keywords(context: &Context, index: usize) -> Vec<String> {
+        context.get_array("documents").unwrap()[index]["metadata"]["keywords"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|keyword| keyword.as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_tf_idf_tags_salient_terms() {
+        let node = KeywordTagNode::new(KeywordMethod::TfIdf).with_max_keywords(3);
+        let mut context = corpus_context();
+
+        let result = node.execute(&context).await;
+        node.post_process(&mut context, &result).await.unwrap();
+
+        assert_eq!(keywords(&context, 0), vec!["pangu", "chunk", "replicas"]);
+        assert_eq!(keywords(&context, 1), vec!["fuxi", "jobs", "fail"]);
+        assert_eq!(keywords(&context, 2)[0], "nuwa");
+        // Terms every document shares never outrank ones unique to it
+        for index in 0..3 {
+            assert!(!keywords(&context, index).contains(&"system".to_string()));
+        }
+        let documents = context.get_array("documents").unwrap();
+        assert_eq!(documents[0]["metadata"]["url"], json!("pangu.txt"));
+    }
+
+    #[tokio::test]
+    async fn test_llm_keywords_are_parsed_and_attached() {
+        let llm = CannedLLM::replying(|prompt| {
+            Ok(if prompt.contains("Pangu") {
+                "Pangu, distributed storage, \"Replicas\", pangu".to_string()
+            } else {
+                "- scheduling\n- jobs.".to_string()
+            })
+        });
+        let node = KeywordTagNode::new(KeywordMethod::Llm(Arc::new(llm)));
+        let mut context = corpus_context();
+
+        let result = node.execute(&context).await;
+        node.post_process(&mut context, &result).await.unwrap();
+
+        assert_eq!(
+            keywords(&context, 0),
+            vec!["pangu", "distributed storage", "replicas"]
+        );
+        assert_eq!(keywords(&context, 1), vec!["scheduling", "jobs"]);
+    }
+}
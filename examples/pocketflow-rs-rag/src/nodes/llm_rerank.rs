@@ -0,0 +1,209 @@
+use crate::state::RagState;
+use anyhow::Result;
+use async_trait::async_trait;
+use pocketflow_rs::utils::llm_wrapper::{LLMOptions, LLMWrapper, strip_code_fence};
+use pocketflow_rs::{Context, Node, ProcessResult};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+const DEFAULT_TOP_N: usize = 3;
+/// Score given to chunks whose relevance the LLM output does not make clear.
+const FALLBACK_SCORE: f64 = 0.0;
+
+/// Asks an LLM to judge each retrieved chunk's relevance to the query on a 0-10
+/// scale, in a single prompt, then reorders `retrieved_documents` by that score and
+/// keeps the top `top_n`. Each kept record gets a `rerank_score` field.
+pub struct LLMRerankNode {
+    client: Arc<dyn LLMWrapper>,
+    top_n: usize,
+}
+
+impl LLMRerankNode {
+    pub fn new(client: Arc<dyn LLMWrapper>) -> Self {
+        Self {
+            client,
+            top_n: DEFAULT_TOP_N,
+        }
+    }
+
+    pub fn with_top_n(mut self, top_n: usize) -> Self {
+        self.top_n = top_n;
+        self
+    }
+
+    fn build_prompt(query: &str, texts: &[&str]) -> String {
+        let passages = texts
+            .iter()
+            .enumerate()
+            .map(|(i, text)| format!("[{}] {}", i, text))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        format!(
+            "Rate how relevant each passage is to the question on a scale from 0 (irrelevant) \
+             to 10 (fully answers it).\n\nQuestion: {}\n\nPassages:\n{}\n\n\
+             Respond with ONLY a JSON array of {} numbers, one score per passage in order.",
+            query,
+            passages,
+            texts.len()
+        )
+    }
+
+    /// Parse one score per passage. A JSON array is expected; otherwise the last
+    /// number on each non-empty line is used. Missing or invalid scores fall back low.
+    fn parse_scores(content: &str, count: usize) -> Vec<f64> {
+        let content = strip_code_fence(content);
+
+        let scores: Vec<Option<f64>> = match serde_json::from_str::<Vec<Value>>(content) {
+            Ok(items) => items.iter().map(|v| v.as_f64()).collect(),
+            Err(_) => {
+                warn!("Rerank scores are not a JSON array, parsing line by line");
+                content
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .map(|line| {
+                        // Drop a leading "[i]" passage index
+                        let line = match line.trim().strip_prefix('[') {
+                            Some(rest) => rest.split_once(']').map_or(rest, |(_, score)| score),
+                            None => line,
+                        };
+                        line.split(|c: char| !(c.is_ascii_digit() || c == '.'))
+                            .filter_map(|token| token.parse::<f64>().ok())
+                            .next_back()
+                    })
+                    .collect()
+            }
+        };
+
+        (0..count)
+            .map(|i| {
+                scores
+                    .get(i)
+                    .copied()
+                    .flatten()
+                    .filter(|score| score.is_finite())
+                    .map_or(FALLBACK_SCORE, |score| score.clamp(0.0, 10.0))
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Node for LLMRerankNode {
+    type State = RagState;
+
+    fn name(&self) -> &str {
+        "LLMRerank"
+    }
+
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let query = context.get_str("user_query")?;
+        let documents = context.get_array("retrieved_documents")?;
+
+        let texts: Vec<&str> = documents
+            .iter()
+            .map(|doc| doc["metadata"]["text"].as_str().unwrap_or_default())
+            .collect();
+        let options = LLMOptions::deterministic();
+        let response = self
+            .client
+            .generate_with_options(&Self::build_prompt(query, &texts), options)
+            .await?;
+        let scores = Self::parse_scores(&response.content, documents.len());
+        info!("Rerank scores: {:?}", scores);
+
+        let mut scored: Vec<(f64, Value)> =
+            scores.into_iter().zip(documents.iter().cloned()).collect();
+        // Stable sort, so equally scored chunks keep their retrieval order
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let reranked = scored
+            .into_iter()
+            .take(self.top_n)
+            .map(|(score, mut doc)| {
+                doc["rerank_score"] = json!(score);
+                doc
+            })
+            .collect();
+        Ok(Value::Array(reranked))
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<RagState>> {
+        match result {
+            Ok(value) => {
+                context.set("retrieved_documents", value.clone());
+                Ok(ProcessResult::new(
+                    RagState::Default,
+                    "documents_reranked".to_string(),
+                ))
+            }
+            Err(e) => Ok(ProcessResult::new(
+                RagState::RerankError,
+                format!("rerank_error: {}", e),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::CannedLLM;
+
+    fn context_with_documents() -> Context {
+        let mut context = Context::new();
+        context.set("user_query", json!("What is Pangu?"));
+        context.set(
+            "retrieved_documents",
+            json!([
+                {"id": "a", "metadata": {"text": "Weather report"}, "score": 0.9},
+                {"id": "b", "metadata": {"text": "Pangu is a storage system"}, "score": 0.8},
+                {"id": "c", "metadata": {"text": "Pangu 2.0 design"}, "score": 0.7},
+                {"id": "d", "metadata": {"text": "Cooking recipes"}, "score": 0.6}
+            ]),
+        );
+        context
+    }
+
+    async fn rerank(content: &str, top_n: usize) -> Vec<Value> {
+        let node = LLMRerankNode::new(Arc::new(CannedLLM::new(content))).with_top_n(top_n);
+        let mut context = context_with_documents();
+        let result = node.execute(&context).await;
+        node.post_process(&mut context, &result).await.unwrap();
+        context
+            .get("retrieved_documents")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .clone()
+    }
+
+    fn ids(docs: &[Value]) -> Vec<&str> {
+        docs.iter().map(|doc| doc["id"].as_str().unwrap()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_rerank_reorders_and_trims() {
+        let docs = rerank("[1, 9, 7.5, 2]", 2).await;
+
+        assert_eq!(ids(&docs), vec!["b", "c"]);
+        assert_eq!(docs[0]["rerank_score"], json!(9.0));
+        assert_eq!(docs[1]["rerank_score"], json!(7.5));
+    }
+
+    #[tokio::test]
+    async fn test_unparseable_scores_default_low() {
+        let docs = rerank("[\"high\", 6, null]", 4).await;
+
+        assert_eq!(ids(&docs), vec!["b", "a", "c", "d"]);
+        assert_eq!(docs[1]["rerank_score"], json!(0.0));
+
+        let docs = rerank("[0] 3\n[1] score: n/a\n[2] 8", 3).await;
+        assert_eq!(ids(&docs), vec!["c", "a", "b"]);
+        assert_eq!(docs[2]["rerank_score"], json!(0.0));
+    }
+}
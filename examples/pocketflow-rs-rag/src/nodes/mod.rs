@@ -1,17 +1,140 @@
+mod answer_quality;
+mod build_bm25_index;
 mod chunk_documents;
+mod context_compression;
 mod create_index;
+mod dedup_chunks;
+mod degraded_answer;
 mod embed_documents;
 mod embed_query;
+mod export_collection;
+mod faithfulness;
 mod file_loader;
+mod filter_chunks;
+mod fuse_results;
 mod generate_answer;
+mod import_collection;
+mod keyword_tag;
+mod llm_rerank;
+mod prune_index;
 mod query_rewrite;
+mod reembed_collection;
 mod retrieve_document;
+mod search_and_index;
+mod semantic_cache;
+mod suggest_followups;
+mod summarize_history;
+mod translate;
+mod validate_sources;
 
+pub use answer_quality::AnswerQualityNode;
+pub use build_bm25_index::BuildBm25IndexNode;
 pub use chunk_documents::ChunkDocumentsNode;
+pub use context_compression::ContextCompressionNode;
 pub use create_index::CreateIndexNode;
+pub use dedup_chunks::{DEDUP_STATS_KEY, DedupChunksNode, DedupStrategy};
+pub use degraded_answer::{DEGRADED_KEY, DegradedAnswerNode};
 pub use embed_documents::EmbedDocumentsNode;
 pub use embed_query::EmbedQueryNode;
-pub use file_loader::FileLoaderNode;
-pub use generate_answer::GenerateAnswerNode;
-pub use query_rewrite::QueryRewriteNode;
+pub use export_collection::ExportCollectionNode;
+pub use faithfulness::{FAITHFULNESS_KEY, FaithfulnessNode};
+pub use file_loader::{CsvMode, FileLoaderNode};
+pub use filter_chunks::{CHUNK_FILTER_STATS_KEY, FilterChunksNode};
+pub use fuse_results::{FuseResultsNode, FusionStrategy, SUB_QUERY_RESULTS_KEY};
+pub use generate_answer::{CITATIONS_ANSWER_PROMPT, DEFAULT_ANSWER_PROMPT, GenerateAnswerNode};
+pub use import_collection::ImportCollectionNode;
+pub use keyword_tag::{KeywordMethod, KeywordTagNode};
+pub use llm_rerank::LLMRerankNode;
+pub use prune_index::PruneIndexNode;
+pub use query_rewrite::{DEFAULT_REWRITE_PROMPT, QueryRewriteNode};
+pub use reembed_collection::ReembedCollectionNode;
 pub use retrieve_document::RetrieveDocumentNode;
+pub use search_and_index::SearchAndIndexNode;
+pub use semantic_cache::{SEMANTIC_CACHE_EMBEDDING_KEY, SemanticCacheNode};
+pub use suggest_followups::SuggestFollowupsNode;
+pub use summarize_history::SummarizeHistoryNode;
+pub use translate::TranslateNode;
+pub use validate_sources::{SOURCE_REPORT_KEY, VALIDATED_URLS_KEY, ValidateSourcesNode};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::RagState;
+    use crate::test_utils::memory_db;
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use pocketflow_rs::utils::embedding::EmbeddingGenerator;
+    use pocketflow_rs::utils::text_chunking::ChunkingStrategy;
+    use pocketflow_rs::{Context, ProgressReporter, build_flow};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    struct ConstantEmbeddingGenerator;
+
+    #[async_trait]
+    impl EmbeddingGenerator for ConstantEmbeddingGenerator {
+        async fn generate_embedding(&self, _text: &str) -> Result<Vec<f64>> {
+            Ok(vec![0.6, 0.8])
+        }
+
+        async fn generate_embeddings(&self, texts: &[String]) -> Result<Vec<Vec<f64>>> {
+            Ok(vec![vec![0.6, 0.8]; texts.len()])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_offline_flow_reports_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let files: Vec<String> = (0..3)
+            .map(|i| {
+                let path = dir.path().join(format!("doc_{}.txt", i));
+                std::fs::write(
+                    &path,
+                    format!("Document {} first. Document {} second.", i, i),
+                )
+                .unwrap();
+                path.to_str().unwrap().to_string()
+            })
+            .collect();
+        let db = memory_db(2);
+        let (progress, mut events) = ProgressReporter::channel();
+
+        let flow = build_flow!(
+            start: ("file_loader", FileLoaderNode::new(files).with_progress(progress.clone())),
+            nodes: [
+                ("chunk_documents", ChunkDocumentsNode::new(20, 0, ChunkingStrategy::Sentence)
+                    .with_progress(progress.clone())),
+                ("embed_documents", EmbedDocumentsNode::from_generator(Arc::new(ConstantEmbeddingGenerator))
+                    .with_progress(progress.clone())),
+                ("create_index", CreateIndexNode::from_db(db.clone()).with_progress(progress))
+            ],
+            edges: [
+                ("file_loader", "chunk_documents", RagState::Default),
+                ("chunk_documents", "embed_documents", RagState::Default),
+                ("embed_documents", "create_index", RagState::Default)
+            ]
+        );
+        flow.run(Context::new()).await.unwrap();
+        drop(flow);
+
+        let mut last = HashMap::new();
+        let mut file_counts = Vec::new();
+        while let Some(event) = events.recv().await {
+            if event.unit == "files" {
+                file_counts.push(event.completed);
+            }
+            last.insert(event.node.clone(), event);
+        }
+
+        assert_eq!(file_counts, vec![1, 2, 3]);
+        assert_eq!(last["FileLoader"].total, Some(3));
+        assert_eq!(last["ChunkDocuments"].unit, "chunks");
+        assert_eq!(last["ChunkDocuments"].completed, 6);
+        assert_eq!(last["EmbedDocuments"].unit, "embeddings");
+        assert_eq!(last["EmbedDocuments"].completed, 6);
+        assert_eq!(last["EmbedDocuments"].total, Some(6));
+        assert_eq!(last["CreateIndex"].unit, "records");
+        assert_eq!(last["CreateIndex"].completed, 6);
+        assert_eq!(db.len(), 6);
+    }
+}
@@ -0,0 +1,251 @@
+use crate::state::RagState;
+use anyhow::Result;
+use async_trait::async_trait;
+use pocketflow_rs::utils::vector_db::{VectorDB, VectorRecord};
+use pocketflow_rs::{Context, Node, ProcessResult};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+/// Removes stale records from a collection: those whose `file_metadata.timestamp`
+/// is older than `max_age`, and, with the source check on, those whose
+/// `file_metadata.url` no longer resolves. A local path resolves while the file
+/// exists; a web url resolves unless it answers 404 or 410, so a site that is only
+/// down keeps its records. Outputs `{"scanned", "expired", "missing_source",
+/// "deleted"}` under `pruned`.
+pub struct PruneIndexNode {
+    db: Arc<dyn VectorDB>,
+    max_age: Option<Duration>,
+    check_sources: bool,
+    client: Arc<reqwest::Client>,
+    batch_size: usize,
+}
+
+impl PruneIndexNode {
+    /// A node that prunes nothing until a policy is set with
+    /// [`PruneIndexNode::with_max_age`] or [`PruneIndexNode::with_source_check`].
+    pub fn new(db: Arc<dyn VectorDB>) -> Self {
+        Self {
+            db,
+            max_age: None,
+            check_sources: false,
+            client: Arc::new(reqwest::Client::new()),
+            batch_size: 100,
+        }
+    }
+
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    pub fn with_source_check(mut self, check_sources: bool) -> Self {
+        self.check_sources = check_sources;
+        self
+    }
+
+    pub fn with_client(mut self, client: Arc<reqwest::Client>) -> Self {
+        self.client = client;
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    fn is_expired(&self, record: &VectorRecord, now: u64) -> bool {
+        let (Some(max_age), Some(timestamp)) = (
+            self.max_age,
+            record.metadata["file_metadata"]["timestamp"].as_u64(),
+        ) else {
+            return false;
+        };
+        now.saturating_sub(timestamp) > max_age.as_secs()
+    }
+
+    async fn source_exists(&self, url: &str) -> bool {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Path::new(url.strip_prefix("file://").unwrap_or(url)).exists();
+        }
+        match self.client.head(url).send().await {
+            Ok(response) => !matches!(response.status(), StatusCode::NOT_FOUND | StatusCode::GONE),
+            Err(e) => {
+                warn!("Could not check source {}, keeping its records: {}", url, e);
+                true
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Node for PruneIndexNode {
+    type State = RagState;
+
+    fn name(&self) -> &str {
+        "PruneIndex"
+    }
+
+    #[allow(unused_variables)]
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        // Sources are checked once, however many chunks they have
+        let mut sources: HashMap<String, bool> = HashMap::new();
+        let mut stale = Vec::new();
+        let mut scanned = 0;
+        let mut expired = 0;
+        let mut missing_source = 0;
+        let mut offset = None;
+
+        loop {
+            let page = self.db.scroll(offset, self.batch_size).await?;
+            scanned += page.records.len();
+            for record in page.records {
+                if self.is_expired(&record, now) {
+                    expired += 1;
+                    stale.push(record.id);
+                    continue;
+                }
+                let Some(url) = record.metadata["file_metadata"]["url"].as_str() else {
+                    continue;
+                };
+                if !self.check_sources {
+                    continue;
+                }
+                let exists = match sources.get(url) {
+                    Some(exists) => *exists,
+                    None => {
+                        let exists = self.source_exists(url).await;
+                        sources.insert(url.to_string(), exists);
+                        exists
+                    }
+                };
+                if !exists {
+                    missing_source += 1;
+                    stale.push(record.id);
+                }
+            }
+
+            match page.next_offset {
+                Some(next) => offset = Some(next),
+                None => break,
+            }
+        }
+
+        // Deleted after scrolling so the pages don't shift under the scroll
+        let deleted = stale.len();
+        if !stale.is_empty() {
+            self.db.delete(stale).await?;
+        }
+        info!(
+            "Pruned {} of {} records ({} expired, {} with missing sources)",
+            deleted, scanned, expired, missing_source
+        );
+
+        Ok(json!({
+            "scanned": scanned,
+            "expired": expired,
+            "missing_source": missing_source,
+            "deleted": deleted,
+        }))
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<RagState>> {
+        match result {
+            Ok(value) => {
+                context.set("pruned", value.clone());
+                Ok(ProcessResult::new(
+                    RagState::Default,
+                    "index_pruned".to_string(),
+                ))
+            }
+            Err(e) => Ok(ProcessResult::new(
+                RagState::PruneError,
+                format!("prune_error: {}", e),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::memory_db;
+
+    fn record(id: &str, url: &str, timestamp: u64) -> VectorRecord {
+        VectorRecord {
+            id: id.to_string(),
+            vector: vec![1.0, 0.0],
+            sparse_vector: None,
+            metadata: serde_json::Map::from_iter(vec![(
+                "file_metadata".to_string(),
+                json!({"url": url, "timestamp": timestamp}),
+            )]),
+            score: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prunes_expired_records_and_missing_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let kept = dir.path().join("kept.txt");
+        std::fs::write(&kept, "Pangu stores every chunk three times.").unwrap();
+        let kept = kept.to_str().unwrap();
+        let deleted = dir.path().join("deleted.txt");
+        let deleted = deleted.to_str().unwrap();
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let db = memory_db(2);
+        db.insert(vec![
+            record("fresh-0", kept, now),
+            record("fresh-1", kept, now - 60),
+            record("old-0", kept, now - 30 * 24 * 3600),
+            record("gone-0", deleted, now),
+            record("gone-1", deleted, now),
+        ])
+        .await
+        .unwrap();
+
+        // Age alone leaves records of deleted files alone
+        let node = PruneIndexNode::new(db.clone())
+            .with_max_age(Duration::from_secs(7 * 24 * 3600))
+            .with_batch_size(2);
+        let mut context = Context::new();
+        let result = node.execute(&context).await;
+        node.post_process(&mut context, &result).await.unwrap();
+        assert_eq!(
+            context.get("pruned").unwrap(),
+            &json!({"scanned": 5, "expired": 1, "missing_source": 0, "deleted": 1})
+        );
+
+        let node = node.with_source_check(true);
+        let result = node.execute(&context).await.unwrap();
+        assert_eq!(
+            result,
+            json!({"scanned": 4, "expired": 0, "missing_source": 2, "deleted": 2})
+        );
+        let mut ids: Vec<String> = db
+            .scroll(None, 10)
+            .await
+            .unwrap()
+            .records
+            .into_iter()
+            .map(|record| record.id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec!["fresh-0", "fresh-1"]);
+    }
+}
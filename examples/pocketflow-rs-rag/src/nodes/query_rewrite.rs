@@ -1,31 +1,23 @@
 use crate::state::RagState;
 use anyhow::Result;
 use async_trait::async_trait;
-use pocketflow_rs::utils::llm_wrapper::{LLMWrapper, OpenAIClient};
+use pocketflow_rs::utils::llm_wrapper::{LLMWrapper, LLMWrapperExt, OpenAIClient};
+use pocketflow_rs::utils::prompt_template::PromptTemplate;
 use pocketflow_rs::{Context, Node, ProcessResult};
-use serde_json::Value;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::HashMap;
 use std::sync::Arc;
 use tracing::info;
 
-pub struct QueryRewriteNode {
-    client: Arc<OpenAIClient>,
+/// The rewrite the model is asked to reply with.
+#[derive(Deserialize)]
+struct RewrittenQuery {
+    query: String,
 }
 
-impl QueryRewriteNode {
-    pub fn new(api_key: String, model: String, endpoint: String) -> Self {
-        Self {
-            client: Arc::new(OpenAIClient::new(api_key, model, endpoint)),
-        }
-    }
-}
-
-#[async_trait]
-impl Node for QueryRewriteNode {
-    type State = RagState;
-
-    async fn execute(&self, context: &Context) -> Result<Value> {
-        let user_query = context.get("user_query").unwrap();
-        let prompt = format!("
+/// The default rewrite prompt, filled in with the `{{query}}` to rewrite.
+pub const DEFAULT_REWRITE_PROMPT: &str = "
 **Role:** You are an AI Query Enhancer for a Retrieval-Augmented Generation (RAG) system.
 
 **Goal:** Your task is to take a raw user query and rewrite it into an optimized query string suitable for vector database search. This involves identifying the user's core intent and transforming the query into a concise, keyword-focused format that maximizes the chances of retrieving relevant documents.
@@ -41,27 +33,71 @@ impl Node for QueryRewriteNode {
 5.  **Consider Expansion (Optional but Recommended):** If the original query is very sparse or could benefit from clarification, cautiously add 1-2 highly relevant synonyms or closely related terms that specify the intent further (e.g., adding \"nutrition\" if the query is just \"apples\"). Avoid overly broad expansion.
 6.  **Format for Embedding:** The final rewritten query should be a simple string, optimized for being turned into a vector embedding for semantic search.
 
-**Output:** Respond with ONLY the rewritten query string. Do not include any explanations or introductory text.
+**Output:** Respond with a JSON object whose only field, \"query\", is the rewritten query string. Do not include any explanations or introductory text.
 
 **Example 1:**
 Original User Query: \"Hey, could you tell me about the financial performance of Tesla last year?\"
-Rewritten Query: `Tesla financial performance 2024 earnings report revenue analysis`
+Rewritten Query: {\"query\": \"Tesla financial performance 2024 earnings report revenue analysis\"}
 
 **Example 2:**
 Original User Query: \"What's the deal with that new AI that makes pictures?\"
-Rewritten Query: `AI image generation model technology explanation diffusion transformer`
+Rewritten Query: {\"query\": \"AI image generation model technology explanation diffusion transformer\"}
 
 **Example 3:**
 Original User Query: \"I need help understanding how to mitigate risks in my supply chain in Europe.\"
-Rewritten Query: `supply chain risk mitigation strategies Europe logistics management`
+Rewritten Query: {\"query\": \"supply chain risk mitigation strategies Europe logistics management\"}
 
 **Now, process the following input:**
 
-Original User Query: \"{}\"
-Rewritten Query:",user_query);
-        let response = self.client.generate(&prompt).await?;
-        info!("Query rewritten: {:?}", response.content);
-        Ok(Value::String(response.content.replace("`", "")))
+Original User Query: \"{{query}}\"
+Rewritten Query:";
+
+pub struct QueryRewriteNode {
+    client: Arc<dyn LLMWrapper>,
+    prompt: PromptTemplate,
+}
+
+impl QueryRewriteNode {
+    pub fn new(api_key: String, model: String, endpoint: String) -> Self {
+        Self::from_client(Arc::new(OpenAIClient::new(api_key, model, endpoint)))
+    }
+
+    pub fn from_client(client: Arc<dyn LLMWrapper>) -> Self {
+        Self {
+            client,
+            prompt: PromptTemplate::new(DEFAULT_REWRITE_PROMPT),
+        }
+    }
+
+    /// Rewrite with `prompt` instead of [`DEFAULT_REWRITE_PROMPT`]. It is filled in
+    /// with the `{{query}}` and must ask for a JSON reply `{"query": "..."}`.
+    pub fn with_prompt(mut self, prompt: PromptTemplate) -> Self {
+        self.prompt = prompt;
+        self
+    }
+}
+
+#[async_trait]
+impl Node for QueryRewriteNode {
+    type State = RagState;
+
+    fn name(&self) -> &str {
+        "QueryRewrite"
+    }
+
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let user_query = context.get_str("user_query")?.to_string();
+        let prompt = self
+            .prompt
+            .render(&HashMap::from([("query".to_string(), user_query)]))?;
+        let schema = json!({
+            "type": "object",
+            "properties": {"query": {"type": "string"}},
+            "required": ["query"],
+        });
+        let rewritten: RewrittenQuery = self.client.generate_json(&prompt, Some(schema)).await?;
+        info!("Query rewritten: {:?}", rewritten.query);
+        Ok(Value::String(rewritten.query))
     }
 
     #[allow(unused_variables)]
@@ -0,0 +1,191 @@
+use crate::state::RagState;
+use anyhow::Result;
+use async_trait::async_trait;
+use pocketflow_rs::utils::embedding::EmbeddingGenerator;
+use pocketflow_rs::utils::vector_db::{VectorDB, VectorRecord};
+use pocketflow_rs::{Context, Node, ProcessResult};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use tracing::info;
+
+/// Migrates a collection to a new embedding model: every stored chunk's text is
+/// re-embedded with `generator` and upserted, with its id and payload, into `target`.
+pub struct ReembedCollectionNode {
+    source: Arc<dyn VectorDB>,
+    target: Arc<dyn VectorDB>,
+    generator: Arc<dyn EmbeddingGenerator>,
+    batch_size: usize,
+}
+
+impl ReembedCollectionNode {
+    pub fn new(
+        source: Arc<dyn VectorDB>,
+        target: Arc<dyn VectorDB>,
+        generator: Arc<dyn EmbeddingGenerator>,
+    ) -> Self {
+        Self {
+            source,
+            target,
+            generator,
+            batch_size: 100,
+        }
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+}
+
+#[async_trait]
+impl Node for ReembedCollectionNode {
+    type State = RagState;
+
+    fn name(&self) -> &str {
+        "ReembedCollection"
+    }
+
+    #[allow(unused_variables)]
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let mut offset = None;
+        let mut migrated = 0;
+        let mut dimension = None;
+
+        loop {
+            let page = self.source.scroll(offset, self.batch_size).await?;
+            if !page.records.is_empty() {
+                let texts = page
+                    .records
+                    .iter()
+                    .map(|record| {
+                        record
+                            .metadata
+                            .get("text")
+                            .and_then(|v| v.as_str())
+                            .map(|s| s.to_string())
+                            .ok_or_else(|| anyhow::anyhow!("No text found in record {}", record.id))
+                    })
+                    .collect::<Result<Vec<String>>>()?;
+
+                let embeddings = self.generator.generate_embeddings(&texts).await?;
+                if embeddings.len() != page.records.len() {
+                    return Err(anyhow::anyhow!(
+                        "Expected {} embeddings, got {}",
+                        page.records.len(),
+                        embeddings.len()
+                    ));
+                }
+
+                let records: Vec<VectorRecord> = page
+                    .records
+                    .into_iter()
+                    .zip(embeddings)
+                    .map(|(record, embedding)| VectorRecord {
+                        id: record.id,
+                        vector: embedding.into_iter().map(|x| x as f32).collect(),
+                        sparse_vector: record.sparse_vector,
+                        metadata: record.metadata,
+                        score: None,
+                    })
+                    .collect();
+                dimension = records.first().map(|record| record.vector.len());
+                migrated += records.len();
+
+                self.target.insert(records).await?;
+                info!("Re-embedded {} records", migrated);
+            }
+
+            match page.next_offset {
+                Some(next) => offset = Some(next),
+                None => break,
+            }
+        }
+
+        Ok(json!({
+            "migrated": migrated,
+            "dimension": dimension,
+        }))
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<RagState>> {
+        match result {
+            Ok(value) => {
+                context.set("reembedded", value.clone());
+                Ok(ProcessResult::new(
+                    RagState::Default,
+                    "collection_reembedded".to_string(),
+                ))
+            }
+            Err(e) => Ok(ProcessResult::new(
+                RagState::EmbeddingError,
+                format!("embedding_error: {}", e),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{memory_db, stored_records};
+
+    struct MockEmbeddingGenerator;
+
+    #[async_trait]
+    impl EmbeddingGenerator for MockEmbeddingGenerator {
+        async fn generate_embedding(&self, text: &str) -> Result<Vec<f64>> {
+            Ok(vec![text.len() as f64, 1.0, 0.0])
+        }
+
+        async fn generate_embeddings(&self, texts: &[String]) -> Result<Vec<Vec<f64>>> {
+            let mut embeddings = Vec::new();
+            for text in texts {
+                embeddings.push(self.generate_embedding(text).await?);
+            }
+            Ok(embeddings)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reembed_collection() {
+        let source = memory_db(2);
+        let records = (0..5)
+            .map(|i| VectorRecord {
+                id: format!("chunk-{}", i),
+                vector: vec![0.5, 0.5],
+                sparse_vector: None,
+                metadata: serde_json::Map::from_iter(vec![(
+                    "text".to_string(),
+                    json!(format!("text {}", i)),
+                )]),
+                score: None,
+            })
+            .collect();
+        source.insert(records).await.unwrap();
+        let target = memory_db(3);
+
+        let node = ReembedCollectionNode::new(
+            source.clone(),
+            target.clone(),
+            Arc::new(MockEmbeddingGenerator),
+        )
+        .with_batch_size(2);
+        let mut context = Context::new();
+        let result = node.execute(&context).await;
+        node.post_process(&mut context, &result).await.unwrap();
+
+        assert_eq!(
+            context.get("reembedded").unwrap(),
+            &json!({"migrated": 5, "dimension": 3})
+        );
+        let migrated = stored_records(target.as_ref()).await;
+        assert_eq!(migrated.len(), 5);
+        assert!(migrated.iter().all(|record| record.vector.len() == 3));
+        assert_eq!(migrated[4].id, "chunk-4");
+        assert_eq!(migrated[4].metadata["text"], json!("text 4"));
+    }
+}
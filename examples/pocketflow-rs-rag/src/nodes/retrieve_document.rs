@@ -1,16 +1,31 @@
+use super::dedup_chunks::cosine_similarity;
 use crate::state::RagState;
 use anyhow::Result;
 use async_trait::async_trait;
+use pocketflow_rs::utils::bm25::Bm25Index;
 use pocketflow_rs::utils::vector_db::{QdrantDB, VectorDB};
-use pocketflow_rs::vector_db::{DistanceMetric, VectorDBOptions};
+use pocketflow_rs::vector_db::{DistanceMetric, VectorDBOptions, VectorRecord};
 use pocketflow_rs::{Context, Node, ProcessResult};
 use serde_json::Value;
+use std::collections::HashMap;
 use std::sync::Arc;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
+/// Candidates searched per retrieved document in hybrid mode, for keyword scoring
+/// to rerank, or with dedup, so dropped duplicates leave `k` distinct records.
+const CANDIDATES_PER_RESULT: usize = 4;
+
+/// Rank offset of the reciprocal rank fusion in hybrid mode.
+const RRF_K: f32 = 60.0;
+
+/// Searches the vector db for the `k` records closest to `query_embedding` and
+/// sets them as `retrieved_documents`, each with its similarity `score`.
 pub struct RetrieveDocumentNode {
-    db: Arc<QdrantDB>,
+    db: Arc<dyn VectorDB>,
     k: usize,
+    min_score: Option<f32>,
+    hybrid_alpha: Option<f32>,
+    dedup_threshold: Option<f32>,
 }
 
 impl RetrieveDocumentNode {
@@ -29,13 +44,118 @@ impl RetrieveDocumentNode {
                 collection_name: collection,
                 dimension,
                 distance_metric,
+                payload_indexes: Vec::new(),
+                sparse_vector_name: None,
             },
         )
         .await?;
-        Ok(Self {
-            db: Arc::new(db),
+        Ok(Self::from_db(Arc::new(db), k))
+    }
+
+    pub fn from_db(db: Arc<dyn VectorDB>, k: usize) -> Self {
+        Self {
+            db,
             k,
-        })
+            min_score: None,
+            hybrid_alpha: None,
+            dedup_threshold: None,
+        }
+    }
+
+    /// Drop records scoring below `min_score`, so weak matches never reach
+    /// `GenerateAnswerNode`. Scores are the db's own, where higher is more similar
+    /// for cosine and dot product. For a db searching by euclidean distance, where
+    /// lower is closer, records farther than `min_score` are dropped instead. If
+    /// every record is dropped the node outputs no documents, and the answer is
+    /// "I don't know."
+    pub fn with_min_score(mut self, min_score: f32) -> Self {
+        self.min_score = Some(min_score);
+        self
+    }
+
+    /// Combine vector similarity with BM25 keyword scoring, so exact terms such as
+    /// error codes or SKUs in the `user_query` rank well. The node searches
+    /// `k * 4` candidates, scores their `text` payloads with BM25 and fuses the
+    /// vector and keyword rankings with reciprocal rank fusion:
+    /// `alpha / (60 + vector rank) + (1 - alpha) / (60 + keyword rank)`, ranks
+    /// starting at 1, so `1.0` is pure vector search and `0.0` pure keyword
+    /// ranking of the candidates. Candidates matching no query term are missing
+    /// from the shorter keyword list and get only their vector share; ties keep
+    /// the vector order. The fused score replaces each record's `score`.
+    ///
+    /// Falls back to pure vector search when there is no query text or none of
+    /// the candidates has a `text` payload to build the keyword index from.
+    pub fn with_hybrid(mut self, alpha: f32) -> Self {
+        self.hybrid_alpha = Some(alpha.clamp(0.0, 1.0));
+        self
+    }
+
+    /// Drop records with the same `text` as, or an embedding with a cosine
+    /// similarity of at least `threshold` to, a better ranked record, so chunks
+    /// stored more than once through overlap don't fill the answer's context. The
+    /// node searches `k * 4` candidates to still return `k` distinct records.
+    pub fn with_dedup_threshold(mut self, threshold: f32) -> Self {
+        self.dedup_threshold = Some(threshold);
+        self
+    }
+
+    /// The records, best ranked first, without those duplicating a record kept
+    /// before them.
+    fn dedup(records: Vec<VectorRecord>, threshold: f32) -> Vec<VectorRecord> {
+        fn text(record: &VectorRecord) -> Option<&str> {
+            record.metadata.get("text").and_then(Value::as_str)
+        }
+        let mut kept: Vec<VectorRecord> = Vec::new();
+        for record in records {
+            let duplicate = kept.iter().any(|other| {
+                (text(other).is_some() && text(other) == text(&record))
+                    || cosine_similarity(&other.vector, &record.vector) >= threshold
+            });
+            if !duplicate {
+                kept.push(record);
+            }
+        }
+        kept
+    }
+
+    /// The candidates reranked by the fusion of their vector and keyword ranks,
+    /// or `None` if no keyword index can be built over them.
+    fn fuse_hybrid(
+        candidates: Vec<VectorRecord>,
+        query: &str,
+        alpha: f32,
+    ) -> Option<Vec<VectorRecord>> {
+        let mut index = Bm25Index::new();
+        for record in &candidates {
+            if let Some(text) = record.metadata.get("text").and_then(Value::as_str) {
+                index.add_document(&record.id, text);
+            }
+        }
+        if index.is_empty() {
+            return None;
+        }
+        let keyword_ranks: HashMap<String, usize> = index
+            .search(query, index.len())
+            .into_iter()
+            .enumerate()
+            .map(|(rank, (id, _))| (id, rank + 1))
+            .collect();
+
+        let mut fused: Vec<VectorRecord> = candidates
+            .into_iter()
+            .enumerate()
+            .map(|(rank, mut record)| {
+                let mut score = alpha / (RRF_K + rank as f32 + 1.0);
+                if let Some(keyword_rank) = keyword_ranks.get(&record.id) {
+                    score += (1.0 - alpha) / (RRF_K + *keyword_rank as f32);
+                }
+                record.score = Some(score);
+                record
+            })
+            .collect();
+        // A stable sort, so ties keep the vector order
+        fused.sort_by(|a, b| b.score.unwrap().total_cmp(&a.score.unwrap()));
+        Some(fused)
     }
 }
 
@@ -43,18 +163,19 @@ impl RetrieveDocumentNode {
 impl Node for RetrieveDocumentNode {
     type State = RagState;
 
+    fn name(&self) -> &str {
+        "RetrieveDocument"
+    }
+
     async fn execute(&self, context: &Context) -> Result<Value> {
-        let query_embedding = context
-            .get("query_embedding")
-            .and_then(|v| v.as_array())
-            .map(|arr| {
-                arr.iter()
-                    .filter_map(|v| v.as_f64().map(|x| x as f32))
-                    .collect::<Vec<f32>>()
-            })
-            .ok_or_else(|| anyhow::anyhow!("No query embedding found in context"))?;
+        let query_embedding: Vec<f32> = context.get_as("query_embedding")?;
 
-        let records = self.db.search(query_embedding, self.k).await?;
+        let candidates = if self.hybrid_alpha.is_some() || self.dedup_threshold.is_some() {
+            self.k * CANDIDATES_PER_RESULT
+        } else {
+            self.k
+        };
+        let records = self.db.search(query_embedding, candidates).await?;
         if records.is_empty() {
             error!("No documents retrieved");
             return Err(anyhow::anyhow!("No documents retrieved"));
@@ -62,6 +183,32 @@ impl Node for RetrieveDocumentNode {
 
         info!("Retrieved documents line: {:?}", records.len());
 
+        let lower_is_closer = matches!(self.db.distance_metric(), Some(DistanceMetric::Euclidean));
+        let mut records: Vec<VectorRecord> = records
+            .into_iter()
+            .filter(|record| match (self.min_score, record.score) {
+                (Some(min_score), Some(score)) if lower_is_closer => score <= min_score,
+                (Some(min_score), Some(score)) => score >= min_score,
+                _ => true,
+            })
+            .collect();
+        if let Some(alpha) = self.hybrid_alpha {
+            let query = context
+                .get_str("user_query")
+                .or_else(|_| context.get_str("rewritten_query"))
+                .ok();
+            match query.and_then(|query| Self::fuse_hybrid(records.clone(), query, alpha)) {
+                Some(fused) => records = fused,
+                None => warn!("No keyword index for hybrid search, using vector search only"),
+            }
+        }
+        if let Some(threshold) = self.dedup_threshold {
+            let total = records.len();
+            records = Self::dedup(records, threshold);
+            info!("Removed {} duplicate documents", total - records.len());
+        }
+        records.truncate(self.k);
+
         let result_array: Vec<Value> = records
             .into_iter()
             .map(|record| record.to_value())
@@ -90,3 +237,192 @@ impl Node for RetrieveDocumentNode {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::memory_db;
+    use pocketflow_rs::utils::vector_db::InMemoryVectorDB;
+    use serde_json::json;
+
+    async fn db() -> Arc<dyn VectorDB> {
+        let db = memory_db(2);
+        let record = |id: &str, vector: Vec<f32>| VectorRecord {
+            id: id.to_string(),
+            vector,
+            sparse_vector: None,
+            metadata: serde_json::Map::new(),
+            score: None,
+        };
+        db.insert(vec![
+            record("close", vec![1.0, 0.0]),
+            record("far", vec![0.0, 1.0]),
+        ])
+        .await
+        .unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn test_results_carry_scores() {
+        let node = RetrieveDocumentNode::from_db(db().await, 2);
+        let mut context = Context::new();
+        context.set("query_embedding", json!([1.0, 0.0]));
+
+        let result = node.execute(&context).await.unwrap();
+
+        assert_eq!(result[0]["id"], json!("close"));
+        assert_eq!(result[0]["score"], json!(1.0));
+        assert_eq!(result[1]["score"], json!(0.0));
+    }
+
+    #[tokio::test]
+    async fn test_min_score_drops_weak_matches() {
+        let node = RetrieveDocumentNode::from_db(db().await, 2).with_min_score(0.5);
+        let mut context = Context::new();
+        context.set("query_embedding", json!([1.0, 0.0]));
+
+        let result = node.execute(&context).await.unwrap();
+        assert_eq!(result.as_array().unwrap().len(), 1);
+        assert_eq!(result[0]["id"], json!("close"));
+
+        // Nothing relevant leaves no documents rather than failing retrieval
+        context.set("query_embedding", json!([-1.0, 0.0]));
+        let result = node.execute(&context).await.unwrap();
+        assert_eq!(result, json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_min_score_is_a_max_distance_for_euclidean() {
+        let db = InMemoryVectorDB::new(VectorDBOptions {
+            collection_name: "documents".to_string(),
+            dimension: 2,
+            distance_metric: DistanceMetric::Euclidean,
+            payload_indexes: Vec::new(),
+            sparse_vector_name: None,
+        });
+        let record = |id: &str, vector: Vec<f32>| VectorRecord {
+            id: id.to_string(),
+            vector,
+            sparse_vector: None,
+            metadata: serde_json::Map::new(),
+            score: None,
+        };
+        db.insert(vec![
+            record("close", vec![1.0, 0.0]),
+            record("far", vec![0.0, 1.0]),
+        ])
+        .await
+        .unwrap();
+        let node = RetrieveDocumentNode::from_db(Arc::new(db), 2).with_min_score(0.5);
+        let mut context = Context::new();
+        context.set("query_embedding", json!([1.0, 0.0]));
+
+        let result = node.execute(&context).await.unwrap();
+        assert_eq!(result.as_array().unwrap().len(), 1);
+        assert_eq!(result[0]["id"], json!("close"));
+        assert_eq!(result[0]["score"], json!(0.0));
+    }
+
+    async fn text_db() -> Arc<dyn VectorDB> {
+        let db = memory_db(2);
+        let record = |id: &str, vector: Vec<f32>, text: &str| VectorRecord {
+            id: id.to_string(),
+            vector,
+            sparse_vector: None,
+            metadata: json!({"text": text}).as_object().unwrap().clone(),
+            score: None,
+        };
+        db.insert(vec![
+            record(
+                "disk",
+                vec![1.0, 0.0],
+                "Disk failures and how to replace a disk",
+            ),
+            record(
+                "errors",
+                vec![0.9, 0.1],
+                "Replication errors in the storage layer",
+            ),
+            record(
+                "code",
+                vec![0.5, 0.5],
+                "Error E1234 means the disk quota is full",
+            ),
+        ])
+        .await
+        .unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn test_hybrid_ranks_exact_terms() {
+        let mut context = Context::new();
+        context.set("query_embedding", json!([1.0, 0.0]));
+        context.set("user_query", json!("what does E1234 mean"));
+
+        let vector = RetrieveDocumentNode::from_db(text_db().await, 1);
+        assert_eq!(
+            vector.execute(&context).await.unwrap()[0]["id"],
+            json!("disk")
+        );
+
+        let hybrid = RetrieveDocumentNode::from_db(text_db().await, 1).with_hybrid(0.3);
+        let result = hybrid.execute(&context).await.unwrap();
+        assert_eq!(result.as_array().unwrap().len(), 1);
+        assert_eq!(result[0]["id"], json!("code"));
+    }
+
+    #[tokio::test]
+    async fn test_hybrid_falls_back_to_vector_search() {
+        // No query text to score the candidates with
+        let mut context = Context::new();
+        context.set("query_embedding", json!([1.0, 0.0]));
+        let node = RetrieveDocumentNode::from_db(text_db().await, 2).with_hybrid(0.3);
+        let result = node.execute(&context).await.unwrap();
+        assert_eq!(result[0]["id"], json!("disk"));
+        assert_eq!(result[0]["score"], json!(1.0));
+
+        // No text payloads to build the keyword index from
+        context.set("user_query", json!("E1234"));
+        let node = RetrieveDocumentNode::from_db(db().await, 2).with_hybrid(0.3);
+        let result = node.execute(&context).await.unwrap();
+        assert_eq!(result[0]["id"], json!("close"));
+        assert_eq!(result[1]["id"], json!("far"));
+    }
+
+    #[tokio::test]
+    async fn test_dedup_keeps_best_of_duplicates() {
+        let db = memory_db(2);
+        let record = |id: &str, vector: Vec<f32>, text: &str| VectorRecord {
+            id: id.to_string(),
+            vector,
+            sparse_vector: None,
+            metadata: json!({"text": text}).as_object().unwrap().clone(),
+            score: None,
+        };
+        db.insert(vec![
+            record("pangu", vec![1.0, 0.0], "Pangu keeps three replicas."),
+            // Stored twice through chunk overlap
+            record("pangu-copy", vec![0.8, 0.6], "Pangu keeps three replicas."),
+            // Nearly the same embedding as the first
+            record("pangu-near", vec![0.99, 0.01], "Pangu keeps 3 replicas."),
+            record("fuxi", vec![0.0, 1.0], "Fuxi schedules the jobs."),
+        ])
+        .await
+        .unwrap();
+        let mut context = Context::new();
+        context.set("query_embedding", json!([1.0, 0.0]));
+
+        let node = RetrieveDocumentNode::from_db(db, 3).with_dedup_threshold(0.95);
+        let result = node.execute(&context).await.unwrap();
+
+        let ids: Vec<&str> = result
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|record| record["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, vec!["pangu", "fuxi"]);
+    }
+}
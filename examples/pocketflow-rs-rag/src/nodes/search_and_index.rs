@@ -0,0 +1,422 @@
+use crate::state::RagState;
+use anyhow::Result;
+use async_trait::async_trait;
+use pocketflow_rs::embedding::EmbeddingGenerator;
+use pocketflow_rs::utils::content_fetcher::{ContentFetcher, FetchOptions};
+use pocketflow_rs::utils::text_chunking::{ChunkingOptions, TextChunker};
+use pocketflow_rs::utils::vector_db::{
+    DistanceMetric, QdrantDB, VectorDB, VectorDBOptions, VectorRecord,
+};
+use pocketflow_rs::utils::web_search::{SearchOptions, SearchResult, WebSearcher};
+use pocketflow_rs::{Context, Node, ProcessResult};
+use serde_json::{Map, Value, json};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+const DEFAULT_MAX_RESULTS: usize = 5;
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Searches the web for `user_query`, fetches each result page, then chunks, embeds
+/// and upserts it into the vector db with the same payload as
+/// [`CreateIndexNode`](crate::nodes::CreateIndexNode), so later retrieval can ground
+/// on fresh web content. Pages that fail to fetch are indexed from their snippet.
+///
+/// With a TTL, records carry an `expires_at` unix timestamp and those indexed by this
+/// node are deleted once expired, the next time it runs.
+pub struct SearchAndIndexNode {
+    searcher: Arc<dyn WebSearcher + Send + Sync>,
+    fetcher: Arc<ContentFetcher>,
+    generator: Arc<dyn EmbeddingGenerator>,
+    db: Arc<dyn VectorDB>,
+    chunker: TextChunker,
+    chunking: ChunkingOptions,
+    max_results: usize,
+    ttl: Option<Duration>,
+    /// Ids indexed with a TTL, with their expiry
+    indexed: Mutex<Vec<(String, u64)>>,
+}
+
+impl SearchAndIndexNode {
+    pub async fn new(
+        searcher: Arc<dyn WebSearcher + Send + Sync>,
+        generator: Arc<dyn EmbeddingGenerator>,
+        db_url: String,
+        api_key: Option<String>,
+        collection: String,
+        dimension: usize,
+    ) -> Result<Self> {
+        let options = VectorDBOptions {
+            collection_name: collection,
+            dimension,
+            distance_metric: DistanceMetric::Cosine,
+            payload_indexes: Vec::new(),
+            sparse_vector_name: None,
+        };
+        let db = QdrantDB::new(db_url, api_key, options).await?;
+        Ok(Self::from_db(searcher, generator, Arc::new(db)))
+    }
+
+    pub fn from_db(
+        searcher: Arc<dyn WebSearcher + Send + Sync>,
+        generator: Arc<dyn EmbeddingGenerator>,
+        db: Arc<dyn VectorDB>,
+    ) -> Self {
+        Self {
+            searcher,
+            fetcher: Arc::new(ContentFetcher::new(FetchOptions::default())),
+            generator,
+            db,
+            chunker: TextChunker::new(),
+            chunking: ChunkingOptions::default(),
+            max_results: DEFAULT_MAX_RESULTS,
+            ttl: None,
+            indexed: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn with_fetcher(mut self, fetcher: Arc<ContentFetcher>) -> Self {
+        self.fetcher = fetcher;
+        self
+    }
+
+    pub fn with_chunking(mut self, chunking: ChunkingOptions) -> Self {
+        self.chunking = chunking;
+        self
+    }
+
+    pub fn with_max_results(mut self, max_results: usize) -> Self {
+        self.max_results = max_results;
+        self
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// The page text, or the snippet if the page can't be fetched.
+    async fn page_text(&self, result: &SearchResult) -> String {
+        match self.fetcher.fetch_content(&result.url).await {
+            Ok(Some(response)) if response.status().is_success() => match response.text().await {
+                Ok(text) if !text.trim().is_empty() => return text,
+                Ok(_) => warn!("Empty page at {}, using the snippet", result.url),
+                Err(e) => warn!("Failed to read {}: {}, using the snippet", result.url, e),
+            },
+            Ok(Some(response)) => warn!(
+                "Fetching {} returned {}, using the snippet",
+                result.url,
+                response.status()
+            ),
+            Ok(None) => info!(
+                "{} is disallowed by robots.txt, using the snippet",
+                result.url
+            ),
+            Err(e) => warn!("Failed to fetch {}: {}, using the snippet", result.url, e),
+        }
+        result.snippet.clone()
+    }
+
+    async fn purge_expired(&self) -> Result<()> {
+        let now = now_secs();
+        let expired: Vec<String> = {
+            let mut indexed = self.indexed.lock().unwrap();
+            let (expired, live): (Vec<_>, Vec<_>) = indexed
+                .drain(..)
+                .partition(|(_, expires_at)| *expires_at <= now);
+            *indexed = live;
+            expired.into_iter().map(|(id, _)| id).collect()
+        };
+        if !expired.is_empty() {
+            info!("Deleting {} expired web records", expired.len());
+            self.db.delete(expired).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Node for SearchAndIndexNode {
+    type State = RagState;
+
+    fn name(&self) -> &str {
+        "SearchAndIndex"
+    }
+
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let query = context.get_str("user_query")?;
+        self.purge_expired().await?;
+
+        let options = SearchOptions {
+            max_results: Some(self.max_results),
+            ..SearchOptions::default()
+        };
+        let results = self.searcher.search_with_options(query, options).await?;
+        info!("Web search returned {} results", results.len());
+
+        let expires_at = self.ttl.map(|ttl| now_secs() + ttl.as_secs());
+        let mut texts = Vec::new();
+        let mut payloads = Vec::new();
+        for result in results.iter().take(self.max_results) {
+            let page = self.page_text(result).await;
+            let metadata = json!({
+                "url": result.url,
+                "title": result.title,
+                "file_type": "web",
+                "source": "web_search",
+                "query": query,
+                "timestamp": now_secs(),
+            });
+            for (chunk_index, text) in self
+                .chunker
+                .chunk_text(&page, &self.chunking)
+                .into_iter()
+                .enumerate()
+            {
+                let id = Uuid::new_v5(
+                    &Uuid::NAMESPACE_URL,
+                    format!("{}#{}", result.url, chunk_index).as_bytes(),
+                )
+                .to_string();
+                let mut payload = Map::from_iter(vec![
+                    ("text".to_string(), json!(text)),
+                    ("chunk_index".to_string(), json!(chunk_index)),
+                    ("file_metadata".to_string(), metadata.clone()),
+                ]);
+                if let Some(expires_at) = expires_at {
+                    payload.insert("expires_at".to_string(), json!(expires_at));
+                }
+                texts.push(text);
+                payloads.push((id, payload));
+            }
+        }
+        if texts.is_empty() {
+            return Err(anyhow::anyhow!("No web content found for '{}'", query));
+        }
+
+        let embeddings = self.generator.generate_embeddings(&texts).await?;
+        if embeddings.len() != texts.len() {
+            return Err(anyhow::anyhow!(
+                "Got {} embeddings for {} chunks",
+                embeddings.len(),
+                texts.len()
+            ));
+        }
+        let records: Vec<VectorRecord> = payloads
+            .into_iter()
+            .zip(embeddings)
+            .map(|((id, metadata), embedding)| VectorRecord {
+                id,
+                vector: embedding.into_iter().map(|x| x as f32).collect(),
+                sparse_vector: None,
+                metadata,
+                score: None,
+            })
+            .collect();
+        let ids: Vec<String> = records.iter().map(|r| r.id.clone()).collect();
+        self.db.insert(records).await?;
+
+        if let Some(expires_at) = expires_at {
+            let mut indexed = self.indexed.lock().unwrap();
+            indexed.retain(|(id, _)| !ids.contains(id));
+            indexed.extend(ids.iter().map(|id| (id.clone(), expires_at)));
+        }
+        Ok(json!({
+            "urls": results.iter().map(|r| r.url.as_str()).collect::<Vec<_>>(),
+            "indexed_chunks": ids.len(),
+        }))
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<RagState>> {
+        match result {
+            Ok(value) => {
+                context.set("web_indexed", value.clone());
+                Ok(ProcessResult::new(
+                    RagState::Default,
+                    "web_indexed".to_string(),
+                ))
+            }
+            Err(e) => Ok(ProcessResult::new(
+                RagState::WebIndexError,
+                format!("web_index_error: {}", e),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{memory_db, stored_records};
+    use pocketflow_rs::utils::text_chunking::ChunkingStrategy;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    struct MockSearcher {
+        base: String,
+    }
+
+    #[async_trait]
+    impl WebSearcher for MockSearcher {
+        async fn search(&self, query: &str) -> Result<Vec<SearchResult>> {
+            self.search_with_options(query, SearchOptions::default())
+                .await
+        }
+
+        #[allow(unused_variables)]
+        async fn search_with_options(
+            &self,
+            query: &str,
+            options: SearchOptions,
+        ) -> Result<Vec<SearchResult>> {
+            Ok(vec![
+                SearchResult {
+                    title: "Pangu".to_string(),
+                    url: format!("{}/pangu", self.base),
+                    snippet: "Pangu overview".to_string(),
+                },
+                SearchResult {
+                    title: "Missing".to_string(),
+                    url: format!("{}/missing", self.base),
+                    snippet: "Pangu was first deployed in 2009.".to_string(),
+                },
+            ])
+        }
+    }
+
+    /// Embeds texts mentioning Pangu along the first axis, others along the second.
+    struct KeywordEmbedder;
+
+    #[async_trait]
+    impl EmbeddingGenerator for KeywordEmbedder {
+        async fn generate_embedding(&self, text: &str) -> Result<Vec<f64>> {
+            Ok(if text.contains("Pangu") {
+                vec![1.0, 0.0]
+            } else {
+                vec![0.0, 1.0]
+            })
+        }
+
+        async fn generate_embeddings(&self, texts: &[String]) -> Result<Vec<Vec<f64>>> {
+            let mut embeddings = Vec::new();
+            for text in texts {
+                embeddings.push(self.generate_embedding(text).await?);
+            }
+            Ok(embeddings)
+        }
+    }
+
+    /// Serve a page at /pangu and 404 for anything else.
+    async fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = [0u8; 1024];
+                let n = socket.read(&mut buf).await.unwrap();
+                let request = String::from_utf8_lossy(&buf[..n]);
+                let path = request.split_whitespace().nth(1).unwrap_or("/");
+                let (status, body) = match path {
+                    "/pangu" => (
+                        "200 OK",
+                        "Pangu is Alibaba's distributed storage system. \
+                         Every chunk is stored with three replicas.",
+                    ),
+                    _ => ("404 Not Found", ""),
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    fn node(base: String, db: Arc<dyn VectorDB>) -> SearchAndIndexNode {
+        let fetcher = ContentFetcher::new(FetchOptions {
+            per_host_delay: Duration::ZERO,
+            respect_robots_txt: false,
+            ..FetchOptions::default()
+        });
+        SearchAndIndexNode::from_db(
+            Arc::new(MockSearcher { base }),
+            Arc::new(KeywordEmbedder),
+            db,
+        )
+        .with_fetcher(Arc::new(fetcher))
+        .with_chunking(ChunkingOptions {
+            chunk_size: 50,
+            overlap: 0,
+            strategy: ChunkingStrategy::Sentence,
+            ..ChunkingOptions::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_search_results_become_retrievable() {
+        let base = serve().await;
+        let db = memory_db(2);
+        let node = node(base.clone(), db.clone());
+        let mut context = Context::new();
+        context.set("user_query", json!("what is pangu"));
+
+        let result = node.execute(&context).await;
+        let outcome = node.post_process(&mut context, &result).await.unwrap();
+        assert_eq!(outcome.state, RagState::Default);
+
+        let retrieved = db.search(vec![1.0, 0.0], 10).await.unwrap();
+        let texts: Vec<&str> = retrieved
+            .iter()
+            .filter(|r| r.score == Some(1.0))
+            .map(|r| r.metadata["text"].as_str().unwrap())
+            .collect();
+        assert!(texts.iter().any(|t| t.contains("distributed storage")));
+        // The page that failed to fetch is indexed from its snippet
+        assert!(texts.contains(&"Pangu was first deployed in 2009."));
+
+        let pangu_url = format!("{}/pangu", base);
+        let record = retrieved
+            .iter()
+            .find(|r| r.metadata["file_metadata"]["url"] == json!(pangu_url))
+            .unwrap();
+        assert_eq!(record.metadata["file_metadata"]["source"], "web_search");
+        assert_eq!(record.metadata["file_metadata"]["title"], "Pangu");
+        assert!(record.metadata.get("expires_at").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_expired_records_are_purged() {
+        let base = serve().await;
+        let db = memory_db(2);
+        let node = node(base, db.clone()).with_ttl(Duration::ZERO);
+        let mut context = Context::new();
+        context.set("user_query", json!("what is pangu"));
+
+        node.execute(&context).await.unwrap();
+        let records = stored_records(db.as_ref()).await;
+        let indexed = records.len();
+        assert!(indexed > 0);
+        assert!(records[0].metadata["expires_at"].is_u64());
+
+        // Re-running upserts the same ids after deleting the expired ones
+        node.execute(&context).await.unwrap();
+        assert_eq!(db.len(), indexed);
+        assert_eq!(node.indexed.lock().unwrap().len(), indexed);
+    }
+}
@@ -0,0 +1,274 @@
+use crate::state::RagState;
+use anyhow::Result;
+use async_trait::async_trait;
+use pocketflow_rs::utils::embedding::EmbeddingGenerator;
+use pocketflow_rs::utils::vector_db::{VectorDB, VectorRecord};
+use pocketflow_rs::{Context, Node, ProcessResult};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Context key holding the `user_query` embedding computed on a cache miss, reused
+/// when storing the answer.
+pub const SEMANTIC_CACHE_EMBEDDING_KEY: &str = "semantic_cache_embedding";
+
+const DEFAULT_THRESHOLD: f32 = 0.95;
+
+/// A cache of whole answers keyed by query meaning rather than wording. The lookup
+/// node, [`SemanticCacheNode::new`], embeds `user_query` and searches the cache
+/// collection of prior `(query, answer)` pairs: if the closest one scores at least
+/// the threshold, its answer becomes the flow `result` and the node routes to
+/// [`RagState::CacheHit`]; otherwise it routes to [`RagState::CacheMiss`]. Connect
+/// only the miss to the rest of the pipeline, as a `default` edge would be taken on
+/// a hit too, so a hit ends the flow before retrieval and generation. The store node,
+/// [`SemanticCacheNode::store`], goes after the answer is generated and adds the new
+/// pair. The cache collection should use cosine distance, so scores are
+/// similarities. Cache failures are logged and never fail the flow.
+pub struct SemanticCacheNode {
+    generator: Arc<dyn EmbeddingGenerator>,
+    db: Arc<dyn VectorDB>,
+    threshold: f32,
+    store: bool,
+}
+
+impl SemanticCacheNode {
+    pub fn new(generator: Arc<dyn EmbeddingGenerator>, db: Arc<dyn VectorDB>) -> Self {
+        Self {
+            generator,
+            db,
+            threshold: DEFAULT_THRESHOLD,
+            store: false,
+        }
+    }
+
+    /// A node storing `user_query` and its `result` answer in the cache.
+    pub fn store(generator: Arc<dyn EmbeddingGenerator>, db: Arc<dyn VectorDB>) -> Self {
+        Self {
+            store: true,
+            ..Self::new(generator, db)
+        }
+    }
+
+    /// Minimum similarity for a cached answer to be served; 0.95 by default.
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    async fn embed(&self, query: &str) -> Result<Vec<f32>> {
+        let embedding = self.generator.generate_embedding(query).await?;
+        if embedding.is_empty() {
+            return Err(anyhow::anyhow!("No embedding generated for query"));
+        }
+        Ok(embedding.into_iter().map(|f| f as f32).collect())
+    }
+
+    async fn lookup(&self, context: &Context) -> Result<Value> {
+        let query = context.get_str("user_query")?;
+        let embedding = self.embed(query).await?;
+        let closest = self
+            .db
+            .search(embedding.clone(), 1)
+            .await?
+            .into_iter()
+            .next();
+        if let Some(record) = closest
+            && let Some(score) = record.score.filter(|score| *score >= self.threshold)
+            && let Some(answer) = record.metadata.get("answer")
+        {
+            info!(
+                "Semantic cache hit for '{}': '{}' scored {}",
+                query,
+                record
+                    .metadata
+                    .get("query")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default(),
+                score
+            );
+            return Ok(json!({"hit": true, "answer": answer}));
+        }
+        Ok(json!({"hit": false, "embedding": embedding}))
+    }
+
+    async fn insert(&self, context: &Context) -> Result<Value> {
+        let query = context.get_str("user_query")?;
+        let answer = context.get_str("result")?;
+        let embedding = match context.get(SEMANTIC_CACHE_EMBEDDING_KEY) {
+            Some(_) => context.get_as(SEMANTIC_CACHE_EMBEDDING_KEY)?,
+            None => self.embed(query).await?,
+        };
+
+        // One entry per query text, so asking again overwrites the old answer
+        let id = Uuid::new_v5(&Uuid::NAMESPACE_URL, query.as_bytes()).to_string();
+        let mut metadata = serde_json::Map::new();
+        metadata.insert("query".to_string(), json!(query));
+        metadata.insert("answer".to_string(), json!(answer));
+        self.db
+            .insert(vec![VectorRecord {
+                id,
+                vector: embedding,
+                sparse_vector: None,
+                metadata,
+                score: None,
+            }])
+            .await?;
+        Ok(json!({"stored": query}))
+    }
+}
+
+#[async_trait]
+impl Node for SemanticCacheNode {
+    type State = RagState;
+
+    fn name(&self) -> &str {
+        if self.store {
+            "SemanticCacheStore"
+        } else {
+            "SemanticCache"
+        }
+    }
+
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        if self.store {
+            self.insert(context).await
+        } else {
+            self.lookup(context).await
+        }
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<RagState>> {
+        let value = match result {
+            Ok(value) => value,
+            Err(e) => {
+                warn!("Semantic cache unavailable, continuing without it: {}", e);
+                let state = if self.store {
+                    RagState::Default
+                } else {
+                    RagState::CacheMiss
+                };
+                return Ok(ProcessResult::new(state, format!("cache_error: {}", e)));
+            }
+        };
+        if self.store {
+            return Ok(ProcessResult::new(
+                RagState::Default,
+                "cache_stored".to_string(),
+            ));
+        }
+
+        if value["hit"].as_bool() == Some(true) {
+            context.set("result", value["answer"].clone());
+            return Ok(ProcessResult::new(
+                RagState::CacheHit,
+                "cache_hit".to_string(),
+            ));
+        }
+        context.set(SEMANTIC_CACHE_EMBEDDING_KEY, value["embedding"].clone());
+        Ok(ProcessResult::new(
+            RagState::CacheMiss,
+            "cache_miss".to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::memory_db;
+    use pocketflow_rs::build_flow;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Embeds queries by whether they mention Pangu and replication, so rewordings
+    /// of the same question land close together.
+    struct KeywordEmbeddingGenerator;
+
+    #[async_trait]
+    impl EmbeddingGenerator for KeywordEmbeddingGenerator {
+        async fn generate_embedding(&self, text: &str) -> Result<Vec<f64>> {
+            let text = text.to_lowercase();
+            Ok(vec![
+                text.contains("pangu") as u8 as f64,
+                text.contains("replica") as u8 as f64,
+                0.1,
+            ])
+        }
+
+        async fn generate_embeddings(&self, texts: &[String]) -> Result<Vec<Vec<f64>>> {
+            let mut embeddings = Vec::new();
+            for text in texts {
+                embeddings.push(self.generate_embedding(text).await?);
+            }
+            Ok(embeddings)
+        }
+    }
+
+    /// Stands in for retrieval and generation, counting how often it runs.
+    #[derive(Default)]
+    struct AnswerNode {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Node for AnswerNode {
+        type State = RagState;
+
+        async fn execute(&self, context: &Context) -> Result<Value> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(json!(format!(
+                "Answer to: {}",
+                context.get_str("user_query")?
+            )))
+        }
+
+        async fn post_process(
+            &self,
+            context: &mut Context,
+            result: &Result<Value>,
+        ) -> Result<ProcessResult<RagState>> {
+            context.set("result", result.as_ref().unwrap().clone());
+            Ok(ProcessResult::new(
+                RagState::Default,
+                "answered".to_string(),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_similar_query_is_served_from_cache() {
+        let generator: Arc<dyn EmbeddingGenerator> = Arc::new(KeywordEmbeddingGenerator);
+        let db: Arc<dyn VectorDB> = memory_db(3);
+        let answer = Arc::new(AnswerNode::default());
+        let mut flow = build_flow!(
+            start: ("semantic_cache", SemanticCacheNode::new(generator.clone(), db.clone())),
+            nodes: [("store", SemanticCacheNode::store(generator, db))],
+            edges: [("semantic_cache", "answer", RagState::CacheMiss), ("answer", "store", RagState::Default)]
+        );
+        flow.add_node("answer", answer.clone());
+
+        let ask = |query: &str| {
+            let mut context = Context::new();
+            context.set("user_query", json!(query));
+            flow.run(context)
+        };
+        let first = ask("How many replicas does Pangu keep?").await.unwrap();
+        assert_eq!(
+            first,
+            json!("Answer to: How many replicas does Pangu keep?")
+        );
+        assert_eq!(answer.calls.load(Ordering::SeqCst), 1);
+
+        let second = ask("how many Pangu replicas are kept").await.unwrap();
+        assert_eq!(second, first);
+        assert_eq!(answer.calls.load(Ordering::SeqCst), 1);
+
+        let unrelated = ask("Who designed Pangu?").await.unwrap();
+        assert_eq!(unrelated, json!("Answer to: Who designed Pangu?"));
+        assert_eq!(answer.calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,166 @@
+use crate::state::RagState;
+use anyhow::Result;
+use async_trait::async_trait;
+use pocketflow_rs::utils::llm_wrapper::{LLMWrapper, strip_code_fence};
+use pocketflow_rs::{Context, Node, ProcessResult};
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+const DEFAULT_MAX_SUGGESTIONS: usize = 3;
+
+pub struct SuggestFollowupsNode {
+    client: Arc<dyn LLMWrapper>,
+    max_suggestions: usize,
+}
+
+impl SuggestFollowupsNode {
+    pub fn new(client: Arc<dyn LLMWrapper>) -> Self {
+        Self {
+            client,
+            max_suggestions: DEFAULT_MAX_SUGGESTIONS,
+        }
+    }
+
+    pub fn with_max_suggestions(mut self, max_suggestions: usize) -> Self {
+        self.max_suggestions = max_suggestions;
+        self
+    }
+
+    /// Parse the LLM output into a list of questions. A JSON array of strings is
+    /// preferred, but a plain (optionally numbered or bulleted) list is accepted too.
+    fn parse_suggestions(&self, content: &str) -> Vec<String> {
+        let content = strip_code_fence(content);
+
+        let suggestions: Vec<String> = match serde_json::from_str::<Vec<Value>>(content) {
+            Ok(items) => items
+                .iter()
+                .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                .collect(),
+            Err(_) => {
+                warn!("Follow-up suggestions are not a JSON array, parsing as plain list");
+                content
+                    .lines()
+                    .map(|line| {
+                        line.trim()
+                            .trim_start_matches(|c: char| {
+                                c.is_ascii_digit() || matches!(c, '-' | '*' | '.' | ')')
+                            })
+                            .trim_matches('"')
+                            .to_string()
+                    })
+                    .collect()
+            }
+        };
+
+        suggestions
+            .into_iter()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .take(self.max_suggestions)
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Node for SuggestFollowupsNode {
+    type State = RagState;
+
+    fn name(&self) -> &str {
+        "SuggestFollowups"
+    }
+
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let question = context.get_str("user_query")?;
+        let answer = context.get_str("result")?;
+
+        let prompt = format!(
+            "
+You are a helpful assistant. Given a question and the answer it received, suggest {} short follow-up questions the user is likely to ask next.\n\n
+Respond with ONLY a JSON array of strings, e.g. [\"question one\", \"question two\"].\n\n
+Question: {}\n\n
+Answer: {}\n\n
+Follow-up questions:",
+            self.max_suggestions, question, answer
+        );
+
+        let response = self.client.generate(&prompt).await?;
+        let suggestions = self.parse_suggestions(&response.content);
+        info!("Suggested follow-ups: {:?}", suggestions);
+
+        Ok(Value::Array(
+            suggestions.into_iter().map(Value::String).collect(),
+        ))
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<RagState>> {
+        match result {
+            Ok(value) => {
+                context.set("followup_questions", value.clone());
+                Ok(ProcessResult::new(
+                    RagState::Default,
+                    "followups_suggested".to_string(),
+                ))
+            }
+            Err(e) => Ok(ProcessResult::new(
+                RagState::FollowupSuggestionError,
+                format!("followup_suggestion_error: {}", e),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::CannedLLM;
+    use serde_json::json;
+
+    fn context_with_answer() -> Context {
+        let mut context = Context::new();
+        context.set("user_query", json!("What is Pangu?"));
+        context.set("result", json!("Pangu is a distributed storage system."));
+        context
+    }
+
+    async fn run_node(node: &SuggestFollowupsNode, context: &mut Context) {
+        let result = node.execute(context).await;
+        node.post_process(context, &result).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_suggestions_are_trimmed_and_capped() {
+        let llm = CannedLLM::new(
+            r#"["  Who built Pangu? ", "How fast is Pangu 2.0?", "", "What is RDMA?", "What is EBS?"]"#,
+        );
+        let node = SuggestFollowupsNode::new(Arc::new(llm));
+        let mut context = context_with_answer();
+        run_node(&node, &mut context).await;
+
+        assert_eq!(
+            context.get("followup_questions").unwrap(),
+            &json!([
+                "Who built Pangu?",
+                "How fast is Pangu 2.0?",
+                "What is RDMA?"
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_malformed_output_falls_back_to_lines() {
+        let llm = CannedLLM::new("1. Who built Pangu?\n2. What is RDMA?\n\n");
+        let node = SuggestFollowupsNode::new(Arc::new(llm)).with_max_suggestions(5);
+        let mut context = context_with_answer();
+        run_node(&node, &mut context).await;
+
+        assert_eq!(
+            context.get("followup_questions").unwrap(),
+            &json!(["Who built Pangu?", "What is RDMA?"])
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use crate::history::{CHAT_HISTORY_KEY, ChatHistory, ChatTurn};
+use crate::state::RagState;
+use anyhow::Result;
+use async_trait::async_trait;
+use pocketflow_rs::utils::llm_wrapper::{LLMOptions, LLMWrapper};
+use pocketflow_rs::{Context, Node, ProcessResult};
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::info;
+
+const DEFAULT_KEEP_RECENT: usize = 4;
+
+/// Keeps the chat history within `max_tokens`: once it grows past the budget,
+/// older turns are replaced by a single LLM-written summary turn, and up to
+/// `keep_recent` of the latest turns are kept verbatim.
+pub struct SummarizeHistoryNode {
+    client: Arc<dyn LLMWrapper>,
+    max_tokens: usize,
+    keep_recent: usize,
+}
+
+impl SummarizeHistoryNode {
+    pub fn new(client: Arc<dyn LLMWrapper>, max_tokens: usize) -> Self {
+        Self {
+            client,
+            max_tokens,
+            keep_recent: DEFAULT_KEEP_RECENT,
+        }
+    }
+
+    pub fn with_keep_recent(mut self, keep_recent: usize) -> Self {
+        self.keep_recent = keep_recent;
+        self
+    }
+
+    /// A quarter of the budget is reserved for the summary.
+    fn summary_budget(&self) -> usize {
+        (self.max_tokens / 4).max(1)
+    }
+
+    /// How many of the latest turns fit next to the summary, at most `keep_recent`
+    /// and at least one.
+    fn recent_count(&self, history: &ChatHistory) -> usize {
+        let available = self.max_tokens.saturating_sub(self.summary_budget());
+        let mut used = 0;
+        let mut count = 0;
+        for turn in history.turns.iter().rev().take(self.keep_recent) {
+            used += turn.token_count();
+            if used > available && count > 0 {
+                break;
+            }
+            count += 1;
+        }
+        count
+    }
+
+    async fn summarize(&self, turns: &[ChatTurn]) -> Result<String> {
+        let transcript = turns
+            .iter()
+            .map(|turn| format!("{}: {}", turn.role, turn.content))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let prompt = format!(
+            "Summarize the following conversation in at most {} words, keeping the facts, \
+             names and open questions needed to continue it.\n\n{}\n\nSummary:",
+            self.summary_budget() * 3 / 4,
+            transcript
+        );
+        let options = LLMOptions::deterministic().with_max_tokens(self.summary_budget() as i32);
+        let response = self.client.generate_with_options(&prompt, options).await?;
+        Ok(response.content.trim().to_string())
+    }
+}
+
+#[async_trait]
+impl Node for SummarizeHistoryNode {
+    type State = RagState;
+
+    fn name(&self) -> &str {
+        "SummarizeHistory"
+    }
+
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let history = ChatHistory::from_context(context)?;
+        let tokens = history.token_count();
+        if tokens <= self.max_tokens {
+            return Ok(serde_json::to_value(&history)?);
+        }
+
+        let split = history.turns.len() - self.recent_count(&history);
+        let (older, recent) = history.turns.split_at(split);
+        if older.is_empty() {
+            return Ok(serde_json::to_value(&history)?);
+        }
+        let summary = self.summarize(older).await?;
+
+        let mut compressed = ChatHistory {
+            turns: vec![ChatTurn::new(
+                "system",
+                format!("Summary of the earlier conversation: {}", summary),
+            )],
+        };
+        compressed.turns.extend_from_slice(recent);
+        info!(
+            "Summarized {} turns, history went from {} to {} tokens",
+            older.len(),
+            tokens,
+            compressed.token_count()
+        );
+        Ok(serde_json::to_value(compressed)?)
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<RagState>> {
+        match result {
+            Ok(value) => {
+                context.set(CHAT_HISTORY_KEY, value.clone());
+                Ok(ProcessResult::new(
+                    RagState::Default,
+                    "history_summarized".to_string(),
+                ))
+            }
+            Err(e) => Ok(ProcessResult::new(
+                RagState::SummarizationError,
+                format!("summarization_error: {}", e),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::CannedLLM;
+
+    fn summarizer() -> Arc<CannedLLM> {
+        Arc::new(CannedLLM::new(
+            "The user asked about Pangu storage and its design.",
+        ))
+    }
+
+    fn long_history(turns: usize) -> ChatHistory {
+        let mut history = ChatHistory::default();
+        for i in 0..turns {
+            history.push(
+                "user",
+                format!(
+                    "Question {} about how Pangu replicates chunks across nodes?",
+                    i
+                ),
+            );
+            history.push(
+                "assistant",
+                format!("Answer {}: Pangu keeps three replicas of every chunk.", i),
+            );
+        }
+        history
+    }
+
+    async fn run_node(node: &SummarizeHistoryNode, context: &mut Context) {
+        let result = node.execute(context).await;
+        node.post_process(context, &result).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_long_history_is_compressed_under_budget() {
+        let llm = summarizer();
+        let node = SummarizeHistoryNode::new(llm.clone(), 100).with_keep_recent(4);
+        let history = long_history(20);
+        assert!(history.token_count() > 100);
+        let mut context = Context::new();
+        history.to_context(&mut context);
+
+        run_node(&node, &mut context).await;
+
+        let compressed = ChatHistory::from_context(&context).unwrap();
+        assert!(compressed.token_count() <= 100);
+        assert_eq!(compressed.turns[0].role, "system");
+        assert!(compressed.turns[0].content.contains("Pangu storage"));
+        assert_eq!(
+            compressed.turns.last(),
+            history.turns.last(),
+            "latest turn is kept verbatim"
+        );
+        let kept = compressed.turns.len() - 1;
+        assert!((1..=4).contains(&kept));
+        assert_eq!(
+            compressed.turns[1..],
+            history.turns[history.turns.len() - kept..]
+        );
+        assert!(llm.prompts()[0].contains("Question 0"));
+    }
+
+    #[tokio::test]
+    async fn test_short_history_is_untouched() {
+        let llm = summarizer();
+        let node = SummarizeHistoryNode::new(llm.clone(), 1000);
+        let history = long_history(2);
+        let mut context = Context::new();
+        history.to_context(&mut context);
+
+        run_node(&node, &mut context).await;
+
+        assert_eq!(ChatHistory::from_context(&context).unwrap(), history);
+        assert!(llm.prompts().is_empty());
+    }
+}
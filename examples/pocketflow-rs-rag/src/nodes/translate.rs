@@ -0,0 +1,105 @@
+use crate::state::RagState;
+use anyhow::Result;
+use async_trait::async_trait;
+use pocketflow_rs::utils::translation::Translator;
+use pocketflow_rs::{Context, Node, ProcessResult};
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::info;
+
+/// Translates the string under a context key into `target_lang` in place, e.g. so a
+/// non-English `user_query` can be searched against an English index.
+pub struct TranslateNode {
+    translator: Arc<dyn Translator>,
+    key: String,
+    target_lang: String,
+}
+
+impl TranslateNode {
+    pub fn new(translator: Arc<dyn Translator>, key: &str, target_lang: &str) -> Self {
+        Self {
+            translator,
+            key: key.to_string(),
+            target_lang: target_lang.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Node for TranslateNode {
+    type State = RagState;
+
+    fn name(&self) -> &str {
+        "Translate"
+    }
+
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let text = context.get_str(&self.key)?;
+
+        let translated = self.translator.translate(text, &self.target_lang).await?;
+        info!("Translated '{}' into {}", self.key, self.target_lang);
+        Ok(Value::String(translated))
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<RagState>> {
+        match result {
+            Ok(value) => {
+                context.set(&self.key, value.clone());
+                Ok(ProcessResult::new(
+                    RagState::Default,
+                    "translated".to_string(),
+                ))
+            }
+            Err(e) => Ok(ProcessResult::new(
+                RagState::TranslationError,
+                format!("translation_error: {}", e),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    struct MockTranslator;
+
+    #[async_trait]
+    impl Translator for MockTranslator {
+        async fn translate(&self, text: &str, target_lang: &str) -> Result<String> {
+            Ok(format!("[{}] {}", target_lang, text))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_translation_replaces_key() {
+        let node = TranslateNode::new(Arc::new(MockTranslator), "user_query", "English");
+        let mut context = Context::new();
+        context.set("user_query", json!("¿Qué es PocketFlow?"));
+
+        let result = node.execute(&context).await;
+        let process_result = node.post_process(&mut context, &result).await.unwrap();
+
+        assert_eq!(process_result.state, RagState::Default);
+        assert_eq!(
+            context.get("user_query").unwrap(),
+            &json!("[English] ¿Qué es PocketFlow?")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_key_is_translation_error() {
+        let node = TranslateNode::new(Arc::new(MockTranslator), "user_query", "English");
+        let mut context = Context::new();
+
+        let result = node.execute(&context).await;
+        let process_result = node.post_process(&mut context, &result).await.unwrap();
+
+        assert_eq!(process_result.state, RagState::TranslationError);
+    }
+}
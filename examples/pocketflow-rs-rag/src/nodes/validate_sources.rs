@@ -0,0 +1,222 @@
+use super::file_loader::FileLoaderNode;
+use crate::state::RagState;
+use anyhow::Result;
+use async_trait::async_trait;
+use pocketflow_rs::{Context, Node, ProcessResult};
+use reqwest::Url;
+use serde_json::{Value, json};
+use std::fs::{self, File};
+use std::path::Path;
+use tracing::warn;
+
+/// Context key holding the normalized sources that passed validation, loaded by
+/// `FileLoaderNode` in place of its own list.
+pub const VALIDATED_URLS_KEY: &str = "validated_urls";
+
+/// Context key holding the validation report, `{"valid": [...], "invalid": [{"url",
+/// "error"}]}`.
+pub const SOURCE_REPORT_KEY: &str = "source_validation";
+
+/// Checks the sources for `FileLoaderNode` up front, without loading anything: web
+/// urls must be well-formed http(s) urls, local paths must be readable files with a
+/// supported extension. Sources are normalized on the way, trimming whitespace,
+/// resolving local paths to absolute ones and dropping a `file://` prefix.
+pub struct ValidateSourcesNode {
+    urls: Vec<String>,
+    lenient: bool,
+}
+
+impl ValidateSourcesNode {
+    /// A strict validator: any invalid source fails the node, routing to
+    /// [`RagState::InvalidSources`].
+    pub fn new(urls: Vec<String>) -> Self {
+        Self {
+            urls,
+            lenient: false,
+        }
+    }
+
+    /// A lenient validator: invalid sources are reported and skipped, and the node
+    /// only fails if none is valid.
+    pub fn lenient(urls: Vec<String>) -> Self {
+        Self {
+            lenient: true,
+            ..Self::new(urls)
+        }
+    }
+
+    /// The normalized source, or why it can't be loaded.
+    fn validate(source: &str) -> Result<String> {
+        let source = source.trim();
+        if source.starts_with("http://") || source.starts_with("https://") {
+            let url =
+                Url::parse(source).map_err(|e| anyhow::anyhow!("not a well-formed URL: {}", e))?;
+            return Ok(url.to_string());
+        }
+        if let Some((scheme, _)) = source.split_once("://")
+            && scheme != "file"
+        {
+            return Err(anyhow::anyhow!("unsupported URL scheme '{}'", scheme));
+        }
+
+        let path = Path::new(source.strip_prefix("file://").unwrap_or(source));
+        let metadata = fs::metadata(path).map_err(|e| anyhow::anyhow!("file not found: {}", e))?;
+        if !metadata.is_file() {
+            return Err(anyhow::anyhow!("not a file"));
+        }
+        FileLoaderNode::detect_file_type(path)?;
+        File::open(path).map_err(|e| anyhow::anyhow!("file is not readable: {}", e))?;
+        Ok(fs::canonicalize(path)?.to_string_lossy().into_owned())
+    }
+}
+
+#[async_trait]
+impl Node for ValidateSourcesNode {
+    type State = RagState;
+
+    fn name(&self) -> &str {
+        "ValidateSources"
+    }
+
+    async fn execute(&self, _context: &Context) -> Result<Value> {
+        let mut valid = Vec::new();
+        let mut invalid = Vec::new();
+        for url in &self.urls {
+            match Self::validate(url) {
+                Ok(normalized) => valid.push(normalized),
+                Err(e) => {
+                    warn!("Invalid source {}: {}", url, e);
+                    invalid.push(json!({"url": url, "error": e.to_string()}));
+                }
+            }
+        }
+        Ok(json!({"valid": valid, "invalid": invalid}))
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<RagState>> {
+        let report = match result {
+            Ok(report) => report,
+            Err(e) => {
+                return Ok(ProcessResult::new(
+                    RagState::InvalidSources,
+                    format!("invalid_sources: {}", e),
+                ));
+            }
+        };
+        context.set(SOURCE_REPORT_KEY, report.clone());
+
+        let invalid = report["invalid"].as_array().map_or(0, Vec::len);
+        let valid = &report["valid"];
+        let no_valid = valid.as_array().is_none_or(Vec::is_empty);
+        if (invalid > 0 && !self.lenient) || no_valid {
+            return Ok(ProcessResult::new(
+                RagState::InvalidSources,
+                format!("invalid_sources: {}", report["invalid"]),
+            ));
+        }
+        context.set(VALIDATED_URLS_KEY, valid.clone());
+        Ok(ProcessResult::new(
+            RagState::Default,
+            "sources_validated".to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_report_lists_each_invalid_source() {
+        let dir = tempdir().unwrap();
+        let text = dir.path().join("notes.txt");
+        fs::write(&text, "Pangu notes").unwrap();
+        let pptx = dir.path().join("notes.pptx");
+        fs::write(&pptx, "binary").unwrap();
+        let folder = dir.path().join("folder.txt");
+        fs::create_dir(&folder).unwrap();
+        let missing = dir.path().join("missing.txt");
+        let path = |p: &Path| p.to_str().unwrap().to_string();
+
+        let node = ValidateSourcesNode::new(vec![
+            format!("  {}  ", path(&text)),
+            format!("file://{}", path(&text)),
+            "https://Example.com/docs/pangu.pdf".to_string(),
+            "http://".to_string(),
+            "ftp://example.com/pangu.txt".to_string(),
+            path(&missing),
+            path(&pptx),
+            path(&folder),
+        ]);
+        let mut context = Context::new();
+        let result = node.execute(&context).await;
+        let outcome = node.post_process(&mut context, &result).await.unwrap();
+
+        assert_eq!(outcome.state, RagState::InvalidSources);
+        assert!(context.get(VALIDATED_URLS_KEY).is_none());
+        let report = context.get(SOURCE_REPORT_KEY).unwrap();
+        let canonical = path(&fs::canonicalize(&text).unwrap());
+        assert_eq!(
+            report["valid"],
+            json!([canonical, canonical, "https://example.com/docs/pangu.pdf"])
+        );
+
+        let errors: Vec<(&str, &str)> = report["invalid"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| {
+                (
+                    entry["url"].as_str().unwrap(),
+                    entry["error"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(errors.len(), 5);
+        assert_eq!(errors[0], ("http://", "not a well-formed URL: empty host"));
+        assert_eq!(
+            errors[1],
+            (
+                "ftp://example.com/pangu.txt",
+                "unsupported URL scheme 'ftp'"
+            )
+        );
+        assert_eq!(errors[2].0, path(&missing));
+        assert!(errors[2].1.starts_with("file not found"));
+        assert_eq!(
+            errors[3],
+            (path(&pptx).as_str(), "Unsupported file type: pptx")
+        );
+        assert_eq!(errors[4], (path(&folder).as_str(), "not a file"));
+    }
+
+    #[tokio::test]
+    async fn test_lenient_passes_valid_sources_to_loader() {
+        let dir = tempdir().unwrap();
+        let text = dir.path().join("notes.txt");
+        fs::write(&text, "Pangu notes").unwrap();
+        let sources = vec![
+            text.to_str().unwrap().to_string(),
+            dir.path().join("missing.txt").to_str().unwrap().to_string(),
+        ];
+
+        let node = ValidateSourcesNode::lenient(sources.clone());
+        let mut context = Context::new();
+        let result = node.execute(&context).await;
+        let outcome = node.post_process(&mut context, &result).await.unwrap();
+        assert_eq!(outcome.state, RagState::Default);
+
+        // The strict loader would fail on the missing file, but only loads the
+        // validated sources
+        let loaded = FileLoaderNode::new(sources)
+            .execute(&context)
+            .await
+            .unwrap();
+        assert_eq!(loaded["documents"].as_array().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,441 @@
+use crate::limits::{Limit, LimitPolicy};
+use crate::nodes::{
+    BuildBm25IndexNode, ChunkDocumentsNode, CreateIndexNode, DEGRADED_KEY, DegradedAnswerNode,
+    EmbedDocumentsNode, EmbedQueryNode, FileLoaderNode, GenerateAnswerNode, QueryRewriteNode,
+    RetrieveDocumentNode, SOURCE_REPORT_KEY, ValidateSourcesNode,
+};
+use crate::state::RagState;
+use anyhow::Result;
+use pocketflow_rs::utils::content_fetcher::FetchOptions;
+use pocketflow_rs::utils::embedding::EmbeddingGenerator;
+use pocketflow_rs::utils::llm_wrapper::{LLMWrapper, OpenAIClient};
+use pocketflow_rs::utils::prompt_template::PromptTemplate;
+use pocketflow_rs::utils::text_chunking::ChunkingStrategy;
+use pocketflow_rs::utils::vector_db::VectorDB;
+use pocketflow_rs::{CancellationToken, Context, Node, ProgressReporter, build_flow};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+/// What `rag_offline` indexes and the services it indexes with.
+pub struct OfflineConfig {
+    /// Paths or urls of the documents to index
+    pub files: Vec<String>,
+    pub embedder: Arc<dyn EmbeddingGenerator>,
+    pub db: Arc<dyn VectorDB>,
+    pub chunk_size: usize,
+    pub overlap: usize,
+    pub strategy: ChunkingStrategy,
+    pub max_documents: Option<usize>,
+    pub max_chunks: Option<usize>,
+    pub limit_policy: LimitPolicy,
+    /// Store the full text of documents up to this many bytes alongside their chunks
+    pub max_document_text: Option<usize>,
+    pub fetch_options: FetchOptions,
+    /// Keep going with the documents that loaded when some files fail
+    pub skip_failed_files: bool,
+    /// Also build a BM25 keyword index over the chunks and save it to this path
+    pub bm25_index: Option<String>,
+    pub progress: ProgressReporter,
+}
+
+impl OfflineConfig {
+    pub fn new(
+        files: Vec<String>,
+        embedder: Arc<dyn EmbeddingGenerator>,
+        db: Arc<dyn VectorDB>,
+    ) -> Self {
+        Self {
+            files,
+            embedder,
+            db,
+            chunk_size: 1000,
+            overlap: 200,
+            strategy: ChunkingStrategy::Sentence,
+            max_documents: None,
+            max_chunks: None,
+            limit_policy: LimitPolicy::Error,
+            max_document_text: None,
+            fetch_options: FetchOptions::default(),
+            skip_failed_files: false,
+            bm25_index: None,
+            progress: ProgressReporter::default(),
+        }
+    }
+}
+
+/// The question `rag_online` answers and the services it answers with.
+pub struct OnlineConfig {
+    pub query: String,
+    /// Number of documents to retrieve
+    pub k: usize,
+    /// Drop retrieved documents scoring below this, answering "I don't know" if
+    /// none are left
+    pub min_score: Option<f32>,
+    /// Fuse vector and BM25 keyword rankings with this weight on the vector one,
+    /// see [`RetrieveDocumentNode::with_hybrid`]
+    pub hybrid_alpha: Option<f32>,
+    /// Drop retrieved documents with the same text as, or an embedding at least
+    /// this similar to, a better ranked one
+    pub dedup_threshold: Option<f32>,
+    /// Replaces the query rewrite prompt, see [`QueryRewriteNode::with_prompt`]
+    pub rewrite_prompt: Option<PromptTemplate>,
+    /// Replaces the answer prompt, see [`GenerateAnswerNode::with_prompt`]
+    pub answer_prompt: Option<PromptTemplate>,
+    /// Rewrites the query and generates the answer
+    pub llm: Arc<dyn LLMWrapper>,
+    /// Streams the answer when `RAG_STREAM=1`, usually the same client as `llm`
+    pub streaming_llm: Option<Arc<OpenAIClient>>,
+    pub embedder: Arc<dyn EmbeddingGenerator>,
+    pub db: Arc<dyn VectorDB>,
+    /// Stop generating after this long and answer with the retrieved snippets
+    pub deadline: Option<Duration>,
+    /// Runs of the flow while no answer was generated and the deadline hasn't passed
+    pub attempts: usize,
+}
+
+impl OnlineConfig {
+    pub fn new(
+        query: String,
+        llm: Arc<dyn LLMWrapper>,
+        embedder: Arc<dyn EmbeddingGenerator>,
+        db: Arc<dyn VectorDB>,
+    ) -> Self {
+        Self {
+            query,
+            k: 3,
+            min_score: None,
+            hybrid_alpha: None,
+            dedup_threshold: None,
+            rewrite_prompt: None,
+            answer_prompt: None,
+            llm,
+            streaming_llm: None,
+            embedder,
+            db,
+            deadline: None,
+            attempts: 1,
+        }
+    }
+}
+
+/// Load, chunk and embed `config.files` into `config.db`. Sources that fail
+/// validation are logged, and skipped if `skip_failed_files` is set.
+pub async fn rag_offline(config: OfflineConfig) -> Result<()> {
+    let (validate_sources, file_loader) = if config.skip_failed_files {
+        (
+            ValidateSourcesNode::lenient(config.files.clone()),
+            FileLoaderNode::lenient(config.files),
+        )
+    } else {
+        (
+            ValidateSourcesNode::new(config.files.clone()),
+            FileLoaderNode::new(config.files),
+        )
+    };
+    let mut file_loader = file_loader
+        .with_fetch_options(config.fetch_options)
+        .with_progress(config.progress.clone());
+    if let Some(max) = config.max_documents {
+        file_loader = file_loader.with_max_documents(Limit::new(max, config.limit_policy));
+    }
+    let mut chunk_documents =
+        ChunkDocumentsNode::new(config.chunk_size, config.overlap, config.strategy)
+            .with_progress(config.progress.clone());
+    if let Some(max) = config.max_chunks {
+        chunk_documents = chunk_documents.with_max_chunks(Limit::new(max, config.limit_policy));
+    }
+    let embed_documents =
+        EmbedDocumentsNode::from_generator(config.embedder).with_progress(config.progress.clone());
+    let mut create_index = CreateIndexNode::from_db(config.db).with_progress(config.progress);
+    if let Some(max_len) = config.max_document_text {
+        create_index = create_index.with_document_text(max_len);
+    }
+
+    let mut flow = build_flow!(
+        start: ("validate_sources", validate_sources),
+        nodes: [
+            ("file_loader", file_loader),
+            ("chunk_documents", chunk_documents),
+            ("embed_documents", embed_documents),
+            ("create_index", create_index)
+        ],
+        edges: [
+            ("validate_sources", "file_loader", RagState::Default),
+            ("file_loader", "chunk_documents", RagState::Default),
+            ("chunk_documents", "embed_documents", RagState::Default),
+            ("embed_documents", "create_index", RagState::Default)
+        ]
+    );
+    if let Some(path) = config.bm25_index {
+        flow.add_node(
+            "bm25_index",
+            Arc::new(BuildBm25IndexNode::new().with_path(path)),
+        );
+        flow.add_edge("create_index", "bm25_index", RagState::Default);
+    }
+
+    let (_, context) = flow.run_full(Context::new()).await?;
+    if let Some(report) = context.get(SOURCE_REPORT_KEY) {
+        for source in report["invalid"].as_array().into_iter().flatten() {
+            warn!(
+                "Invalid source {}: {}",
+                source["url"].as_str().unwrap_or_default(),
+                source["error"].as_str().unwrap_or_default()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Answer `config.query` from the documents indexed in `config.db`, returning the
+/// generated answer.
+///
+/// When generation fails, the flow routes to `DegradedAnswerNode`, which answers
+/// with the retrieved snippets. The flow is rerun up to `config.attempts` times for
+/// a generated answer, and once `config.deadline` passes the run in progress is
+/// cancelled and the snippets of the last retrieval are returned. Only a run that
+/// retrieves nothing in time fails.
+pub async fn rag_online(config: OnlineConfig) -> Result<Value> {
+    let mut context = Context::new();
+    context.set("user_query", json!(config.query.clone()));
+
+    let mut query_rewrite = QueryRewriteNode::from_client(config.llm.clone());
+    if let Some(prompt) = config.rewrite_prompt {
+        query_rewrite = query_rewrite.with_prompt(prompt);
+    }
+    let mut generate_node = match config.streaming_llm {
+        Some(client) => GenerateAnswerNode::from_openai(client, config.query),
+        None => GenerateAnswerNode::from_client(config.llm, config.query),
+    };
+    if let Some(prompt) = config.answer_prompt {
+        generate_node = generate_node.with_prompt(prompt);
+    }
+    let mut retrieve_node = RetrieveDocumentNode::from_db(config.db, config.k);
+    if let Some(min_score) = config.min_score {
+        retrieve_node = retrieve_node.with_min_score(min_score);
+    }
+    if let Some(alpha) = config.hybrid_alpha {
+        retrieve_node = retrieve_node.with_hybrid(alpha);
+    }
+    if let Some(threshold) = config.dedup_threshold {
+        retrieve_node = retrieve_node.with_dedup_threshold(threshold);
+    }
+    let flow = build_flow!(
+        start: ("query_rewrite", query_rewrite),
+        nodes: [
+            ("embed_query", EmbedQueryNode::from_generator(config.embedder)),
+            ("retrieve", retrieve_node),
+            ("generate", generate_node),
+            ("degraded", DegradedAnswerNode)
+        ],
+        edges: [
+            ("query_rewrite", "embed_query", RagState::Default),
+            ("embed_query", "retrieve", RagState::Default),
+            ("retrieve", "generate", RagState::Default),
+            ("generate", "degraded", RagState::GenerationError)
+        ]
+    );
+
+    let cancel = CancellationToken::new();
+    let timer = config.deadline.map(|deadline| {
+        let cancel = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(deadline).await;
+            cancel.cancel();
+        })
+    });
+
+    let mut degraded = None;
+    let mut last_error = None;
+    for attempt in 1..=config.attempts.max(1) {
+        let (result, context) = flow
+            .run_lenient_with_cancel(context.clone(), cancel.clone())
+            .await;
+        match result {
+            Ok(answer) if context.get(DEGRADED_KEY).is_none() => {
+                if let Some(timer) = &timer {
+                    timer.abort();
+                }
+                return Ok(answer);
+            }
+            Ok(answer) => degraded = Some(answer),
+            Err(e) => {
+                warn!("Online flow attempt {} failed: {}", attempt, e);
+                // Cancelled mid-generation, the retrieval may still be usable
+                if let Ok(answer) = DegradedAnswerNode.execute(&context).await {
+                    degraded = Some(answer);
+                }
+                last_error = Some(e);
+            }
+        }
+        if cancel.is_cancelled() {
+            break;
+        }
+    }
+    if let Some(timer) = timer {
+        timer.abort();
+    }
+
+    match degraded {
+        Some(answer) => Ok(answer),
+        None => Err(last_error.expect("an attempt without an answer failed")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{CannedLLM, memory_db};
+    use async_trait::async_trait;
+    use pocketflow_rs::utils::vector_db::InMemoryVectorDB;
+
+    /// Rewrites every query to "pangu storage" and answers with the prompt's context
+    /// lines.
+    fn rewrite_and_answer(prompt: &str) -> Result<String> {
+        if prompt.contains("Query Enhancer") {
+            return Ok(r#"{"query": "pangu storage"}"#.to_string());
+        }
+        let context = prompt
+            .lines()
+            .filter(|line| line.contains(".txt: "))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Ok(format!("Answer from:\n{}", context))
+    }
+
+    /// Embeds text about storage along the first axis and anything else along the
+    /// second.
+    struct KeywordEmbeddingGenerator;
+
+    #[async_trait]
+    impl EmbeddingGenerator for KeywordEmbeddingGenerator {
+        async fn generate_embedding(&self, text: &str) -> Result<Vec<f64>> {
+            if text.to_lowercase().contains("stor") {
+                Ok(vec![1.0, 0.0])
+            } else {
+                Ok(vec![0.0, 1.0])
+            }
+        }
+
+        async fn generate_embeddings(&self, texts: &[String]) -> Result<Vec<Vec<f64>>> {
+            let mut embeddings = Vec::new();
+            for text in texts {
+                embeddings.push(self.generate_embedding(text).await?);
+            }
+            Ok(embeddings)
+        }
+    }
+
+    /// Indexes a document about storage and one about scheduling in `dir`,
+    /// returning the collection and the storage document's path.
+    async fn index_documents(dir: &std::path::Path) -> (Arc<InMemoryVectorDB>, String) {
+        let storage = dir.join("storage.txt");
+        std::fs::write(&storage, "Pangu stores every chunk three times.").unwrap();
+        let scheduling = dir.join("scheduling.txt");
+        std::fs::write(&scheduling, "Fuxi schedules the jobs of the cluster.").unwrap();
+        let db = memory_db(2);
+
+        let files = vec![
+            storage.to_str().unwrap().to_string(),
+            scheduling.to_str().unwrap().to_string(),
+        ];
+        rag_offline(OfflineConfig::new(
+            files,
+            Arc::new(KeywordEmbeddingGenerator),
+            db.clone(),
+        ))
+        .await
+        .unwrap();
+        assert_eq!(db.len(), 2);
+        (db, storage.to_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn test_rag_online_answers_from_indexed_documents() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, storage) = index_documents(dir.path()).await;
+
+        let llm = Arc::new(CannedLLM::replying(rewrite_and_answer));
+        let mut config = OnlineConfig::new(
+            "How does Pangu store data?".to_string(),
+            llm.clone(),
+            Arc::new(KeywordEmbeddingGenerator),
+            db,
+        );
+        config.k = 1;
+        let answer = rag_online(config).await.unwrap();
+
+        let answer = answer.as_str().unwrap();
+        assert!(answer.starts_with(&format!("Answer from:\n{}: ", storage)));
+        assert!(answer.contains("Pangu stores every chunk three times."));
+        assert!(!answer.contains("Fuxi"));
+        let prompts = llm.prompts();
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[1].contains("Question: How does Pangu store data?"));
+    }
+
+    #[tokio::test]
+    async fn test_rag_online_degrades_to_snippets_at_deadline() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, storage) = index_documents(dir.path()).await;
+        let llm = Arc::new(
+            CannedLLM::replying(rewrite_and_answer)
+                .with_stall(|prompt| !prompt.contains("Query Enhancer")),
+        );
+        let mut config = OnlineConfig::new(
+            "How does Pangu store data?".to_string(),
+            llm,
+            Arc::new(KeywordEmbeddingGenerator),
+            db,
+        );
+        config.k = 1;
+        config.deadline = Some(Duration::from_millis(200));
+        config.attempts = 3;
+
+        let started = std::time::Instant::now();
+        let answer = rag_online(config).await.unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(
+            answer.as_str().unwrap(),
+            format!(
+                "No answer could be generated. The most relevant passages found:\n\n\
+                 1. {}: Pangu stores every chunk three times.",
+                storage
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rag_online_retries_failed_generation() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, _) = index_documents(dir.path()).await;
+        let llm = Arc::new(CannedLLM::replying(|prompt| {
+            if prompt.contains("Query Enhancer") {
+                rewrite_and_answer(prompt)
+            } else {
+                Err(anyhow::anyhow!("model overloaded"))
+            }
+        }));
+        let mut config = OnlineConfig::new(
+            "How does Pangu store data?".to_string(),
+            llm.clone(),
+            Arc::new(KeywordEmbeddingGenerator),
+            db,
+        );
+        config.attempts = 2;
+
+        let answer = rag_online(config).await.unwrap();
+
+        assert!(
+            answer
+                .as_str()
+                .unwrap()
+                .contains("Pangu stores every chunk")
+        );
+        // A rewrite and a generation per attempt
+        assert_eq!(llm.prompts().len(), 4);
+    }
+}
@@ -1,6 +1,6 @@
 use pocketflow_rs::ProcessState;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub enum RagState {
     // Offline states
     FileLoadedError,
@@ -21,8 +21,25 @@ pub enum RagState {
     QueryEmbeddingError,
     RetrievalError,
     GenerationError,
+    #[default]
     Default,
     QueryRewriteError,
+    FollowupSuggestionError,
+    TranslationError,
+    RerankError,
+    ExportError,
+    ImportError,
+    NoAnswer,
+    DedupError,
+    SummarizationError,
+    Unfaithful,
+    WebIndexError,
+    CompressionError,
+    InvalidSources,
+    CacheHit,
+    CacheMiss,
+    PruneError,
+    KeywordTagError,
 }
 
 impl ProcessState for RagState {
@@ -53,12 +70,131 @@ impl ProcessState for RagState {
             RagState::GenerationError => "generation_error".to_string(),
             RagState::Default => "default".to_string(),
             RagState::QueryRewriteError => "query_rewrite_error".to_string(),
+            RagState::FollowupSuggestionError => "followup_suggestion_error".to_string(),
+            RagState::TranslationError => "translation_error".to_string(),
+            RagState::RerankError => "rerank_error".to_string(),
+            RagState::ExportError => "export_error".to_string(),
+            RagState::ImportError => "import_error".to_string(),
+            RagState::NoAnswer => "no_answer".to_string(),
+            RagState::DedupError => "dedup_error".to_string(),
+            RagState::SummarizationError => "summarization_error".to_string(),
+            RagState::Unfaithful => "unfaithful".to_string(),
+            RagState::WebIndexError => "web_index_error".to_string(),
+            RagState::CompressionError => "compression_error".to_string(),
+            RagState::InvalidSources => "invalid_sources".to_string(),
+            RagState::CacheHit => "cache_hit".to_string(),
+            RagState::CacheMiss => "cache_miss".to_string(),
+            RagState::PruneError => "prune_error".to_string(),
+            RagState::KeywordTagError => "keyword_tag_error".to_string(),
         }
     }
 }
 
-impl Default for RagState {
-    fn default() -> Self {
-        RagState::Default
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use pocketflow_rs::nodes::{AggregateNode, AggregateOp, StateAdapter};
+    use pocketflow_rs::{BaseState, Context, Flow, Node, ProcessResult};
+    use serde_json::{Value, json};
+    use std::sync::Arc;
+
+    /// Stores its label as the flow result.
+    struct LabelNode(&'static str);
+
+    #[async_trait]
+    impl Node for LabelNode {
+        type State = RagState;
+
+        async fn execute(&self, _context: &Context) -> Result<Value> {
+            Ok(json!(self.0))
+        }
+    }
+
+    /// Fails when the context holds `fail`, routing to `RetrievalError`.
+    struct RetrieveNode;
+
+    #[async_trait]
+    impl Node for RetrieveNode {
+        type State = RagState;
+
+        async fn execute(&self, context: &Context) -> Result<Value> {
+            match context.get("fail") {
+                Some(_) => Err(anyhow::anyhow!("vector db unreachable")),
+                None => Ok(json!("retrieved")),
+            }
+        }
+
+        async fn post_process(
+            &self,
+            _context: &mut Context,
+            result: &Result<Value>,
+        ) -> Result<ProcessResult<RagState>> {
+            match result {
+                Ok(_) => Ok(ProcessResult::new(
+                    RagState::Default,
+                    "documents_retrieved".to_string(),
+                )),
+                Err(e) => Ok(ProcessResult::new(
+                    RagState::RetrievalError,
+                    format!("retrieval_error: {}", e),
+                )),
+            }
+        }
+    }
+
+    async fn run_retrieval(flow: &Flow<RagState>, fail: bool) -> Value {
+        let mut context = Context::new();
+        if fail {
+            context.set("fail", json!(true));
+        }
+        flow.run(context).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_error_state_routes_to_fallback() {
+        let mut flow = Flow::new("retrieve", Arc::new(RetrieveNode));
+        flow.add_node("generate", Arc::new(LabelNode("generate")));
+        flow.add_node("fallback", Arc::new(LabelNode("fallback")));
+        flow.add_edge("retrieve", "generate", RagState::Default);
+        flow.add_edge("retrieve", "fallback", RagState::RetrievalError);
+        assert_eq!(run_retrieval(&flow, false).await, json!("generate"));
+        assert_eq!(run_retrieval(&flow, true).await, json!("fallback"));
+
+        // Without an edge of its own, the error takes the default edge
+        let mut flow = Flow::new("retrieve", Arc::new(RetrieveNode));
+        flow.add_node("generate", Arc::new(LabelNode("generate")));
+        flow.add_edge("retrieve", "generate", RagState::Default);
+        assert_eq!(run_retrieval(&flow, true).await, json!("generate"));
+
+        // And with no edge matching at all, the flow stops at the failed node
+        let mut flow = Flow::new("retrieve", Arc::new(RetrieveNode));
+        flow.add_node("generate", Arc::new(LabelNode("generate")));
+        flow.add_edge("retrieve", "generate", RagState::DocumentsRetrieved);
+        assert_eq!(run_retrieval(&flow, true).await, Value::Null);
+    }
+
+    async fn run_flow(scores: Value) -> Value {
+        let mean = AggregateNode::new("scores", AggregateOp::Mean, "mean", BaseState::Failure);
+        let adapter = StateAdapter::new(Arc::new(mean), |state| match state {
+            BaseState::Failure => RagState::NoAnswer,
+            _ => RagState::DocumentsRetrieved,
+        });
+        let mut flow = Flow::new("mean", Arc::new(adapter));
+        flow.add_node("answer", Arc::new(LabelNode("answer")));
+        flow.add_node("fallback", Arc::new(LabelNode("fallback")));
+        flow.add_edge("mean", "answer", RagState::DocumentsRetrieved);
+        flow.add_edge("mean", "fallback", RagState::NoAnswer);
+
+        let mut context = Context::new();
+        context.set("scores", scores);
+        flow.run(context).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_base_state_node_in_rag_flow() {
+        assert_eq!(run_flow(json!([0.5, 0.7])).await, json!("answer"));
+        assert_eq!(run_flow(json!("no scores")).await, json!("fallback"));
     }
 }
@@ -0,0 +1,86 @@
+//! Fixtures shared by the example's unit tests.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use pocketflow_rs::utils::llm_wrapper::{LLMOptions, LLMResponse, LLMWrapper};
+use pocketflow_rs::utils::vector_db::{
+    DistanceMetric, InMemoryVectorDB, VectorDB, VectorDBOptions, VectorRecord,
+};
+use std::sync::{Arc, Mutex};
+
+/// An empty in-memory cosine collection of `dimension`-dimensional vectors.
+pub(crate) fn memory_db(dimension: usize) -> Arc<InMemoryVectorDB> {
+    Arc::new(InMemoryVectorDB::new(VectorDBOptions {
+        collection_name: "test".to_string(),
+        dimension,
+        distance_metric: DistanceMetric::Cosine,
+        payload_indexes: Vec::new(),
+        sparse_vector_name: None,
+    }))
+}
+
+/// Every record stored in `db`, in insertion order.
+pub(crate) async fn stored_records(db: &dyn VectorDB) -> Vec<VectorRecord> {
+    db.scroll(None, usize::MAX).await.unwrap().records
+}
+
+/// Answers each prompt with a canned reply and records the prompts it was sent.
+pub(crate) struct CannedLLM {
+    reply: Box<dyn Fn(&str) -> Result<String> + Send + Sync>,
+    stall: Box<dyn Fn(&str) -> bool + Send + Sync>,
+    prompts: Mutex<Vec<String>>,
+}
+
+impl CannedLLM {
+    /// Replies `content` to every prompt.
+    pub(crate) fn new(content: &str) -> Self {
+        let content = content.to_string();
+        Self::replying(move |_| Ok(content.clone()))
+    }
+
+    /// Replies with what `reply` returns for the prompt.
+    pub(crate) fn replying(reply: impl Fn(&str) -> Result<String> + Send + Sync + 'static) -> Self {
+        Self {
+            reply: Box::new(reply),
+            stall: Box::new(|_| false),
+            prompts: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Never replies to prompts `stall` holds for, to test deadlines.
+    pub(crate) fn with_stall(
+        mut self,
+        stall: impl Fn(&str) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.stall = Box::new(stall);
+        self
+    }
+
+    pub(crate) fn prompts(&self) -> Vec<String> {
+        self.prompts.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl LLMWrapper for CannedLLM {
+    async fn generate(&self, prompt: &str) -> Result<LLMResponse> {
+        self.generate_with_options(prompt, LLMOptions::default())
+            .await
+    }
+
+    async fn generate_with_options(
+        &self,
+        prompt: &str,
+        _options: LLMOptions,
+    ) -> Result<LLMResponse> {
+        self.prompts.lock().unwrap().push(prompt.to_string());
+        if (self.stall)(prompt) {
+            std::future::pending::<()>().await;
+        }
+        Ok(LLMResponse {
+            content: (self.reply)(prompt)?,
+            usage: None,
+            cached: false,
+        })
+    }
+}
@@ -3,17 +3,21 @@ use async_trait::async_trait;
 use chrono::NaiveDate;
 use duckdb::types::ValueRef;
 use duckdb::{Connection, Result as DuckResult};
-use openai_api_rust::chat::*;
-use openai_api_rust::*;
+use pocketflow_rs::utils::llm_wrapper::{LLMWrapper, LLMWrapperExt, OpenAIClient};
 use pocketflow_rs::{Context, Node, ProcessResult, ProcessState};
+use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
+use std::sync::Arc;
 use tracing::{error, info};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub enum SqlExecutorState {
     SchemaRetrieved,
     SqlGenerated,
     SqlExecuted,
+    /// The database rejected the generated SQL; see [`SQL_ERROR_KEY`].
+    SqlError,
+    #[default]
     Default,
 }
 
@@ -27,23 +31,33 @@ impl ProcessState for SqlExecutorState {
             SqlExecutorState::SchemaRetrieved => "schema_retrieved".to_string(),
             SqlExecutorState::SqlGenerated => "sql_generated".to_string(),
             SqlExecutorState::SqlExecuted => "sql_executed".to_string(),
+            SqlExecutorState::SqlError => "sql_error".to_string(),
             SqlExecutorState::Default => "default".to_string(),
         }
     }
 }
 
-impl Default for SqlExecutorState {
-    fn default() -> Self {
-        SqlExecutorState::Default
-    }
-}
-
 #[derive(Debug, thiserror::Error)]
 pub enum WorkflowError {
     #[error("NodeExecution: {0}")]
     NodeExecution(String),
+    #[error("SqlExecution: {0}")]
+    SqlExecution(String),
 }
 
+/// Context key holding the database schema, kept apart from `result` so a
+/// retried generation still sees it.
+pub const SCHEMA_KEY: &str = "schema";
+/// Context key holding the SQL the database last rejected.
+pub const FAILED_SQL_KEY: &str = "failed_sql";
+/// Context key holding the database's error for [`FAILED_SQL_KEY`].
+pub const SQL_ERROR_KEY: &str = "sql_error";
+/// Context key counting the failed executions so far.
+pub const SQL_ATTEMPTS_KEY: &str = "sql_attempts";
+
+/// How many times `ExecuteSQLNode` routes a failed query back by default.
+pub const DEFAULT_MAX_SQL_RETRIES: usize = 3;
+
 pub struct SchemaRetrievalNode {
     db_path: String,
 }
@@ -65,7 +79,7 @@ impl Node for SchemaRetrievalNode {
 
         let query = "SELECT table_name FROM information_schema.tables WHERE table_schema='main'";
         let mut stmt = conn.prepare(query)?;
-        let tables = stmt.query_map([], |row| Ok(row.get(0)?));
+        let tables = stmt.query_map([], |row| row.get::<_, String>(0));
 
         let tables = tables.context("获取表名失败")?;
 
@@ -104,7 +118,9 @@ impl Node for SchemaRetrievalNode {
         context: &mut Context,
         result: &Result<Value>,
     ) -> Result<ProcessResult<SqlExecutorState>> {
-        context.set("result", result.as_ref().unwrap().clone());
+        let schema = result.as_ref().unwrap().clone();
+        context.set(SCHEMA_KEY, schema.clone());
+        context.set("result", schema);
         Ok(ProcessResult::new(
             SqlExecutorState::SchemaRetrieved,
             "schema_retrieved".to_string(),
@@ -112,17 +128,98 @@ impl Node for SchemaRetrievalNode {
     }
 }
 
+/// SQL dialect named in the generation prompt so the model uses matching syntax.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqlDialect {
+    DuckDB,
+    Postgres,
+    MySQL,
+}
+
+impl SqlDialect {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SqlDialect::DuckDB => "DuckDB",
+            SqlDialect::Postgres => "PostgreSQL",
+            SqlDialect::MySQL => "MySQL",
+        }
+    }
+}
+
+/// The reply `OpenAISQLGenerationNode` asks the model for.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GeneratedSql {
+    pub sql: String,
+    pub explanation: String,
+}
+
 pub struct OpenAISQLGenerationNode {
-    api_key: String,
+    client: Arc<dyn LLMWrapper>,
     user_query: String,
+    examples: Vec<(String, String)>,
+    dialect: Option<SqlDialect>,
 }
 
 impl OpenAISQLGenerationNode {
     pub fn new(api_key: String, user_query: String) -> Self {
-        Self {
+        let client = OpenAIClient::new(
             api_key,
+            "qwen-plus".to_string(),
+            "https://dashscope.aliyuncs.com/compatible-mode/v1/".to_string(),
+        );
+        Self::from_client(Arc::new(client), user_query)
+    }
+
+    pub fn from_client(client: Arc<dyn LLMWrapper>, user_query: String) -> Self {
+        Self {
+            client,
             user_query,
+            examples: Vec::new(),
+            dialect: None,
+        }
+    }
+
+    /// Few-shot `(question, sql)` pairs showing the schema's conventions.
+    pub fn with_examples(mut self, examples: Vec<(String, String)>) -> Self {
+        self.examples = examples;
+        self
+    }
+
+    pub fn with_dialect(mut self, dialect: SqlDialect) -> Self {
+        self.dialect = Some(dialect);
+        self
+    }
+
+    /// `failure` is the previously rejected SQL and the database's error.
+    fn build_prompt(&self, schema_json: &str, failure: Option<(&str, &str)>) -> String {
+        let mut prompt = String::from(
+            "You are a SQL expert. Based on the provided database schema and user query, generate the correct SQL query. Respond with a JSON object holding the SQL query as \"sql\" and a one-sentence explanation of it as \"explanation\". The condition content uses English, you can choose to query some fields first, then make a general query.",
+        );
+        if let Some(dialect) = self.dialect {
+            prompt.push_str(&format!(
+                " Write the query in the {} SQL dialect.",
+                dialect.name()
+            ));
         }
+
+        prompt.push_str(&format!("\n\ndatabase schema:\n{}\n\n", schema_json));
+        if !self.examples.is_empty() {
+            prompt.push_str("examples:\n");
+            for (question, sql) in &self.examples {
+                prompt.push_str(&format!("question: {}\nsql: {}\n\n", question, sql));
+            }
+        }
+        prompt.push_str(&format!(
+            "user query:\n{}\n\nPlease generate a SQL query to answer this question.",
+            self.user_query
+        ));
+        if let Some((sql, error)) = failure {
+            prompt.push_str(&format!(
+                "\n\nYour previous query failed.\nprevious sql:\n{}\n\ndatabase error:\n{}\n\nPlease generate a corrected SQL query that avoids this error.",
+                sql, error
+            ));
+        }
+        prompt
     }
 }
 
@@ -180,57 +277,38 @@ impl Node for OpenAISQLGenerationNode {
     type State = SqlExecutorState;
 
     async fn execute(&self, context: &Context) -> Result<Value> {
-        let schema = context.get("result").ok_or_else(|| {
-            WorkflowError::NodeExecution("Failed to get database schema".to_string())
-        })?;
-
-        let system_prompt = "You are a SQL expert. Based on the provided database schema and user query, generate the correct SQL query. Only return the SQL query, do not include any explanation or other text. The condition content uses English, you can choose to query some fields first, then make a general query.";
+        let schema = context
+            .get(SCHEMA_KEY)
+            .or_else(|| context.get("result"))
+            .ok_or_else(|| {
+                WorkflowError::NodeExecution("Failed to get database schema".to_string())
+            })?;
 
         let schema_json =
             serde_json::to_string_pretty(schema).context("Failed to serialize database schema")?;
 
-        let user_prompt = format!(
-            "database schema:\n{}\n\nuser query:\n{}\n\nPlease generate a SQL query to answer this question.",
-            schema_json, self.user_query
-        );
-
-        let auth = Auth::new(self.api_key.as_str());
-        let openai = OpenAI::new(auth, "https://dashscope.aliyuncs.com/compatible-mode/v1/");
-        let body = ChatBody {
-            model: "qwen-plus".to_string(),
-            max_tokens: Some(1024),
-            temperature: Some(0.8_f32),
-            top_p: Some(0_f32),
-            n: Some(1),
-            stream: Some(false),
-            stop: None,
-            presence_penalty: None,
-            frequency_penalty: None,
-            logit_bias: None,
-            user: None,
-            messages: vec![
-                Message {
-                    role: Role::System,
-                    content: system_prompt.to_string(),
-                },
-                Message {
-                    role: Role::User,
-                    content: user_prompt,
-                },
-            ],
-        };
-        let rs = openai.chat_completion_create(&body);
-        if rs.is_err() {
-            error!("OpenAI Error {}", rs.as_ref().err().unwrap().to_string());
-        }
-        let choice = rs.unwrap().choices;
-        let message = &choice[0].message.as_ref().unwrap();
-
-        let sql = message.content.clone();
-
-        println!("生成的SQL查询: {}", sql);
-
-        Ok(Value::String(sql))
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "sql": {"type": "string"},
+                "explanation": {"type": "string"},
+            },
+            "required": ["sql", "explanation"],
+        });
+        let failure = context
+            .get(FAILED_SQL_KEY)
+            .and_then(Value::as_str)
+            .zip(context.get(SQL_ERROR_KEY).and_then(Value::as_str));
+        let generated: GeneratedSql = self
+            .client
+            .generate_json(&self.build_prompt(&schema_json, failure), Some(schema))
+            .await
+            .inspect_err(|e| error!("OpenAI Error {}", e))?;
+
+        println!("生成的SQL查询: {}", generated.sql);
+        println!("说明: {}", generated.explanation);
+
+        Ok(serde_json::to_value(generated)?)
     }
 
     async fn post_process(
@@ -246,16 +324,78 @@ impl Node for OpenAISQLGenerationNode {
     }
 }
 
+/// Runs the generated SQL. A query the database rejects is recorded under
+/// [`FAILED_SQL_KEY`] and [`SQL_ERROR_KEY`] and routed as
+/// [`SqlExecutorState::SqlError`], so an edge back to `OpenAISQLGenerationNode`
+/// can ask for a corrected query, up to `max_retries` times.
 pub struct ExecuteSQLNode {
     db_path: String,
+    max_retries: usize,
 }
 
 impl ExecuteSQLNode {
     pub fn new(db_path: String) -> Self {
-        Self { db_path }
+        Self {
+            db_path,
+            max_retries: DEFAULT_MAX_SQL_RETRIES,
+        }
+    }
+
+    /// How many failed queries are sent back for correction before giving up.
+    pub fn with_max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+}
+
+/// The generated `{"sql", "explanation"}` object, or the bare query.
+fn generated_sql(context: &Context) -> Option<&str> {
+    context
+        .get("result")
+        .and_then(|v| v.get("sql").unwrap_or(v).as_str())
+}
+
+fn value_to_string(value_ref: ValueRef) -> String {
+    match value_ref {
+        ValueRef::Null => "NULL".to_string(),
+        ValueRef::Boolean(b) => b.to_string(),
+        ValueRef::TinyInt(i) => i.to_string(),
+        ValueRef::SmallInt(i) => i.to_string(),
+        ValueRef::Int(i) => i.to_string(),
+        ValueRef::BigInt(i) => i.to_string(),
+        ValueRef::Float(f) => f.to_string(),
+        ValueRef::Double(d) => d.to_string(),
+        ValueRef::Text(bytes) => String::from_utf8_lossy(bytes).to_string(),
+        ValueRef::Blob(_) => "[BLOB]".to_string(),
+        ValueRef::Date32(d) => {
+            let date = NaiveDate::from_num_days_from_ce_opt(d + 719163).unwrap();
+            date.format("%Y-%m-%d").to_string()
+        }
+        _ => format!("Unsupported: {:?}", value_ref),
     }
 }
 
+/// The column names and stringified rows `sql` returns.
+fn query_rows(conn: &Connection, sql: &str) -> DuckResult<(Vec<String>, Vec<Vec<String>>)> {
+    let mut stmt = conn.prepare(sql)?;
+    let mut rows = stmt.query([])?;
+
+    let mut headers = Vec::new();
+    let mut data_rows = Vec::new();
+    while let Some(row) = rows.next()? {
+        // Get column names from the first row
+        if headers.is_empty() {
+            headers = row.as_ref().column_names();
+        }
+        let mut row_values = Vec::with_capacity(headers.len());
+        for i in 0..headers.len() {
+            row_values.push(value_to_string(row.get_ref(i)?));
+        }
+        data_rows.push(row_values);
+    }
+    Ok((headers, data_rows))
+}
+
 #[async_trait]
 impl Node for ExecuteSQLNode {
     type State = SqlExecutorState;
@@ -263,79 +403,14 @@ impl Node for ExecuteSQLNode {
     async fn execute(&self, context: &Context) -> Result<Value> {
         let conn = Connection::open(&self.db_path)?;
 
-        let sql = context
-            .get("result")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| {
-                WorkflowError::NodeExecution("SQL query not found in context".to_string())
-            })?;
+        let sql = generated_sql(context).ok_or_else(|| {
+            WorkflowError::NodeExecution("SQL query not found in context".to_string())
+        })?;
 
         info!("ExecuteSQLNode: Get Sql: {}", sql);
 
-        let mut stmt = conn.prepare(sql)?;
-        let mut rows = stmt.query([])?;
-
-        let mut headers = Vec::new();
-        let mut data_rows = Vec::new();
-
-        if let Some(first_row) = rows.next()? {
-            // Get column names from the first row
-            headers = first_row.as_ref().column_names();
-            let column_count = headers.len();
-
-            // Process first row
-            let mut row_values = Vec::with_capacity(column_count);
-            for i in 0..column_count {
-                let value_ref = first_row.get_ref(i)?;
-                let string_value = match value_ref {
-                    ValueRef::Null => "NULL".to_string(),
-                    ValueRef::Boolean(b) => b.to_string(),
-                    ValueRef::TinyInt(i) => i.to_string(),
-                    ValueRef::SmallInt(i) => i.to_string(),
-                    ValueRef::Int(i) => i.to_string(),
-                    ValueRef::BigInt(i) => i.to_string(),
-                    ValueRef::Float(f) => f.to_string(),
-                    ValueRef::Double(d) => d.to_string(),
-                    ValueRef::Text(bytes) => String::from_utf8_lossy(bytes).to_string(),
-                    ValueRef::Blob(_) => "[BLOB]".to_string(),
-                    ValueRef::Date32(d) => {
-                        let date = NaiveDate::from_num_days_from_ce_opt(d as i32 + 719163).unwrap();
-                        date.format("%Y-%m-%d").to_string()
-                    }
-                    _ => format!("Unsupported: {:?}", value_ref),
-                };
-                row_values.push(string_value);
-            }
-            data_rows.push(row_values);
-
-            // Process remaining rows
-            while let Some(row) = rows.next()? {
-                let mut row_values = Vec::with_capacity(column_count);
-                for i in 0..column_count {
-                    let value_ref = row.get_ref(i)?;
-                    let string_value = match value_ref {
-                        ValueRef::Null => "NULL".to_string(),
-                        ValueRef::Boolean(b) => b.to_string(),
-                        ValueRef::TinyInt(i) => i.to_string(),
-                        ValueRef::SmallInt(i) => i.to_string(),
-                        ValueRef::Int(i) => i.to_string(),
-                        ValueRef::BigInt(i) => i.to_string(),
-                        ValueRef::Float(f) => f.to_string(),
-                        ValueRef::Double(d) => d.to_string(),
-                        ValueRef::Text(bytes) => String::from_utf8_lossy(bytes).to_string(),
-                        ValueRef::Blob(_) => "[BLOB]".to_string(),
-                        ValueRef::Date32(d) => {
-                            let date =
-                                NaiveDate::from_num_days_from_ce_opt(d as i32 + 719163).unwrap();
-                            date.format("%Y-%m-%d").to_string()
-                        }
-                        _ => format!("Unsupported: {:?}", value_ref),
-                    };
-                    row_values.push(string_value);
-                }
-                data_rows.push(row_values);
-            }
-        }
+        let (headers, data_rows) =
+            query_rows(&conn, sql).map_err(|e| WorkflowError::SqlExecution(e.to_string()))?;
 
         print_table(&headers, &data_rows);
 
@@ -350,10 +425,159 @@ impl Node for ExecuteSQLNode {
         context: &mut Context,
         result: &Result<Value>,
     ) -> Result<ProcessResult<SqlExecutorState>> {
-        context.set("result", result.as_ref().unwrap().clone());
-        Ok(ProcessResult::new(
-            SqlExecutorState::SqlExecuted,
-            "sql_executed".to_string(),
-        ))
+        match result {
+            Ok(value) => {
+                context.remove(FAILED_SQL_KEY);
+                context.remove(SQL_ERROR_KEY);
+                context.set("result", value.clone());
+                Ok(ProcessResult::new(
+                    SqlExecutorState::SqlExecuted,
+                    "sql_executed".to_string(),
+                ))
+            }
+            Err(e) => {
+                let Some(WorkflowError::SqlExecution(message)) = e.downcast_ref::<WorkflowError>()
+                else {
+                    return Err(anyhow::anyhow!("Failed to execute SQL: {}", e));
+                };
+                let attempts = context.get_i64(SQL_ATTEMPTS_KEY).unwrap_or(0) as usize + 1;
+                if attempts > self.max_retries {
+                    return Err(anyhow::anyhow!(
+                        "SQL still failing after {} retries: {}",
+                        self.max_retries,
+                        message
+                    ));
+                }
+
+                error!("SQL attempt {} failed: {}", attempts, message);
+                let sql = generated_sql(context).unwrap_or_default().to_string();
+                context.set(FAILED_SQL_KEY, Value::String(sql));
+                context.set(SQL_ERROR_KEY, Value::String(message.clone()));
+                context.set(SQL_ATTEMPTS_KEY, json!(attempts));
+                Ok(ProcessResult::new(
+                    SqlExecutorState::SqlError,
+                    "sql_error".to_string(),
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pocketflow_rs::utils::llm_wrapper::{LLMOptions, LLMResponse};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingLLM {
+        prompts: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl LLMWrapper for RecordingLLM {
+        async fn generate(&self, prompt: &str) -> Result<LLMResponse> {
+            self.generate_with_options(prompt, LLMOptions::default())
+                .await
+        }
+
+        #[allow(unused_variables)]
+        async fn generate_with_options(
+            &self,
+            prompt: &str,
+            options: LLMOptions,
+        ) -> Result<LLMResponse> {
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            Ok(LLMResponse {
+                content: r#"{"sql": "SELECT 1", "explanation": "Selects one."}"#.to_string(),
+                usage: None,
+                cached: false,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prompt_includes_examples_and_dialect() {
+        let llm = Arc::new(RecordingLLM::default());
+        let node =
+            OpenAISQLGenerationNode::from_client(llm.clone(), "How many orders?".to_string())
+                .with_dialect(SqlDialect::DuckDB)
+                .with_examples(vec![(
+                    "Customers from Berlin?".to_string(),
+                    "SELECT * FROM customers WHERE city = 'Berlin'".to_string(),
+                )]);
+        let mut context = Context::new();
+        context.set(
+            "result",
+            json!({"orders": [{"name": "id", "type": "INTEGER"}]}),
+        );
+
+        let result = node.execute(&context).await.unwrap();
+
+        assert_eq!(
+            result,
+            json!({"sql": "SELECT 1", "explanation": "Selects one."})
+        );
+        let prompts = llm.prompts.lock().unwrap();
+        let prompt = &prompts[0];
+        assert!(prompt.contains("DuckDB SQL dialect"));
+        assert!(prompt.contains("question: Customers from Berlin?"));
+        assert!(prompt.contains("sql: SELECT * FROM customers WHERE city = 'Berlin'"));
+        assert!(prompt.contains("user query:\nHow many orders?"));
+    }
+
+    #[tokio::test]
+    async fn test_prompt_includes_failed_sql_and_error() {
+        let llm = Arc::new(RecordingLLM::default());
+        let node =
+            OpenAISQLGenerationNode::from_client(llm.clone(), "How many orders?".to_string());
+        let mut context = Context::new();
+        context.set(
+            SCHEMA_KEY,
+            json!({"orders": [{"name": "id", "type": "INTEGER"}]}),
+        );
+        context.set("result", json!({"sql": "SELEC COUNT(*) FROM orders"}));
+        context.set(FAILED_SQL_KEY, json!("SELEC COUNT(*) FROM orders"));
+        context.set(
+            SQL_ERROR_KEY,
+            json!("Parser Error: syntax error at or near \"SELEC\""),
+        );
+
+        node.execute(&context).await.unwrap();
+
+        let prompts = llm.prompts.lock().unwrap();
+        let prompt = &prompts[0];
+        assert!(prompt.contains("\"orders\""));
+        assert!(prompt.contains("previous sql:\nSELEC COUNT(*) FROM orders"));
+        assert!(prompt.contains("database error:\nParser Error: syntax error"));
+    }
+
+    #[tokio::test]
+    async fn test_sql_error_routes_back_until_retry_cap() {
+        let node = ExecuteSQLNode::new(":memory:".to_string()).with_max_retries(1);
+        let mut context = Context::new();
+        context.set("result", json!({"sql": "SELEC 1", "explanation": "Typo."}));
+
+        let result = node.execute(&context).await;
+        let process_result = node.post_process(&mut context, &result).await.unwrap();
+
+        assert_eq!(process_result.state, SqlExecutorState::SqlError);
+        assert_eq!(context.get_str(FAILED_SQL_KEY).unwrap(), "SELEC 1");
+        assert!(context.get_str(SQL_ERROR_KEY).unwrap().contains("SELEC"));
+        assert_eq!(context.get_i64(SQL_ATTEMPTS_KEY).unwrap(), 1);
+
+        let result = node.execute(&context).await;
+        assert!(node.post_process(&mut context, &result).await.is_err());
+
+        context.set("result", json!({"sql": "SELECT 1 AS one"}));
+        let result = node.execute(&context).await;
+        let process_result = node.post_process(&mut context, &result).await.unwrap();
+
+        assert_eq!(process_result.state, SqlExecutorState::SqlExecuted);
+        assert!(context.get(FAILED_SQL_KEY).is_none());
+        assert_eq!(
+            context.get("result"),
+            Some(&json!({"columns": ["one"], "data": [["1"]]}))
+        );
     }
 }
@@ -2,8 +2,8 @@ use std::env;
 
 use anyhow::Result;
 use duckdb::Connection;
-use pocketflow_rs::{Context, build_flow};
-use text2sql::flow::{ExecuteSQLNode, OpenAISQLGenerationNode, SchemaRetrievalNode};
+use pocketflow_rs::{CancellationToken, Context, build_flow};
+use text2sql::flow::{ExecuteSQLNode, OpenAISQLGenerationNode, SchemaRetrievalNode, SqlDialect};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -29,7 +29,8 @@ async fn main() -> Result<()> {
 
     let schema_retrieval = SchemaRetrievalNode::new(db_path.to_string());
     let openai_sql_gen =
-        OpenAISQLGenerationNode::new(env::var("DASH_SCOPE_API_KEY").unwrap(), user_query);
+        OpenAISQLGenerationNode::new(env::var("DASH_SCOPE_API_KEY").unwrap(), user_query)
+            .with_dialect(SqlDialect::DuckDB);
     let execute_sql = ExecuteSQLNode::new(db_path.to_string());
 
     let flow = build_flow! (
@@ -40,12 +41,23 @@ async fn main() -> Result<()> {
         ],
         edges: [
             ("start", "generate_sql", text2sql::flow::SqlExecutorState::Default),
-            ("generate_sql", "execute_sql", text2sql::flow::SqlExecutorState::Default)
+            ("generate_sql", "execute_sql", text2sql::flow::SqlExecutorState::Default),
+            // Rejected SQL goes back for a corrected query
+            ("execute_sql", "generate_sql", text2sql::flow::SqlExecutorState::SqlError)
         ]
     );
     let context = Context::new();
 
-    let result = flow.run(context).await?;
+    // Ctrl-C aborts the query, even while waiting on the LLM
+    let cancel = CancellationToken::new();
+    let on_ctrl_c = cancel.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            on_ctrl_c.cancel();
+        }
+    });
+
+    let result = flow.run_with_cancel(context, cancel).await?;
     println!("result: {:?}", result);
 
     Ok(())
@@ -0,0 +1,149 @@
+use crate::Params;
+use serde_json::Value;
+use std::str::FromStr;
+use tracing::warn;
+
+/// Runtime toggles for a node, read from its [`Params`] first and then from the
+/// environment, so behaviors can be flipped without recompiling. A key like
+/// `stream` is looked up as `params["stream"]`, then as `<PREFIX>STREAM`.
+/// Unparseable values log a warning and fall back to the default.
+#[derive(Debug, Clone, Default)]
+pub struct NodeConfig {
+    params: Params,
+    env_prefix: String,
+}
+
+impl NodeConfig {
+    pub fn new(env_prefix: &str) -> Self {
+        Self {
+            params: Params::new(),
+            env_prefix: env_prefix.to_string(),
+        }
+    }
+
+    pub fn with_params(mut self, params: Params) -> Self {
+        self.params = params;
+        self
+    }
+
+    fn env_var(&self, key: &str) -> String {
+        format!("{}{}", self.env_prefix, key.to_uppercase())
+    }
+
+    /// The raw value for `key` as a string, and where it came from.
+    fn raw(&self, key: &str) -> Option<(String, String)> {
+        if let Some(value) = self.params.get(key).filter(|v| !v.is_null()) {
+            let raw = match value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            return Some((raw, format!("param '{}'", key)));
+        }
+        let var = self.env_var(key);
+        std::env::var(&var).ok().map(|raw| (raw, var))
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<String> {
+        self.raw(key).map(|(raw, _)| raw)
+    }
+
+    /// Accepts `1`/`true`/`yes`/`on` and `0`/`false`/`no`/`off`, case-insensitively.
+    pub fn get_bool(&self, key: &str, default: bool) -> bool {
+        let Some((raw, source)) = self.raw(key) else {
+            return default;
+        };
+        match raw.trim().to_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => true,
+            "0" | "false" | "no" | "off" => false,
+            _ => {
+                warn!(
+                    "Invalid boolean {:?} for {}, using default {}",
+                    raw, source, default
+                );
+                default
+            }
+        }
+    }
+
+    pub fn get_usize(&self, key: &str, default: usize) -> usize {
+        self.parsed(key, default)
+    }
+
+    pub fn get_f64(&self, key: &str, default: f64) -> f64 {
+        self.parsed(key, default)
+    }
+
+    fn parsed<T: FromStr + std::fmt::Debug>(&self, key: &str, default: T) -> T {
+        let Some((raw, source)) = self.raw(key) else {
+            return default;
+        };
+        raw.trim().parse().unwrap_or_else(|_| {
+            warn!(
+                "Invalid value {:?} for {}, using default {:?}",
+                raw, source, default
+            );
+            default
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn params(pairs: &[(&str, Value)]) -> Params {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_reads_params() {
+        let config = NodeConfig::new("CONFIG_TEST_PARAMS_").with_params(params(&[
+            ("stream", json!(true)),
+            ("cache", json!("off")),
+            ("top_k", json!(5)),
+            ("temperature", json!("0.5")),
+        ]));
+
+        assert!(config.get_bool("stream", false));
+        assert!(!config.get_bool("cache", true));
+        assert_eq!(config.get_usize("top_k", 3), 5);
+        assert_eq!(config.get_f64("temperature", 1.0), 0.5);
+        assert!(config.get_bool("missing", true));
+    }
+
+    #[test]
+    fn test_reads_env_with_params_taking_precedence() {
+        // SAFETY: the variable names are unique to this test
+        unsafe {
+            std::env::set_var("CONFIG_TEST_ENV_STREAM", "1");
+            std::env::set_var("CONFIG_TEST_ENV_CACHE", "yes");
+        }
+        let config =
+            NodeConfig::new("CONFIG_TEST_ENV_").with_params(params(&[("cache", json!(false))]));
+
+        assert!(config.get_bool("stream", false));
+        assert!(!config.get_bool("cache", true));
+        assert_eq!(config.get_str("stream").as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn test_invalid_values_fall_back_to_defaults() {
+        // SAFETY: the variable name is unique to this test
+        unsafe {
+            std::env::set_var("CONFIG_TEST_INVALID_STREAM", "sometimes");
+        }
+        let config = NodeConfig::new("CONFIG_TEST_INVALID_").with_params(params(&[
+            ("top_k", json!("many")),
+            ("temperature", json!([1])),
+        ]));
+
+        assert!(!config.get_bool("stream", false));
+        assert!(config.get_bool("stream", true));
+        assert_eq!(config.get_usize("top_k", 3), 3);
+        assert_eq!(config.get_f64("temperature", 0.7), 0.7);
+    }
+}
@@ -1,8 +1,15 @@
+use anyhow::{Context as _, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Context {
     data: HashMap<String, Value>,
     metadata: HashMap<String, Value>,
@@ -27,6 +34,48 @@ impl Context {
         self.data.get(key)
     }
 
+    /// Deserialize the value under `key`, failing with an error naming the key and
+    /// the expected type if it is missing or has another shape.
+    pub fn get_as<T: DeserializeOwned>(&self, key: &str) -> Result<T> {
+        let value = self.require(key)?;
+        T::deserialize(value).map_err(|e| {
+            anyhow::anyhow!(
+                "Context value under '{}' is not a valid {}: {}",
+                key,
+                std::any::type_name::<T>(),
+                e
+            )
+        })
+    }
+
+    pub fn get_str(&self, key: &str) -> Result<&str> {
+        self.require(key)?
+            .as_str()
+            .ok_or_else(|| Self::wrong_type(key, "a string"))
+    }
+
+    pub fn get_array(&self, key: &str) -> Result<&Vec<Value>> {
+        self.require(key)?
+            .as_array()
+            .ok_or_else(|| Self::wrong_type(key, "an array"))
+    }
+
+    pub fn get_i64(&self, key: &str) -> Result<i64> {
+        self.require(key)?
+            .as_i64()
+            .ok_or_else(|| Self::wrong_type(key, "an integer"))
+    }
+
+    fn require(&self, key: &str) -> Result<&Value> {
+        self.data
+            .get(key)
+            .ok_or_else(|| anyhow::anyhow!("No value found in context under '{}'", key))
+    }
+
+    fn wrong_type(key: &str, expected: &str) -> anyhow::Error {
+        anyhow::anyhow!("Context value under '{}' is not {}", key, expected)
+    }
+
     pub fn get_metadata(&self, key: &str) -> Option<&Value> {
         self.metadata.get(key)
     }
@@ -64,6 +113,26 @@ impl Context {
         }
     }
 
+    /// The data and metadata entries added or changed relative to `base`. Keys
+    /// removed since `base` are not included.
+    pub fn changes_since(&self, base: &Context) -> Context {
+        fn changed(
+            entries: &HashMap<String, Value>,
+            base: &HashMap<String, Value>,
+        ) -> HashMap<String, Value> {
+            entries
+                .iter()
+                .filter(|(key, value)| base.get(*key) != Some(value))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect()
+        }
+
+        Context {
+            data: changed(&self.data, &base.data),
+            metadata: changed(&self.metadata, &base.metadata),
+        }
+    }
+
     pub fn clear(&mut self) {
         self.data.clear();
         self.metadata.clear();
@@ -76,6 +145,79 @@ impl Context {
     pub fn contains_metadata_key(&self, key: &str) -> bool {
         self.metadata.contains_key(key)
     }
+
+    /// Replace the value under `key` with `f` applied to the current one (`None` if
+    /// unset), returning the stored result.
+    pub fn update<F>(&mut self, key: &str, f: F) -> &Value
+    where
+        F: FnOnce(Option<&Value>) -> Value,
+    {
+        let value = f(self.data.get(key));
+        self.data.insert(key.to_string(), value);
+        &self.data[key]
+    }
+
+    /// Write the data and metadata to `path` as JSON, e.g. to checkpoint a long run.
+    /// The file is written to a temporary path and renamed into place, so a crash
+    /// mid-write leaves the previous file intact.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_string(self)?)
+            .with_context(|| format!("Failed to write context {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to write context {}", path.display()))
+    }
+
+    /// Read a context written by [`Context::save`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read context {}", path.display()))?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Estimated size in bytes of the data and metadata, serialized as JSON.
+    pub fn estimated_size(&self) -> usize {
+        self.data
+            .iter()
+            .chain(&self.metadata)
+            .map(|(key, value)| key.len() + serialized_size(value))
+            .sum()
+    }
+
+    /// Serialized size in bytes of each data key's value, largest first.
+    pub fn key_sizes(&self) -> Vec<(String, usize)> {
+        let mut sizes: Vec<(String, usize)> = self
+            .data
+            .iter()
+            .map(|(key, value)| (key.clone(), serialized_size(value)))
+            .collect();
+        sizes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        sizes
+    }
+}
+
+fn serialized_size(value: &Value) -> usize {
+    struct ByteCounter(usize);
+
+    impl io::Write for ByteCounter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = ByteCounter(0);
+    serde_json::to_writer(&mut counter, value).unwrap_or_default();
+    counter.0
 }
 
 impl fmt::Display for Context {
@@ -100,8 +242,157 @@ impl fmt::Display for Context {
     }
 }
 
+/// A [`Context`] shared between concurrent tasks. Clones refer to the same context.
+#[derive(Debug, Clone, Default)]
+pub struct SharedContext {
+    inner: Arc<RwLock<Context>>,
+}
+
+impl SharedContext {
+    pub fn new(context: Context) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(context)),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<Value> {
+        self.inner.read().unwrap().get(key).cloned()
+    }
+
+    pub fn set(&self, key: &str, value: Value) {
+        self.inner.write().unwrap().set(key, value);
+    }
+
+    /// Like [`Context::update`], holding the lock across the read-modify-write so
+    /// concurrent updates are not lost.
+    pub fn update<F>(&self, key: &str, f: F) -> Value
+    where
+        F: FnOnce(Option<&Value>) -> Value,
+    {
+        self.inner.write().unwrap().update(key, f).clone()
+    }
+
+    /// A copy of the current context.
+    pub fn snapshot(&self) -> Context {
+        self.inner.read().unwrap().clone()
+    }
+}
+
+impl From<Context> for SharedContext {
+    fn from(context: Context) -> Self {
+        Self::new(context)
+    }
+}
+
 impl From<HashMap<String, Value>> for Context {
     fn from(data: HashMap<String, Value>) -> Self {
         Self::from_data(data)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn increment(value: Option<&Value>) -> Value {
+        json!(value.and_then(|v| v.as_u64()).unwrap_or(0) + 1)
+    }
+
+    #[test]
+    fn test_typed_getters() {
+        let mut context = Context::new();
+        context.set("query", json!("what is pangu"));
+        context.set("k", json!(5));
+        context.set("ids", json!(["a", "b"]));
+
+        assert_eq!(context.get_str("query").unwrap(), "what is pangu");
+        assert_eq!(context.get_i64("k").unwrap(), 5);
+        assert_eq!(context.get_array("ids").unwrap().len(), 2);
+        assert_eq!(
+            context.get_as::<Vec<String>>("ids").unwrap(),
+            vec!["a", "b"]
+        );
+
+        assert_eq!(
+            context.get_str("missing").unwrap_err().to_string(),
+            "No value found in context under 'missing'"
+        );
+        assert_eq!(
+            context.get_array("query").unwrap_err().to_string(),
+            "Context value under 'query' is not an array"
+        );
+        assert!(
+            context
+                .get_as::<Vec<u32>>("ids")
+                .unwrap_err()
+                .to_string()
+                .starts_with("Context value under 'ids' is not a valid alloc::vec::Vec<u32>")
+        );
+    }
+
+    #[test]
+    fn test_update_increments_counter() {
+        let mut context = Context::new();
+
+        assert_eq!(context.update("count", increment), &json!(1));
+        assert_eq!(context.update("count", increment), &json!(2));
+        assert_eq!(context.get("count"), Some(&json!(2)));
+    }
+
+    #[test]
+    fn test_update_appends_to_array() {
+        let mut context = Context::new();
+        for step in ["plan", "act"] {
+            context.update("log", |value| {
+                let mut log = value
+                    .and_then(|v| v.as_array())
+                    .cloned()
+                    .unwrap_or_default();
+                log.push(json!(step));
+                Value::Array(log)
+            });
+        }
+
+        assert_eq!(context.get("log"), Some(&json!(["plan", "act"])));
+    }
+
+    #[tokio::test]
+    async fn test_shared_update_is_atomic() {
+        let shared = SharedContext::default();
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let shared = shared.clone();
+                tokio::spawn(async move {
+                    for _ in 0..100 {
+                        shared.update("count", increment);
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(shared.get("count"), Some(json!(800)));
+        assert_eq!(shared.snapshot().get("count"), Some(&json!(800)));
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("pocketflow-context-{}.json", std::process::id()));
+        let mut context = Context::new();
+        context.set("query", json!("what is pangu"));
+        context.set_metadata("attempt", json!(2));
+
+        context.save(&path).unwrap();
+        assert!(!path.with_extension("tmp").exists());
+        let loaded = Context::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.get("query"), Some(&json!("what is pangu")));
+        assert_eq!(loaded.get_metadata("attempt"), Some(&json!(2)));
+        assert!(Context::load(&path).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use crate::context::Context;
+use anyhow::{Context as _, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A loaded document shared by loaders, chunkers and indexers. Stored in a
+/// [`Context`] as `{"content": ..., "metadata": ...}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Document {
+    pub content: String,
+    #[serde(default)]
+    pub metadata: Value,
+}
+
+impl Document {
+    pub fn new(content: impl Into<String>, metadata: Value) -> Self {
+        Self {
+            content: content.into(),
+            metadata,
+        }
+    }
+
+    /// The `url` metadata field, if set.
+    pub fn url(&self) -> Option<&str> {
+        self.metadata.get("url").and_then(|v| v.as_str())
+    }
+
+    pub fn from_value(value: &Value) -> Result<Self> {
+        Ok(Self::deserialize(value)?)
+    }
+
+    pub fn to_value(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+
+    /// Read the array of documents stored under `key`.
+    pub fn list_from_context(context: &Context, key: &str) -> Result<Vec<Self>> {
+        let value = context
+            .get(key)
+            .ok_or_else(|| anyhow::anyhow!("No {} found in context", key))?;
+        Vec::<Self>::deserialize(value).with_context(|| format!("Invalid documents in {}", key))
+    }
+
+    /// Store `documents` as an array under `key`.
+    pub fn list_to_context(context: &mut Context, key: &str, documents: &[Self]) {
+        context.set(
+            key,
+            Value::Array(documents.iter().map(Self::to_value).collect()),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_json_round_trip() {
+        let doc = Document::new("Hello", json!({"url": "docs/a.txt", "file_type": "text"}));
+        let value = doc.to_value();
+
+        assert_eq!(
+            value,
+            json!({"content": "Hello", "metadata": {"url": "docs/a.txt", "file_type": "text"}})
+        );
+        assert_eq!(Document::from_value(&value).unwrap(), doc);
+        assert_eq!(doc.url(), Some("docs/a.txt"));
+    }
+
+    #[test]
+    fn test_missing_metadata_defaults_to_null() {
+        let doc = Document::from_value(&json!({"content": "Hello"})).unwrap();
+        assert_eq!(doc.metadata, Value::Null);
+        assert_eq!(doc.url(), None);
+        assert!(Document::from_value(&json!({"metadata": {}})).is_err());
+    }
+
+    #[test]
+    fn test_context_round_trip() {
+        let docs = vec![
+            Document::new("First", json!({"url": "a.txt"})),
+            Document::new("Second", json!({"url": "b.txt"})),
+        ];
+        let mut context = Context::new();
+        Document::list_to_context(&mut context, "documents", &docs);
+
+        assert_eq!(
+            Document::list_from_context(&context, "documents").unwrap(),
+            docs
+        );
+        assert!(Document::list_from_context(&context, "missing").is_err());
+    }
+}
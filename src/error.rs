@@ -0,0 +1,119 @@
+use std::time::Duration;
+
+/// Categorized failures produced by this crate's utils and nodes. Functions return
+/// `anyhow::Result`, so callers match on the category with
+/// `err.downcast_ref::<pocketflow_rs::Error>()`. Some variants depend on the
+/// enabled features, so matches need a wildcard arm.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[cfg(any(feature = "openai", feature = "anthropic", feature = "websearch"))]
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[cfg(feature = "qdrant")]
+    #[error("vector db error: {0}")]
+    VectorDb(#[from] qdrant_client::QdrantError),
+    #[cfg(feature = "pgvector")]
+    #[error("postgres error: {0}")]
+    Postgres(#[from] sqlx::Error),
+    /// The LLM API failed or returned an unusable response.
+    #[error("LLM error: {0}")]
+    Llm(String),
+    #[error("timed out after {0:?}")]
+    Timeout(Duration),
+    /// The run was cancelled through its `CancellationToken`.
+    #[error("flow cancelled")]
+    Cancelled,
+    /// Items of a `BatchNode` failed, listed by index with their errors.
+    #[error("{} of {total} batch items failed: {}", errors.len(), batch_errors(errors))]
+    Batch {
+        total: usize,
+        errors: Vec<(usize, String)>,
+    },
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+fn batch_errors(errors: &[(usize, String)]) -> String {
+    errors
+        .iter()
+        .map(|(index, error)| format!("item {}: {}", index, error))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "openai")]
+    #[tokio::test]
+    async fn test_reqwest_error_maps_to_http() {
+        // Nothing listens on a port that was just released
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let reqwest_error = reqwest::get(format!("http://127.0.0.1:{}", port))
+            .await
+            .unwrap_err();
+
+        let error: anyhow::Error = Error::from(reqwest_error).into();
+        assert!(matches!(
+            error.downcast_ref::<Error>(),
+            Some(Error::Http(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_flow_error_keeps_category() {
+        use crate::context::Context;
+        use crate::flow::Flow;
+        use crate::node::{BaseState, Node, ProcessResult};
+        use async_trait::async_trait;
+        use serde_json::Value;
+        use std::sync::Arc;
+
+        struct FailingNode;
+
+        #[async_trait]
+        impl Node for FailingNode {
+            type State = BaseState;
+
+            async fn execute(&self, _context: &Context) -> anyhow::Result<Value> {
+                Ok(Value::Null)
+            }
+
+            /// Abort the flow, as a node does on an unrecoverable failure
+            async fn post_process(
+                &self,
+                _context: &mut Context,
+                _result: &anyhow::Result<Value>,
+            ) -> anyhow::Result<ProcessResult<BaseState>> {
+                Err(Error::Timeout(Duration::from_secs(30)).into())
+            }
+        }
+
+        let error = Flow::new("failing", Arc::new(FailingNode))
+            .run(Context::new())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<Error>(),
+            Some(Error::Timeout(_))
+        ));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            Error::Timeout(Duration::from_secs(3)).to_string(),
+            "timed out after 3s"
+        );
+        assert_eq!(
+            Error::Llm("empty choices".into()).to_string(),
+            "LLM error: empty choices"
+        );
+    }
+}
@@ -1,17 +1,159 @@
 use crate::{
     context::Context,
-    node::{Node, ProcessState},
+    node::{Node, ProcessResult, ProcessState},
+    observer::FlowObserver,
+    recording::Recording,
+    spec::{EdgeSpec, FlowRegistry, FlowSpec, NodeSpec},
 };
 use anyhow::Result;
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
-use std::sync::Arc;
-use tracing::info;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::{Instrument, Span, field, info, info_span, warn};
+
+pub const RETRIES_USED_KEY: &str = "retries_used";
+/// Context metadata key under which [`Flow::run_resumable`] records the node to
+/// resume from.
+pub const CHECKPOINT_NODE_KEY: &str = "checkpoint_node";
+
+const DEFAULT_MAX_STEPS: usize = 1000;
+
+/// Whether node `execute` calls run live, are recorded, or are replayed.
+enum RunMode {
+    Live,
+    Record {
+        path: PathBuf,
+        recording: Mutex<Recording>,
+    },
+    Replay(Recording),
+}
+
+/// One node run recorded by [`Flow::run_traced`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeExecution {
+    /// The name the node is registered under.
+    pub node: String,
+    /// Its [`Node::name`], as recorded in the `node.type` span field.
+    #[serde(default)]
+    pub node_type: String,
+    /// The condition of the state its `post_process` returned.
+    pub condition: String,
+    /// The message of its `ProcessResult`.
+    pub message: String,
+    pub duration_ms: u64,
+}
+
+/// The nodes a run went through, in the order they finished.
+pub type FlowTrace = Vec<NodeExecution>;
+
+/// A flow's graph as its renderings draw it: every node name, those only edges
+/// lead to included, in name order, and every edge, plain ones by source node
+/// first, then parallel ones.
+pub(crate) struct FlowGraph<'a> {
+    pub(crate) start: &'a str,
+    pub(crate) nodes: Vec<&'a str>,
+    pub(crate) edges: Vec<GraphEdge<'a>>,
+}
+
+pub(crate) struct GraphEdge<'a> {
+    pub(crate) from: &'a str,
+    pub(crate) to: &'a str,
+    pub(crate) condition: &'a str,
+    /// Added by [`Flow::add_parallel_edges`]
+    pub(crate) parallel: bool,
+}
+
+/// A problem in a flow's graph found by [`Flow::validate`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum FlowError {
+    /// The start node was never added, so nothing in the flow can run.
+    #[error("start node '{0}' is not a node of the flow")]
+    UnknownStart(String),
+    /// An edge, plain or parallel, leads from or to a node that was never added.
+    #[error("edge '{from}' -> '{to}' references unknown node '{node}'")]
+    UnknownNode {
+        from: String,
+        to: String,
+        node: String,
+    },
+    /// No path of edges leads from the start node to this node, so it never runs.
+    #[error("node '{0}' is unreachable from the start node")]
+    Unreachable(String),
+    /// The node has no outgoing edges but was not marked with
+    /// [`Flow::set_terminal`], so the flow silently stops after it.
+    #[error("node '{0}' has no outgoing edges and is not a terminal")]
+    DeadEnd(String),
+}
+
+/// How the contexts of parallel branches are merged back once all of them finish
+/// (see [`Flow::add_parallel_edges`]). Only the keys a branch added or changed are
+/// merged, so a branch that left a key alone never reverts another branch's write
+/// to it, and keys a branch removed stay in place. Conflicts are two branches
+/// writing the same data key; metadata is always merged last-write-wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeStrategy {
+    /// Branches are merged in the order they were listed, so the last one wins.
+    #[default]
+    LastWriteWins,
+    /// The first branch, in listed order, to write a key wins.
+    FirstWriteWins,
+    /// Fail the run if two branches write different values to the same key.
+    ErrorOnConflict,
+}
+
+impl MergeStrategy {
+    /// Merge one branch's `changes` into `context`. `written` maps each data key
+    /// merged so far to the branch that wrote it.
+    fn merge(
+        self,
+        context: &mut Context,
+        branch: &str,
+        changes: &Context,
+        written: &mut HashMap<String, String>,
+    ) -> Result<()> {
+        for (key, value) in changes.get_all_data() {
+            match written.get(key) {
+                None => {}
+                Some(_) if self == MergeStrategy::LastWriteWins => {}
+                Some(_) if self == MergeStrategy::FirstWriteWins => continue,
+                Some(other) if context.get(key) != Some(value) => {
+                    return Err(anyhow::anyhow!(
+                        "Parallel branches '{}' and '{}' wrote different values to '{}'",
+                        other,
+                        branch,
+                        key
+                    ));
+                }
+                Some(_) => {}
+            }
+            context.set(key, value.clone());
+            written.insert(key.clone(), branch.to_string());
+        }
+        for (key, value) in changes.get_all_metadata() {
+            context.set_metadata(key, value.clone());
+        }
+        Ok(())
+    }
+}
 
 pub struct Flow<S: ProcessState + Default> {
     nodes: HashMap<String, Arc<dyn Node<State = S>>>,
     edges: HashMap<String, Vec<(String, String)>>, // (to_node, condition)
+    parallel_edges: HashMap<String, Vec<(Vec<String>, String)>>, // (to_nodes, condition)
+    merge_strategy: MergeStrategy,
     start_node: String,
+    retry_budget: Option<usize>,
+    context_size_warning: Option<usize>,
+    node_specs: HashMap<String, NodeSpec>, // specs of nodes built by from_spec
+    run_mode: RunMode,
+    max_steps: usize,
+    observer: Option<Arc<dyn FlowObserver<S>>>,
+    terminals: HashSet<String>,
 }
 
 impl<S: ProcessState + Default> Flow<S> {
@@ -22,14 +164,316 @@ impl<S: ProcessState + Default> Flow<S> {
         Self {
             nodes,
             edges: HashMap::new(),
+            parallel_edges: HashMap::new(),
+            merge_strategy: MergeStrategy::default(),
             start_node: start_node_name.to_string(),
+            retry_budget: None,
+            context_size_warning: None,
+            node_specs: HashMap::new(),
+            run_mode: RunMode::Live,
+            max_steps: DEFAULT_MAX_STEPS,
+            observer: None,
+            terminals: HashSet::new(),
+        }
+    }
+
+    /// Build a flow from a declarative spec, creating each node with the factory
+    /// registered for its type.
+    pub fn from_spec(spec: &FlowSpec, registry: &FlowRegistry<S>) -> Result<Self> {
+        let start_spec = spec
+            .nodes
+            .iter()
+            .find(|node| node.name == spec.start)
+            .ok_or_else(|| anyhow::anyhow!("Start node '{}' is not defined", spec.start))?;
+
+        let mut flow = Self::new(&spec.start, registry.build(start_spec)?);
+        for node_spec in &spec.nodes {
+            if node_spec.name != spec.start {
+                flow.add_node(&node_spec.name, registry.build(node_spec)?);
+            }
+            flow.node_specs
+                .insert(node_spec.name.clone(), node_spec.clone());
+        }
+
+        for edge in &spec.edges {
+            for name in [&edge.from, &edge.to] {
+                if !flow.nodes.contains_key(name) {
+                    return Err(anyhow::anyhow!("Edge references unknown node '{}'", name));
+                }
+            }
+            if !edge.parallel {
+                flow.edges
+                    .entry(edge.from.clone())
+                    .or_default()
+                    .push((edge.to.clone(), edge.condition.clone()));
+                continue;
+            }
+            let groups = flow.parallel_edges.entry(edge.from.clone()).or_default();
+            match groups
+                .iter_mut()
+                .find(|(_, condition)| *condition == edge.condition)
+            {
+                Some((targets, _)) => targets.push(edge.to.clone()),
+                None => groups.push((vec![edge.to.clone()], edge.condition.clone())),
+            }
+        }
+
+        Ok(flow)
+    }
+
+    /// Describe the flow's structure as a spec. Nodes not built by `from_spec` are
+    /// listed with their `Node::name` as type and no params.
+    pub fn to_spec(&self) -> FlowSpec {
+        let mut names: Vec<&String> = self.nodes.keys().collect();
+        names.sort();
+        let nodes = names
+            .into_iter()
+            .map(|name| {
+                self.node_specs
+                    .get(name)
+                    .cloned()
+                    .unwrap_or_else(|| NodeSpec {
+                        name: name.clone(),
+                        node_type: self.nodes[name].name().to_string(),
+                        params: Value::Null,
+                    })
+            })
+            .collect();
+
+        let mut from_names: Vec<&String> = self.edges.keys().collect();
+        from_names.sort();
+        let mut parallel_from_names: Vec<&String> = self.parallel_edges.keys().collect();
+        parallel_from_names.sort();
+        let edges = from_names
+            .into_iter()
+            .flat_map(|from| {
+                self.edges[from].iter().map(|(to, condition)| EdgeSpec {
+                    from: from.clone(),
+                    to: to.clone(),
+                    condition: condition.clone(),
+                    parallel: false,
+                })
+            })
+            .chain(parallel_from_names.into_iter().flat_map(|from| {
+                self.parallel_edges[from]
+                    .iter()
+                    .flat_map(move |(targets, condition)| {
+                        targets.iter().map(move |to| EdgeSpec {
+                            from: from.clone(),
+                            to: to.clone(),
+                            condition: condition.clone(),
+                            parallel: true,
+                        })
+                    })
+            }))
+            .collect();
+
+        FlowSpec {
+            start: self.start_node.clone(),
+            nodes,
+            edges,
+        }
+    }
+
+    /// The renderings share the graph so they draw the same one.
+    pub(crate) fn graph(&self) -> FlowGraph<'_> {
+        let mut names: Vec<&str> = self
+            .nodes
+            .keys()
+            .chain(self.edges.values().flatten().map(|(to, _)| to))
+            .chain(
+                self.parallel_edges
+                    .values()
+                    .flatten()
+                    .flat_map(|(to, _)| to),
+            )
+            .map(String::as_str)
+            .collect();
+        names.sort();
+        names.dedup();
+
+        let mut edges = Vec::new();
+        let mut from_names: Vec<&String> = self.edges.keys().collect();
+        from_names.sort();
+        for from in from_names {
+            for (to, condition) in &self.edges[from] {
+                edges.push(GraphEdge {
+                    from,
+                    to,
+                    condition,
+                    parallel: false,
+                });
+            }
+        }
+        let mut from_names: Vec<&String> = self.parallel_edges.keys().collect();
+        from_names.sort();
+        for from in from_names {
+            for (branches, condition) in &self.parallel_edges[from] {
+                for to in branches {
+                    edges.push(GraphEdge {
+                        from,
+                        to,
+                        condition,
+                        parallel: true,
+                    });
+                }
+            }
+        }
+        FlowGraph {
+            start: &self.start_node,
+            nodes: names,
+            edges,
+        }
+    }
+
+    /// Render the flow as a Mermaid `flowchart TD`. Nodes are boxes labeled with
+    /// their names, the start node is a rounded stadium, and edges are labeled with
+    /// their condition, except `default` ones. Parallel edges are drawn thick.
+    pub fn to_mermaid(&self) -> String {
+        let graph = self.graph();
+        let ids: HashMap<&str, String> = graph
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (*name, format!("n{}", i)))
+            .collect();
+
+        let mut lines = vec!["flowchart TD".to_string()];
+        for name in &graph.nodes {
+            let label = mermaid_label(name);
+            if *name == graph.start {
+                lines.push(format!("    {}([{}])", ids[name], label));
+            } else {
+                lines.push(format!("    {}[{}]", ids[name], label));
+            }
+        }
+
+        for edge in graph.edges {
+            let arrow = if edge.parallel { "==>" } else { "-->" };
+            if edge.condition == "default" {
+                lines.push(format!("    {} {} {}", ids[edge.from], arrow, ids[edge.to]));
+            } else {
+                lines.push(format!(
+                    "    {} {}|{}| {}",
+                    ids[edge.from],
+                    arrow,
+                    mermaid_label(edge.condition),
+                    ids[edge.to]
+                ));
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Render the flow as a Graphviz `digraph`, e.g. for `dot -Tsvg`, drawing the
+    /// same graph as [`Flow::to_mermaid`]. Nodes are boxes and the start node a
+    /// filled ellipse. Edges are labeled with their condition, except `default`
+    /// ones; edges on conditions ending in `_error` are red and parallel edges bold.
+    pub fn to_dot(&self) -> String {
+        let graph = self.graph();
+        let mut lines = vec![
+            "digraph flow {".to_string(),
+            "    node [shape=box];".to_string(),
+        ];
+        for name in &graph.nodes {
+            if *name == graph.start {
+                lines.push(format!(
+                    "    {} [shape=ellipse, style=filled, fillcolor=lightblue];",
+                    dot_id(name)
+                ));
+            } else {
+                lines.push(format!("    {};", dot_id(name)));
+            }
+        }
+
+        for edge in &graph.edges {
+            let mut attributes = Vec::new();
+            if edge.condition != "default" {
+                attributes.push(format!("label={}", dot_id(edge.condition)));
+            }
+            if edge.condition.ends_with("_error") {
+                attributes.push("color=red, fontcolor=red".to_string());
+            }
+            if edge.parallel {
+                attributes.push("style=bold".to_string());
+            }
+            let attributes = if attributes.is_empty() {
+                String::new()
+            } else {
+                format!(" [{}]", attributes.join(", "))
+            };
+            lines.push(format!(
+                "    {} -> {}{};",
+                dot_id(edge.from),
+                dot_id(edge.to),
+                attributes
+            ));
         }
+        lines.push("}".to_string());
+        lines.join("\n")
+    }
+
+    /// Cap the total number of retries across all nodes in a single run. Once spent,
+    /// failing nodes are not retried again for the rest of the run.
+    pub fn set_retry_budget(&mut self, budget: usize) {
+        self.retry_budget = Some(budget);
+    }
+
+    /// Log a warning, naming the largest keys, once the context grows beyond
+    /// `threshold_bytes` of serialized JSON during a run.
+    pub fn set_context_size_warning(&mut self, threshold_bytes: usize) {
+        self.context_size_warning = Some(threshold_bytes);
+    }
+
+    /// Cap the number of node executions in a single run (1000 by default), so a
+    /// cyclic flow such as retrieve → evaluate → re-retrieve can't loop forever.
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Set how the contexts of parallel branches are merged; last-write-wins by
+    /// default.
+    pub fn with_merge_strategy(mut self, strategy: MergeStrategy) -> Self {
+        self.merge_strategy = strategy;
+        self
+    }
+
+    /// Report node timings and outcomes of every run to `observer`, e.g. a
+    /// [`crate::TracingObserver`] or a metrics exporter.
+    pub fn with_observer(mut self, observer: Arc<dyn FlowObserver<S>>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Record the output of every successful node `execute` and write the recording
+    /// to `path` after each run, for later replay with [`Flow::set_replay`].
+    pub fn set_recording(&mut self, path: impl Into<PathBuf>) {
+        self.run_mode = RunMode::Record {
+            path: path.into(),
+            recording: Mutex::new(Recording::default()),
+        };
+    }
+
+    /// Replay the recording at `path` instead of calling node `execute`, so runs
+    /// are deterministic and make no LLM or database calls. `prepare` and
+    /// `post_process` still run; a node reached with a context that was not
+    /// recorded fails.
+    pub fn set_replay(&mut self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        self.run_mode = RunMode::Replay(Recording::load(path.as_ref())?);
+        Ok(())
     }
 
     pub fn add_node(&mut self, name: &str, node: Arc<dyn Node<State = S>>) {
+        self.node_specs.remove(name);
         self.nodes.insert(name.to_string(), node);
     }
 
+    /// Route from `from` to `to` when `from` returns `condition`. Error states route
+    /// like any other, so an edge on e.g. a retrieval error state leads to a fallback
+    /// node. An edge on the exact condition wins over a `default` edge, which catches
+    /// every condition without one of its own, errors included; with neither the
+    /// flow stops.
     pub fn add_edge(&mut self, from: &str, to: &str, condition: S) {
         self.edges
             .entry(from.to_string())
@@ -37,64 +481,599 @@ impl<S: ProcessState + Default> Flow<S> {
             .push((to.to_string(), condition.to_condition()));
     }
 
-    pub async fn run(&self, mut context: Context) -> Result<Value> {
-        let mut current_node = self.start_node.clone();
+    /// Fan out from `from`: when it returns `condition`, the `to` nodes run
+    /// concurrently, each on its own copy of the context, and the flow waits for
+    /// all of them before merging their changes back with its [`MergeStrategy`].
+    /// It then continues to the node the branches' own edges lead to; branches
+    /// routing to different nodes fail the run. Parallel edges take precedence over
+    /// plain edges on the same condition, and [`Flow::to_spec`] keeps them as
+    /// edges with `parallel: true`.
+    pub fn add_parallel_edges(&mut self, from: &str, to: Vec<&str>, condition: S) {
+        self.parallel_edges
+            .entry(from.to_string())
+            .or_default()
+            .push((
+                to.into_iter().map(str::to_string).collect(),
+                condition.to_condition(),
+            ));
+    }
+
+    /// Mark `name` as a node the flow is meant to end at, so [`Flow::validate`]
+    /// doesn't report it for having no outgoing edges.
+    pub fn set_terminal(&mut self, name: &str) {
+        self.terminals.insert(name.to_string());
+    }
+
+    /// Check the graph without running it: every edge must lead from and to added
+    /// nodes, every node must be reachable from the start node, and every node
+    /// without outgoing edges must be marked with [`Flow::set_terminal`]. Returns all
+    /// problems found, edges first, each group in name order. Meant for tests or
+    /// startup checks, since a typo in an edge otherwise only shows as the flow
+    /// stopping early.
+    pub fn validate(&self) -> std::result::Result<(), Vec<FlowError>> {
+        let mut errors = Vec::new();
+        // Without a start every node would be unreachable, hiding the cause
+        let known_start = self.nodes.contains_key(&self.start_node);
+        if !known_start {
+            errors.push(FlowError::UnknownStart(self.start_node.clone()));
+        }
 
-        while let Some(node) = self.nodes.get(&current_node) {
-            // Prepare
-            info!("Preparing node: {}", current_node);
-            node.prepare(&mut context).await?;
+        let mut targets: Vec<(&String, &String)> = self
+            .edges
+            .iter()
+            .flat_map(|(from, edges)| edges.iter().map(move |(to, _)| (from, to)))
+            .chain(self.parallel_edges.iter().flat_map(|(from, edges)| {
+                edges
+                    .iter()
+                    .flat_map(move |(to, _)| to.iter().map(move |to| (from, to)))
+            }))
+            .collect();
+        targets.sort();
+        targets.dedup();
+        for &(from, to) in &targets {
+            for node in [from, to] {
+                if !self.nodes.contains_key(node) {
+                    errors.push(FlowError::UnknownNode {
+                        from: from.clone(),
+                        to: to.clone(),
+                        node: node.clone(),
+                    });
+                    break;
+                }
+            }
+        }
+
+        let mut reachable = HashSet::from([&self.start_node]);
+        let mut queue = VecDeque::from([&self.start_node]);
+        while let Some(name) = queue.pop_front() {
+            for &(from, to) in &targets {
+                if from == name && self.nodes.contains_key(to) && reachable.insert(to) {
+                    queue.push_back(to);
+                }
+            }
+        }
+
+        let sources: HashSet<&String> = targets.iter().map(|&(from, _)| from).collect();
+        let mut names: Vec<&String> = self.nodes.keys().collect();
+        names.sort();
+        errors.extend(
+            names
+                .iter()
+                .filter(|name| known_start && !reachable.contains(*name))
+                .map(|name| FlowError::Unreachable((*name).clone())),
+        );
+        errors.extend(
+            names
+                .iter()
+                .filter(|name| !sources.contains(*name) && !self.terminals.contains(**name))
+                .map(|name| FlowError::DeadEnd((*name).clone())),
+        );
 
-            // Execute
-            info!("Executing node: {}", current_node);
-            let result = node.execute(&context).await;
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
 
-            // Post process
-            info!("Post processing node: {}", current_node);
-            let process_result = node.post_process(&mut context, &result).await?;
+    /// Run the flow and return the context's `result`. The context is dropped, so on
+    /// error whatever earlier nodes stored in it is lost; see [`Flow::run_lenient`].
+    /// Errors keep their [`crate::Error`] category for callers to downcast.
+    pub async fn run(&self, context: Context) -> Result<Value> {
+        self.run_lenient(context).await.0
+    }
 
-            // Find next node based on the state returned by post_process
-            if let Some(edges) = self.edges.get(&current_node) {
-                // Get the condition from the node state
-                let condition = process_result.state.to_condition();
+    /// Like [`Flow::run`], but also returns the final context, so metadata the caller
+    /// seeded (trace ids, tenant) or nodes added can be read back after the run.
+    pub async fn run_full(&self, context: Context) -> Result<(Value, Context)> {
+        let (result, context) = self.run_lenient(context).await;
+        Ok((result?, context))
+    }
 
-                // Try to find an edge matching the condition
-                let next_node_info = edges
-                    .iter()
-                    .find(|(_, edge_condition)| edge_condition == &condition);
-
-                if let Some((next, _)) = next_node_info {
-                    current_node = next.clone();
-                } else {
-                    // If no matching edge found, try the default condition
-                    let default_edge = edges
-                        .iter()
-                        .find(|(_, edge_condition)| edge_condition == "default");
-
-                    if let Some((next, _)) = default_edge {
-                        current_node = next.clone();
-                    } else {
-                        info!(
-                            "No edge found for node '{}' with condition '{}'. Stopping flow.",
-                            current_node, condition
-                        );
-                        break;
+    /// Like [`Flow::run`], but always hands back the final context alongside the
+    /// result, so data produced before a failing node can still be salvaged.
+    pub async fn run_lenient(&self, context: Context) -> (Result<Value>, Context) {
+        self.run_lenient_with_cancel(context, CancellationToken::new())
+            .await
+    }
+
+    /// Like [`Flow::run`], but stops once `cancel` is cancelled, failing with
+    /// [`crate::Error::Cancelled`]. The token is checked before each node's
+    /// `prepare`, `execute` and `post_process`, and a running `execute`, retries
+    /// included, is dropped as soon as it fires.
+    pub async fn run_with_cancel(
+        &self,
+        mut context: Context,
+        cancel: CancellationToken,
+    ) -> Result<Value> {
+        self.run_from(self.start_node.clone(), &mut context, None, &cancel, None)
+            .await
+    }
+
+    /// Combines [`Flow::run_lenient`] and [`Flow::run_with_cancel`]: the context is
+    /// handed back however the run ends, so a caller that gave up on a flow, e.g. at
+    /// a deadline, can still use what the finished nodes produced.
+    pub async fn run_lenient_with_cancel(
+        &self,
+        mut context: Context,
+        cancel: CancellationToken,
+    ) -> (Result<Value>, Context) {
+        let result = self
+            .run_from(self.start_node.clone(), &mut context, None, &cancel, None)
+            .await;
+        (result, context)
+    }
+
+    /// Like [`Flow::run`], but also returns the trace of the nodes that ran: the
+    /// state condition and message each one finished with and how long it took.
+    /// Parallel branches are listed in the order they finished.
+    pub async fn run_traced(&self, mut context: Context) -> Result<(Value, FlowTrace)> {
+        self.run_traced_in(&mut context).await
+    }
+
+    /// [`Flow::run_traced`] on a context the caller keeps.
+    pub(crate) async fn run_traced_in(&self, context: &mut Context) -> Result<(Value, FlowTrace)> {
+        let trace = Mutex::new(FlowTrace::new());
+        let result = self
+            .run_from(
+                self.start_node.clone(),
+                context,
+                None,
+                &CancellationToken::new(),
+                Some(&trace),
+            )
+            .await?;
+        Ok((result, trace.into_inner().unwrap()))
+    }
+
+    /// Like [`Flow::run`], but saves the context to `checkpoint_path` after every
+    /// node, with the node to run next under [`CHECKPOINT_NODE_KEY`] in its metadata.
+    /// If a checkpoint already exists, e.g. left by a run that crashed, the flow
+    /// resumes from it with the saved context instead of `context` and the start
+    /// node. The checkpoint is removed once the flow finishes.
+    pub async fn run_resumable(
+        &self,
+        context: Context,
+        checkpoint_path: impl AsRef<Path>,
+    ) -> Result<Value> {
+        let checkpoint_path = checkpoint_path.as_ref();
+        let (mut context, start) = if checkpoint_path.exists() {
+            let context = Context::load(checkpoint_path)?;
+            let start = context
+                .get_metadata(CHECKPOINT_NODE_KEY)
+                .and_then(Value::as_str)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Checkpoint {} has no node to resume from",
+                        checkpoint_path.display()
+                    )
+                })?
+                .to_string();
+            info!(
+                "Resuming from checkpoint {} at node '{}'",
+                checkpoint_path.display(),
+                start
+            );
+            (context, start)
+        } else {
+            (context, self.start_node.clone())
+        };
+
+        let result = self
+            .run_from(
+                start,
+                &mut context,
+                Some(checkpoint_path),
+                &CancellationToken::new(),
+                None,
+            )
+            .await?;
+        // A flow that stopped before its first node never wrote a checkpoint
+        if let Err(e) = std::fs::remove_file(checkpoint_path)
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            return Err(e.into());
+        }
+        Ok(result)
+    }
+
+    async fn run_from(
+        &self,
+        start: String,
+        context: &mut Context,
+        checkpoint_path: Option<&Path>,
+        cancel: &CancellationToken,
+        trace: Option<&Mutex<FlowTrace>>,
+    ) -> Result<Value> {
+        let span = info_span!("flow_run", start_node = %start);
+        let mut result = self
+            .run_nodes(start, context, checkpoint_path, cancel, trace)
+            .instrument(span)
+            .await;
+        if let RunMode::Record { path, recording } = &self.run_mode {
+            let saved = recording.lock().unwrap().save(path);
+            if let Err(e) = saved {
+                result = result.and(Err(e));
+            }
+        }
+        if let Some(observer) = &self.observer {
+            observer.on_flow_end(context);
+        }
+        result
+    }
+
+    async fn run_nodes(
+        &self,
+        start: String,
+        context: &mut Context,
+        checkpoint_path: Option<&Path>,
+        cancel: &CancellationToken,
+        trace: Option<&Mutex<FlowTrace>>,
+    ) -> Result<Value> {
+        let mut current_node = start;
+        let mut size_warned = false;
+        let mut steps = 0;
+
+        while let Some(node) = self.nodes.get(&current_node) {
+            if steps >= self.max_steps {
+                return Err(self.max_steps_exceeded(&current_node));
+            }
+            steps += 1;
+            let process_result = self
+                .run_step(&current_node, node.as_ref(), context, cancel, trace)
+                .await?;
+
+            if !size_warned
+                && let Some(threshold) = self.context_size_warning
+                && let Some(message) = context_size_warning(context, threshold)
+            {
+                warn!("After node '{}': {}", current_node, message);
+                size_warned = true;
+            }
+
+            // Find next node based on the state returned by post_process
+            let condition = process_result.state.to_condition();
+            let next_node = match self.parallel_branches(&current_node, &condition) {
+                Some(branches) => {
+                    if steps + branches.len() > self.max_steps {
+                        return Err(self.max_steps_exceeded(&branches[0]));
                     }
+                    steps += branches.len();
+                    info!(
+                        "Step {}: '{}' -> {:?} in parallel on '{}'",
+                        steps, current_node, branches, condition
+                    );
+                    self.run_parallel(branches, context, cancel, trace).await?
                 }
-            } else {
-                info!(
-                    "Node '{}' has no outgoing edges. Stopping flow.",
-                    current_node
+                None => self.route(&current_node, &condition).cloned(),
+            };
+
+            if let Some(path) = checkpoint_path {
+                // A finished flow resumes at its missing next node and stops at once
+                let next = next_node.as_deref().unwrap_or_default();
+                context.set_metadata(CHECKPOINT_NODE_KEY, Value::String(next.to_string()));
+                context.save(path)?;
+            }
+
+            match next_node {
+                Some(next) => {
+                    info!(
+                        "Step {}: '{}' -> '{}' on '{}'",
+                        steps, current_node, next, condition
+                    );
+                    current_node = next;
+                }
+                None => {
+                    info!(
+                        "No edge found for node '{}' with condition '{}'. Stopping flow.",
+                        current_node, condition
+                    );
+                    break;
+                }
+            }
+        }
+
+        Ok(context.get("result").unwrap_or(&Value::Null).clone())
+    }
+
+    fn max_steps_exceeded(&self, next_node: &str) -> anyhow::Error {
+        anyhow::anyhow!(
+            "Maximum steps exceeded: {} node executions without finishing, next node '{}'",
+            self.max_steps,
+            next_node
+        )
+    }
+
+    /// The node the edges out of `from` lead to on `condition`, falling back to a
+    /// `default` edge; see [`Flow::add_edge`].
+    fn route(&self, from: &str, condition: &str) -> Option<&String> {
+        let edges = self.edges.get(from)?;
+        edges
+            .iter()
+            .find(|(_, edge_condition)| edge_condition == condition)
+            .or_else(|| {
+                edges
+                    .iter()
+                    .find(|(_, edge_condition)| edge_condition == "default")
+            })
+            .map(|(to, _)| to)
+    }
+
+    fn parallel_branches(&self, from: &str, condition: &str) -> Option<&[String]> {
+        self.parallel_edges
+            .get(from)?
+            .iter()
+            .find(|(_, edge_condition)| edge_condition == condition)
+            .map(|(to, _)| to.as_slice())
+    }
+
+    /// Run `branches` concurrently on copies of `context`, merge their changes back
+    /// and return the node they all route to next.
+    async fn run_parallel(
+        &self,
+        branches: &[String],
+        context: &mut Context,
+        cancel: &CancellationToken,
+        trace: Option<&Mutex<FlowTrace>>,
+    ) -> Result<Option<String>> {
+        let base = context.clone();
+        let runs = branches.iter().map(|name| {
+            let mut branch_context = base.clone();
+            async move {
+                let node = self.nodes.get(name).ok_or_else(|| {
+                    anyhow::anyhow!("Parallel edge references unknown node '{}'", name)
+                })?;
+                let process_result = self
+                    .run_step(name, node.as_ref(), &mut branch_context, cancel, trace)
+                    .await?;
+                Ok::<_, anyhow::Error>((process_result, branch_context))
+            }
+        });
+        let outcomes = futures::future::join_all(runs).await;
+
+        let mut written = HashMap::new();
+        let mut next_nodes = Vec::with_capacity(branches.len());
+        for (name, outcome) in branches.iter().zip(outcomes) {
+            let (process_result, branch_context) = outcome?;
+            let changes = branch_context.changes_since(&base);
+            self.merge_strategy
+                .merge(context, name, &changes, &mut written)?;
+            let next = self.route(name, &process_result.state.to_condition());
+            next_nodes.push((name, next));
+        }
+
+        let next = next_nodes.first().and_then(|(_, next)| *next);
+        if next_nodes.iter().any(|(_, other)| *other != next) {
+            let routes = next_nodes
+                .iter()
+                .map(|(name, next)| format!("'{}' -> {:?}", name, next))
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(anyhow::anyhow!(
+                "Parallel branches route to different nodes: {}",
+                routes
+            ));
+        }
+        Ok(next.cloned())
+    }
+
+    /// Run one node inside its own tracing span, appending it to `trace` once it
+    /// finishes.
+    async fn run_step(
+        &self,
+        name: &str,
+        node: &dyn Node<State = S>,
+        context: &mut Context,
+        cancel: &CancellationToken,
+        trace: Option<&Mutex<FlowTrace>>,
+    ) -> Result<ProcessResult<S>> {
+        let span = info_span!(
+            "node",
+            node.name = %name,
+            node.type = %node.name(),
+            node.condition = field::Empty,
+            node.duration_ms = field::Empty,
+            node.error = field::Empty,
+        );
+        if let Some(observer) = &self.observer {
+            observer.on_node_start(name);
+        }
+        let started = Instant::now();
+        let process_result = self
+            .run_node(name, node, context, cancel)
+            .instrument(span.clone())
+            .await;
+        let duration = started.elapsed();
+        span.record("node.duration_ms", duration.as_millis() as u64);
+        if let Some(observer) = &self.observer {
+            match &process_result {
+                Ok(process_result) => observer.on_node_finish(name, duration, process_result),
+                Err(e) => observer.on_node_error(name, duration, e),
+            }
+        }
+        let process_result = process_result.inspect_err(|e| {
+            span.record("node.error", e.to_string());
+        })?;
+        span.record("node.condition", process_result.state.to_condition());
+        if let Some(trace) = trace {
+            trace.lock().unwrap().push(NodeExecution {
+                node: name.to_string(),
+                node_type: node.name().to_string(),
+                condition: process_result.state.to_condition(),
+                message: process_result.message.clone(),
+                duration_ms: duration.as_millis() as u64,
+            });
+        }
+        Ok(process_result)
+    }
+
+    async fn run_node(
+        &self,
+        name: &str,
+        node: &dyn Node<State = S>,
+        context: &mut Context,
+        cancel: &CancellationToken,
+    ) -> Result<ProcessResult<S>> {
+        let check_cancelled = || {
+            if cancel.is_cancelled() {
+                return Err(crate::Error::Cancelled);
+            }
+            Ok(())
+        };
+
+        // Prepare
+        check_cancelled()?;
+        info!("Preparing node: {} ({})", name, node.name());
+        node.prepare(context).await?;
+
+        // Execute
+        check_cancelled()?;
+        info!("Executing node: {}", name);
+        let result = tokio::select! {
+            result = self.execute_with_retries(name, node, context) => result,
+            _ = cancel.cancelled() => {
+                info!("Cancelled node: {}", name);
+                return Err(crate::Error::Cancelled.into());
+            }
+        };
+        if let Err(e) = &result {
+            Span::current().record("node.error", e.to_string());
+        }
+
+        // Post process
+        check_cancelled()?;
+        info!("Post processing node: {}", name);
+        node.post_process(context, &result).await
+    }
+
+    async fn execute_with_retries(
+        &self,
+        name: &str,
+        node: &dyn Node<State = S>,
+        context: &mut Context,
+    ) -> Result<Value> {
+        if let RunMode::Replay(recording) = &self.run_mode {
+            return recording.replay(name, context);
+        }
+
+        let policy = node.retry_policy();
+        let mut backoff = policy.backoff();
+        let mut result = execute_with_timeout(node, context).await;
+        let mut attempt = 0;
+
+        while result.is_err() && attempt < policy.max_retries {
+            if !self.consume_retry(context) {
+                warn!(
+                    "Retry budget exhausted, not retrying node '{}': {}",
+                    name,
+                    result.as_ref().unwrap_err()
                 );
                 break;
             }
+            attempt += 1;
+            let delay = backoff.next().unwrap_or_default();
+            info!(
+                "Retrying node: {} (attempt {}) in {:?}: {}",
+                name,
+                attempt,
+                delay,
+                result.as_ref().unwrap_err()
+            );
+            tokio::time::sleep(delay).await;
+            result = execute_with_timeout(node, context).await;
         }
 
-        Ok(context.get("result").unwrap_or(&Value::Null).clone())
+        if let (RunMode::Record { recording, .. }, Ok(output)) = (&self.run_mode, &result) {
+            recording.lock().unwrap().record(name, context, output);
+        }
+        result
+    }
+
+    /// Record one retry in the context metadata, returning false if the budget is spent.
+    fn consume_retry(&self, context: &mut Context) -> bool {
+        let used = context
+            .get_metadata(RETRIES_USED_KEY)
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0) as usize;
+
+        if self.retry_budget.is_some_and(|budget| used >= budget) {
+            return false;
+        }
+
+        context.set_metadata(RETRIES_USED_KEY, Value::from(used + 1));
+        true
+    }
+}
+
+/// Run `node`'s `execute`, failing with [`crate::Error::Timeout`] once it exceeds
+/// the node's timeout.
+async fn execute_with_timeout<S: ProcessState + Default>(
+    node: &dyn Node<State = S>,
+    context: &Context,
+) -> Result<Value> {
+    let Some(timeout) = node.timeout() else {
+        return node.execute(context).await;
+    };
+    match tokio::time::timeout(timeout, node.execute(context)).await {
+        Ok(result) => result,
+        Err(_) => {
+            warn!("Node '{}' timed out after {:?}", node.name(), timeout);
+            Err(crate::Error::Timeout(timeout).into())
+        }
+    }
+}
+
+/// `text` as a quoted Mermaid label, so spaces and punctuation are kept as is.
+fn mermaid_label(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "#quot;"))
+}
+
+/// `text` as a quoted DOT id.
+fn dot_id(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// A warning naming the largest keys if the context exceeds `threshold` bytes.
+fn context_size_warning(context: &Context, threshold: usize) -> Option<String> {
+    let size = context.estimated_size();
+    if size <= threshold {
+        return None;
     }
+
+    let largest = context
+        .key_sizes()
+        .into_iter()
+        .take(3)
+        .map(|(key, size)| format!("'{}' ({} bytes)", key, size))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Some(format!(
+        "context size {} bytes exceeds {} bytes; largest keys: {}",
+        size, threshold, largest
+    ))
 }
 
-#[allow(dead_code)]
+/// Runs one flow over many contexts, up to `batch_size` of them at a time.
 pub struct BatchFlow<S: ProcessState + Default> {
     flow: Flow<S>,
     batch_size: usize,
@@ -112,18 +1091,44 @@ impl<S: ProcessState + Default> BatchFlow<S> {
         }
     }
 
-    pub async fn run_batch(&self, contexts: Vec<Context>) -> Result<()> {
+    /// Run the flow on every context and return their results in input order.
+    /// Every context runs to the end even if others fail; failures are logged and
+    /// the first one is returned.
+    pub async fn run_batch(&self, contexts: Vec<Context>) -> Result<Vec<Value>> {
+        let total = contexts.len();
         info!(
-            "Starting batch flow execution with {} items",
-            contexts.len()
+            "Starting batch flow execution with {} items, {} at a time",
+            total, self.batch_size
         );
 
-        for context in contexts {
-            self.flow.run(context).await?;
+        let outcomes: Vec<Result<Value>> = stream::iter(contexts)
+            .map(|context| self.flow.run(context))
+            .buffered(self.batch_size.max(1))
+            .collect()
+            .await;
+
+        let mut results = Vec::with_capacity(total);
+        let mut first_error = None;
+        let mut failed = 0;
+        for (index, outcome) in outcomes.into_iter().enumerate() {
+            match outcome {
+                Ok(value) => results.push(value),
+                Err(e) => {
+                    warn!("Batch item {} failed: {}", index, e);
+                    failed += 1;
+                    first_error.get_or_insert((index, e));
+                }
+            }
+        }
+        if let Some((index, e)) = first_error {
+            return Err(e.context(format!(
+                "{} of {} batch items failed, first at index {}",
+                failed, total, index
+            )));
         }
 
         info!("Batch flow execution completed");
-        Ok(())
+        Ok(results)
     }
 }
 
@@ -164,6 +1169,26 @@ macro_rules! build_flow {
         g
     }};
 
+    // Marks the nodes the flow is meant to end at, for `Flow::validate`
+    (
+        start: ($start_name:expr, $start_node:expr),
+        nodes: [$(($name:expr, $node:expr)),* $(,)?],
+        edges: [
+            $($edge:tt),* $(,)?
+        ],
+        terminals: [$($terminal:expr),* $(,)?]
+    ) => {{
+        let mut g = build_flow!(
+            start: ($start_name, $start_node),
+            nodes: [$(($name, $node)),*],
+            edges: [$($edge),*]
+        );
+        $(
+            g.set_terminal($terminal);
+        )*
+        g
+    }};
+
 
     (@edge $g:expr, ($from:expr, $to:expr, $condition:expr)) => {
         $g.add_edge($from, $to, $condition);
@@ -213,9 +1238,11 @@ macro_rules! build_batch_flow {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::node::{Node, ProcessResult, ProcessState};
+    use crate::node::{Node, ProcessResult, ProcessState, RetryPolicy};
     use async_trait::async_trait;
     use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
 
     #[derive(Debug, Clone, PartialEq)]
     #[allow(dead_code)]
@@ -306,6 +1333,79 @@ mod tests {
         assert_eq!(result, json!({"final_result": "finished"}));
     }
 
+    #[tokio::test]
+    async fn test_run_traced_records_branch_taken() {
+        let mut flow = Flow::<CustomState>::new(
+            "start",
+            Arc::new(TestNode::new(json!("checked"), CustomState::Failure)),
+        );
+        flow.add_node(
+            "success",
+            Arc::new(TestNode::new(json!("ok"), CustomState::Default)),
+        );
+        flow.add_node(
+            "failure",
+            Arc::new(TestNode::new(json!("recovered"), CustomState::Default)),
+        );
+        flow.add_edge("start", "success", CustomState::Success);
+        flow.add_edge("start", "failure", CustomState::Failure);
+
+        let (result, trace) = flow.run_traced(Context::new()).await.unwrap();
+
+        assert_eq!(result, json!("recovered"));
+        let steps: Vec<(&str, &str, &str)> = trace
+            .iter()
+            .map(|step| {
+                (
+                    step.node.as_str(),
+                    step.condition.as_str(),
+                    step.message.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            steps,
+            vec![("start", "failure", "test"), ("failure", "default", "test")]
+        );
+
+        let dumped = serde_json::to_value(&trace).unwrap();
+        assert_eq!(dumped[1]["node"], json!("failure"));
+        assert!(dumped[1]["duration_ms"].is_u64());
+        let loaded: FlowTrace = serde_json::from_value(dumped).unwrap();
+        assert_eq!(loaded, trace);
+    }
+
+    /// Overrides `Node::name`, as the library nodes do.
+    struct NamedNode;
+
+    #[async_trait]
+    impl Node for NamedNode {
+        type State = CustomState;
+
+        fn name(&self) -> &str {
+            "Named"
+        }
+
+        async fn execute(&self, _context: &Context) -> Result<Value> {
+            Ok(Value::Null)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_traced_records_node_type() {
+        let mut flow = Flow::<CustomState>::new("first", Arc::new(NamedNode));
+        flow.add_node(
+            "second",
+            Arc::new(TestNode::new(json!("done"), CustomState::Default)),
+        );
+        flow.add_edge("first", "second", CustomState::Default);
+
+        let (_, trace) = flow.run_traced(Context::new()).await.unwrap();
+
+        assert_eq!(trace[0].node_type, "Named");
+        assert!(trace[1].node_type.ends_with("TestNode"));
+    }
+
     #[tokio::test]
     async fn test_batch_flow() {
         let node1 = TestNode::new(json!({"data": "test1"}), CustomState::Success);
@@ -321,18 +1421,107 @@ mod tests {
             .add_edge("next", "end", CustomState::Default);
 
         let contexts = vec![Context::new(), Context::new()];
-        batch_flow.run_batch(contexts).await.unwrap();
+        let results = batch_flow.run_batch(contexts).await.unwrap();
+        assert_eq!(results, vec![json!({"data": "test2"}); 2]);
     }
 
-    #[tokio::test]
-    async fn test_build_flow_macro() {
-        // Test basic flow with start node only
-        let node1 = TestNode::new(json!({"data": "test1"}), CustomState::Success);
-        let flow1 = build_flow!(
-            start: ("start", node1)
-        );
-        let context = Context::new();
-        let result = flow1.run(context).await.unwrap();
+    /// Returns its context's `item` after a delay that shrinks as items grow, so
+    /// later items finish first. Tracks how many run at once and fails on `fail_on`.
+    struct BatchItemNode {
+        running: AtomicUsize,
+        max_running: AtomicUsize,
+        calls: AtomicUsize,
+        fail_on: Vec<u64>,
+    }
+
+    impl BatchItemNode {
+        fn new(fail_on: Vec<u64>) -> Self {
+            Self {
+                running: AtomicUsize::new(0),
+                max_running: AtomicUsize::new(0),
+                calls: AtomicUsize::new(0),
+                fail_on,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Node for BatchItemNode {
+        type State = CustomState;
+
+        async fn execute(&self, context: &Context) -> Result<Value> {
+            let item = context.get("item").and_then(Value::as_u64).unwrap();
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_running.fetch_max(running, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(40 - 5 * item)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+
+            if self.fail_on.contains(&item) {
+                return Err(anyhow::anyhow!("item {} failed", item));
+            }
+            Ok(json!(item))
+        }
+
+        async fn post_process(
+            &self,
+            context: &mut Context,
+            result: &Result<Value>,
+        ) -> Result<ProcessResult<CustomState>> {
+            match result {
+                Ok(value) => {
+                    context.set("result", value.clone());
+                    Ok(ProcessResult::new(CustomState::Default, "ok".to_string()))
+                }
+                Err(e) => Err(anyhow::anyhow!("{}", e)),
+            }
+        }
+    }
+
+    fn batch_contexts(count: u64) -> Vec<Context> {
+        (0..count)
+            .map(|item| {
+                let mut context = Context::new();
+                context.set("item", json!(item));
+                context
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_batch_flow_runs_concurrently_in_order() {
+        let node = Arc::new(BatchItemNode::new(Vec::new()));
+        let batch_flow = BatchFlow::<CustomState>::new("item", node.clone(), 3);
+
+        let results = batch_flow.run_batch(batch_contexts(7)).await.unwrap();
+        assert_eq!(results, (0..7).map(|item| json!(item)).collect::<Vec<_>>());
+        assert_eq!(node.max_running.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_batch_flow_failure_does_not_stop_other_items() {
+        let node = Arc::new(BatchItemNode::new(vec![2, 4]));
+        let batch_flow = BatchFlow::<CustomState>::new("item", node.clone(), 2);
+
+        let error = batch_flow.run_batch(batch_contexts(6)).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "2 of 6 batch items failed, first at index 2"
+        );
+        assert_eq!(error.root_cause().to_string(), "item 2 failed");
+        // Items after the failures still ran
+        assert_eq!(node.calls.load(Ordering::SeqCst), 6);
+    }
+
+    #[tokio::test]
+    async fn test_build_flow_macro() {
+        // Test basic flow with start node only
+        let node1 = TestNode::new(json!({"data": "test1"}), CustomState::Success);
+        let flow1 = build_flow!(
+            start: ("start", node1)
+        );
+        let context = Context::new();
+        let result = flow1.run(context).await.unwrap();
         assert_eq!(result, json!({"data": "test1"}));
 
         // Test flow with multiple nodes
@@ -365,4 +1554,884 @@ mod tests {
         let result = flow3.run(context).await.unwrap();
         assert_eq!(result, json!({"data": "test2"}));
     }
+
+    struct FlakyNode {
+        failures: usize,
+        max_retries: usize,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl FlakyNode {
+        fn new(failures: usize, max_retries: usize) -> (Self, Arc<AtomicUsize>) {
+            let calls = Arc::new(AtomicUsize::new(0));
+            let node = Self {
+                failures,
+                max_retries,
+                calls: calls.clone(),
+            };
+            (node, calls)
+        }
+    }
+
+    #[async_trait]
+    impl Node for FlakyNode {
+        type State = CustomState;
+
+        async fn execute(&self, context: &Context) -> Result<Value> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            if call < self.failures {
+                return Err(anyhow::anyhow!("flaky failure {}", call));
+            }
+            Ok(context
+                .get_metadata(RETRIES_USED_KEY)
+                .cloned()
+                .unwrap_or(json!(0)))
+        }
+
+        fn retry_policy(&self) -> RetryPolicy {
+            RetryPolicy::new(self.max_retries).with_initial_backoff(Duration::ZERO)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_budget() {
+        let (node_a, calls_a) = FlakyNode::new(2, 3);
+        let (node_b, calls_b) = FlakyNode::new(5, 5);
+        let (node_c, calls_c) = FlakyNode::new(1, 2);
+        let (report, _) = FlakyNode::new(0, 0);
+
+        let mut flow = build_flow!(
+            start: ("a", node_a),
+            nodes: [("b", node_b), ("c", node_c), ("report", report)],
+            edges: [
+                ("a", "b", CustomState::Default),
+                ("b", "c", CustomState::Default),
+                ("c", "report", CustomState::Default)
+            ]
+        );
+        flow.set_retry_budget(3);
+
+        let result = flow.run(Context::new()).await.unwrap();
+
+        // a uses two retries, b gets the last one, c is not retried at all
+        assert_eq!(calls_a.load(Ordering::SeqCst), 3);
+        assert_eq!(calls_b.load(Ordering::SeqCst), 2);
+        assert_eq!(calls_c.load(Ordering::SeqCst), 1);
+        assert_eq!(result, json!(3));
+    }
+
+    struct FailingNode;
+
+    #[async_trait]
+    impl Node for FailingNode {
+        type State = CustomState;
+
+        async fn prepare(&self, _context: &mut Context) -> Result<()> {
+            Err(anyhow::anyhow!("prepare failed"))
+        }
+
+        async fn execute(&self, _context: &Context) -> Result<Value> {
+            Ok(Value::Null)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_lenient_keeps_context_on_error() {
+        let node1 = TestNode::new(json!({"data": "test1"}), CustomState::Success);
+        let flow = build_flow!(
+            start: ("start", node1),
+            nodes: [("fail", FailingNode)],
+            edges: [
+                ("start", "fail", CustomState::Success)
+            ]
+        );
+
+        let (result, context) = flow.run_lenient(Context::new()).await;
+
+        assert_eq!(result.unwrap_err().to_string(), "prepare failed");
+        assert_eq!(context.get("result"), Some(&json!({"data": "test1"})));
+    }
+
+    #[tokio::test]
+    async fn test_run_full_returns_seeded_metadata() {
+        let node1 = TestNode::new(json!({"data": "test1"}), CustomState::Success);
+        let node2 = TestNode::new(json!({"data": "test2"}), CustomState::Default);
+        let flow = build_flow!(
+            start: ("start", node1),
+            nodes: [("next", node2)],
+            edges: [
+                ("start", "next", CustomState::Success)
+            ]
+        );
+        let mut context = Context::new();
+        context.set_metadata("trace_id", json!("trace-42"));
+        context.set("tenant_input", json!("acme"));
+
+        let (result, context) = flow.run_full(context).await.unwrap();
+
+        assert_eq!(result, json!({"data": "test2"}));
+        assert_eq!(context.get_metadata("trace_id"), Some(&json!("trace-42")));
+        assert_eq!(context.get("tenant_input"), Some(&json!("acme")));
+    }
+
+    /// Fails `failures` times, recording when each call was made.
+    struct BackoffNode {
+        failures: usize,
+        policy: RetryPolicy,
+        calls: Arc<std::sync::Mutex<Vec<Instant>>>,
+    }
+
+    #[async_trait]
+    impl Node for BackoffNode {
+        type State = CustomState;
+
+        async fn execute(&self, _context: &Context) -> Result<Value> {
+            let mut calls = self.calls.lock().unwrap();
+            calls.push(Instant::now());
+            if calls.len() <= self.failures {
+                return Err(anyhow::anyhow!("attempt {} failed", calls.len()));
+            }
+            Ok(json!("done"))
+        }
+
+        fn retry_policy(&self) -> RetryPolicy {
+            self.policy.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_policy_backs_off() {
+        let policy = RetryPolicy::new(3)
+            .with_initial_backoff(Duration::from_millis(20))
+            .with_backoff_multiplier(2.0);
+        let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let node = BackoffNode {
+            failures: 2,
+            policy: policy.clone(),
+            calls: calls.clone(),
+        };
+
+        let result = Flow::new("flaky", Arc::new(node))
+            .run(Context::new())
+            .await
+            .unwrap();
+
+        assert_eq!(result, json!("done"));
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 3);
+        assert!(calls[1] - calls[0] >= Duration::from_millis(20));
+        assert!(calls[2] - calls[1] >= Duration::from_millis(40));
+    }
+
+    #[tokio::test]
+    async fn test_retry_policy_surfaces_last_error() {
+        let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let node = BackoffNode {
+            failures: 5,
+            policy: RetryPolicy::new(2).with_initial_backoff(Duration::from_millis(1)),
+            calls: calls.clone(),
+        };
+
+        let (result, context) = Flow::new("flaky", Arc::new(node))
+            .run_lenient(Context::new())
+            .await;
+
+        result.unwrap();
+        assert_eq!(calls.lock().unwrap().len(), 3);
+        assert_eq!(context.get("error"), Some(&json!("attempt 3 failed")));
+    }
+
+    #[tokio::test]
+    async fn test_max_steps_stops_cycle() {
+        let (ping, ping_calls) = FlakyNode::new(0, 0);
+        let (pong, pong_calls) = FlakyNode::new(0, 0);
+        let flow = build_flow!(
+            start: ("ping", ping),
+            nodes: [("pong", pong)],
+            edges: [
+                ("ping", "pong", CustomState::Default),
+                ("pong", "ping", CustomState::Default)
+            ]
+        )
+        .with_max_steps(5);
+
+        let error = flow.run(Context::new()).await.unwrap_err();
+
+        assert!(error.to_string().contains("Maximum steps exceeded"));
+        assert_eq!(ping_calls.load(Ordering::SeqCst), 3);
+        assert_eq!(pong_calls.load(Ordering::SeqCst), 2);
+    }
+
+    /// Writes its value under `key`, first waiting on a barrier shared with its
+    /// sibling branches if it has one.
+    struct WriteNode {
+        key: &'static str,
+        value: Value,
+        barrier: Option<Arc<tokio::sync::Barrier>>,
+    }
+
+    impl WriteNode {
+        fn new(key: &'static str, value: Value) -> Self {
+            Self {
+                key,
+                value,
+                barrier: None,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Node for WriteNode {
+        type State = CustomState;
+
+        async fn execute(&self, _context: &Context) -> Result<Value> {
+            if let Some(barrier) = &self.barrier {
+                barrier.wait().await;
+            }
+            Ok(self.value.clone())
+        }
+
+        async fn post_process(
+            &self,
+            context: &mut Context,
+            result: &Result<Value>,
+        ) -> Result<ProcessResult<CustomState>> {
+            context.set(self.key, result.as_ref().unwrap().clone());
+            Ok(ProcessResult::new(
+                CustomState::Default,
+                "written".to_string(),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parallel_edges_run_concurrently_and_join() {
+        // Each branch waits for the other, so running them one after the other hangs
+        let barrier = Arc::new(tokio::sync::Barrier::new(2));
+        let branch = |key, value| WriteNode {
+            barrier: Some(barrier.clone()),
+            ..WriteNode::new(key, value)
+        };
+        let mut flow = Flow::new("loader", Arc::new(WriteNode::new("docs", json!(2))));
+        flow.add_node("embed_a", Arc::new(branch("batch_a", json!("embedded a"))));
+        flow.add_node("embed_b", Arc::new(branch("batch_b", json!("embedded b"))));
+        flow.add_node("store", Arc::new(WriteNode::new("result", json!("stored"))));
+        flow.add_parallel_edges("loader", vec!["embed_a", "embed_b"], CustomState::Default);
+        flow.add_edge("embed_a", "store", CustomState::Default);
+        flow.add_edge("embed_b", "store", CustomState::Default);
+
+        let (result, context) =
+            tokio::time::timeout(Duration::from_secs(5), flow.run_full(Context::new()))
+                .await
+                .expect("branches did not run concurrently")
+                .unwrap();
+
+        assert_eq!(result, json!("stored"));
+        assert_eq!(context.get("docs"), Some(&json!(2)));
+        assert_eq!(context.get("batch_a"), Some(&json!("embedded a")));
+        assert_eq!(context.get("batch_b"), Some(&json!("embedded b")));
+    }
+
+    #[tokio::test]
+    async fn test_parallel_merge_strategies() {
+        let run = |strategy| async move {
+            let mut flow = Flow::new("start", Arc::new(WriteNode::new("result", json!("start"))))
+                .with_merge_strategy(strategy);
+            flow.add_node("first", Arc::new(WriteNode::new("shared", json!("first"))));
+            flow.add_node(
+                "second",
+                Arc::new(WriteNode::new("shared", json!("second"))),
+            );
+            flow.add_node("other", Arc::new(WriteNode::new("other", json!(1))));
+            flow.add_parallel_edges(
+                "start",
+                vec!["first", "other", "second"],
+                CustomState::Default,
+            );
+            flow.run_full(Context::new())
+                .await
+                .map(|(_, context)| context.get("shared").cloned())
+        };
+
+        assert_eq!(
+            run(MergeStrategy::LastWriteWins).await.unwrap(),
+            Some(json!("second"))
+        );
+        assert_eq!(
+            run(MergeStrategy::FirstWriteWins).await.unwrap(),
+            Some(json!("first"))
+        );
+        let error = run(MergeStrategy::ErrorOnConflict).await.unwrap_err();
+        assert!(error.to_string().contains("'first' and 'second'"));
+    }
+
+    #[tokio::test]
+    async fn test_parallel_branches_must_agree_on_next_node() {
+        let mut flow = Flow::new("start", Arc::new(WriteNode::new("a", json!(0))));
+        flow.add_node("left", Arc::new(WriteNode::new("left", json!(1))));
+        flow.add_node("right", Arc::new(WriteNode::new("right", json!(2))));
+        flow.add_node("x", Arc::new(WriteNode::new("x", json!(3))));
+        flow.add_node("y", Arc::new(WriteNode::new("y", json!(4))));
+        flow.add_parallel_edges("start", vec!["left", "right"], CustomState::Default);
+        flow.add_edge("left", "x", CustomState::Default);
+        flow.add_edge("right", "y", CustomState::Default);
+
+        let error = flow.run(Context::new()).await.unwrap_err();
+
+        assert!(error.to_string().contains("route to different nodes"));
+    }
+
+    #[test]
+    fn test_to_mermaid() {
+        let mut flow = Flow::new(
+            "load docs",
+            Arc::new(TestNode::new(json!(1), CustomState::Success)),
+        );
+        flow.add_node("embed", Arc::new(WriteNode::new("embedded", json!(2))));
+        flow.add_node("report", Arc::new(WriteNode::new("report", json!(3))));
+        flow.add_edge("load docs", "embed", CustomState::Success);
+        flow.add_edge("load docs", "report", CustomState::Failure);
+        flow.add_edge("embed", "report", CustomState::Default);
+
+        assert_eq!(
+            flow.to_mermaid(),
+            [
+                "flowchart TD",
+                "    n0[\"embed\"]",
+                "    n1([\"load docs\"])",
+                "    n2[\"report\"]",
+                "    n0 --> n2",
+                "    n1 -->|\"success\"| n0",
+                "    n1 -->|\"failure\"| n2",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[derive(Debug, Clone, PartialEq, Default)]
+    enum NumberState {
+        Small,
+        Medium,
+        Large,
+        RetrievalError,
+        #[default]
+        Default,
+    }
+
+    impl ProcessState for NumberState {
+        fn is_default(&self) -> bool {
+            matches!(self, NumberState::Default)
+        }
+
+        fn to_condition(&self) -> String {
+            match self {
+                NumberState::Small => "small".to_string(),
+                NumberState::Medium => "medium".to_string(),
+                NumberState::Large => "large".to_string(),
+                NumberState::RetrievalError => "retrieval_error".to_string(),
+                NumberState::Default => "default".to_string(),
+            }
+        }
+    }
+
+    struct NoopNode;
+
+    #[async_trait]
+    impl Node for NoopNode {
+        type State = NumberState;
+
+        async fn execute(&self, _context: &Context) -> Result<Value> {
+            Ok(Value::Null)
+        }
+    }
+
+    #[test]
+    fn test_to_dot_basic_example() {
+        // The flow of examples/basic.rs
+        let flow = build_flow!(
+            start: ("start", NoopNode),
+            nodes: [
+                ("rand", NoopNode),
+                ("small", NoopNode),
+                ("medium", NoopNode),
+                ("large", NoopNode)
+            ],
+            edges: [
+                ("start", "rand", NumberState::Default),
+                ("rand", "small", NumberState::Small),
+                ("rand", "medium", NumberState::Medium),
+                ("rand", "large", NumberState::Large)
+            ]
+        );
+
+        assert_eq!(
+            flow.to_dot(),
+            r#"digraph flow {
+    node [shape=box];
+    "large";
+    "medium";
+    "rand";
+    "small";
+    "start" [shape=ellipse, style=filled, fillcolor=lightblue];
+    "rand" -> "small" [label="small"];
+    "rand" -> "medium" [label="medium"];
+    "rand" -> "large" [label="large"];
+    "start" -> "rand";
+}"#
+        );
+    }
+
+    #[test]
+    fn test_to_dot_error_and_parallel_edges() {
+        let mut flow = Flow::new("retrieve \"docs\"", Arc::new(NoopNode));
+        flow.add_node("fallback", Arc::new(NoopNode));
+        flow.add_edge("retrieve \"docs\"", "fallback", NumberState::RetrievalError);
+        flow.add_parallel_edges("fallback", vec!["small", "large"], NumberState::Default);
+
+        let dot = flow.to_dot();
+
+        assert!(dot.contains(
+            r#"    "retrieve \"docs\"" -> "fallback" [label="retrieval_error", color=red, fontcolor=red];"#
+        ));
+        assert!(dot.contains(r#"    "fallback" -> "small" [style=bold];"#));
+        assert!(dot.contains(r#"    "fallback" -> "large" [style=bold];"#));
+    }
+
+    #[test]
+    fn test_validate_reports_all_problems() {
+        let node = || TestNode::new(json!(1), CustomState::Success);
+        let valid = build_flow!(
+            start: ("load", node()),
+            nodes: [("embed", node()), ("report", node())],
+            edges: [
+                ("load", "embed", CustomState::Success),
+                ("load", "report", CustomState::Failure),
+                ("embed", "report", CustomState::Default)
+            ],
+            terminals: ["report"]
+        );
+        assert_eq!(valid.validate(), Ok(()));
+
+        let mut flow = build_flow!(
+            start: ("load", node()),
+            nodes: [("embed", node()), ("report", node()), ("orphan", node())],
+            edges: [
+                ("load", "embd", CustomState::Success),
+                ("load", "report", CustomState::Failure)
+            ]
+        );
+        flow.add_parallel_edges("report", vec!["embed", "sumarize"], CustomState::Default);
+
+        assert_eq!(
+            flow.validate().unwrap_err(),
+            vec![
+                FlowError::UnknownNode {
+                    from: "load".to_string(),
+                    to: "embd".to_string(),
+                    node: "embd".to_string(),
+                },
+                FlowError::UnknownNode {
+                    from: "report".to_string(),
+                    to: "sumarize".to_string(),
+                    node: "sumarize".to_string(),
+                },
+                FlowError::Unreachable("orphan".to_string()),
+                FlowError::DeadEnd("embed".to_string()),
+                FlowError::DeadEnd("orphan".to_string()),
+            ]
+        );
+        assert_eq!(
+            FlowError::DeadEnd("embed".to_string()).to_string(),
+            "node 'embed' has no outgoing edges and is not a terminal"
+        );
+    }
+
+    #[test]
+    fn test_validate_reports_unknown_start() {
+        let node = || TestNode::new(json!(1), CustomState::Success);
+        let mut flow = build_flow!(
+            start: ("load", node()),
+            nodes: [("report", node())],
+            edges: [("load", "report", CustomState::Default)],
+            terminals: ["report"]
+        );
+        flow.start_node = "lod".to_string();
+
+        assert_eq!(
+            flow.validate().unwrap_err(),
+            vec![FlowError::UnknownStart("lod".to_string())]
+        );
+    }
+
+    struct ParamNode {
+        value: Value,
+    }
+
+    #[async_trait]
+    impl Node for ParamNode {
+        type State = CustomState;
+
+        async fn execute(&self, _context: &Context) -> Result<Value> {
+            Ok(self.value.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spec_yaml_round_trip() {
+        let yaml = r#"
+start: first
+nodes:
+  - name: first
+    type: param
+    params: {value: 1}
+  - name: second
+    type: param
+    params: {value: 2}
+edges:
+  - from: first
+    to: second
+"#;
+        let mut registry = FlowRegistry::<CustomState>::new();
+        registry.register("param", |params| {
+            Ok(Arc::new(ParamNode {
+                value: params["value"].clone(),
+            }))
+        });
+
+        let spec = FlowSpec::from_yaml(yaml).unwrap();
+        let flow = Flow::from_spec(&spec, &registry).unwrap();
+        assert_eq!(flow.to_spec(), spec);
+
+        let reloaded = FlowSpec::from_yaml(&flow.to_spec().to_yaml().unwrap()).unwrap();
+        let rebuilt = Flow::from_spec(&reloaded, &registry).unwrap();
+        let result = rebuilt.run(Context::new()).await.unwrap();
+        assert_eq!(result, json!(2));
+
+        let mut unknown = spec.clone();
+        unknown.nodes[1].node_type = "missing".to_string();
+        assert!(Flow::from_spec(&unknown, &registry).is_err());
+    }
+
+    #[test]
+    fn test_spec_round_trips_parallel_edges() {
+        let yaml = r#"
+start: first
+nodes:
+  - name: first
+    type: param
+  - name: join
+    type: param
+  - name: left
+    type: param
+  - name: right
+    type: param
+edges:
+  - from: left
+    to: join
+  - from: first
+    to: left
+    parallel: true
+  - from: first
+    to: right
+    parallel: true
+"#;
+        let mut registry = FlowRegistry::<CustomState>::new();
+        registry.register("param", |params| {
+            Ok(Arc::new(ParamNode {
+                value: params.clone(),
+            }))
+        });
+
+        let spec = FlowSpec::from_yaml(yaml).unwrap();
+        let flow = Flow::from_spec(&spec, &registry).unwrap();
+        assert_eq!(
+            flow.parallel_branches("first", "default"),
+            Some(&["left".to_string(), "right".to_string()][..])
+        );
+        assert_eq!(flow.to_spec(), spec);
+
+        let reloaded = FlowSpec::from_yaml(&flow.to_spec().to_yaml().unwrap()).unwrap();
+        let rebuilt = Flow::from_spec(&reloaded, &registry).unwrap();
+        assert_eq!(rebuilt.to_spec(), spec);
+        assert!(
+            !flow
+                .to_spec()
+                .to_yaml()
+                .unwrap()
+                .contains("parallel: false")
+        );
+    }
+
+    #[test]
+    fn test_context_size_warning_names_big_key() {
+        let mut context = Context::new();
+        context.set("query", json!("small"));
+        context.set("embeddings", json!(vec![0.125; 1000]));
+
+        assert!(context_size_warning(&context, 100_000).is_none());
+
+        let warning = context_size_warning(&context, 1000).unwrap();
+        assert!(warning.contains("largest keys: 'embeddings'"));
+        assert_eq!(context.key_sizes()[0].0, "embeddings");
+    }
+
+    /// Appends its name to the context's `visited` list, or fails while `crash` is
+    /// set, like a process dying mid-flow.
+    struct StepNode {
+        name: &'static str,
+        crash: Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    #[async_trait]
+    impl Node for StepNode {
+        type State = CustomState;
+
+        async fn execute(&self, _context: &Context) -> Result<Value> {
+            if self.crash.load(Ordering::SeqCst) {
+                return Err(anyhow::anyhow!("{} crashed", self.name));
+            }
+            Ok(json!(self.name))
+        }
+
+        async fn post_process(
+            &self,
+            context: &mut Context,
+            result: &Result<Value>,
+        ) -> Result<ProcessResult<CustomState>> {
+            let name = result.as_ref().map_err(|e| anyhow::anyhow!("{}", e))?;
+            context.update("visited", |visited| {
+                let mut visited = visited.cloned().unwrap_or_else(|| json!([]));
+                visited.as_array_mut().unwrap().push(name.clone());
+                visited
+            });
+            context.set("result", name.clone());
+            Ok(ProcessResult::new(CustomState::Default, "step".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_resumable_resumes_after_crash() {
+        let path =
+            std::env::temp_dir().join(format!("pocketflow-checkpoint-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let step = |name, crash: &Arc<std::sync::atomic::AtomicBool>| {
+            Arc::new(StepNode {
+                name,
+                crash: crash.clone(),
+            })
+        };
+        let healthy = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let crash = Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let mut flow = Flow::<CustomState>::new("load", step("load", &healthy));
+        flow.add_node("chunk", step("chunk", &healthy));
+        flow.add_node("embed", step("embed", &crash));
+        flow.add_edge("load", "chunk", CustomState::Default);
+        flow.add_edge("chunk", "embed", CustomState::Default);
+
+        let mut context = Context::new();
+        context.set("source", json!("pangu.pdf"));
+        let err = flow.run_resumable(context, &path).await.unwrap_err();
+        assert!(err.to_string().contains("embed crashed"));
+        let checkpoint = Context::load(&path).unwrap();
+        assert_eq!(checkpoint.get("visited"), Some(&json!(["load", "chunk"])));
+        assert_eq!(
+            checkpoint.get_metadata(CHECKPOINT_NODE_KEY),
+            Some(&json!("embed"))
+        );
+
+        // The restarted run ignores its fresh context and picks up at "embed"
+        crash.store(false, Ordering::SeqCst);
+        let result = flow.run_resumable(Context::new(), &path).await.unwrap();
+        assert_eq!(result, json!("embed"));
+        assert!(!path.exists());
+
+        // With no checkpoint left, the next run starts over
+        let (result, context) = flow.run_full(Context::new()).await.unwrap();
+        assert_eq!(result, json!("embed"));
+        assert_eq!(
+            context.get("visited"),
+            Some(&json!(["load", "chunk", "embed"]))
+        );
+    }
+
+    /// Sleeps for a minute in `execute`, like a hung LLM request.
+    struct HungNode {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Node for HungNode {
+        type State = CustomState;
+
+        async fn execute(&self, _context: &Context) -> Result<Value> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(Value::Null)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_with_cancel_interrupts_execute() {
+        let hung = Arc::new(HungNode {
+            calls: AtomicUsize::new(0),
+        });
+        let mut flow = Flow::<CustomState>::new(
+            "start",
+            Arc::new(TestNode::new(json!("started"), CustomState::Default)),
+        );
+        flow.add_node("hung", hung.clone());
+        flow.add_edge("start", "hung", CustomState::Default);
+
+        let cancel = CancellationToken::new();
+        let canceller = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            canceller.cancel();
+        });
+        let started = Instant::now();
+        let err = flow
+            .run_with_cancel(Context::new(), cancel.clone())
+            .await
+            .unwrap_err();
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(matches!(
+            err.downcast_ref::<crate::Error>(),
+            Some(crate::Error::Cancelled)
+        ));
+        assert_eq!(err.to_string(), "flow cancelled");
+        assert_eq!(hung.calls.load(Ordering::SeqCst), 1);
+
+        // An already cancelled token stops the flow before its first node
+        let flow = Flow::<CustomState>::new("hung", hung.clone());
+        assert!(flow.run_with_cancel(Context::new(), cancel).await.is_err());
+        assert_eq!(hung.calls.load(Ordering::SeqCst), 1);
+    }
+
+    /// Sleeps far past its timeout, routing failures to `Failure`.
+    struct SlowNode;
+
+    #[async_trait]
+    impl Node for SlowNode {
+        type State = CustomState;
+
+        async fn execute(&self, _context: &Context) -> Result<Value> {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(Value::Null)
+        }
+
+        fn timeout(&self) -> Option<Duration> {
+            Some(Duration::from_millis(50))
+        }
+
+        async fn post_process(
+            &self,
+            context: &mut Context,
+            result: &Result<Value>,
+        ) -> Result<ProcessResult<CustomState>> {
+            let Err(e) = result else {
+                return Ok(ProcessResult::new(CustomState::Success, "done".to_string()));
+            };
+            if let Some(crate::Error::Timeout(timeout)) = e.downcast_ref::<crate::Error>() {
+                context.set("timed_out_after_ms", json!(timeout.as_millis() as u64));
+            }
+            context.set("error", json!(e.to_string()));
+            Ok(ProcessResult::new(CustomState::Failure, e.to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_node_timeout_routes_to_error_edge() {
+        let mut flow = Flow::<CustomState>::new("slow", Arc::new(SlowNode));
+        flow.add_node(
+            "fallback",
+            Arc::new(TestNode::new(json!("fallback"), CustomState::Default)),
+        );
+        flow.add_edge("slow", "fallback", CustomState::Failure);
+
+        let started = Instant::now();
+        let (result, context) = flow.run_full(Context::new()).await.unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(result, json!("fallback"));
+        assert_eq!(context.get("timed_out_after_ms").unwrap(), &json!(50));
+        assert_eq!(context.get_str("error").unwrap(), "timed out after 50ms");
+    }
+
+    /// Logs every hook call as a line.
+    #[derive(Default)]
+    struct LogObserver {
+        events: Mutex<Vec<String>>,
+    }
+
+    impl FlowObserver<CustomState> for LogObserver {
+        fn on_node_start(&self, name: &str) {
+            self.events.lock().unwrap().push(format!("start {}", name));
+        }
+
+        fn on_node_finish(
+            &self,
+            name: &str,
+            _duration: Duration,
+            result: &ProcessResult<CustomState>,
+        ) {
+            self.events.lock().unwrap().push(format!(
+                "finish {} on {}",
+                name,
+                result.state.to_condition()
+            ));
+        }
+
+        fn on_node_error(&self, name: &str, _duration: Duration, error: &anyhow::Error) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("error {}: {}", name, error));
+        }
+
+        fn on_flow_end(&self, context: &Context) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("end with {:?}", context.get("result")));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_observer_sees_every_node() {
+        let observer = Arc::new(LogObserver::default());
+        let mut flow = Flow::<CustomState>::new(
+            "start",
+            Arc::new(TestNode::new(json!("started"), CustomState::Failure)),
+        )
+        .with_observer(observer.clone());
+        flow.add_node(
+            "recover",
+            Arc::new(TestNode::new(json!("recovered"), CustomState::Default)),
+        );
+        flow.add_edge("start", "recover", CustomState::Failure);
+
+        flow.run(Context::new()).await.unwrap();
+
+        assert_eq!(
+            *observer.events.lock().unwrap(),
+            vec![
+                "start start",
+                "finish start on failure",
+                "start recover",
+                "finish recover on default",
+                "end with Some(String(\"recovered\"))",
+            ]
+        );
+
+        // A node aborting the run is reported as an error, and the run still ends
+        let observer = Arc::new(LogObserver::default());
+        let flow =
+            Flow::<CustomState>::new("fail", Arc::new(FailingNode)).with_observer(observer.clone());
+        assert!(flow.run(Context::new()).await.is_err());
+        assert_eq!(
+            *observer.events.lock().unwrap(),
+            vec!["start fail", "error fail: prepare failed", "end with None"]
+        );
+    }
 }
@@ -1,11 +1,30 @@
+pub mod config;
 pub mod context;
+pub mod document;
+pub mod error;
 pub mod flow;
 pub mod node;
+pub mod nodes;
+pub mod observer;
+pub mod otel;
+pub mod progress;
+pub mod recording;
+pub mod spec;
+#[cfg(test)]
+mod test_utils;
 pub mod utils;
 
-pub use context::Context;
+pub use config::NodeConfig;
+pub use context::{Context, SharedContext};
+pub use document::Document;
+pub use error::Error;
 pub use flow::*;
 pub use node::*;
+pub use observer::{FlowObserver, TracingObserver};
+pub use progress::{ProgressEvent, ProgressReporter};
+pub use recording::Recording;
+pub use spec::*;
+pub use tokio_util::sync::CancellationToken;
 pub use utils::*;
 
 pub type Params = std::collections::HashMap<String, serde_json::Value>;
@@ -1,8 +1,16 @@
-use crate::{Params, context::Context};
+use crate::{Params, context::Context, utils::backoff::Backoff};
 use anyhow::Result;
 use async_trait::async_trait;
+use futures::future::join_all;
 use std::collections::HashMap;
+use std::future::Future;
+use std::marker::PhantomData;
 use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Upper bound for a single delay between retries.
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(60);
 
 pub trait ProcessState: Send + Sync {
     fn is_default(&self) -> bool;
@@ -52,6 +60,50 @@ impl<S: ProcessState + Default> Default for ProcessResult<S> {
     }
 }
 
+/// How a flow retries a node's failing `execute`: up to `max_retries` times, waiting
+/// `initial_backoff` before the first retry and `backoff_multiplier` times longer
+/// before each following one (capped at a minute).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    pub max_retries: usize,
+    pub initial_backoff: Duration,
+    pub backoff_multiplier: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            initial_backoff: Duration::from_millis(500),
+            backoff_multiplier: 2.0,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: usize) -> Self {
+        Self {
+            max_retries,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    pub fn with_backoff_multiplier(mut self, backoff_multiplier: f64) -> Self {
+        self.backoff_multiplier = backoff_multiplier;
+        self
+    }
+
+    /// The delays before each retry.
+    pub fn backoff(&self) -> Backoff {
+        Backoff::new(self.initial_backoff, MAX_RETRY_BACKOFF).with_factor(self.backoff_multiplier)
+    }
+}
+
 #[async_trait]
 pub trait Node: Send + Sync {
     type State: ProcessState + Default;
@@ -63,6 +115,26 @@ pub trait Node: Send + Sync {
 
     async fn execute(&self, context: &Context) -> Result<serde_json::Value>;
 
+    /// A human-readable name for logs and traces, reported alongside the key the
+    /// node is registered under in a flow. Defaults to the Rust type name.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+
+    /// How the flow retries `execute` after it returns an error; `prepare` and
+    /// `post_process` are never retried. Defaults to no retries.
+    fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::default()
+    }
+
+    /// How long a single `execute` may run before the flow drops it and hands
+    /// [`crate::Error::Timeout`] to `post_process` in its place, so error edges
+    /// apply as for any other failure. Each retry gets the full timeout again.
+    /// Defaults to `None`, waiting indefinitely.
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
+
     #[allow(unused_variables)]
     async fn post_process(
         &self,
@@ -115,29 +187,207 @@ impl Node for BaseNode {
 
 impl BaseNodeTrait for BaseNode {}
 
-#[allow(dead_code)]
-pub struct BatchNode {
-    base: BaseNode,
+/// The per-item work of a [`BatchNode`]. Implemented for async closures taking
+/// and returning a `Value`.
+#[async_trait]
+pub trait BatchItemProcessor: Send + Sync {
+    async fn process(&self, item: serde_json::Value) -> Result<serde_json::Value>;
+}
+
+#[async_trait]
+impl<F, Fut> BatchItemProcessor for F
+where
+    F: Fn(serde_json::Value) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<serde_json::Value>> + Send,
+{
+    async fn process(&self, item: serde_json::Value) -> Result<serde_json::Value> {
+        self(item).await
+    }
+}
+
+/// Processes the array under `input_key` in slices of `batch_size` items: the
+/// items of a slice run concurrently, and each slice starts once the previous
+/// one finished. The results, in input order, are stored under `output_key`,
+/// `result` by default.
+///
+/// Every item runs even when others fail. The failures are then reported
+/// together as one [`crate::Error::Batch`], and nothing is stored.
+pub struct BatchNode<S: ProcessState + Default = BaseState> {
+    processor: Arc<dyn BatchItemProcessor>,
+    input_key: String,
+    output_key: String,
     batch_size: usize,
+    _state: PhantomData<fn() -> S>,
 }
 
-impl BatchNode {
-    pub fn new(params: Params, batch_size: usize) -> Self {
+impl<S: ProcessState + Default> BatchNode<S> {
+    pub fn new(
+        input_key: &str,
+        batch_size: usize,
+        processor: impl BatchItemProcessor + 'static,
+    ) -> Self {
         Self {
-            base: BaseNode::new(params),
+            processor: Arc::new(processor),
+            input_key: input_key.to_string(),
+            output_key: "result".to_string(),
             batch_size,
+            _state: PhantomData,
         }
     }
+
+    pub fn with_output_key(mut self, output_key: &str) -> Self {
+        self.output_key = output_key.to_string();
+        self
+    }
 }
 
 #[async_trait]
-impl Node for BatchNode {
-    type State = BaseState;
+impl<S: ProcessState + Default> Node for BatchNode<S> {
+    type State = S;
+
+    fn name(&self) -> &str {
+        "Batch"
+    }
 
-    #[allow(unused_variables)]
     async fn execute(&self, context: &Context) -> Result<serde_json::Value> {
-        Ok(serde_json::Value::Null)
+        let items = context.get_array(&self.input_key)?;
+        let batch_size = self.batch_size.max(1);
+        let mut results = Vec::with_capacity(items.len());
+        let mut errors = Vec::new();
+
+        for (batch, slice) in items.chunks(batch_size).enumerate() {
+            debug!(
+                "Processing batch {} of {} items from '{}'",
+                batch + 1,
+                slice.len(),
+                self.input_key
+            );
+            let outcomes = join_all(
+                slice
+                    .iter()
+                    .map(|item| self.processor.process(item.clone())),
+            )
+            .await;
+            for (offset, outcome) in outcomes.into_iter().enumerate() {
+                match outcome {
+                    Ok(value) => results.push(value),
+                    Err(e) => {
+                        let index = batch * batch_size + offset;
+                        warn!("Batch item {} failed: {}", index, e);
+                        errors.push((index, e.to_string()));
+                    }
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(crate::Error::Batch {
+                total: items.len(),
+                errors,
+            }
+            .into());
+        }
+        Ok(serde_json::Value::Array(results))
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<serde_json::Value>,
+    ) -> Result<ProcessResult<S>> {
+        match result {
+            Ok(value) => {
+                context.set(&self.output_key, value.clone());
+                Ok(ProcessResult::default())
+            }
+            Err(e) => {
+                context.set("error", serde_json::Value::String(e.to_string()));
+                Ok(ProcessResult::new(S::default(), e.to_string()))
+            }
+        }
     }
 }
 
 impl BaseNodeTrait for BatchNode {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_batch_node_processes_items_in_batches() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let node: BatchNode = BatchNode::new("numbers", 3, {
+            let running = running.clone();
+            let max_running = max_running.clone();
+            move |item: serde_json::Value| {
+                let running = running.clone();
+                let max_running = max_running.clone();
+                async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(now, Ordering::SeqCst);
+                    tokio::task::yield_now().await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    Ok(json!(item.as_i64().unwrap() * 10))
+                }
+            }
+        })
+        .with_output_key("scaled");
+        let mut context = Context::new();
+        context.set("numbers", json!([1, 2, 3, 4, 5, 6, 7]));
+
+        let result = node.execute(&context).await;
+        node.post_process(&mut context, &result).await.unwrap();
+
+        assert_eq!(
+            context.get("scaled").unwrap(),
+            &json!([10, 20, 30, 40, 50, 60, 70])
+        );
+        assert_eq!(max_running.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_batch_node_reports_every_failed_item() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let node: BatchNode = BatchNode::new("words", 2, {
+            let calls = calls.clone();
+            move |item: serde_json::Value| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    let word = item.as_str().unwrap_or_default().to_string();
+                    if word.is_empty() {
+                        return Err(anyhow::anyhow!("empty word"));
+                    }
+                    Ok(json!(word.to_uppercase()))
+                }
+            }
+        });
+        let mut context = Context::new();
+        context.set("words", json!(["a", "", "b", "c", ""]));
+
+        let result = node.execute(&context).await;
+        node.post_process(&mut context, &result).await.unwrap();
+
+        let err = result.unwrap_err();
+        match err.downcast_ref::<crate::Error>() {
+            Some(crate::Error::Batch { total, errors }) => {
+                assert_eq!(*total, 5);
+                assert_eq!(
+                    errors,
+                    &vec![(1, "empty word".to_string()), (4, "empty word".to_string())]
+                );
+            }
+            other => panic!("expected a batch error, got {:?}", other),
+        }
+        assert_eq!(
+            err.to_string(),
+            "2 of 5 batch items failed: item 1: empty word; item 4: empty word"
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+        assert!(context.get("result").is_none());
+        assert_eq!(context.get_str("error").unwrap(), err.to_string());
+    }
+}
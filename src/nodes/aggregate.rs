@@ -0,0 +1,158 @@
+use crate::context::Context;
+use crate::node::{Node, ProcessResult, ProcessState};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{Value, json};
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateOp {
+    Sum,
+    Mean,
+    /// The number of numeric elements.
+    Count,
+    Min,
+    Max,
+}
+
+/// Aggregates the numeric array under `input_key` and writes the number to
+/// `output_key`. Non-numeric elements are skipped with a warning. `Mean`, `Min` and
+/// `Max` of an array without numbers are `null`; `Sum` and `Count` are `0`.
+pub struct AggregateNode<S: ProcessState + Default + Clone> {
+    input_key: String,
+    op: AggregateOp,
+    output_key: String,
+    error_state: S,
+}
+
+impl<S: ProcessState + Default + Clone> AggregateNode<S> {
+    pub fn new(input_key: &str, op: AggregateOp, output_key: &str, error_state: S) -> Self {
+        Self {
+            input_key: input_key.to_string(),
+            op,
+            output_key: output_key.to_string(),
+            error_state,
+        }
+    }
+
+    fn aggregate(&self, numbers: &[f64]) -> Value {
+        match self.op {
+            AggregateOp::Sum => json!(numbers.iter().sum::<f64>()),
+            AggregateOp::Count => json!(numbers.len()),
+            AggregateOp::Mean if numbers.is_empty() => Value::Null,
+            AggregateOp::Mean => json!(numbers.iter().sum::<f64>() / numbers.len() as f64),
+            AggregateOp::Min => numbers
+                .iter()
+                .copied()
+                .reduce(f64::min)
+                .map_or(Value::Null, |n| json!(n)),
+            AggregateOp::Max => numbers
+                .iter()
+                .copied()
+                .reduce(f64::max)
+                .map_or(Value::Null, |n| json!(n)),
+        }
+    }
+}
+
+#[async_trait]
+impl<S: ProcessState + Default + Clone> Node for AggregateNode<S> {
+    type State = S;
+
+    fn name(&self) -> &str {
+        "Aggregate"
+    }
+
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let items = context.get_array(&self.input_key)?;
+
+        let mut numbers = Vec::with_capacity(items.len());
+        for (index, item) in items.iter().enumerate() {
+            match item.as_f64() {
+                Some(number) => numbers.push(number),
+                None => warn!(
+                    "Skipping non-numeric element {} of '{}': {}",
+                    index, self.input_key, item
+                ),
+            }
+        }
+        Ok(self.aggregate(&numbers))
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<S>> {
+        match result {
+            Ok(value) => {
+                context.set(&self.output_key, value.clone());
+                Ok(ProcessResult::new(S::default(), "aggregated".to_string()))
+            }
+            Err(e) => {
+                context.set("error", Value::String(e.to_string()));
+                Ok(ProcessResult::new(self.error_state.clone(), e.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::BaseState;
+
+    async fn run_op(op: AggregateOp, input: Value) -> (BaseState, Option<Value>) {
+        let node = AggregateNode::new("scores", op, "out", BaseState::Failure);
+        let mut context = Context::new();
+        context.set("scores", input);
+        let result = node.execute(&context).await;
+        let outcome = node.post_process(&mut context, &result).await.unwrap();
+        (outcome.state, context.get("out").cloned())
+    }
+
+    #[tokio::test]
+    async fn test_numeric_array() {
+        let scores = json!([3, 1.5, 4, 1.5]);
+
+        assert_eq!(
+            run_op(AggregateOp::Sum, scores.clone()).await.1,
+            Some(json!(10.0))
+        );
+        assert_eq!(
+            run_op(AggregateOp::Mean, scores.clone()).await.1,
+            Some(json!(2.5))
+        );
+        assert_eq!(
+            run_op(AggregateOp::Count, scores.clone()).await.1,
+            Some(json!(4))
+        );
+        assert_eq!(
+            run_op(AggregateOp::Min, scores.clone()).await.1,
+            Some(json!(1.5))
+        );
+        assert_eq!(run_op(AggregateOp::Max, scores).await.1, Some(json!(4.0)));
+    }
+
+    #[tokio::test]
+    async fn test_mixed_array_skips_non_numbers() {
+        let mixed = json!([2, "three", null, 4, {"n": 5}, true]);
+
+        assert_eq!(
+            run_op(AggregateOp::Sum, mixed.clone()).await,
+            (BaseState::Default, Some(json!(6.0)))
+        );
+        assert_eq!(
+            run_op(AggregateOp::Count, mixed.clone()).await.1,
+            Some(json!(2))
+        );
+        assert_eq!(
+            run_op(AggregateOp::Mean, json!(["a", "b"])).await.1,
+            Some(Value::Null)
+        );
+        assert_eq!(
+            run_op(AggregateOp::Sum, json!("not an array")).await,
+            (BaseState::Failure, None)
+        );
+    }
+}
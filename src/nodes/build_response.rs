@@ -0,0 +1,219 @@
+use crate::context::Context;
+use crate::node::{Node, ProcessResult, ProcessState};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{Map, Value};
+
+/// What `BuildResponseNode` does with a field whose source is missing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissingField {
+    #[default]
+    Null,
+    Error,
+}
+
+/// Assembles one object from scattered context values and writes it to
+/// `output_key`. Each field is read from a path: a context key, optionally followed
+/// by `.field` and `[index]` steps into the value, e.g. `usage.total_tokens` or
+/// `retrieved_documents[0].id`. A leading `$.` is allowed.
+pub struct BuildResponseNode<S: ProcessState + Default + Clone> {
+    fields: Vec<(String, String)>,
+    output_key: String,
+    missing: MissingField,
+    error_state: S,
+}
+
+impl<S: ProcessState + Default + Clone> BuildResponseNode<S> {
+    /// `fields` are `(output_field, path)` pairs.
+    pub fn new(fields: &[(&str, &str)], output_key: &str, error_state: S) -> Self {
+        Self {
+            fields: fields
+                .iter()
+                .map(|(field, path)| (field.to_string(), path.to_string()))
+                .collect(),
+            output_key: output_key.to_string(),
+            missing: MissingField::default(),
+            error_state,
+        }
+    }
+
+    pub fn with_missing(mut self, missing: MissingField) -> Self {
+        self.missing = missing;
+        self
+    }
+}
+
+/// One step of a path into a value.
+enum PathStep<'a> {
+    Field(&'a str),
+    Index(usize),
+}
+
+/// Split `path` into the context key and the steps into its value.
+fn parse_path(path: &str) -> Result<(&str, Vec<PathStep<'_>>)> {
+    let trimmed = path.strip_prefix("$.").unwrap_or(path);
+    let mut key = None;
+    let mut steps = Vec::new();
+    for part in trimmed.split('.') {
+        let (name, mut indexes) = part.split_at(part.find('[').unwrap_or(part.len()));
+        if name.is_empty() {
+            return Err(anyhow::anyhow!("Invalid path '{}': empty field", path));
+        }
+        match key {
+            None => key = Some(name),
+            Some(_) => steps.push(PathStep::Field(name)),
+        }
+        while !indexes.is_empty() {
+            let Some((index, rest)) = indexes
+                .strip_prefix('[')
+                .and_then(|rest| rest.split_once(']'))
+                .and_then(|(index, rest)| Some((index.parse().ok()?, rest)))
+            else {
+                return Err(anyhow::anyhow!("Invalid path '{}': bad index", path));
+            };
+            steps.push(PathStep::Index(index));
+            indexes = rest;
+        }
+    }
+    Ok((key.unwrap_or_default(), steps))
+}
+
+fn lookup<'a>(context: &'a Context, path: &str) -> Result<Option<&'a Value>> {
+    let (key, steps) = parse_path(path)?;
+    let mut value = context.get(key);
+    for step in steps {
+        value = match step {
+            PathStep::Field(name) => value.and_then(|v| v.get(name)),
+            PathStep::Index(index) => value.and_then(|v| v.get(index)),
+        };
+    }
+    Ok(value)
+}
+
+#[async_trait]
+impl<S: ProcessState + Default + Clone> Node for BuildResponseNode<S> {
+    type State = S;
+
+    fn name(&self) -> &str {
+        "BuildResponse"
+    }
+
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let mut response = Map::new();
+        for (field, path) in &self.fields {
+            let value = match lookup(context, path)? {
+                Some(value) => value.clone(),
+                None if self.missing == MissingField::Error => {
+                    return Err(anyhow::anyhow!(
+                        "Missing '{}' for response field '{}'",
+                        path,
+                        field
+                    ));
+                }
+                None => Value::Null,
+            };
+            response.insert(field.clone(), value);
+        }
+        Ok(Value::Object(response))
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<S>> {
+        match result {
+            Ok(value) => {
+                context.set(&self.output_key, value.clone());
+                Ok(ProcessResult::new(
+                    S::default(),
+                    "response_built".to_string(),
+                ))
+            }
+            Err(e) => {
+                context.set("error", Value::String(e.to_string()));
+                Ok(ProcessResult::new(self.error_state.clone(), e.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::BaseState;
+    use serde_json::json;
+
+    fn rag_context() -> Context {
+        let mut context = Context::new();
+        context.set("answer", json!("Pangu stores every chunk three times."));
+        context.set(
+            "retrieved_documents",
+            json!([
+                {"id": "doc-1", "url": "pangu.pdf"},
+                {"id": "doc-2", "url": "fuxi.pdf"},
+            ]),
+        );
+        context.set(
+            "usage",
+            json!({"prompt_tokens": 120, "completion_tokens": 30, "total_tokens": 150}),
+        );
+        context
+    }
+
+    #[tokio::test]
+    async fn test_builds_response_from_context_keys() {
+        let node = BuildResponseNode::new(
+            &[
+                ("answer", "answer"),
+                ("sources", "$.retrieved_documents"),
+                ("tokens", "usage.total_tokens"),
+                ("top_source", "retrieved_documents[0].url"),
+            ],
+            "response",
+            BaseState::Failure,
+        );
+        let mut context = rag_context();
+
+        let result = node.execute(&context).await;
+        let outcome = node.post_process(&mut context, &result).await.unwrap();
+        assert_eq!(outcome.state, BaseState::Default);
+        assert_eq!(
+            context.get("response").unwrap(),
+            &json!({
+                "answer": "Pangu stores every chunk three times.",
+                "sources": [
+                    {"id": "doc-1", "url": "pangu.pdf"},
+                    {"id": "doc-2", "url": "fuxi.pdf"},
+                ],
+                "tokens": 150,
+                "top_source": "pangu.pdf",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_field_is_null_or_error() {
+        let fields = [("answer", "answer"), ("trace", "trace.steps[2]")];
+        let node = BuildResponseNode::new(&fields, "response", BaseState::Failure);
+        let context = rag_context();
+        assert_eq!(
+            node.execute(&context).await.unwrap(),
+            json!({"answer": "Pangu stores every chunk three times.", "trace": null})
+        );
+
+        let node = node.with_missing(MissingField::Error);
+        let mut context = rag_context();
+        let result = node.execute(&context).await;
+        let outcome = node.post_process(&mut context, &result).await.unwrap();
+        assert_eq!(outcome.state, BaseState::Failure);
+        assert_eq!(
+            context.get("error").unwrap(),
+            &json!("Missing 'trace.steps[2]' for response field 'trace'")
+        );
+        assert!(context.get("response").is_none());
+
+        let node = BuildResponseNode::new(&[("bad", "usage[x]")], "response", BaseState::Failure);
+        assert!(node.execute(&rag_context()).await.is_err());
+    }
+}
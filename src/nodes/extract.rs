@@ -0,0 +1,206 @@
+#![cfg(feature = "schema")]
+
+use crate::context::Context;
+use crate::node::{Node, ProcessResult, ProcessState};
+use crate::nodes::schema_validate::SchemaValidateNode;
+use crate::utils::llm_wrapper::{LLMOptions, LLMWrapper, parse_json_reply};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::warn;
+
+const DEFAULT_MAX_REPAIRS: usize = 2;
+
+/// Extracts entities matching a JSON Schema from the text under `input_key`
+/// (`retrieved_documents` by default) and writes the object under `output_key`.
+/// Output that does not match the schema goes through the same repair loop as
+/// [`SchemaValidateNode`]; output that stays invalid routes to `error_state`.
+pub struct ExtractNode<S: ProcessState + Default + Clone> {
+    client: Arc<dyn LLMWrapper>,
+    schema: Value,
+    input_key: String,
+    output_key: String,
+    validator: SchemaValidateNode<S>,
+    error_state: S,
+}
+
+impl<S: ProcessState + Default + Clone> ExtractNode<S> {
+    pub fn new(
+        client: Arc<dyn LLMWrapper>,
+        schema: Value,
+        output_key: &str,
+        error_state: S,
+    ) -> Result<Self> {
+        let validator = SchemaValidateNode::new(output_key, schema.clone(), error_state.clone())?
+            .with_repair(client.clone(), DEFAULT_MAX_REPAIRS);
+        Ok(Self {
+            client,
+            schema,
+            input_key: "retrieved_documents".to_string(),
+            output_key: output_key.to_string(),
+            validator,
+            error_state,
+        })
+    }
+
+    pub fn with_input_key(mut self, input_key: &str) -> Self {
+        self.input_key = input_key.to_string();
+        self
+    }
+
+    pub fn with_max_repairs(mut self, max_repairs: usize) -> Self {
+        self.validator = self.validator.with_repair(self.client.clone(), max_repairs);
+        self
+    }
+
+    /// The text to extract from: a string, or an array of strings or documents
+    /// whose `text` (directly or under `metadata`) or `content` is joined.
+    fn assemble_text(value: &Value) -> String {
+        match value {
+            Value::String(text) => text.clone(),
+            Value::Array(items) => items
+                .iter()
+                .filter_map(|item| {
+                    item.as_str()
+                        .or_else(|| item["text"].as_str())
+                        .or_else(|| item["metadata"]["text"].as_str())
+                        .or_else(|| item["content"].as_str())
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+            other => other.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl<S: ProcessState + Default + Clone> Node for ExtractNode<S> {
+    type State = S;
+
+    fn name(&self) -> &str {
+        "Extract"
+    }
+
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let input = context
+            .get(&self.input_key)
+            .ok_or_else(|| anyhow::anyhow!("No value found under '{}'", self.input_key))?;
+        let text = Self::assemble_text(input);
+
+        let prompt = format!(
+            "Extract the information described by the JSON Schema below from the text.\n\n\
+             Schema:\n{}\n\nText:\n{}\n\n\
+             Respond with ONLY a JSON value matching the schema.",
+            self.schema, text
+        );
+        let options = LLMOptions::for_extraction();
+        let response = self.client.generate_with_options(&prompt, options).await?;
+        // Non-JSON output is handed to the repair loop as a string
+        let value = parse_json_reply::<Value>(&response.content).unwrap_or_else(|e| {
+            warn!("Extraction output is not valid JSON: {}", e);
+            Value::String(response.content.clone())
+        });
+
+        self.validator.validate_and_repair(value).await
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<S>> {
+        match result {
+            Ok(value) => {
+                context.set(&self.output_key, value.clone());
+                Ok(ProcessResult::new(S::default(), "extracted".to_string()))
+            }
+            Err(e) => {
+                context.set("error", Value::String(e.to_string()));
+                Ok(ProcessResult::new(self.error_state.clone(), e.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::BaseState;
+    use crate::test_utils::ScriptedLLM;
+    use serde_json::json;
+
+    fn invoice_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "vendor": {"type": "string"},
+                "date": {"type": "string", "pattern": "^\\d{4}-\\d{2}-\\d{2}$"},
+                "amount": {"type": "number"}
+            },
+            "required": ["vendor", "date", "amount"]
+        })
+    }
+
+    fn context_with_documents() -> Context {
+        let mut context = Context::new();
+        context.set(
+            "retrieved_documents",
+            json!([
+                {"id": "a", "metadata": {"text": "Invoice from Acme Corp, issued 2024-03-15."}},
+                {"id": "b", "metadata": {"text": "Total due: $1,250.00"}}
+            ]),
+        );
+        context
+    }
+
+    #[tokio::test]
+    async fn test_extracts_object_matching_schema() {
+        let llm = Arc::new(ScriptedLLM::new(&[
+            "```json\n{\"vendor\": \"Acme Corp\", \"date\": \"2024-03-15\", \"amount\": 1250.0}\n```",
+        ]));
+        let node =
+            ExtractNode::new(llm.clone(), invoice_schema(), "invoice", BaseState::Failure).unwrap();
+        let mut context = context_with_documents();
+        let result = node.execute(&context).await;
+        let outcome = node.post_process(&mut context, &result).await.unwrap();
+
+        assert_eq!(outcome.state, BaseState::Default);
+        assert_eq!(
+            context.get("invoice").unwrap(),
+            &json!({"vendor": "Acme Corp", "date": "2024-03-15", "amount": 1250.0})
+        );
+        let prompts = llm.prompts();
+        assert!(prompts[0].contains("Invoice from Acme Corp"));
+        assert!(prompts[0].contains("Total due"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_extraction_is_repaired() {
+        let llm = Arc::new(ScriptedLLM::new(&[
+            r#"{"vendor": "Acme Corp", "date": "March 15, 2024", "amount": "1,250"}"#,
+            r#"{"vendor": "Acme Corp", "date": "2024-03-15", "amount": 1250}"#,
+        ]));
+        let node = ExtractNode::new(llm, invoice_schema(), "invoice", BaseState::Failure).unwrap();
+        let mut context = context_with_documents();
+        let result = node.execute(&context).await;
+        let outcome = node.post_process(&mut context, &result).await.unwrap();
+
+        assert_eq!(outcome.state, BaseState::Default);
+        assert_eq!(context.get("invoice").unwrap()["date"], json!("2024-03-15"));
+    }
+
+    #[tokio::test]
+    async fn test_unrepairable_extraction_routes_to_error_state() {
+        let llm = Arc::new(ScriptedLLM::new(&["no idea", "still no idea"]));
+        let node = ExtractNode::new(llm, invoice_schema(), "invoice", BaseState::Failure)
+            .unwrap()
+            .with_max_repairs(1);
+        let mut context = context_with_documents();
+        let result = node.execute(&context).await;
+        let outcome = node.post_process(&mut context, &result).await.unwrap();
+
+        assert_eq!(outcome.state, BaseState::Failure);
+        assert!(context.get("invoice").is_none());
+    }
+}
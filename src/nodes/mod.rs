@@ -0,0 +1,19 @@
+pub mod aggregate;
+pub mod build_response;
+pub mod extract;
+pub mod schema_validate;
+pub mod stage;
+pub mod state_adapter;
+pub mod sub_flow;
+pub mod threshold_router;
+
+pub use aggregate::{AggregateNode, AggregateOp};
+pub use build_response::{BuildResponseNode, MissingField};
+#[cfg(feature = "schema")]
+pub use extract::ExtractNode;
+#[cfg(feature = "schema")]
+pub use schema_validate::SchemaValidateNode;
+pub use stage::{StageBoundaryNode, StageLoaderNode};
+pub use state_adapter::StateAdapter;
+pub use sub_flow::SubFlowNode;
+pub use threshold_router::ThresholdRouterNode;
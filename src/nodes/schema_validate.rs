@@ -0,0 +1,213 @@
+#![cfg(feature = "schema")]
+
+use crate::context::Context;
+use crate::node::{Node, ProcessResult, ProcessState};
+use crate::utils::llm_wrapper::{LLMOptions, LLMWrapper, parse_json_reply};
+use anyhow::Result;
+use async_trait::async_trait;
+use jsonschema::Validator;
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// Validates the value under a context key against a JSON Schema. With a repair
+/// client set, an invalid value is sent back to the LLM along with the validation
+/// errors, up to `max_repairs` times. A valid (possibly repaired) value is written
+/// back under the key; a value that stays invalid routes to `error_state`.
+pub struct SchemaValidateNode<S: ProcessState + Default + Clone> {
+    key: String,
+    schema: Value,
+    validator: Validator,
+    repair_client: Option<Arc<dyn LLMWrapper>>,
+    max_repairs: usize,
+    error_state: S,
+}
+
+impl<S: ProcessState + Default + Clone> SchemaValidateNode<S> {
+    pub fn new(key: &str, schema: Value, error_state: S) -> Result<Self> {
+        let validator = jsonschema::validator_for(&schema)
+            .map_err(|e| anyhow::anyhow!("Invalid JSON schema: {}", e))?;
+        Ok(Self {
+            key: key.to_string(),
+            schema,
+            validator,
+            repair_client: None,
+            max_repairs: 0,
+            error_state,
+        })
+    }
+
+    pub fn with_repair(mut self, client: Arc<dyn LLMWrapper>, max_repairs: usize) -> Self {
+        self.repair_client = Some(client);
+        self.max_repairs = max_repairs;
+        self
+    }
+
+    fn validation_errors(&self, value: &Value) -> Vec<String> {
+        self.validator
+            .iter_errors(value)
+            .map(|e| format!("{}: {}", e.instance_path(), e))
+            .collect()
+    }
+
+    async fn repair(
+        &self,
+        client: &dyn LLMWrapper,
+        value: &Value,
+        errors: &[String],
+    ) -> Result<Value> {
+        let prompt = format!(
+            "The following JSON value does not match its JSON Schema.\n\n\
+             Schema:\n{}\n\nValue:\n{}\n\nValidation errors:\n{}\n\n\
+             Respond with ONLY the corrected JSON value.",
+            self.schema,
+            value,
+            errors.join("\n")
+        );
+        let options = LLMOptions::deterministic();
+        let response = client.generate_with_options(&prompt, options).await?;
+        Ok(parse_json_reply(&response.content)?)
+    }
+
+    /// Validate `value`, repairing it with the repair client if one is set, and
+    /// return the valid value or an error listing the remaining violations.
+    pub(crate) async fn validate_and_repair(&self, mut value: Value) -> Result<Value> {
+        let mut errors = self.validation_errors(&value);
+        let mut repairs = 0;
+        while !errors.is_empty() {
+            let Some(client) = self
+                .repair_client
+                .as_deref()
+                .filter(|_| repairs < self.max_repairs)
+            else {
+                return Err(anyhow::anyhow!(
+                    "Value under '{}' does not match schema: {}",
+                    self.key,
+                    errors.join("; ")
+                ));
+            };
+
+            repairs += 1;
+            info!(
+                "Repairing '{}' (attempt {}): {:?}",
+                self.key, repairs, errors
+            );
+            match self.repair(client, &value, &errors).await {
+                Ok(repaired) => {
+                    value = repaired;
+                    errors = self.validation_errors(&value);
+                }
+                Err(e) => warn!("Repair attempt {} failed: {}", repairs, e),
+            }
+        }
+
+        Ok(value)
+    }
+}
+
+#[async_trait]
+impl<S: ProcessState + Default + Clone> Node for SchemaValidateNode<S> {
+    type State = S;
+
+    fn name(&self) -> &str {
+        "SchemaValidate"
+    }
+
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let value = context
+            .get(&self.key)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("No value found under '{}'", self.key))?;
+        self.validate_and_repair(value).await
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<S>> {
+        match result {
+            Ok(value) => {
+                context.set(&self.key, value.clone());
+                Ok(ProcessResult::new(S::default(), "schema_valid".to_string()))
+            }
+            Err(e) => {
+                context.set("error", Value::String(e.to_string()));
+                Ok(ProcessResult::new(self.error_state.clone(), e.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::BaseState;
+    use crate::test_utils::ScriptedLLM;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string"},
+                "age": {"type": "integer", "minimum": 0}
+            },
+            "required": ["name", "age"]
+        })
+    }
+
+    async fn run(
+        node: &SchemaValidateNode<BaseState>,
+        value: Value,
+    ) -> (ProcessResult<BaseState>, Context) {
+        let mut context = Context::new();
+        context.set("person", value);
+        let result = node.execute(&context).await;
+        let process_result = node.post_process(&mut context, &result).await.unwrap();
+        (process_result, context)
+    }
+
+    #[tokio::test]
+    async fn test_valid_value_passes() {
+        let node = SchemaValidateNode::new("person", schema(), BaseState::Failure).unwrap();
+        let (result, context) = run(&node, json!({"name": "Ada", "age": 36})).await;
+
+        assert_eq!(result.state, BaseState::Default);
+        assert_eq!(
+            context.get("person").unwrap(),
+            &json!({"name": "Ada", "age": 36})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_value_repaired_on_retry() {
+        let llm = ScriptedLLM::new(&[
+            r#"{"name": "Ada", "age": "36"}"#,
+            "```json\n{\"name\": \"Ada\", \"age\": 36}\n```",
+        ]);
+        let node = SchemaValidateNode::new("person", schema(), BaseState::Failure)
+            .unwrap()
+            .with_repair(Arc::new(llm), 3);
+        let (result, context) = run(&node, json!({"name": "Ada"})).await;
+
+        assert_eq!(result.state, BaseState::Default);
+        assert_eq!(
+            context.get("person").unwrap(),
+            &json!({"name": "Ada", "age": 36})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unrepairable_value_routes_to_error_state() {
+        let llm = ScriptedLLM::new(&["not json", r#"{"age": -1}"#]);
+        let node = SchemaValidateNode::new("person", schema(), BaseState::Failure)
+            .unwrap()
+            .with_repair(Arc::new(llm), 2);
+        let (result, context) = run(&node, json!({"name": 7})).await;
+
+        assert_eq!(result.state, BaseState::Failure);
+        assert!(result.message.contains("does not match schema"));
+        assert_eq!(context.get("person").unwrap(), &json!({"name": 7}));
+    }
+}
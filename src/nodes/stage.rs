@@ -0,0 +1,218 @@
+use crate::context::Context;
+use crate::node::{Node, ProcessResult, ProcessState};
+use anyhow::{Context as _, Result};
+use async_trait::async_trait;
+use serde_json::{Value, json};
+use std::path::PathBuf;
+use tokio::fs;
+use tracing::info;
+
+/// Persists the value under a context key to a JSON file, so a long pipeline can be
+/// split into stages that hand off through disk. The file is written to a temporary
+/// path and renamed into place, so a crashed stage never leaves a partial file.
+pub struct StageBoundaryNode<S: ProcessState + Default + Clone> {
+    key: String,
+    path: PathBuf,
+    evict: bool,
+    error_state: S,
+}
+
+impl<S: ProcessState + Default + Clone> StageBoundaryNode<S> {
+    pub fn new(key: &str, path: impl Into<PathBuf>, error_state: S) -> Self {
+        Self {
+            key: key.to_string(),
+            path: path.into(),
+            evict: false,
+            error_state,
+        }
+    }
+
+    /// Remove the key from the context once it is persisted, freeing its memory
+    /// for the stages that follow.
+    pub fn with_evict(mut self) -> Self {
+        self.evict = true;
+        self
+    }
+}
+
+#[async_trait]
+impl<S: ProcessState + Default + Clone> Node for StageBoundaryNode<S> {
+    type State = S;
+
+    fn name(&self) -> &str {
+        "StageBoundary"
+    }
+
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let value = context
+            .get(&self.key)
+            .ok_or_else(|| anyhow::anyhow!("No value found under '{}'", self.key))?;
+
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).await?;
+        }
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_vec(value)?)
+            .await
+            .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, &self.path).await?;
+
+        info!("Persisted '{}' to {}", self.key, self.path.display());
+        Ok(json!({"key": self.key, "path": self.path.display().to_string()}))
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<S>> {
+        match result {
+            Ok(_) => {
+                if self.evict {
+                    context.remove(&self.key);
+                }
+                Ok(ProcessResult::new(S::default(), "stage_saved".to_string()))
+            }
+            Err(e) => {
+                context.set("error", Value::String(e.to_string()));
+                Ok(ProcessResult::new(self.error_state.clone(), e.to_string()))
+            }
+        }
+    }
+}
+
+/// Loads a file written by [`StageBoundaryNode`] back under its context key, to
+/// resume a pipeline at that stage.
+pub struct StageLoaderNode<S: ProcessState + Default + Clone> {
+    key: String,
+    path: PathBuf,
+    error_state: S,
+}
+
+impl<S: ProcessState + Default + Clone> StageLoaderNode<S> {
+    pub fn new(key: &str, path: impl Into<PathBuf>, error_state: S) -> Self {
+        Self {
+            key: key.to_string(),
+            path: path.into(),
+            error_state,
+        }
+    }
+}
+
+#[async_trait]
+impl<S: ProcessState + Default + Clone> Node for StageLoaderNode<S> {
+    type State = S;
+
+    fn name(&self) -> &str {
+        "StageLoader"
+    }
+
+    async fn execute(&self, _context: &Context) -> Result<Value> {
+        let bytes = fs::read(&self.path)
+            .await
+            .with_context(|| format!("Failed to open stage file {}", self.path.display()))?;
+        let value = serde_json::from_slice(&bytes)
+            .with_context(|| format!("Invalid stage file {}", self.path.display()))?;
+        info!("Loaded '{}' from {}", self.key, self.path.display());
+        Ok(value)
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<S>> {
+        match result {
+            Ok(value) => {
+                context.set(&self.key, value.clone());
+                Ok(ProcessResult::new(S::default(), "stage_loaded".to_string()))
+            }
+            Err(e) => {
+                context.set("error", Value::String(e.to_string()));
+                Ok(ProcessResult::new(self.error_state.clone(), e.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow::Flow;
+    use crate::node::BaseState;
+    use std::sync::{Arc, Mutex};
+
+    /// Records the value under `key` when it runs.
+    struct CaptureNode {
+        key: String,
+        captured: Arc<Mutex<Option<Value>>>,
+    }
+
+    #[async_trait]
+    impl Node for CaptureNode {
+        type State = BaseState;
+
+        async fn execute(&self, context: &Context) -> Result<Value> {
+            *self.captured.lock().unwrap() = context.get(&self.key).cloned();
+            Ok(Value::Null)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chunks_round_trip_across_flows() {
+        let path = std::env::temp_dir()
+            .join(format!("stage-{}", std::process::id()))
+            .join("chunks.json");
+        let chunks = json!([
+            {"id": "a-0", "text": "First chunk", "chunk_index": 0},
+            {"id": "a-1", "text": "Second chunk", "chunk_index": 1}
+        ]);
+
+        let boundary =
+            StageBoundaryNode::new("documents_chunked", &path, BaseState::Failure).with_evict();
+        let mut context = Context::new();
+        context.set("documents_chunked", chunks.clone());
+        let (result, context) = Flow::new("save", Arc::new(boundary))
+            .run_lenient(context)
+            .await;
+        result.unwrap();
+        assert!(context.get("documents_chunked").is_none());
+
+        let captured = Arc::new(Mutex::new(None));
+        let mut resumed = Flow::new(
+            "load",
+            Arc::new(StageLoaderNode::new(
+                "documents_chunked",
+                &path,
+                BaseState::Failure,
+            )),
+        );
+        resumed.add_node(
+            "embed",
+            Arc::new(CaptureNode {
+                key: "documents_chunked".to_string(),
+                captured: captured.clone(),
+            }),
+        );
+        resumed.add_edge("load", "embed", BaseState::Default);
+        resumed.run(Context::new()).await.unwrap();
+
+        assert_eq!(captured.lock().unwrap().as_ref(), Some(&chunks));
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_missing_stage_file_routes_to_error_state() {
+        let node = StageLoaderNode::new(
+            "documents_chunked",
+            "/nonexistent/stage.json",
+            BaseState::Failure,
+        );
+        let mut context = Context::new();
+        let result = node.execute(&context).await;
+        let outcome = node.post_process(&mut context, &result).await.unwrap();
+
+        assert_eq!(outcome.state, BaseState::Failure);
+        assert!(context.get("error").is_some());
+    }
+}
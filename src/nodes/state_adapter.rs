@@ -0,0 +1,61 @@
+use crate::context::Context;
+use crate::node::{Node, ProcessResult, ProcessState, RetryPolicy};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Runs a node whose `State` is `S` in a flow of `T` states, mapping the state its
+/// `post_process` returns with `map`. Everything else, including retries, is
+/// delegated to the wrapped node.
+///
+/// Edges out of the adapter match the condition of the mapped state, never the
+/// inner one, so `map` decides the routing. Map every inner state the node can
+/// return, e.g. its failure state to the flow's error state: a state mapped to one
+/// without a matching edge falls back to the `default` edge like any other.
+pub struct StateAdapter<S: ProcessState + Default, T: ProcessState + Default> {
+    inner: Arc<dyn Node<State = S>>,
+    map: Box<dyn Fn(S) -> T + Send + Sync>,
+}
+
+impl<S: ProcessState + Default, T: ProcessState + Default> StateAdapter<S, T> {
+    pub fn new(
+        inner: Arc<dyn Node<State = S>>,
+        map: impl Fn(S) -> T + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            inner,
+            map: Box::new(map),
+        }
+    }
+}
+
+#[async_trait]
+impl<S: ProcessState + Default, T: ProcessState + Default> Node for StateAdapter<S, T> {
+    type State = T;
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        self.inner.retry_policy()
+    }
+
+    async fn prepare(&self, context: &mut Context) -> Result<()> {
+        self.inner.prepare(context).await
+    }
+
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        self.inner.execute(context).await
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<T>> {
+        let ProcessResult { state, message } = self.inner.post_process(context, result).await?;
+        Ok(ProcessResult::new((self.map)(state), message))
+    }
+}
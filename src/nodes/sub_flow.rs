@@ -0,0 +1,297 @@
+use crate::context::Context;
+use crate::flow::Flow;
+use crate::node::{Node, ProcessResult, ProcessState};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{Value, json};
+use tracing::info;
+
+/// Runs a whole flow as one node of another, e.g. a RAG pipeline as a single step
+/// of an agent flow. The inner flow's state type `S` can differ from the outer
+/// one, `T`.
+///
+/// The inner flow runs on a child context, never the parent itself: a copy of the
+/// whole parent context, or only its metadata and the keys given to
+/// [`SubFlowNode::with_inputs`]. Once it finishes, the data and metadata keys it
+/// added or changed are merged back into the parent, or only the data keys given to
+/// [`SubFlowNode::with_outputs`]; keys it removed stay in the parent. An inner
+/// `result` is merged like any other key. If the inner flow fails, nothing is
+/// merged back and the node returns `error_state` with the error under `error`.
+///
+/// The outer flow routes on the state `map` makes of the condition of the last
+/// inner node; by default every run that finishes routes on `T::default()`.
+pub struct SubFlowNode<S: ProcessState + Default, T: ProcessState + Default + Clone = S> {
+    flow: Flow<S>,
+    inputs: Option<Vec<String>>,
+    outputs: Option<Vec<String>>,
+    map: Box<dyn Fn(&str) -> T + Send + Sync>,
+    error_state: T,
+}
+
+impl<S: ProcessState + Default, T: ProcessState + Default + Clone> SubFlowNode<S, T> {
+    pub fn new(flow: Flow<S>, error_state: T) -> Self {
+        Self {
+            flow,
+            inputs: None,
+            outputs: None,
+            map: Box::new(|_| T::default()),
+            error_state,
+        }
+    }
+
+    /// Pass only these data keys to the inner flow.
+    pub fn with_inputs(mut self, keys: &[&str]) -> Self {
+        self.inputs = Some(keys.iter().map(|key| key.to_string()).collect());
+        self
+    }
+
+    /// Merge only these data keys back from the inner flow.
+    pub fn with_outputs(mut self, keys: &[&str]) -> Self {
+        self.outputs = Some(keys.iter().map(|key| key.to_string()).collect());
+        self
+    }
+
+    /// Map the condition the inner flow finished on to the state the outer flow
+    /// routes on.
+    pub fn with_state_map(mut self, map: impl Fn(&str) -> T + Send + Sync + 'static) -> Self {
+        self.map = Box::new(map);
+        self
+    }
+
+    fn child_context(&self, context: &Context) -> Context {
+        let Some(keys) = &self.inputs else {
+            return context.clone();
+        };
+        let mut child = Context::new();
+        for key in keys {
+            if let Some(value) = context.get(key) {
+                child.set(key, value.clone());
+            }
+        }
+        for (key, value) in context.get_all_metadata() {
+            child.set_metadata(key, value.clone());
+        }
+        child
+    }
+
+    fn output_changes(&self, changes: Context) -> Context {
+        let Some(keys) = &self.outputs else {
+            return changes;
+        };
+        let mut outputs = Context::new();
+        for key in keys {
+            if let Some(value) = changes.get(key) {
+                outputs.set(key, value.clone());
+            }
+        }
+        for (key, value) in changes.get_all_metadata() {
+            outputs.set_metadata(key, value.clone());
+        }
+        outputs
+    }
+}
+
+#[async_trait]
+impl<S: ProcessState + Default, T: ProcessState + Default + Clone> Node for SubFlowNode<S, T> {
+    type State = T;
+
+    fn name(&self) -> &str {
+        "SubFlow"
+    }
+
+    /// Returns the changes to merge back and the inner flow's final condition, so
+    /// recorded runs replay without running the inner flow.
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let mut child = self.child_context(context);
+        let base = child.clone();
+        let (_, trace) = self.flow.run_traced_in(&mut child).await?;
+        let condition = trace
+            .last()
+            .map(|step| step.condition.clone())
+            .unwrap_or_default();
+        info!(
+            "Sub-flow finished after {} nodes on '{}'",
+            trace.len(),
+            condition
+        );
+
+        let changes = self.output_changes(child.changes_since(&base));
+        Ok(json!({
+            "condition": condition,
+            "changes": serde_json::to_value(changes)?,
+        }))
+    }
+
+    async fn post_process(
+        &self,
+        context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<T>> {
+        match result {
+            Ok(value) => {
+                let changes: Context = serde_json::from_value(value["changes"].clone())?;
+                context.merge(&changes);
+                let condition = value["condition"].as_str().unwrap_or_default();
+                Ok(ProcessResult::new(
+                    (self.map)(condition),
+                    format!("sub_flow_finished: {}", condition),
+                ))
+            }
+            Err(e) => {
+                context.set("error", Value::String(e.to_string()));
+                Ok(ProcessResult::new(self.error_state.clone(), e.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_flow;
+    use crate::node::BaseState;
+    use crate::nodes::ThresholdRouterNode;
+    use std::sync::Arc;
+
+    #[derive(Debug, Clone, PartialEq, Default)]
+    enum SizeState {
+        Small,
+        Large,
+        #[default]
+        Default,
+    }
+
+    impl ProcessState for SizeState {
+        fn is_default(&self) -> bool {
+            matches!(self, SizeState::Default)
+        }
+
+        fn to_condition(&self) -> String {
+            match self {
+                SizeState::Small => "small".to_string(),
+                SizeState::Large => "large".to_string(),
+                SizeState::Default => "default".to_string(),
+            }
+        }
+    }
+
+    /// Stores `number` times ten under `scaled`, and under `saw_parent` whether the
+    /// parent's `unrelated` key was visible. Aborts the flow without a number.
+    struct ScaleNode;
+
+    #[async_trait]
+    impl Node for ScaleNode {
+        type State = SizeState;
+
+        async fn execute(&self, context: &Context) -> Result<Value> {
+            let number = context
+                .get("number")
+                .and_then(Value::as_f64)
+                .ok_or_else(|| anyhow::anyhow!("No number to scale"))?;
+            Ok(json!(number * 10.0))
+        }
+
+        async fn post_process(
+            &self,
+            context: &mut Context,
+            result: &Result<Value>,
+        ) -> Result<ProcessResult<SizeState>> {
+            let scaled = result.as_ref().map_err(|e| anyhow::anyhow!("{}", e))?;
+            context.set("scaled", scaled.clone());
+            context.set("saw_parent", json!(context.contains_key("unrelated")));
+            Ok(ProcessResult::default())
+        }
+    }
+
+    fn inner_flow() -> Flow<SizeState> {
+        build_flow!(
+            start: ("scale", ScaleNode),
+            nodes: [(
+                "classify",
+                ThresholdRouterNode::new(
+                    "scaled",
+                    vec![(100.0, SizeState::Small), (f64::INFINITY, SizeState::Large)],
+                    SizeState::Default,
+                )
+            )],
+            edges: [("scale", "classify", SizeState::Default)]
+        )
+    }
+
+    struct LabelNode(&'static str);
+
+    #[async_trait]
+    impl Node for LabelNode {
+        type State = BaseState;
+
+        async fn execute(&self, _context: &Context) -> Result<Value> {
+            Ok(json!(self.0))
+        }
+    }
+
+    async fn run_outer(sub_flow: SubFlowNode<SizeState, BaseState>, number: Value) -> Context {
+        let mut flow = Flow::new("sub", Arc::new(sub_flow));
+        flow.add_node("big", Arc::new(LabelNode("big")));
+        flow.add_node("small", Arc::new(LabelNode("small")));
+        flow.add_node("failed", Arc::new(LabelNode("failed")));
+        flow.add_edge("sub", "big", BaseState::Success);
+        flow.add_edge("sub", "small", BaseState::Default);
+        flow.add_edge("sub", "failed", BaseState::Failure);
+
+        let mut context = Context::new();
+        context.set("number", number);
+        context.set("unrelated", json!("parent only"));
+        context.set_metadata("trace_id", json!("abc"));
+        flow.run_full(context).await.unwrap().1
+    }
+
+    fn large_to_success(condition: &str) -> BaseState {
+        match condition {
+            "large" => BaseState::Success,
+            _ => BaseState::Default,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inner_condition_routes_outer_flow() {
+        let sub_flow =
+            || SubFlowNode::new(inner_flow(), BaseState::Failure).with_state_map(large_to_success);
+
+        let context = run_outer(sub_flow(), json!(50)).await;
+        assert_eq!(context.get("result"), Some(&json!("big")));
+        assert_eq!(context.get("scaled"), Some(&json!(500.0)));
+        // The whole parent context is passed in and every change merged back
+        assert_eq!(context.get("saw_parent"), Some(&json!(true)));
+        assert_eq!(context.get("unrelated"), Some(&json!("parent only")));
+
+        let context = run_outer(sub_flow(), json!(5)).await;
+        assert_eq!(context.get("result"), Some(&json!("small")));
+    }
+
+    #[tokio::test]
+    async fn test_inputs_and_outputs_scope_the_child_context() {
+        let sub_flow = SubFlowNode::new(inner_flow(), BaseState::Failure)
+            .with_inputs(&["number"])
+            .with_outputs(&["saw_parent"]);
+
+        let context = run_outer(sub_flow, json!(50)).await;
+
+        assert_eq!(context.get("saw_parent"), Some(&json!(false)));
+        assert!(!context.contains_key("scaled"));
+        assert_eq!(context.get("unrelated"), Some(&json!("parent only")));
+        assert_eq!(context.get_metadata("trace_id"), Some(&json!("abc")));
+        // Without a state map, a finished sub-flow routes on the default state
+        assert_eq!(context.get("result"), Some(&json!("small")));
+    }
+
+    #[tokio::test]
+    async fn test_failed_sub_flow_routes_to_error_state() {
+        let sub_flow = SubFlowNode::new(inner_flow(), BaseState::Failure);
+
+        let context = run_outer(sub_flow, Value::Null).await;
+
+        assert_eq!(context.get("result"), Some(&json!("failed")));
+        assert_eq!(context.get("error"), Some(&json!("No number to scale")));
+        assert!(!context.contains_key("scaled"));
+    }
+}
@@ -0,0 +1,184 @@
+use crate::context::Context;
+use crate::node::{Node, ProcessResult, ProcessState};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::Value;
+use tracing::warn;
+
+/// Routes on the number under `input_key`: the first bucket whose upper bound is
+/// greater than the number wins. Numbers at or above the last bound, and missing or
+/// non-numeric values, route to `default_state`.
+pub struct ThresholdRouterNode<S: ProcessState + Default + Clone> {
+    input_key: String,
+    buckets: Vec<(f64, S)>,
+    default_state: S,
+}
+
+impl<S: ProcessState + Default + Clone> ThresholdRouterNode<S> {
+    /// `buckets` are `(upper_bound, state)` pairs; they are sorted by bound, so the
+    /// order they are given in does not matter. Use `f64::INFINITY` for an open
+    /// upper bucket.
+    pub fn new(input_key: &str, mut buckets: Vec<(f64, S)>, default_state: S) -> Self {
+        buckets.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self {
+            input_key: input_key.to_string(),
+            buckets,
+            default_state,
+        }
+    }
+
+    fn route(&self, number: f64) -> Option<&S> {
+        self.buckets
+            .iter()
+            .find(|(upper_bound, _)| number < *upper_bound)
+            .map(|(_, state)| state)
+    }
+}
+
+#[async_trait]
+impl<S: ProcessState + Default + Clone> Node for ThresholdRouterNode<S> {
+    type State = S;
+
+    fn name(&self) -> &str {
+        "ThresholdRouter"
+    }
+
+    async fn execute(&self, context: &Context) -> Result<Value> {
+        let value = context.get(&self.input_key).cloned().unwrap_or(Value::Null);
+        if !value.is_number() {
+            warn!(
+                "'{}' is not a number, routing to the default state: {}",
+                self.input_key, value
+            );
+            return Ok(Value::Null);
+        }
+        Ok(value)
+    }
+
+    async fn post_process(
+        &self,
+        _context: &mut Context,
+        result: &Result<Value>,
+    ) -> Result<ProcessResult<S>> {
+        let number = result.as_ref().ok().and_then(|value| value.as_f64());
+        let state = match number.and_then(|n| self.route(n)) {
+            Some(state) => state.clone(),
+            None => {
+                if let Some(n) = number {
+                    warn!(
+                        "{} under '{}' is above every bucket, routing to the default state",
+                        n, self.input_key
+                    );
+                }
+                self.default_state.clone()
+            }
+        };
+        let condition = state.to_condition();
+        Ok(ProcessResult::new(state, condition))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_flow;
+    use serde_json::json;
+
+    #[derive(Debug, Clone, PartialEq, Default)]
+    enum NumberState {
+        Small,
+        Medium,
+        Large,
+        #[default]
+        Default,
+    }
+
+    impl ProcessState for NumberState {
+        fn is_default(&self) -> bool {
+            matches!(self, NumberState::Default)
+        }
+
+        fn to_condition(&self) -> String {
+            match self {
+                NumberState::Small => "small".to_string(),
+                NumberState::Medium => "medium".to_string(),
+                NumberState::Large => "large".to_string(),
+                NumberState::Default => "default".to_string(),
+            }
+        }
+    }
+
+    /// Returns its label, so the flow result names the branch that ran.
+    struct LabelNode(&'static str);
+
+    #[async_trait]
+    impl Node for LabelNode {
+        type State = NumberState;
+
+        async fn execute(&self, _context: &Context) -> Result<Value> {
+            Ok(json!(self.0))
+        }
+    }
+
+    fn router() -> ThresholdRouterNode<NumberState> {
+        ThresholdRouterNode::new(
+            "number",
+            vec![
+                (f64::INFINITY, NumberState::Large),
+                (33.0, NumberState::Small),
+                (66.0, NumberState::Medium),
+            ],
+            NumberState::Default,
+        )
+    }
+
+    async fn run_flow(number: Value) -> Value {
+        let flow = build_flow!(
+            start: ("rand", router()),
+            nodes: [
+                ("small", LabelNode("small")),
+                ("medium", LabelNode("medium")),
+                ("large", LabelNode("large"))
+            ],
+            edges: [
+                ("rand", "small", NumberState::Small),
+                ("rand", "medium", NumberState::Medium),
+                ("rand", "large", NumberState::Large)
+            ]
+        );
+        let mut context = Context::new();
+        context.set("number", number);
+        flow.run(context).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_small_medium_large_routing() {
+        assert_eq!(run_flow(json!(0)).await, json!("small"));
+        assert_eq!(run_flow(json!(32.9)).await, json!("small"));
+        assert_eq!(run_flow(json!(33)).await, json!("medium"));
+        assert_eq!(run_flow(json!(65)).await, json!("medium"));
+        assert_eq!(run_flow(json!(66)).await, json!("large"));
+        assert_eq!(run_flow(json!(1e9)).await, json!("large"));
+    }
+
+    #[tokio::test]
+    async fn test_out_of_range_and_non_numeric_use_default() {
+        let node = ThresholdRouterNode::new(
+            "number",
+            vec![(10.0, NumberState::Small), (20.0, NumberState::Medium)],
+            NumberState::Default,
+        );
+        for input in [json!(20), json!("15"), Value::Null] {
+            let mut context = Context::new();
+            context.set("number", input.clone());
+            let result = node.execute(&context).await;
+            let outcome = node.post_process(&mut context, &result).await.unwrap();
+            assert_eq!(outcome.state, NumberState::Default, "input {}", input);
+        }
+
+        let mut context = Context::new();
+        let result = node.execute(&context).await;
+        let outcome = node.post_process(&mut context, &result).await.unwrap();
+        assert_eq!(outcome.state, NumberState::Default);
+    }
+}
@@ -0,0 +1,47 @@
+use crate::context::Context;
+use crate::node::{ProcessResult, ProcessState};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Hooks a [`crate::Flow`] calls as it runs, e.g. to export node latencies and
+/// success counts as metrics without touching the flow. Nodes are reported under
+/// the name they are registered with, parallel branches included. Every hook does
+/// nothing by default.
+pub trait FlowObserver<S: ProcessState>: Send + Sync {
+    /// Called before the node's `prepare`.
+    #[allow(unused_variables)]
+    fn on_node_start(&self, name: &str) {}
+
+    /// Called after the node's `post_process` with the state the flow routes on,
+    /// so a failure `post_process` handled arrives here as an error state.
+    #[allow(unused_variables)]
+    fn on_node_finish(&self, name: &str, duration: Duration, result: &ProcessResult<S>) {}
+
+    /// Called instead of [`FlowObserver::on_node_finish`] when the node aborts the
+    /// run: its `prepare` or `post_process` failed, or the run was cancelled.
+    #[allow(unused_variables)]
+    fn on_node_error(&self, name: &str, duration: Duration, error: &anyhow::Error) {}
+
+    /// Called once the run ends, successfully or not, with the final context.
+    #[allow(unused_variables)]
+    fn on_flow_end(&self, context: &Context) {}
+}
+
+/// Logs how long each node ran and the state it finished on.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracingObserver;
+
+impl<S: ProcessState> FlowObserver<S> for TracingObserver {
+    fn on_node_finish(&self, name: &str, duration: Duration, result: &ProcessResult<S>) {
+        info!(
+            "Node '{}' finished in {:?} on '{}'",
+            name,
+            duration,
+            result.state.to_condition()
+        );
+    }
+
+    fn on_node_error(&self, name: &str, duration: Duration, error: &anyhow::Error) {
+        warn!("Node '{}' failed after {:?}: {}", name, duration, error);
+    }
+}
@@ -0,0 +1,183 @@
+#![cfg(feature = "otel")]
+
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+
+#[derive(Debug, Clone)]
+pub struct OtelOptions {
+    pub endpoint: String,
+    pub service_name: String,
+}
+
+impl Default for OtelOptions {
+    fn default() -> Self {
+        Self {
+            endpoint: "http://localhost:4318/v1/traces".to_string(),
+            service_name: "pocketflow".to_string(),
+        }
+    }
+}
+
+/// Build a tracer provider that exports spans to an OTLP/HTTP endpoint.
+pub fn otlp_tracer_provider(options: &OtelOptions) -> anyhow::Result<SdkTracerProvider> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(options.endpoint.clone())
+        .build()?;
+
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name(options.service_name.clone())
+                .build(),
+        )
+        .build())
+}
+
+/// A `tracing` layer turning the `flow_run` and `node` spans emitted by `Flow::run`
+/// into OpenTelemetry spans. Use this to add OpenTelemetry to an existing subscriber.
+pub fn otel_layer<S>(provider: &SdkTracerProvider) -> OpenTelemetryLayer<S, SdkTracer>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    tracing_opentelemetry::layer().with_tracer(provider.tracer("pocketflow_rs"))
+}
+
+/// Install a global subscriber exporting flow traces over OTLP. Keep the returned
+/// provider alive and call `shutdown` on it before exiting to flush pending spans.
+pub fn init_otel(options: &OtelOptions) -> anyhow::Result<SdkTracerProvider> {
+    let provider = otlp_tracer_provider(options)?;
+    tracing_subscriber::registry()
+        .with(otel_layer(&provider))
+        .try_init()?;
+    Ok(provider)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BaseState, Context, Node, build_flow};
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use opentelemetry_sdk::trace::InMemorySpanExporter;
+    use serde_json::Value;
+
+    struct EchoNode;
+
+    #[async_trait]
+    impl Node for EchoNode {
+        type State = BaseState;
+
+        #[allow(unused_variables)]
+        async fn execute(&self, context: &Context) -> Result<Value> {
+            Ok(Value::Null)
+        }
+    }
+
+    struct NamedNode;
+
+    #[async_trait]
+    impl Node for NamedNode {
+        type State = BaseState;
+
+        fn name(&self) -> &str {
+            "Named"
+        }
+
+        #[allow(unused_variables)]
+        async fn execute(&self, context: &Context) -> Result<Value> {
+            Ok(Value::Null)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_one_trace_per_flow_run() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry().with(otel_layer(&provider));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let flow = build_flow!(
+            start: ("first", EchoNode),
+            nodes: [("second", EchoNode), ("third", EchoNode)],
+            edges: [
+                ("first", "second", BaseState::Default),
+                ("second", "third", BaseState::Default)
+            ]
+        );
+        flow.run(Context::new()).await.unwrap();
+        provider.force_flush().unwrap();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let root = spans.iter().find(|s| s.name == "flow_run").unwrap();
+        let nodes: Vec<_> = spans.iter().filter(|s| s.name == "node").collect();
+
+        assert_eq!(spans.len(), 4);
+        assert_eq!(nodes.len(), 3);
+        for node in nodes {
+            assert_eq!(node.parent_span_id, root.span_context.span_id());
+            assert_eq!(node.span_context.trace_id(), root.span_context.trace_id());
+            assert!(
+                node.attributes
+                    .iter()
+                    .any(|kv| kv.key.as_str() == "node.name")
+            );
+            assert!(
+                node.attributes
+                    .iter()
+                    .any(|kv| kv.key.as_str() == "node.condition"
+                        && kv.value.as_str() == "default")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_node_type_in_trace() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry().with(otel_layer(&provider));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let flow = build_flow!(
+            start: ("first", NamedNode),
+            nodes: [("second", EchoNode)],
+            edges: [("first", "second", BaseState::Default)]
+        );
+        flow.run(Context::new()).await.unwrap();
+        provider.force_flush().unwrap();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let node_type = |key: &str| {
+            spans
+                .iter()
+                .filter(|s| s.name == "node")
+                .find(|s| {
+                    s.attributes
+                        .iter()
+                        .any(|kv| kv.key.as_str() == "node.name" && kv.value.as_str() == key)
+                })
+                .and_then(|s| {
+                    s.attributes
+                        .iter()
+                        .find(|kv| kv.key.as_str() == "node.type")
+                })
+                .map(|kv| kv.value.as_str().to_string())
+                .unwrap()
+        };
+
+        assert_eq!(node_type("first"), "Named");
+        assert!(node_type("second").ends_with("EchoNode"));
+    }
+}
@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Progress of a long-running node, e.g. 40 of 120 chunks embedded. `completed` is
+/// cumulative, so the last event of a node carries its final count.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgressEvent {
+    /// The reporting node's [`crate::Node::name`].
+    pub node: String,
+    /// What is counted, e.g. `files`, `chunks`, `embeddings` or `records`.
+    pub unit: String,
+    pub completed: usize,
+    /// How many to expect, when known up front.
+    pub total: Option<usize>,
+}
+
+impl fmt::Display for ProgressEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.total {
+            Some(total) => write!(
+                f,
+                "{}: {}/{} {}",
+                self.node, self.completed, total, self.unit
+            ),
+            None => write!(f, "{}: {} {}", self.node, self.completed, self.unit),
+        }
+    }
+}
+
+pub type ProgressCallback = Arc<dyn Fn(&ProgressEvent) + Send + Sync>;
+
+/// Hands [`ProgressEvent`]s from nodes to a callback, e.g. one drawing a progress
+/// bar. The default reporter drops every event.
+#[derive(Clone, Default)]
+pub struct ProgressReporter {
+    callback: Option<ProgressCallback>,
+}
+
+impl ProgressReporter {
+    pub fn new(callback: impl Fn(&ProgressEvent) + Send + Sync + 'static) -> Self {
+        Self {
+            callback: Some(Arc::new(callback)),
+        }
+    }
+
+    /// A reporter streaming its events to the returned receiver.
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<ProgressEvent>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let reporter = Self::new(move |event| {
+            // Nobody listening is fine, progress is informational
+            let _ = sender.send(event.clone());
+        });
+        (reporter, receiver)
+    }
+
+    pub fn report(&self, node: &str, unit: &str, completed: usize, total: Option<usize>) {
+        if let Some(callback) = &self.callback {
+            callback(&ProgressEvent {
+                node: node.to_string(),
+                unit: unit.to_string(),
+                completed,
+                total,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_channel_streams_events() {
+        let (reporter, mut receiver) = ProgressReporter::channel();
+        reporter.report("EmbedDocuments", "embeddings", 10, Some(30));
+        reporter.report("ChunkDocuments", "chunks", 7, None);
+        drop(reporter);
+
+        let first = receiver.recv().await.unwrap();
+        assert_eq!(first.to_string(), "EmbedDocuments: 10/30 embeddings");
+        assert_eq!(
+            receiver.recv().await.unwrap().to_string(),
+            "ChunkDocuments: 7 chunks"
+        );
+        assert_eq!(receiver.recv().await, None);
+
+        // Without a callback, events are dropped
+        ProgressReporter::default().report("EmbedDocuments", "embeddings", 1, None);
+    }
+}
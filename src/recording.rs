@@ -0,0 +1,154 @@
+use crate::context::Context;
+use anyhow::{Context as _, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// The `execute` outputs of a recorded flow run, keyed by node name and a hash of
+/// the context data the node executed with. See [`crate::Flow::set_recording`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Recording {
+    outputs: BTreeMap<String, Value>,
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read recording {}", path.display()))?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write recording {}", path.display()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.outputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outputs.is_empty()
+    }
+
+    /// `node@<sha256 of the context data>`; metadata such as retry counts is left
+    /// out so it doesn't change the key.
+    fn key(node: &str, context: &Context) -> String {
+        let data: BTreeMap<&String, &Value> = context.get_all_data().iter().collect();
+        let json = serde_json::to_string(&data).unwrap_or_default();
+        format!("{}@{:x}", node, Sha256::digest(json.as_bytes()))
+    }
+
+    pub(crate) fn record(&mut self, node: &str, context: &Context, output: &Value) {
+        self.outputs
+            .insert(Self::key(node, context), output.clone());
+    }
+
+    pub(crate) fn replay(&self, node: &str, context: &Context) -> Result<Value> {
+        self.outputs
+            .get(&Self::key(node, context))
+            .cloned()
+            .ok_or_else(|| {
+                anyhow::anyhow!("No recorded output for node '{}' with this context", node)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow::Flow;
+    use crate::node::{BaseState, Node, ProcessResult};
+    use async_trait::async_trait;
+    use serde_json::json;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Appends its tag to the context's `result`, counting its calls.
+    struct AppendNode {
+        tag: &'static str,
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Node for AppendNode {
+        type State = BaseState;
+
+        async fn execute(&self, context: &Context) -> Result<Value> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let so_far = context
+                .get("result")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default();
+            Ok(json!(format!("{}{}", so_far, self.tag)))
+        }
+
+        async fn post_process(
+            &self,
+            context: &mut Context,
+            result: &Result<Value>,
+        ) -> Result<ProcessResult<BaseState>> {
+            let value = result.as_ref().map_err(|e| anyhow::anyhow!("{}", e))?;
+            context.set("result", value.clone());
+            Ok(ProcessResult::new(
+                BaseState::Default,
+                "appended".to_string(),
+            ))
+        }
+    }
+
+    fn two_node_flow() -> (Flow<BaseState>, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let node = |tag| {
+            Arc::new(AppendNode {
+                tag,
+                calls: calls.clone(),
+            })
+        };
+        let mut flow = Flow::new("first", node("a"));
+        flow.add_node("second", node("b"));
+        flow.add_edge("first", "second", BaseState::Default);
+        (flow, calls)
+    }
+
+    fn seeded_context(input: &str) -> Context {
+        let mut context = Context::new();
+        context.set("result", json!(input));
+        context
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let path = std::env::temp_dir().join(format!("recording-{}.json", std::process::id()));
+
+        let (mut flow, calls) = two_node_flow();
+        flow.set_recording(&path);
+        let recorded = flow.run(seeded_context(">")).await.unwrap();
+        assert_eq!(recorded, json!(">ab"));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(Recording::load(&path).unwrap().len(), 2);
+
+        let (mut flow, calls) = two_node_flow();
+        flow.set_replay(&path).unwrap();
+        let replayed = flow.run(seeded_context(">")).await.unwrap();
+        assert_eq!(replayed, recorded);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        // A context that was never recorded fails instead of calling the node
+        let error = flow.run(seeded_context("<")).await.unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("No recorded output for node 'first'")
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,100 @@
+use crate::node::{Node, ProcessState};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A declarative description of a flow's structure, loadable from YAML or JSON and
+/// turned into a runnable flow with [`crate::Flow::from_spec`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlowSpec {
+    pub start: String,
+    pub nodes: Vec<NodeSpec>,
+    #[serde(default)]
+    pub edges: Vec<EdgeSpec>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeSpec {
+    pub name: String,
+    /// The type name the node's factory is registered under in a [`FlowRegistry`].
+    #[serde(rename = "type")]
+    pub node_type: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EdgeSpec {
+    pub from: String,
+    pub to: String,
+    #[serde(default = "default_condition")]
+    pub condition: String,
+    /// Parallel edges sharing `from` and `condition` form one fan-out, their
+    /// targets run concurrently as with [`crate::Flow::add_parallel_edges`].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub parallel: bool,
+}
+
+fn default_condition() -> String {
+    "default".to_string()
+}
+
+impl FlowSpec {
+    pub fn from_yaml(yaml: &str) -> Result<Self> {
+        Ok(serde_yaml::from_str(yaml)?)
+    }
+
+    pub fn to_yaml(&self) -> Result<String> {
+        Ok(serde_yaml::to_string(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+type NodeFactory<S> = Box<dyn Fn(&Value) -> Result<Arc<dyn Node<State = S>>> + Send + Sync>;
+
+/// Maps node type names to factories building a node from its spec `params`.
+pub struct FlowRegistry<S: ProcessState + Default> {
+    factories: HashMap<String, NodeFactory<S>>,
+}
+
+impl<S: ProcessState + Default> Default for FlowRegistry<S> {
+    fn default() -> Self {
+        Self {
+            factories: HashMap::new(),
+        }
+    }
+}
+
+impl<S: ProcessState + Default> FlowRegistry<S> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<F>(&mut self, node_type: &str, factory: F)
+    where
+        F: Fn(&Value) -> Result<Arc<dyn Node<State = S>>> + Send + Sync + 'static,
+    {
+        self.factories
+            .insert(node_type.to_string(), Box::new(factory));
+    }
+
+    pub fn build(&self, spec: &NodeSpec) -> Result<Arc<dyn Node<State = S>>> {
+        let factory = self.factories.get(&spec.node_type).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unknown node type '{}' for node '{}'",
+                spec.node_type,
+                spec.name
+            )
+        })?;
+        factory(&spec.params)
+    }
+}
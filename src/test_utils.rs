@@ -0,0 +1,64 @@
+//! Fixtures shared by the crate's unit tests.
+
+#[cfg(any(feature = "openai", feature = "anthropic"))]
+pub(crate) use scripted_llm::ScriptedLLM;
+
+#[cfg(any(feature = "openai", feature = "anthropic"))]
+mod scripted_llm {
+    use crate::utils::llm_wrapper::{LLMOptions, LLMResponse, LLMWrapper};
+    use async_trait::async_trait;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    /// Replies with the queued replies in order, repeating the last one, and records
+    /// the prompt and options of every request.
+    #[derive(Default)]
+    pub(crate) struct ScriptedLLM {
+        replies: Mutex<VecDeque<String>>,
+        pub(crate) requests: Mutex<Vec<(String, LLMOptions)>>,
+    }
+
+    impl ScriptedLLM {
+        pub(crate) fn new(replies: &[&str]) -> Self {
+            Self {
+                replies: Mutex::new(replies.iter().map(|r| r.to_string()).collect()),
+                ..Default::default()
+            }
+        }
+
+        pub(crate) fn prompts(&self) -> Vec<String> {
+            let requests = self.requests.lock().unwrap();
+            requests.iter().map(|(prompt, _)| prompt.clone()).collect()
+        }
+    }
+
+    #[async_trait]
+    impl LLMWrapper for ScriptedLLM {
+        async fn generate(&self, prompt: &str) -> anyhow::Result<LLMResponse> {
+            self.generate_with_options(prompt, LLMOptions::default())
+                .await
+        }
+
+        async fn generate_with_options(
+            &self,
+            prompt: &str,
+            options: LLMOptions,
+        ) -> anyhow::Result<LLMResponse> {
+            self.requests
+                .lock()
+                .unwrap()
+                .push((prompt.to_string(), options));
+            let mut replies = self.replies.lock().unwrap();
+            let content = if replies.len() > 1 {
+                replies.pop_front().unwrap()
+            } else {
+                replies.front().cloned().unwrap_or_default()
+            };
+            Ok(LLMResponse {
+                content,
+                usage: None,
+                cached: false,
+            })
+        }
+    }
+}
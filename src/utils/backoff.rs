@@ -0,0 +1,112 @@
+use rand::Rng;
+use std::time::Duration;
+
+/// Exponential backoff: an endless iterator of retry delays growing from `base` by
+/// `factor` each step, capped at `max`. With `jitter` in `0.0..=1.0`, each delay is
+/// randomly scaled by up to that fraction in either direction (still capped at `max`)
+/// so that many clients retrying together spread out.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    pub base: Duration,
+    pub max: Duration,
+    pub factor: f64,
+    pub jitter: f64,
+    attempt: u32,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(100), Duration::from_secs(10))
+            .with_factor(2.0)
+            .with_jitter(0.1)
+    }
+}
+
+impl Backoff {
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max,
+            factor: 2.0,
+            jitter: 0.0,
+            attempt: 0,
+        }
+    }
+
+    pub fn with_factor(mut self, factor: f64) -> Self {
+        self.factor = factor;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Start the sequence over from `base`, e.g. after a successful call.
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+
+    /// The delay for the given attempt, before jitter.
+    pub fn delay_for(&self, attempt: u32) -> Duration {
+        let delay = self.base.as_secs_f64() * self.factor.powi(attempt as i32);
+        if !delay.is_finite() || delay >= self.max.as_secs_f64() {
+            self.max
+        } else {
+            Duration::from_secs_f64(delay)
+        }
+    }
+}
+
+impl Iterator for Backoff {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let delay = self.delay_for(self.attempt);
+        self.attempt = self.attempt.saturating_add(1);
+
+        if self.jitter == 0.0 {
+            return Some(delay);
+        }
+        let scale = rand::thread_rng().gen_range(1.0 - self.jitter..=1.0 + self.jitter);
+        Some(delay.mul_f64(scale).min(self.max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_caps_at_max() {
+        let backoff = Backoff::new(Duration::from_millis(100), Duration::from_secs(1));
+        let delays: Vec<Duration> = backoff.take(6).collect();
+
+        assert_eq!(
+            delays,
+            vec![
+                Duration::from_millis(100),
+                Duration::from_millis(200),
+                Duration::from_millis(400),
+                Duration::from_millis(800),
+                Duration::from_secs(1),
+                Duration::from_secs(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_backoff_jitter_within_bounds() {
+        let backoff = Backoff::new(Duration::from_millis(100), Duration::from_secs(1))
+            .with_factor(3.0)
+            .with_jitter(0.5);
+
+        for (attempt, delay) in backoff.clone().take(100).enumerate() {
+            let expected = backoff.delay_for(attempt as u32);
+            assert!(delay >= expected.mul_f64(0.5));
+            assert!(delay <= expected.mul_f64(1.5));
+            assert!(delay <= Duration::from_secs(1));
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Lowercased alphanumeric terms of `text`.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(|term| term.to_lowercase())
+        .collect()
+}
+
+/// A keyword index scoring documents with Okapi BM25.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bm25Index {
+    pub k1: f64,
+    pub b: f64,
+    ids: Vec<String>,
+    lengths: Vec<usize>,
+    /// term -> (document index, term frequency)
+    postings: HashMap<String, Vec<(usize, usize)>>,
+}
+
+impl Default for Bm25Index {
+    fn default() -> Self {
+        Self {
+            k1: 1.2,
+            b: 0.75,
+            ids: Vec::new(),
+            lengths: Vec::new(),
+            postings: HashMap::new(),
+        }
+    }
+}
+
+impl Bm25Index {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_document(&mut self, id: &str, text: &str) {
+        let doc = self.ids.len();
+        let terms = tokenize(text);
+
+        let mut frequencies: HashMap<String, usize> = HashMap::new();
+        for term in &terms {
+            *frequencies.entry(term.clone()).or_default() += 1;
+        }
+        for (term, tf) in frequencies {
+            self.postings.entry(term).or_default().push((doc, tf));
+        }
+
+        self.ids.push(id.to_string());
+        self.lengths.push(terms.len());
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn idf(&self, term: &str) -> f64 {
+        let n = self.ids.len() as f64;
+        let df = self.postings.get(term).map_or(0, |p| p.len()) as f64;
+        ((n - df + 0.5) / (df + 0.5) + 1.0).ln()
+    }
+
+    /// BM25 score of every document matching at least one query term.
+    pub fn score(&self, query: &str) -> HashMap<usize, f64> {
+        let avg_length = self.lengths.iter().sum::<usize>() as f64 / self.ids.len().max(1) as f64;
+        let mut scores: HashMap<usize, f64> = HashMap::new();
+
+        for term in tokenize(query) {
+            let Some(postings) = self.postings.get(&term) else {
+                continue;
+            };
+            let idf = self.idf(&term);
+            for &(doc, tf) in postings {
+                let tf = tf as f64;
+                let norm = 1.0 - self.b + self.b * self.lengths[doc] as f64 / avg_length;
+                *scores.entry(doc).or_default() +=
+                    idf * tf * (self.k1 + 1.0) / (tf + self.k1 * norm);
+            }
+        }
+
+        scores
+    }
+
+    /// The ids of the `k` best matching documents with their scores, best first.
+    pub fn search(&self, query: &str, k: usize) -> Vec<(String, f64)> {
+        let mut ranked: Vec<(usize, f64)> = self.score(query).into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked
+            .into_iter()
+            .take(k)
+            .map(|(doc, score)| (self.ids[doc].clone(), score))
+            .collect()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corpus() -> Bm25Index {
+        let mut index = Bm25Index::new();
+        index.add_document("a", "the cat sat on the mat");
+        index.add_document("b", "the dog sat on the log");
+        index.add_document("c", "the quokka is a small marsupial");
+        index.add_document("d", "the the the the the");
+        index
+    }
+
+    #[test]
+    fn test_rare_term_ranks_above_common_terms() {
+        let index = corpus();
+        let results = index.search("the quokka", 4);
+
+        assert_eq!(results[0].0, "c");
+        assert!(results[0].1 > results[1].1);
+        assert!(index.idf("quokka") > index.idf("the"));
+    }
+
+    #[test]
+    fn test_search_limits_and_skips_non_matches() {
+        let index = corpus();
+
+        assert_eq!(index.search("sat", 1).len(), 1);
+        let ids: Vec<String> = index
+            .search("sat", 10)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(ids, vec!["a", "b"]);
+        assert!(index.search("unicorn", 10).is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let index = corpus();
+        let path = std::env::temp_dir().join(format!("bm25-{}.json", std::process::id()));
+        index.save(&path).unwrap();
+        let loaded = Bm25Index::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            loaded.search("the quokka", 2),
+            index.search("the quokka", 2)
+        );
+    }
+}
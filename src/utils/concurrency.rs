@@ -0,0 +1,74 @@
+use anyhow::Result;
+use futures::{StreamExt, TryStreamExt, stream};
+use std::future::Future;
+
+/// Run `f` over `items` with at most `concurrency` calls in flight (at least one),
+/// returning the results in the order of `items`. Stops at the first error in that
+/// order, dropping the calls still running.
+pub async fn bounded_map<T, U, F, Fut>(
+    items: impl IntoIterator<Item = T>,
+    concurrency: usize,
+    f: F,
+) -> Result<Vec<U>>
+where
+    F: FnMut(T) -> Fut,
+    Fut: Future<Output = Result<U>>,
+{
+    stream::iter(items)
+        .map(f)
+        .buffered(concurrency.max(1))
+        .try_collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_preserves_order_within_bound() {
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+
+        let results = bounded_map(0..10u64, 3, |i| {
+            let running = &running;
+            let max_running = &max_running;
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                // Later items finish first
+                tokio::time::sleep(Duration::from_millis(20 - 2 * i)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                Ok(i * i)
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(results, (0..10).map(|i| i * i).collect::<Vec<_>>());
+        assert_eq!(max_running.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_returns_first_error() {
+        let calls = AtomicUsize::new(0);
+
+        let err = bounded_map(0..100, 0, |i| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                match i {
+                    3 | 5 => Err(anyhow::anyhow!("item {} failed", i)),
+                    _ => Ok(i),
+                }
+            }
+        })
+        .await
+        .unwrap_err();
+
+        assert_eq!(err.to_string(), "item 3 failed");
+        // A concurrency of 0 runs one at a time, so nothing past the error started
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+}
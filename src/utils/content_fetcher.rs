@@ -0,0 +1,308 @@
+#![cfg(feature = "websearch")]
+
+use crate::error::Error;
+use reqwest::{Client, Response, Url};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+#[derive(Debug, Clone)]
+pub struct FetchOptions {
+    pub user_agent: String,
+    /// Minimum time between two requests to the same host
+    pub per_host_delay: Duration,
+    pub respect_robots_txt: bool,
+}
+
+impl Default for FetchOptions {
+    fn default() -> Self {
+        Self {
+            user_agent: "pocketflow_rs".to_string(),
+            per_host_delay: Duration::from_secs(1),
+            respect_robots_txt: true,
+        }
+    }
+}
+
+/// Fetches web pages politely: requests to the same host are spaced by
+/// `per_host_delay`, and urls disallowed by the site's robots.txt are skipped.
+pub struct ContentFetcher {
+    client: Arc<Client>,
+    options: FetchOptions,
+    last_request: Mutex<HashMap<String, Instant>>,
+    robots: Mutex<HashMap<String, RobotsRules>>,
+}
+
+impl ContentFetcher {
+    pub fn new(options: FetchOptions) -> Self {
+        let client = Client::builder()
+            .user_agent(options.user_agent.clone())
+            .build()
+            .unwrap_or_default();
+        Self {
+            client: Arc::new(client),
+            options,
+            last_request: Mutex::new(HashMap::new()),
+            robots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Send requests through `client`, e.g. one shared with other utils or set up with
+    /// a proxy. Its own User-Agent is sent; `options.user_agent` still selects the
+    /// robots.txt rules.
+    pub fn with_client(mut self, client: Arc<Client>) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Fetch `url`, or return `None` if robots.txt disallows it.
+    pub async fn fetch_content(&self, url: &str) -> anyhow::Result<Option<Response>> {
+        let parsed = Url::parse(url)?;
+        let host = parsed.host_str().unwrap_or_default().to_string();
+
+        if self.options.respect_robots_txt && !self.is_allowed(&parsed).await? {
+            info!("Skipping {}: disallowed by robots.txt", url);
+            return Ok(None);
+        }
+
+        self.wait_for_host(&host).await;
+        info!("Fetching content from {}", url);
+        let response = self.client.get(parsed).send().await.map_err(Error::from)?;
+        Ok(Some(response))
+    }
+
+    async fn wait_for_host(&self, host: &str) {
+        let wait = {
+            let mut last_request = self.last_request.lock().unwrap();
+            let now = Instant::now();
+            let next_allowed = last_request
+                .get(host)
+                .map_or(now, |last| *last + self.options.per_host_delay);
+            let scheduled = next_allowed.max(now);
+            last_request.insert(host.to_string(), scheduled);
+            scheduled - now
+        };
+
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    async fn is_allowed(&self, url: &Url) -> anyhow::Result<bool> {
+        let origin = url.origin().ascii_serialization();
+        let cached = self
+            .robots
+            .lock()
+            .unwrap()
+            .get(&origin)
+            .map(|rules| rules.is_allowed(url.path()));
+        if let Some(allowed) = cached {
+            return Ok(allowed);
+        }
+
+        let robots_url = format!("{}/robots.txt", origin);
+        self.wait_for_host(url.host_str().unwrap_or_default()).await;
+        let rules = match self.client.get(&robots_url).send().await {
+            Ok(response) if response.status().is_success() => {
+                RobotsRules::parse(&response.text().await?, &self.options.user_agent)
+            }
+            Ok(response) => {
+                info!("No robots.txt at {} ({})", robots_url, response.status());
+                RobotsRules::default()
+            }
+            Err(e) => {
+                warn!("Failed to fetch {}: {}", robots_url, e);
+                RobotsRules::default()
+            }
+        };
+
+        let allowed = rules.is_allowed(url.path());
+        self.robots.lock().unwrap().insert(origin, rules);
+        Ok(allowed)
+    }
+}
+
+/// The Allow/Disallow rules of a robots.txt that apply to one user agent. Rules
+/// are matched as path prefixes; the longest match wins, with Allow winning ties.
+#[derive(Debug, Clone, Default)]
+struct RobotsRules {
+    rules: Vec<(bool, String)>,
+}
+
+impl RobotsRules {
+    fn parse(content: &str, user_agent: &str) -> Self {
+        let user_agent = user_agent.to_lowercase();
+        let mut specific = Vec::new();
+        let mut wildcard = Vec::new();
+        let mut group_agents: Vec<String> = Vec::new();
+        let mut in_rules = false;
+
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let key = key.trim().to_lowercase();
+            let value = value.trim();
+
+            match key.as_str() {
+                "user-agent" => {
+                    // A user-agent line after rules starts a new group
+                    if in_rules {
+                        group_agents.clear();
+                        in_rules = false;
+                    }
+                    group_agents.push(value.to_lowercase());
+                }
+                "allow" | "disallow" => {
+                    in_rules = true;
+                    // An empty Disallow allows everything
+                    if value.is_empty() {
+                        continue;
+                    }
+                    let rule = (key == "allow", value.to_string());
+                    if group_agents
+                        .iter()
+                        .any(|agent| agent != "*" && user_agent.contains(agent.as_str()))
+                    {
+                        specific.push(rule.clone());
+                    }
+                    if group_agents.iter().any(|agent| agent == "*") {
+                        wildcard.push(rule);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Self {
+            rules: if specific.is_empty() {
+                wildcard
+            } else {
+                specific
+            },
+        }
+    }
+
+    fn is_allowed(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(_, prefix)| path.starts_with(prefix.as_str()))
+            .max_by_key(|(allow, prefix)| (prefix.len(), *allow))
+            .is_none_or(|(allow, _)| *allow)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const ROBOTS_TXT: &str = "User-agent: *\nDisallow: /private\nAllow: /private/open\n";
+
+    /// Serve robots.txt and a plain "ok" page for any other path.
+    async fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = [0u8; 1024];
+                let n = socket.read(&mut buf).await.unwrap();
+                let request = String::from_utf8_lossy(&buf[..n]);
+                let path = request.split_whitespace().nth(1).unwrap_or("/");
+                let body = if path == "/robots.txt" {
+                    ROBOTS_TXT
+                } else {
+                    "ok"
+                };
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_robots_txt_disallowed_url_skipped() {
+        let base = serve().await;
+        let fetcher = ContentFetcher::new(FetchOptions {
+            per_host_delay: Duration::ZERO,
+            ..FetchOptions::default()
+        });
+
+        let skipped = fetcher
+            .fetch_content(&format!("{}/private/page", base))
+            .await
+            .unwrap();
+        assert!(skipped.is_none());
+
+        for path in ["/public/page", "/private/open/page"] {
+            let response = fetcher
+                .fetch_content(&format!("{}{}", base, path))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(response.text().await.unwrap(), "ok");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_injected_client_user_agent_is_sent() {
+        // Echo the request's User-Agent header back as the body
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let n = socket.read(&mut buf).await.unwrap();
+            let request = String::from_utf8_lossy(&buf[..n]);
+            let user_agent = request
+                .lines()
+                .find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    name.eq_ignore_ascii_case("user-agent")
+                        .then(|| value.trim().to_string())
+                })
+                .unwrap_or_default();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                user_agent.len(),
+                user_agent
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let client = Client::builder()
+            .user_agent("corp-crawler/2.0")
+            .build()
+            .unwrap();
+        let fetcher = ContentFetcher::new(FetchOptions {
+            respect_robots_txt: false,
+            ..FetchOptions::default()
+        })
+        .with_client(Arc::new(client));
+
+        let response = fetcher
+            .fetch_content(&format!("http://{}/page", addr))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "corp-crawler/2.0");
+    }
+
+    #[test]
+    fn test_robots_rules_prefer_specific_user_agent() {
+        let content = "User-agent: *\nDisallow: /\n\nUser-agent: pocketflow\nDisallow: /admin\n";
+        let rules = RobotsRules::parse(content, "pocketflow_rs");
+
+        assert!(rules.is_allowed("/docs"));
+        assert!(!rules.is_allowed("/admin/users"));
+    }
+}
@@ -1,9 +1,25 @@
-#![cfg(feature = "openai")]
+#![cfg(any(feature = "openai", feature = "local-embeddings"))]
 
+use anyhow::Context as _;
 use async_trait::async_trait;
+#[cfg(feature = "local-embeddings")]
+use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+#[cfg(feature = "openai")]
 use openai_api_rust::embeddings::*;
+#[cfg(feature = "openai")]
 use openai_api_rust::*;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+#[cfg(feature = "local-embeddings")]
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+#[cfg(feature = "openai")]
 use tracing::info;
+#[cfg(feature = "local-embeddings")]
+use tracing::warn;
 
 #[derive(Debug, Clone)]
 pub struct EmbeddingOptions {
@@ -21,11 +37,12 @@ impl Default for EmbeddingOptions {
 }
 
 #[async_trait]
-pub trait EmbeddingGenerator {
+pub trait EmbeddingGenerator: Send + Sync {
     async fn generate_embedding(&self, text: &str) -> anyhow::Result<Vec<f64>>;
     async fn generate_embeddings(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f64>>>;
 }
 
+#[cfg(feature = "openai")]
 #[allow(dead_code)]
 pub struct OpenAIEmbeddingGenerator {
     api_key: String,
@@ -33,6 +50,7 @@ pub struct OpenAIEmbeddingGenerator {
     client: OpenAI,
 }
 
+#[cfg(feature = "openai")]
 impl OpenAIEmbeddingGenerator {
     pub fn new(api_key: &str, endpoint: &str, options: EmbeddingOptions) -> Self {
         let auth = Auth::new(api_key);
@@ -45,6 +63,7 @@ impl OpenAIEmbeddingGenerator {
     }
 }
 
+#[cfg(feature = "openai")]
 #[async_trait]
 impl EmbeddingGenerator for OpenAIEmbeddingGenerator {
     async fn generate_embedding(&self, text: &str) -> anyhow::Result<Vec<f64>> {
@@ -71,17 +90,369 @@ impl EmbeddingGenerator for OpenAIEmbeddingGenerator {
                 .into_iter()
                 .map(|x: EmbeddingData| x.embedding.unwrap())
                 .collect();
+            if let Some(expected) = self.options.dimensions
+                && let Some(index) = result.iter().position(|e| e.len() != expected)
+            {
+                return Err(anyhow::anyhow!(
+                    "Embedding {} from model '{}' has {} dimensions, expected {}",
+                    results.len() + index,
+                    self.options.model,
+                    result[index].len(),
+                    expected
+                ));
+            }
             results.extend(result);
         }
         Ok(results)
     }
 }
 
+/// Embeds text offline with a [fastembed](https://docs.rs/fastembed) ONNX model,
+/// downloaded to fastembed's cache dir on first use. `options.model` names the
+/// model either as a fastembed `EmbeddingModel` variant, e.g. `BGESmallENV15Q`, or
+/// by its code, e.g. `Xenova/bge-small-en-v1.5`. A model's dimension is fixed, so a
+/// different `options.dimensions` is ignored with a warning.
+#[cfg(feature = "local-embeddings")]
+pub struct FastEmbedGenerator {
+    model: Arc<Mutex<TextEmbedding>>,
+    dimension: usize,
+}
+
+#[cfg(feature = "local-embeddings")]
+impl FastEmbedGenerator {
+    /// The quantized English model used by the RAG example.
+    pub const DEFAULT_MODEL: &str = "BGESmallENV15Q";
+
+    /// Load the model, blocking while it is downloaded the first time.
+    pub fn new(options: EmbeddingOptions) -> anyhow::Result<Self> {
+        let model = Self::parse_model(&options.model)?;
+        let dimension = TextEmbedding::get_model_info(&model)?.dim;
+        if let Some(dimensions) = options.dimensions
+            && dimensions != dimension
+        {
+            warn!(
+                "Model '{}' embeds into {} dimensions, ignoring the requested {}",
+                options.model, dimension, dimensions
+            );
+        }
+        let embedding = TextEmbedding::try_new(InitOptions::new(model))?;
+        Ok(Self {
+            model: Arc::new(Mutex::new(embedding)),
+            dimension,
+        })
+    }
+
+    /// The dimension of the model's embeddings.
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn parse_model(name: &str) -> anyhow::Result<EmbeddingModel> {
+        name.parse::<EmbeddingModel>().or_else(|_| {
+            TextEmbedding::list_supported_models()
+                .into_iter()
+                .find(|info| info.model_code.eq_ignore_ascii_case(name))
+                .map(|info| info.model)
+                .ok_or_else(|| anyhow::anyhow!("Unknown local embedding model '{}'", name))
+        })
+    }
+}
+
+#[cfg(feature = "local-embeddings")]
+#[async_trait]
+impl EmbeddingGenerator for FastEmbedGenerator {
+    async fn generate_embedding(&self, text: &str) -> anyhow::Result<Vec<f64>> {
+        let embeds = self.generate_embeddings(&[text.to_string()]).await?;
+        Ok(embeds.into_iter().next().unwrap_or_default())
+    }
+
+    async fn generate_embeddings(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f64>>> {
+        let model = self.model.clone();
+        let texts = texts.to_vec();
+        // Inference is CPU-bound, keep it off the async workers
+        let embeddings = tokio::task::spawn_blocking(move || {
+            model
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Embedding model poisoned by a panic"))?
+                .embed(texts, None)
+        })
+        .await??;
+        Ok(embeddings
+            .into_iter()
+            .map(|embedding| embedding.into_iter().map(f64::from).collect())
+            .collect())
+    }
+}
+
+/// Caches the embeddings of an inner [`EmbeddingGenerator`] in memory, keyed on the
+/// SHA-256 of model and text, and only forwards texts it hasn't seen. With
+/// [`CachingEmbeddingGenerator::with_file`] the cache is loaded from and saved to a
+/// JSON file, so it survives restarts.
+pub struct CachingEmbeddingGenerator<G: EmbeddingGenerator> {
+    inner: G,
+    model: String,
+    entries: RwLock<HashMap<String, Vec<f64>>>,
+    path: Option<PathBuf>,
+}
+
+impl<G: EmbeddingGenerator> CachingEmbeddingGenerator<G> {
+    pub fn new(inner: G, model: impl Into<String>) -> Self {
+        Self {
+            inner,
+            model: model.into(),
+            entries: RwLock::new(HashMap::new()),
+            path: None,
+        }
+    }
+
+    /// Back the cache with the JSON file at `path`, loading it if it exists. The
+    /// file is rewritten whenever new embeddings are cached.
+    pub fn with_file(mut self, path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if path.exists() {
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read embedding cache {}", path.display()))?;
+            let entries: HashMap<String, Vec<f64>> = serde_json::from_str(&content)
+                .with_context(|| format!("Invalid embedding cache {}", path.display()))?;
+            self.entries.write().unwrap().extend(entries);
+        }
+        self.path = Some(path.to_path_buf());
+        Ok(self)
+    }
+
+    /// Number of cached embeddings.
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn cache_key(&self, text: &str) -> String {
+        let key = serde_json::json!([self.model, text]).to_string();
+        format!("{:x}", Sha256::digest(key.as_bytes()))
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string(&*self.entries.read().unwrap())?;
+        fs::write(path, content)
+            .with_context(|| format!("Failed to write embedding cache {}", path.display()))
+    }
+}
+
+#[async_trait]
+impl<G: EmbeddingGenerator> EmbeddingGenerator for CachingEmbeddingGenerator<G> {
+    async fn generate_embedding(&self, text: &str) -> anyhow::Result<Vec<f64>> {
+        let embeds = self.generate_embeddings(&[text.to_string()]).await?;
+        Ok(embeds.into_iter().next().unwrap_or_default())
+    }
+
+    async fn generate_embeddings(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f64>>> {
+        let keys: Vec<String> = texts.iter().map(|text| self.cache_key(text)).collect();
+        let mut missing: Vec<(&String, &String)> = Vec::new();
+        {
+            let entries = self.entries.read().unwrap();
+            for (key, text) in keys.iter().zip(texts) {
+                if !entries.contains_key(key) && !missing.iter().any(|(k, _)| *k == key) {
+                    missing.push((key, text));
+                }
+            }
+        }
+        debug!(
+            "Embedding cache: {} of {} texts missing",
+            missing.len(),
+            texts.len()
+        );
+
+        if !missing.is_empty() {
+            let missing_texts: Vec<String> =
+                missing.iter().map(|(_, text)| text.to_string()).collect();
+            let embeddings = self.inner.generate_embeddings(&missing_texts).await?;
+            if embeddings.len() != missing.len() {
+                return Err(anyhow::anyhow!(
+                    "Expected {} embeddings, got {}",
+                    missing.len(),
+                    embeddings.len()
+                ));
+            }
+            self.entries.write().unwrap().extend(
+                missing
+                    .iter()
+                    .map(|(key, _)| key.to_string())
+                    .zip(embeddings),
+            );
+            self.save()?;
+        }
+
+        let entries = self.entries.read().unwrap();
+        Ok(keys.iter().map(|key| entries[key].clone()).collect())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::env;
+    #[cfg(feature = "openai")]
+    use std::io::{BufRead, BufReader, Read, Write};
+    #[cfg(feature = "openai")]
+    use std::net::TcpListener;
+
+    /// Serves one embeddings response with the given vectors and returns its endpoint.
+    #[cfg(feature = "openai")]
+    fn serve_embeddings(embeddings: Vec<Vec<f64>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(socket.try_clone().unwrap());
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':')
+                    && name.eq_ignore_ascii_case("content-length")
+                {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0u8; content_length];
+            reader.read_exact(&mut body).unwrap();
+
+            let data: Vec<_> = embeddings
+                .iter()
+                .enumerate()
+                .map(|(index, embedding)| {
+                    serde_json::json!({"object": "embedding", "embedding": embedding, "index": index})
+                })
+                .collect();
+            let response = serde_json::json!({
+                "object": "list",
+                "data": data,
+                "model": "test-embedding",
+                "usage": {"prompt_tokens": 1, "total_tokens": 1},
+            })
+            .to_string();
+            write!(
+                socket,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                response.len(),
+                response
+            )
+            .unwrap();
+        });
+        format!("http://{}/v1/", addr)
+    }
+
+    #[cfg(feature = "openai")]
+    #[tokio::test]
+    async fn test_wrong_dimensions_error() {
+        let endpoint = serve_embeddings(vec![vec![0.1, 0.2, 0.3, 0.4], vec![0.1, 0.2, 0.3]]);
+        let generator = OpenAIEmbeddingGenerator::new(
+            "test-key",
+            &endpoint,
+            EmbeddingOptions {
+                model: "test-embedding".to_string(),
+                dimensions: Some(4),
+            },
+        );
+
+        let error = generator
+            .generate_embeddings(&["first".to_string(), "second".to_string()])
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Embedding 1 from model 'test-embedding' has 3 dimensions, expected 4"
+        );
+    }
+
+    /// Embeds a text as its length, recording every batch it is asked for.
+    #[derive(Default)]
+    struct RecordingGenerator {
+        batches: std::sync::Mutex<Vec<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl EmbeddingGenerator for RecordingGenerator {
+        async fn generate_embedding(&self, text: &str) -> anyhow::Result<Vec<f64>> {
+            Ok(vec![text.len() as f64])
+        }
+
+        async fn generate_embeddings(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f64>>> {
+            self.batches.lock().unwrap().push(texts.to_vec());
+            Ok(texts.iter().map(|text| vec![text.len() as f64]).collect())
+        }
+    }
+
+    fn texts(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|text| text.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_caching_generator_forwards_only_misses() {
+        let generator = CachingEmbeddingGenerator::new(RecordingGenerator::default(), "test");
+        assert_eq!(generator.generate_embedding("bb").await.unwrap(), vec![2.0]);
+
+        let embeddings = generator
+            .generate_embeddings(&texts(&["a", "bb", "cccc", "a"]))
+            .await
+            .unwrap();
+
+        assert_eq!(embeddings, vec![vec![1.0], vec![2.0], vec![4.0], vec![1.0]]);
+        assert_eq!(
+            *generator.inner.batches.lock().unwrap(),
+            vec![texts(&["bb"]), texts(&["a", "cccc"])]
+        );
+        assert_eq!(generator.len(), 3);
+
+        // Another model doesn't share the cache
+        let other = CachingEmbeddingGenerator {
+            model: "other".to_string(),
+            ..generator
+        };
+        other.generate_embedding("bb").await.unwrap();
+        assert_eq!(other.inner.batches.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_caching_generator_file_survives_restart() {
+        let path = env::temp_dir().join(format!("embedding-cache-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let generator = CachingEmbeddingGenerator::new(RecordingGenerator::default(), "test")
+            .with_file(&path)
+            .unwrap();
+        generator
+            .generate_embeddings(&texts(&["a", "bb"]))
+            .await
+            .unwrap();
+
+        let restarted = CachingEmbeddingGenerator::new(RecordingGenerator::default(), "test")
+            .with_file(&path)
+            .unwrap();
+        let embeddings = restarted
+            .generate_embeddings(&texts(&["bb", "a"]))
+            .await
+            .unwrap();
+        fs::remove_file(&path).unwrap();
 
+        assert_eq!(embeddings, vec![vec![2.0], vec![1.0]]);
+        assert!(restarted.inner.batches.lock().unwrap().is_empty());
+    }
+
+    #[cfg(feature = "openai")]
     #[tokio::test]
     #[ignore = "E2E case, requires API keys"]
     async fn test_e2e_embedding_generator() {
@@ -97,4 +468,35 @@ mod tests {
         let embedding = generator.generate_embedding(text).await.unwrap();
         println!("{:?}", embedding);
     }
+
+    #[cfg(feature = "local-embeddings")]
+    #[test]
+    fn test_fastembed_model_names() {
+        assert_eq!(
+            FastEmbedGenerator::parse_model(FastEmbedGenerator::DEFAULT_MODEL).unwrap(),
+            EmbeddingModel::BGESmallENV15Q
+        );
+        assert_eq!(
+            FastEmbedGenerator::parse_model("xenova/bge-small-en-v1.5").unwrap(),
+            EmbeddingModel::BGESmallENV15
+        );
+        assert!(FastEmbedGenerator::parse_model("text-embedding-ada-002").is_err());
+    }
+
+    #[cfg(feature = "local-embeddings")]
+    #[tokio::test]
+    #[ignore = "E2E case, downloads the model"]
+    async fn test_e2e_fastembed_generator() {
+        let generator = FastEmbedGenerator::new(EmbeddingOptions {
+            model: FastEmbedGenerator::DEFAULT_MODEL.to_string(),
+            dimensions: Some(1024),
+        })
+        .unwrap();
+        let embeddings = generator
+            .generate_embeddings(&["Hello, world!".to_string(), "Pangu".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(embeddings.len(), 2);
+        assert!(embeddings.iter().all(|e| e.len() == generator.dimension()));
+    }
 }
@@ -0,0 +1,336 @@
+#![cfg(feature = "hnsw")]
+
+use crate::utils::vector_db::{
+    DistanceMetric, ScrollPage, VectorDB, VectorDBOptions, VectorRecord, check_dimension, score,
+};
+use async_trait::async_trait;
+use hnsw_rs::prelude::{DataId, Distance, Hnsw};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use tracing::info;
+
+/// The number of layers `hnsw_rs` supports.
+const MAX_LAYERS: usize = 16;
+
+/// Tunables of an [`HnswVectorDB`] graph.
+#[derive(Debug, Clone)]
+pub struct HnswOptions {
+    /// Links per point and layer; more links raise recall and memory use.
+    pub m: usize,
+    /// Candidates considered when linking an inserted point; higher builds a
+    /// better graph, more slowly.
+    pub ef_construction: usize,
+    /// Candidates considered per search, raised to `k` if lower; higher raises
+    /// recall and latency.
+    pub ef_search: usize,
+    /// Expected number of points, used to size the graph up front.
+    pub max_elements: usize,
+}
+
+impl Default for HnswOptions {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 200,
+            ef_search: 64,
+            max_elements: 10_000,
+        }
+    }
+}
+
+/// The distance the graph is built on: non-negative, smallest for the best match.
+struct HnswDistance(DistanceMetric);
+
+impl Distance<f32> for HnswDistance {
+    fn eval(&self, a: &[f32], b: &[f32]) -> f32 {
+        match self.0 {
+            DistanceMetric::Cosine => (1.0 - score(&self.0, a, b)).max(0.0),
+            _ => score(&self.0, a, b),
+        }
+    }
+}
+
+/// Records by graph point id, `None` once deleted or replaced, and the live point
+/// of each record id.
+#[derive(Default)]
+struct Points {
+    records: Vec<Option<VectorRecord>>,
+    ids: HashMap<String, DataId>,
+}
+
+/// A vector db held in memory like `InMemoryVectorDB`, but searched through an
+/// HNSW graph instead of by comparing the query with every record. Searches are
+/// approximate: on a few thousand records or more they are far faster, at the cost
+/// of sometimes missing a true nearest neighbor. Raise `ef_search` (or `m` and
+/// `ef_construction`) for better recall and slower searches, and prefer
+/// `InMemoryVectorDB` when exact results matter more than latency. Scores are
+/// exact and match `InMemoryVectorDB`'s. The graph can't remove points, so
+/// deleted and replaced records stay in it, skipped by searches, for the life of
+/// the db. Only the cosine and euclidean metrics are supported.
+pub struct HnswVectorDB {
+    options: VectorDBOptions,
+    hnsw_options: HnswOptions,
+    index: Hnsw<'static, f32, HnswDistance>,
+    points: RwLock<Points>,
+}
+
+impl HnswVectorDB {
+    pub fn new(options: VectorDBOptions, hnsw_options: HnswOptions) -> anyhow::Result<Self> {
+        if matches!(options.distance_metric, DistanceMetric::DotProduct) {
+            return Err(anyhow::anyhow!(
+                "HNSW collection '{}' doesn't support the dot product metric, \
+                 use cosine on the same vectors instead",
+                options.collection_name
+            ));
+        }
+        let index = Hnsw::new(
+            hnsw_options.m,
+            hnsw_options.max_elements,
+            MAX_LAYERS,
+            hnsw_options.ef_construction,
+            HnswDistance(options.distance_metric.clone()),
+        );
+        Ok(Self {
+            options,
+            hnsw_options,
+            index,
+            points: RwLock::new(Points::default()),
+        })
+    }
+
+    /// The number of records stored, deleted ones excluded.
+    pub fn len(&self) -> usize {
+        self.points.read().unwrap().ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl VectorDB for HnswVectorDB {
+    fn dimension(&self) -> Option<usize> {
+        Some(self.options.dimension)
+    }
+
+    fn distance_metric(&self) -> Option<DistanceMetric> {
+        Some(self.options.distance_metric.clone())
+    }
+
+    /// Records are added to the graph one by one; a record with an id that is
+    /// already stored replaces the stored record.
+    async fn insert(&self, records: Vec<VectorRecord>) -> anyhow::Result<()> {
+        for record in &records {
+            check_dimension(&self.options, &record.vector)?;
+        }
+        info!(
+            "Inserting {} records into HNSW collection '{}'",
+            records.len(),
+            self.options.collection_name
+        );
+        let mut points = self.points.write().unwrap();
+        for record in records {
+            let point = points.records.len();
+            self.index.insert((&record.vector, point));
+            if let Some(replaced) = points.ids.insert(record.id.clone(), point) {
+                points.records[replaced] = None;
+            }
+            points.records.push(Some(record));
+        }
+        Ok(())
+    }
+
+    async fn search(&self, query: Vec<f32>, k: usize) -> anyhow::Result<Vec<VectorRecord>> {
+        check_dimension(&self.options, &query)?;
+        let points = self.points.read().unwrap();
+        if points.ids.is_empty() || k == 0 {
+            return Ok(Vec::new());
+        }
+        let is_live = |point: &DataId| points.records[*point].is_some();
+        let neighbours = self.index.search_filter(
+            &query,
+            k,
+            self.hnsw_options.ef_search.max(k),
+            Some(&is_live),
+        );
+
+        let mut results: Vec<VectorRecord> = neighbours
+            .iter()
+            .filter_map(|neighbour| points.records[neighbour.d_id].as_ref())
+            .map(|record| VectorRecord {
+                score: Some(score(&self.options.distance_metric, &record.vector, &query)),
+                ..record.clone()
+            })
+            .collect();
+        let ascending = matches!(self.options.distance_metric, DistanceMetric::Euclidean);
+        results.sort_by(|a, b| {
+            let (a, b) = (a.score.unwrap(), b.score.unwrap());
+            if ascending {
+                a.total_cmp(&b)
+            } else {
+                b.total_cmp(&a)
+            }
+        });
+        results.truncate(k);
+        Ok(results)
+    }
+
+    async fn delete(&self, ids: Vec<String>) -> anyhow::Result<()> {
+        let mut points = self.points.write().unwrap();
+        for id in ids {
+            if let Some(point) = points.ids.remove(&id) {
+                points.records[point] = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// Pages in insertion order; offsets are graph point ids.
+    async fn scroll(&self, offset: Option<String>, limit: usize) -> anyhow::Result<ScrollPage> {
+        let start = match offset {
+            Some(offset) => offset
+                .parse::<usize>()
+                .map_err(|_| anyhow::anyhow!("Invalid scroll offset: {}", offset))?,
+            None => 0,
+        };
+        let points = self.points.read().unwrap();
+        let mut live = points
+            .records
+            .iter()
+            .enumerate()
+            .skip(start)
+            .filter_map(|(point, record)| Some((point, record.as_ref()?)));
+        let records = live
+            .by_ref()
+            .take(limit)
+            .map(|(_, record)| record.clone())
+            .collect();
+        Ok(ScrollPage {
+            records,
+            next_offset: live.next().map(|(point, _)| point.to_string()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::vector_db::InMemoryVectorDB;
+    use serde_json::{Map, json};
+
+    fn options(dimension: usize, distance_metric: DistanceMetric) -> VectorDBOptions {
+        VectorDBOptions {
+            collection_name: "test".to_string(),
+            dimension,
+            distance_metric,
+            payload_indexes: Vec::new(),
+            sparse_vector_name: None,
+        }
+    }
+
+    fn record(id: &str, vector: Vec<f32>) -> VectorRecord {
+        VectorRecord {
+            id: id.to_string(),
+            vector,
+            sparse_vector: None,
+            metadata: Map::from_iter([("text".to_string(), json!(id))]),
+            score: None,
+        }
+    }
+
+    /// Deterministic pseudo-random vectors in [-1, 1).
+    fn vectors(count: usize, dimension: usize) -> Vec<Vec<f32>> {
+        let mut state: u64 = 42;
+        let mut next = || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+        };
+        (0..count)
+            .map(|_| (0..dimension).map(|_| next()).collect())
+            .collect()
+    }
+
+    fn ids(records: &[VectorRecord]) -> Vec<&str> {
+        records.iter().map(|record| record.id.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_search_recall_matches_brute_force() {
+        for metric in [DistanceMetric::Cosine, DistanceMetric::Euclidean] {
+            let hnsw =
+                HnswVectorDB::new(options(16, metric.clone()), HnswOptions::default()).unwrap();
+            let exact = InMemoryVectorDB::new(options(16, metric.clone()));
+            let data = vectors(1100, 16);
+            let (points, queries) = data.split_at(1000);
+            let records: Vec<VectorRecord> = points
+                .iter()
+                .enumerate()
+                .map(|(i, vector)| record(&format!("point-{}", i), vector.clone()))
+                .collect();
+            for batch in records.chunks(250) {
+                hnsw.insert(batch.to_vec()).await.unwrap();
+            }
+            exact.insert(records).await.unwrap();
+            assert_eq!(hnsw.len(), 1000);
+
+            let mut found = 0;
+            for query in queries {
+                let approximate = hnsw.search(query.clone(), 10).await.unwrap();
+                let expected = exact.search(query.clone(), 10).await.unwrap();
+                assert_eq!(approximate.len(), 10);
+                // Scores are exact, not derived from graph distances
+                let top = &approximate[0];
+                assert_eq!(top.score, Some(score(&metric, &top.vector, query)));
+                found += ids(&approximate)
+                    .iter()
+                    .filter(|id| ids(&expected).contains(id))
+                    .count();
+            }
+            let recall = found as f32 / (queries.len() * 10) as f32;
+            assert!(recall >= 0.9, "{:?} recall {}", metric, recall);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upsert_delete_and_scroll() {
+        let db =
+            HnswVectorDB::new(options(2, DistanceMetric::Cosine), HnswOptions::default()).unwrap();
+        db.insert(vec![
+            record("a", vec![1.0, 0.0]),
+            record("b", vec![0.0, 1.0]),
+            record("c", vec![0.7, 0.7]),
+        ])
+        .await
+        .unwrap();
+        // Replacing "a" leaves its old point behind, skipped by searches
+        db.insert(vec![record("a", vec![-1.0, 0.0])]).await.unwrap();
+        assert_eq!(db.len(), 3);
+
+        let results = db.search(vec![1.0, 0.0], 3).await.unwrap();
+        assert_eq!(ids(&results), vec!["c", "b", "a"]);
+        assert!((results[0].score.unwrap() - 0.70710677).abs() < 1e-6);
+        assert_eq!(results[0].metadata["text"], json!("c"));
+
+        db.delete(vec!["c".to_string(), "missing".to_string()])
+            .await
+            .unwrap();
+        let results = db.search(vec![1.0, 0.0], 3).await.unwrap();
+        assert_eq!(ids(&results), vec!["b", "a"]);
+        assert!(db.search(vec![1.0], 3).await.is_err());
+
+        let page = db.scroll(None, 1).await.unwrap();
+        assert_eq!(ids(&page.records), vec!["b"]);
+        let page = db.scroll(page.next_offset, 5).await.unwrap();
+        assert_eq!(ids(&page.records), vec!["a"]);
+        assert_eq!(page.next_offset, None);
+
+        let dot = HnswVectorDB::new(
+            options(2, DistanceMetric::DotProduct),
+            HnswOptions::default(),
+        );
+        assert!(dot.is_err());
+    }
+}
@@ -0,0 +1,41 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// A minimal async key-value store for caches and other state shared across runs.
+#[async_trait]
+pub trait KeyValueStore: Send + Sync {
+    async fn get(&self, key: &str) -> Result<Option<Value>>;
+    async fn set(&self, key: &str, value: Value) -> Result<()>;
+    async fn delete(&self, key: &str) -> Result<()>;
+}
+
+#[derive(Debug, Default)]
+pub struct InMemoryKeyValueStore {
+    entries: RwLock<HashMap<String, Value>>,
+}
+
+impl InMemoryKeyValueStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl KeyValueStore for InMemoryKeyValueStore {
+    async fn get(&self, key: &str) -> Result<Option<Value>> {
+        Ok(self.entries.read().unwrap().get(key).cloned())
+    }
+
+    async fn set(&self, key: &str, value: Value) -> Result<()> {
+        self.entries.write().unwrap().insert(key.to_string(), value);
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.entries.write().unwrap().remove(key);
+        Ok(())
+    }
+}
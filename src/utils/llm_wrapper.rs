@@ -1,17 +1,31 @@
-#![cfg(feature = "openai")]
+#![cfg(any(feature = "openai", feature = "anthropic"))]
 
-use std::{collections::HashMap, hash::RandomState};
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::RandomState,
+    sync::Arc,
+};
 
 use async_trait::async_trait;
+#[cfg(feature = "openai")]
 use openai_api_rust::chat::*;
+#[cfg(feature = "openai")]
 use openai_api_rust::*;
-use serde::{Deserialize, Serialize};
-use tracing::info;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+
+use crate::error::Error;
+use crate::utils::kv_store::KeyValueStore;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LLMResponse {
     pub content: String,
     pub usage: Option<LLMUsage>,
+    /// True if this response was served from a cache rather than the model.
+    #[serde(default)]
+    pub cached: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,7 +36,7 @@ pub struct LLMUsage {
 }
 
 #[async_trait]
-pub trait LLMWrapper {
+pub trait LLMWrapper: Send + Sync {
     async fn generate(&self, prompt: &str) -> anyhow::Result<LLMResponse>;
     async fn generate_with_options(
         &self,
@@ -31,8 +45,72 @@ pub trait LLMWrapper {
     ) -> anyhow::Result<LLMResponse>;
 }
 
+/// Structured output for every [`LLMWrapper`], trait objects included.
+#[async_trait]
+pub trait LLMWrapperExt: LLMWrapper {
+    /// Generate a JSON reply and deserialize it into `T`. The request is sent in
+    /// JSON mode at temperature 0, constrained to `schema` if one is given, which
+    /// is also appended to the prompt. A reply that fails to deserialize is retried
+    /// once with the error appended to the prompt.
+    async fn generate_json<T: DeserializeOwned>(
+        &self,
+        prompt: &str,
+        schema: Option<Value>,
+    ) -> anyhow::Result<T> {
+        let mut options = LLMOptions::for_extraction();
+        let prompt = match schema {
+            Some(schema) => {
+                let prompt = format!(
+                    "{}\n\nRespond with JSON matching this JSON Schema:\n{}",
+                    prompt, schema
+                );
+                options = options.with_json_schema(schema);
+                prompt
+            }
+            None => prompt.to_string(),
+        };
+
+        let response = self.generate_with_options(&prompt, options.clone()).await?;
+        let error = match parse_json_reply(&response.content) {
+            Ok(value) => return Ok(value),
+            Err(e) => e,
+        };
+        warn!("Retrying malformed JSON reply: {}", error);
+        let retry_prompt = format!(
+            "{}\n\nYour previous reply could not be parsed: {}\nPrevious reply:\n{}\n\n\
+             Reply again with the corrected JSON only.",
+            prompt, error, response.content
+        );
+        let response = self.generate_with_options(&retry_prompt, options).await?;
+        parse_json_reply(&response.content)
+            .map_err(|e| Error::Llm(format!("reply is not the expected JSON: {}", e)).into())
+    }
+}
+
+impl<W: LLMWrapper + ?Sized> LLMWrapperExt for W {}
+
+/// Deserialize a JSON reply, ignoring a Markdown code fence around it.
+pub fn parse_json_reply<T: DeserializeOwned>(content: &str) -> serde_json::Result<T> {
+    serde_json::from_str(strip_code_fence(content))
+}
+
+/// `content` trimmed and without a Markdown code fence around it, with or without
+/// a `json` language tag.
+pub fn strip_code_fence(content: &str) -> &str {
+    let content = content.trim();
+    match content.strip_prefix("```") {
+        Some(fenced) => fenced
+            .trim_start_matches("json")
+            .trim_end_matches("```")
+            .trim(),
+        None => content,
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct LLMOptions {
+    /// Model to use for this request instead of the client's default.
+    pub model: Option<String>,
     pub temperature: Option<f32>,
     pub max_tokens: Option<i32>,
     pub top_p: Option<f32>,
@@ -40,16 +118,90 @@ pub struct LLMOptions {
     pub presence_penalty: Option<f32>,
     pub stop: Option<Vec<String>>,
     pub logit_bias: Option<HashMap<String, String, RandomState>>,
+    /// Ask for a JSON object reply (`response_format: json_object`).
+    pub json_mode: bool,
+    /// Constrain the reply to this JSON Schema (`response_format: json_schema`);
+    /// implies `json_mode`.
+    pub json_schema: Option<Value>,
 }
 
-#[allow(dead_code)]
+impl LLMOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Temperature 0, for tasks with one right answer such as rewriting or judging.
+    pub fn deterministic() -> Self {
+        Self::new().with_temperature(0.0)
+    }
+
+    /// A high temperature for open-ended generation such as suggestions.
+    pub fn creative() -> Self {
+        Self::new().with_temperature(0.9).with_top_p(0.95)
+    }
+
+    /// Temperature 0 in JSON mode, for structured extraction.
+    pub fn for_extraction() -> Self {
+        Self::deterministic().with_json_mode(true)
+    }
+
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: i32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn with_top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    pub fn with_frequency_penalty(mut self, frequency_penalty: f32) -> Self {
+        self.frequency_penalty = Some(frequency_penalty);
+        self
+    }
+
+    pub fn with_presence_penalty(mut self, presence_penalty: f32) -> Self {
+        self.presence_penalty = Some(presence_penalty);
+        self
+    }
+
+    pub fn with_stop(mut self, stop: Vec<String>) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    pub fn with_json_mode(mut self, json_mode: bool) -> Self {
+        self.json_mode = json_mode;
+        self
+    }
+
+    pub fn with_json_schema(mut self, schema: Value) -> Self {
+        self.json_mode = true;
+        self.json_schema = Some(schema);
+        self
+    }
+}
+
+#[cfg(feature = "openai")]
 pub struct OpenAIClient {
     api_key: String,
     model: String,
     endpoint: String,
     client: OpenAI,
+    http: Arc<reqwest::Client>,
 }
 
+#[cfg(feature = "openai")]
 impl OpenAIClient {
     pub fn new(api_key: String, model: String, endpoint: String) -> Self {
         let auth = Auth::new(&api_key);
@@ -59,10 +211,149 @@ impl OpenAIClient {
             model,
             endpoint,
             client,
+            http: Arc::new(reqwest::Client::new()),
         }
     }
+
+    /// Send streaming requests through `client` instead of a default one.
+    pub fn with_http_client(mut self, client: Arc<reqwest::Client>) -> Self {
+        self.http = client;
+        self
+    }
+
+    /// The chat completions request body for a raw HTTP call.
+    fn request_body(&self, prompt: &str, options: &LLMOptions, stream: bool) -> Value {
+        let mut body = json!({
+            "model": options.model.as_deref().unwrap_or(&self.model),
+            "messages": [{"role": "user", "content": prompt}],
+            "stream": stream,
+            "temperature": options.temperature,
+            "max_tokens": options.max_tokens,
+            "top_p": options.top_p,
+            "frequency_penalty": options.frequency_penalty,
+            "presence_penalty": options.presence_penalty,
+            "stop": options.stop,
+            "logit_bias": options.logit_bias,
+        });
+        if stream {
+            body["stream_options"] = json!({"include_usage": true});
+        }
+        if let Some(schema) = &options.json_schema {
+            body["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": {"name": "response", "schema": schema},
+            });
+        } else if options.json_mode {
+            body["response_format"] = json!({"type": "json_object"});
+        }
+        body
+    }
+
+    async fn post(&self, body: &Value) -> anyhow::Result<reqwest::Response> {
+        Ok(self
+            .http
+            .post(format!(
+                "{}/chat/completions",
+                self.endpoint.trim_end_matches('/')
+            ))
+            .bearer_auth(&self.api_key)
+            .json(body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(Error::from)?)
+    }
+
+    /// `ChatBody` has no `response_format`, so JSON mode requests go over raw HTTP.
+    async fn generate_over_http(
+        &self,
+        prompt: &str,
+        options: LLMOptions,
+    ) -> anyhow::Result<LLMResponse> {
+        info!("Sending JSON mode request to OpenAI API");
+        let response: Value = self
+            .post(&self.request_body(prompt, &options, false))
+            .await?
+            .json()
+            .await
+            .map_err(Error::from)?;
+        let content = response["choices"][0]["message"]["content"]
+            .as_str()
+            .ok_or_else(|| Error::Llm("response has no message".to_string()))?;
+        Ok(LLMResponse {
+            content: content.to_string(),
+            usage: parse_usage(&response["usage"]),
+            cached: false,
+        })
+    }
+
+    /// Stream a chat completion, calling `on_token` with each content delta as it
+    /// arrives, and return the full response once the stream ends.
+    pub async fn generate_with_callback(
+        &self,
+        prompt: &str,
+        options: LLMOptions,
+        mut on_token: impl FnMut(&str) + Send,
+    ) -> anyhow::Result<LLMResponse> {
+        info!("Sending streaming request to OpenAI API");
+        let mut response = self
+            .post(&self.request_body(prompt, &options, true))
+            .await?;
+
+        let mut content = String::new();
+        let mut usage = None;
+        // Raw bytes, as a character may be split across network chunks
+        let mut buffer: Vec<u8> = Vec::new();
+        'stream: while let Some(chunk) = response.chunk().await.map_err(Error::from)? {
+            buffer.extend_from_slice(&chunk);
+
+            // Server-sent events: handle each complete "data: ..." line
+            while let Some(newline) = buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=newline).collect();
+                let line = std::str::from_utf8(&line)?;
+                let Some(data) = line.trim().strip_prefix("data:") else {
+                    continue;
+                };
+                let data = data.trim();
+                if data == "[DONE]" {
+                    break 'stream;
+                }
+
+                let event: Value = serde_json::from_str(data)?;
+                if let Some(delta) = event["choices"][0]["delta"]["content"].as_str()
+                    && !delta.is_empty()
+                {
+                    on_token(delta);
+                    content.push_str(delta);
+                }
+                if let Some(u) = event.get("usage").and_then(parse_usage) {
+                    usage = Some(u);
+                }
+            }
+        }
+
+        Ok(LLMResponse {
+            content,
+            usage,
+            cached: false,
+        })
+    }
 }
 
+#[cfg(feature = "openai")]
+fn parse_usage(u: &Value) -> Option<LLMUsage> {
+    if u.is_null() {
+        return None;
+    }
+    let tokens = |key: &str| u[key].as_u64().map(|n| n as u32);
+    Some(LLMUsage {
+        prompt_tokens: tokens("prompt_tokens"),
+        completion_tokens: tokens("completion_tokens"),
+        total_tokens: tokens("total_tokens"),
+    })
+}
+
+#[cfg(feature = "openai")]
 #[async_trait]
 impl LLMWrapper for OpenAIClient {
     async fn generate(&self, prompt: &str) -> anyhow::Result<LLMResponse> {
@@ -75,8 +366,11 @@ impl LLMWrapper for OpenAIClient {
         prompt: &str,
         options: LLMOptions,
     ) -> anyhow::Result<LLMResponse> {
+        if options.json_mode || options.json_schema.is_some() {
+            return self.generate_over_http(prompt, options).await;
+        }
         let chat = ChatBody {
-            model: self.model.clone(),
+            model: options.model.unwrap_or_else(|| self.model.clone()),
             temperature: options.temperature,
             max_tokens: options.max_tokens,
             presence_penalty: options.presence_penalty,
@@ -94,9 +388,16 @@ impl LLMWrapper for OpenAIClient {
         };
 
         info!("Sending request to OpenAI API");
-        let response = self.client.chat_completion_create(&chat).unwrap();
-        let choice = response.choices;
-        let content = &choice[0].message.as_ref().unwrap().content;
+        let response = self
+            .client
+            .chat_completion_create(&chat)
+            .map_err(|e| Error::Llm(e.to_string()))?;
+        let content = &response
+            .choices
+            .first()
+            .and_then(|choice| choice.message.as_ref())
+            .ok_or_else(|| Error::Llm("response has no message".to_string()))?
+            .content;
         let u = response.usage;
         let usage = LLMUsage {
             prompt_tokens: u.prompt_tokens,
@@ -107,6 +408,719 @@ impl LLMWrapper for OpenAIClient {
         Ok(LLMResponse {
             content: content.clone(),
             usage: Some(usage),
+            cached: false,
+        })
+    }
+}
+
+/// A client for Anthropic's Messages API. `LLMOptions` map onto the request as
+/// `temperature`, `max_tokens` (required by the API, 4096 if unset), `top_p` and
+/// `stop_sequences`; the penalties and `logit_bias` have no equivalent and are
+/// ignored. JSON mode, with or without a schema, prefills the reply with `{` so the
+/// model continues a JSON object.
+#[cfg(feature = "anthropic")]
+pub struct AnthropicClient {
+    api_key: String,
+    model: String,
+    endpoint: String,
+    http: Arc<reqwest::Client>,
+}
+
+#[cfg(feature = "anthropic")]
+impl AnthropicClient {
+    pub const DEFAULT_ENDPOINT: &str = "https://api.anthropic.com/v1";
+    const API_VERSION: &str = "2023-06-01";
+    const DEFAULT_MAX_TOKENS: i32 = 4096;
+
+    pub fn new(api_key: String, model: String, endpoint: String) -> Self {
+        Self {
+            api_key,
+            model,
+            endpoint,
+            http: Arc::new(reqwest::Client::new()),
+        }
+    }
+
+    pub fn with_http_client(mut self, client: Arc<reqwest::Client>) -> Self {
+        self.http = client;
+        self
+    }
+
+    fn request_body(&self, prompt: &str, options: &LLMOptions) -> Value {
+        let mut messages = vec![json!({"role": "user", "content": prompt})];
+        if options.json_mode || options.json_schema.is_some() {
+            messages.push(json!({"role": "assistant", "content": "{"}));
+        }
+        let mut body = json!({
+            "model": options.model.as_deref().unwrap_or(&self.model),
+            "max_tokens": options.max_tokens.unwrap_or(Self::DEFAULT_MAX_TOKENS),
+            "messages": messages,
+        });
+        if let Some(temperature) = options.temperature {
+            body["temperature"] = json!(temperature);
+        }
+        if let Some(top_p) = options.top_p {
+            body["top_p"] = json!(top_p);
+        }
+        if let Some(stop) = &options.stop {
+            body["stop_sequences"] = json!(stop);
+        }
+        body
+    }
+}
+
+#[cfg(feature = "anthropic")]
+#[async_trait]
+impl LLMWrapper for AnthropicClient {
+    async fn generate(&self, prompt: &str) -> anyhow::Result<LLMResponse> {
+        self.generate_with_options(prompt, LLMOptions::default())
+            .await
+    }
+
+    async fn generate_with_options(
+        &self,
+        prompt: &str,
+        options: LLMOptions,
+    ) -> anyhow::Result<LLMResponse> {
+        info!("Sending request to Anthropic API");
+        let response = self
+            .http
+            .post(format!("{}/messages", self.endpoint.trim_end_matches('/')))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", Self::API_VERSION)
+            .json(&self.request_body(prompt, &options))
+            .send()
+            .await
+            .map_err(Error::from)?;
+        let status = response.status();
+        let response: Value = response.json().await.map_err(Error::from)?;
+        if !status.is_success() {
+            let message = response["error"]["message"]
+                .as_str()
+                .unwrap_or("no error message");
+            return Err(Error::Llm(format!("{}: {}", status, message)).into());
+        }
+
+        let mut content: String = response["content"]
+            .as_array()
+            .ok_or_else(|| Error::Llm("response has no content".to_string()))?
+            .iter()
+            .filter(|block| block["type"] == "text")
+            .filter_map(|block| block["text"].as_str())
+            .collect();
+        if options.json_mode || options.json_schema.is_some() {
+            content.insert(0, '{');
+        }
+        let tokens = |key: &str| response["usage"][key].as_u64().map(|n| n as u32);
+        let (input_tokens, output_tokens) = (tokens("input_tokens"), tokens("output_tokens"));
+        let usage = LLMUsage {
+            prompt_tokens: input_tokens,
+            completion_tokens: output_tokens,
+            total_tokens: input_tokens.zip(output_tokens).map(|(i, o)| i + o),
+        };
+
+        Ok(LLMResponse {
+            content,
+            usage: Some(usage),
+            cached: false,
+        })
+    }
+}
+
+/// Caches responses of an inner [`LLMWrapper`] in a [`KeyValueStore`], keyed on the
+/// SHA-256 of model, prompt and options. Calls with a temperature above
+/// `max_temperature` bypass the cache unless `force` is set; an unset temperature
+/// is cached.
+pub struct CachingLLM<T: LLMWrapper> {
+    inner: T,
+    store: Arc<dyn KeyValueStore>,
+    model: String,
+    max_temperature: f32,
+    force: bool,
+}
+
+impl<T: LLMWrapper> CachingLLM<T> {
+    pub fn new(inner: T, store: Arc<dyn KeyValueStore>, model: impl Into<String>) -> Self {
+        Self {
+            inner,
+            store,
+            model: model.into(),
+            max_temperature: 0.3,
+            force: false,
+        }
+    }
+
+    pub fn with_max_temperature(mut self, max_temperature: f32) -> Self {
+        self.max_temperature = max_temperature;
+        self
+    }
+
+    /// Cache every response regardless of temperature.
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    fn cache_key(&self, prompt: &str, options: &LLMOptions) -> String {
+        let logit_bias = options
+            .logit_bias
+            .as_ref()
+            .map(|bias| bias.iter().collect::<BTreeMap<_, _>>());
+        let mut key = json!({
+            "model": options.model.as_deref().unwrap_or(&self.model),
+            "prompt": prompt,
+            "temperature": options.temperature,
+            "max_tokens": options.max_tokens,
+            "top_p": options.top_p,
+            "frequency_penalty": options.frequency_penalty,
+            "presence_penalty": options.presence_penalty,
+            "stop": options.stop,
+            "logit_bias": logit_bias,
+        });
+        if options.json_mode {
+            key["json_mode"] = json!(true);
+        }
+        if let Some(schema) = &options.json_schema {
+            key["json_schema"] = schema.clone();
+        }
+        format!("llm:{:x}", Sha256::digest(key.to_string().as_bytes()))
+    }
+}
+
+#[async_trait]
+impl<T: LLMWrapper> LLMWrapper for CachingLLM<T> {
+    async fn generate(&self, prompt: &str) -> anyhow::Result<LLMResponse> {
+        self.generate_with_options(prompt, LLMOptions::default())
+            .await
+    }
+
+    async fn generate_with_options(
+        &self,
+        prompt: &str,
+        options: LLMOptions,
+    ) -> anyhow::Result<LLMResponse> {
+        let cacheable = self.force
+            || options
+                .temperature
+                .is_none_or(|temperature| temperature <= self.max_temperature);
+        if !cacheable {
+            return self.inner.generate_with_options(prompt, options).await;
+        }
+
+        let key = self.cache_key(prompt, &options);
+        if let Some(value) = self.store.get(&key).await? {
+            debug!("LLM cache hit: {}", key);
+            let mut response: LLMResponse = serde_json::from_value(value)?;
+            response.cached = true;
+            return Ok(response);
+        }
+
+        let response = self.inner.generate_with_options(prompt, options).await?;
+        self.store
+            .set(&key, serde_json::to_value(&response)?)
+            .await?;
+        Ok(response)
+    }
+}
+
+/// Tries an ordered list of providers, moving on to the next when one fails with a
+/// retryable error. Returns the first success, or the last error once all have failed.
+/// By default every error is retryable; narrow this with `with_retryable`.
+pub struct FallbackLLM {
+    providers: Vec<Arc<dyn LLMWrapper>>,
+    is_retryable: Box<dyn Fn(&anyhow::Error) -> bool + Send + Sync>,
+}
+
+impl FallbackLLM {
+    pub fn new(providers: Vec<Arc<dyn LLMWrapper>>) -> Self {
+        Self {
+            providers,
+            is_retryable: Box::new(|_| true),
+        }
+    }
+
+    pub fn with_retryable<F>(mut self, is_retryable: F) -> Self
+    where
+        F: Fn(&anyhow::Error) -> bool + Send + Sync + 'static,
+    {
+        self.is_retryable = Box::new(is_retryable);
+        self
+    }
+}
+
+#[async_trait]
+impl LLMWrapper for FallbackLLM {
+    async fn generate(&self, prompt: &str) -> anyhow::Result<LLMResponse> {
+        self.generate_with_options(prompt, LLMOptions::default())
+            .await
+    }
+
+    async fn generate_with_options(
+        &self,
+        prompt: &str,
+        options: LLMOptions,
+    ) -> anyhow::Result<LLMResponse> {
+        let mut last_error = anyhow::anyhow!("FallbackLLM has no providers");
+        for (index, provider) in self.providers.iter().enumerate() {
+            match provider
+                .generate_with_options(prompt, options.clone())
+                .await
+            {
+                Ok(response) => {
+                    info!("LLM request served by provider {}", index);
+                    return Ok(response);
+                }
+                Err(e) if (self.is_retryable)(&e) => {
+                    warn!("LLM provider {} failed, falling back: {}", index, e);
+                    last_error = e;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::ScriptedLLM;
+    use crate::utils::kv_store::InMemoryKeyValueStore;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingLLM {
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl LLMWrapper for CountingLLM {
+        async fn generate(&self, prompt: &str) -> anyhow::Result<LLMResponse> {
+            self.generate_with_options(prompt, LLMOptions::default())
+                .await
+        }
+
+        #[allow(unused_variables)]
+        async fn generate_with_options(
+            &self,
+            prompt: &str,
+            options: LLMOptions,
+        ) -> anyhow::Result<LLMResponse> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(LLMResponse {
+                content: format!("answer {}", call),
+                usage: Some(LLMUsage {
+                    prompt_tokens: Some(10),
+                    completion_tokens: Some(5),
+                    total_tokens: Some(15),
+                }),
+                cached: false,
+            })
+        }
+    }
+
+    fn caching_llm() -> (CachingLLM<CountingLLM>, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let llm = CachingLLM::new(
+            CountingLLM {
+                calls: calls.clone(),
+            },
+            Arc::new(InMemoryKeyValueStore::new()),
+            "test-model",
+        );
+        (llm, calls)
+    }
+
+    #[cfg(feature = "openai")]
+    #[tokio::test]
+    async fn test_generate_with_callback_streams_deltas() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpListener;
+
+        let events = [
+            r#"{"choices":[{"delta":{"role":"assistant"}}]}"#,
+            r#"{"choices":[{"delta":{"content":"Hello"}}]}"#,
+            r#"{"choices":[{"delta":{"content":", "}}]}"#,
+            r#"{"choices":[{"delta":{"content":"world"}}]}"#,
+            r#"{"choices":[{"delta":{"content":"，世界"}}]}"#,
+            r#"{"choices":[],"usage":{"prompt_tokens":3,"completion_tokens":3,"total_tokens":6}}"#,
+            "[DONE]",
+        ];
+        let body: String = events
+            .iter()
+            .map(|event| format!("data: {}\n\n", event))
+            .collect();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 8192];
+            let _ = socket.read(&mut buf).await.unwrap();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            // Send the body in two chunks split inside "世"
+            let split = response.find('世').unwrap() + 1;
+            socket
+                .write_all(&response.as_bytes()[..split])
+                .await
+                .unwrap();
+            socket.flush().await.unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            socket
+                .write_all(&response.as_bytes()[split..])
+                .await
+                .unwrap();
+        });
+
+        let client = OpenAIClient::new(
+            "test-key".to_string(),
+            "test-model".to_string(),
+            format!("http://{}/v1", addr),
+        );
+        let mut tokens = Vec::new();
+        let response = client
+            .generate_with_callback("Say hello", LLMOptions::default(), |token| {
+                tokens.push(token.to_string())
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(tokens, vec!["Hello", ", ", "world", "，世界"]);
+        assert_eq!(response.content, tokens.concat());
+        assert_eq!(response.usage.unwrap().total_tokens, Some(6));
+    }
+
+    /// Serves one request with `response` and returns the request body.
+    fn capture_request(response: Value) -> (String, std::thread::JoinHandle<Value>) {
+        use std::io::{BufRead, BufReader, Read, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(socket.try_clone().unwrap());
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':')
+                    && name.eq_ignore_ascii_case("content-length")
+                {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0u8; content_length];
+            reader.read_exact(&mut body).unwrap();
+
+            let response = response.to_string();
+            write!(
+                socket,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                response.len(),
+                response
+            )
+            .unwrap();
+            serde_json::from_slice::<Value>(&body).unwrap()
+        });
+        (format!("http://{}/v1/", addr), server)
+    }
+
+    /// A chat completion replying "ok".
+    #[cfg(feature = "openai")]
+    fn chat_completion() -> Value {
+        json!({
+            "choices": [{"index": 0, "message": {"role": "assistant", "content": "ok"}}],
+            "usage": {"prompt_tokens": 1, "completion_tokens": 1, "total_tokens": 2},
         })
     }
+
+    #[cfg(feature = "openai")]
+    #[tokio::test]
+    async fn test_options_model_overrides_client_default() {
+        let (endpoint, server) = capture_request(chat_completion());
+        let client = OpenAIClient::new(
+            "test-key".to_string(),
+            "default-model".to_string(),
+            endpoint,
+        );
+        let options = LLMOptions {
+            model: Some("override-model".to_string()),
+            ..LLMOptions::default()
+        };
+        let response = client
+            .generate_with_options("classify this", options)
+            .await
+            .unwrap();
+
+        assert_eq!(response.content, "ok");
+        assert_eq!(server.join().unwrap()["model"], "override-model");
+    }
+
+    #[cfg(feature = "openai")]
+    #[tokio::test]
+    async fn test_json_mode_sets_response_format() {
+        let (endpoint, server) = capture_request(chat_completion());
+        let client = OpenAIClient::new(
+            "test-key".to_string(),
+            "default-model".to_string(),
+            endpoint,
+        );
+        let response = client
+            .generate_with_options("extract this", LLMOptions::for_extraction())
+            .await
+            .unwrap();
+
+        assert_eq!(response.content, "ok");
+        assert_eq!(response.usage.unwrap().total_tokens, Some(2));
+        let body = server.join().unwrap();
+        assert_eq!(body["response_format"], json!({"type": "json_object"}));
+        assert_eq!(body["temperature"], json!(0.0));
+        assert_eq!(body["stream"], json!(false));
+    }
+
+    #[cfg(feature = "openai")]
+    #[tokio::test]
+    async fn test_json_schema_sets_response_format() {
+        let (endpoint, server) = capture_request(chat_completion());
+        let client = OpenAIClient::new(
+            "test-key".to_string(),
+            "default-model".to_string(),
+            endpoint,
+        );
+        let schema = json!({"type": "object", "properties": {"query": {"type": "string"}}});
+        let options = LLMOptions::default().with_json_schema(schema.clone());
+        assert!(options.json_mode);
+        client
+            .generate_with_options("rewrite this", options)
+            .await
+            .unwrap();
+
+        let body = server.join().unwrap();
+        assert_eq!(
+            body["response_format"],
+            json!({
+                "type": "json_schema",
+                "json_schema": {"name": "response", "schema": schema},
+            })
+        );
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Rewrite {
+        query: String,
+    }
+
+    #[tokio::test]
+    async fn test_generate_json_deserializes_reply() {
+        let llm = ScriptedLLM::new(&["```json\n{\"query\": \"pangu storage\"}\n```"]);
+        let schema = json!({"type": "object", "required": ["query"]});
+
+        let rewrite: Rewrite = llm
+            .generate_json("rewrite this", Some(schema.clone()))
+            .await
+            .unwrap();
+
+        assert_eq!(rewrite.query, "pangu storage");
+        let requests = llm.requests.lock().unwrap();
+        let (prompt, options) = &requests[0];
+        assert!(prompt.starts_with("rewrite this"));
+        assert!(prompt.contains(&schema.to_string()));
+        assert!(options.json_mode);
+        assert_eq!(options.json_schema, Some(schema));
+        assert_eq!(options.temperature, Some(0.0));
+    }
+
+    #[tokio::test]
+    async fn test_generate_json_retries_malformed_reply_once() {
+        let llm = ScriptedLLM::new(&["{\"query\": pangu", "{\"query\": \"pangu\"}"]);
+        let rewrite: Rewrite = llm.generate_json("rewrite this", None).await.unwrap();
+        assert_eq!(rewrite.query, "pangu");
+        {
+            let requests = llm.requests.lock().unwrap();
+            assert_eq!(requests.len(), 2);
+            assert!(requests[1].0.contains("could not be parsed"));
+            assert!(requests[1].0.contains("{\"query\": pangu"));
+        }
+
+        // A second malformed reply fails, without a third request
+        let llm = ScriptedLLM::new(&["not json", "{\"answer\": 42}"]);
+        let error = llm
+            .generate_json::<Rewrite>("rewrite this", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(error.downcast_ref::<Error>(), Some(Error::Llm(_))));
+        assert_eq!(llm.requests.lock().unwrap().len(), 2);
+    }
+
+    #[cfg(feature = "anthropic")]
+    #[tokio::test]
+    async fn test_anthropic_request_and_response() {
+        let (endpoint, server) = capture_request(json!({
+            "type": "message",
+            "role": "assistant",
+            "content": [
+                {"type": "text", "text": "\"answer\": "},
+                {"type": "text", "text": "42}"},
+            ],
+            "usage": {"input_tokens": 12, "output_tokens": 5},
+        }));
+        let client = AnthropicClient::new(
+            "test-key".to_string(),
+            "claude-default".to_string(),
+            endpoint,
+        );
+        let options = LLMOptions::for_extraction()
+            .with_top_p(0.9)
+            .with_stop(vec!["END".to_string()]);
+        let response = client
+            .generate_with_options("extract the answer", options)
+            .await
+            .unwrap();
+
+        assert_eq!(response.content, "{\"answer\": 42}");
+        let usage = response.usage.unwrap();
+        assert_eq!(usage.prompt_tokens, Some(12));
+        assert_eq!(usage.completion_tokens, Some(5));
+        assert_eq!(usage.total_tokens, Some(17));
+        let body = server.join().unwrap();
+        assert_eq!(body["model"], "claude-default");
+        assert_eq!(body["max_tokens"], json!(4096));
+        assert_eq!(body["temperature"], json!(0.0));
+        assert_eq!(body["stop_sequences"], json!(["END"]));
+        assert_eq!(
+            body["messages"],
+            json!([
+                {"role": "user", "content": "extract the answer"},
+                {"role": "assistant", "content": "{"},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_strip_code_fence() {
+        assert_eq!(strip_code_fence("  [1, 2] "), "[1, 2]");
+        assert_eq!(strip_code_fence("```json\n[1, 2]\n```"), "[1, 2]");
+        assert_eq!(strip_code_fence("```\n{\"a\": 1}\n```\n"), "{\"a\": 1}");
+        let value: Value = parse_json_reply("```json\n{\"a\": 1}\n```").unwrap();
+        assert_eq!(value, json!({"a": 1}));
+    }
+
+    #[test]
+    fn test_presets() {
+        let deterministic = LLMOptions::deterministic();
+        assert_eq!(deterministic.temperature, Some(0.0));
+        assert!(!deterministic.json_mode);
+
+        let creative = LLMOptions::creative();
+        assert_eq!(creative.temperature, Some(0.9));
+        assert_eq!(creative.top_p, Some(0.95));
+        assert!(!creative.json_mode);
+
+        let extraction = LLMOptions::for_extraction().with_max_tokens(256);
+        assert_eq!(extraction.temperature, Some(0.0));
+        assert!(extraction.json_mode);
+        assert_eq!(extraction.max_tokens, Some(256));
+        assert_eq!(extraction.model, None);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_prompt_hits_cache() {
+        let (llm, calls) = caching_llm();
+
+        let first = llm.generate("rewrite this query").await.unwrap();
+        let second = llm.generate("rewrite this query").await.unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(!first.cached);
+        assert!(second.cached);
+        assert_eq!(second.content, "answer 0");
+        assert_eq!(second.usage.unwrap().total_tokens, Some(15));
+    }
+
+    #[tokio::test]
+    async fn test_high_temperature_skips_cache() {
+        let (llm, calls) = caching_llm();
+        let options = LLMOptions {
+            temperature: Some(0.9),
+            ..LLMOptions::default()
+        };
+
+        llm.generate_with_options("tell a story", options.clone())
+            .await
+            .unwrap();
+        llm.generate_with_options("tell a story", options.clone())
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let llm = llm.with_force(true);
+        llm.generate_with_options("tell a story", options.clone())
+            .await
+            .unwrap();
+        let forced = llm
+            .generate_with_options("tell a story", options)
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert!(forced.cached);
+    }
+
+    struct FailingLLM;
+
+    #[async_trait]
+    impl LLMWrapper for FailingLLM {
+        async fn generate(&self, prompt: &str) -> anyhow::Result<LLMResponse> {
+            self.generate_with_options(prompt, LLMOptions::default())
+                .await
+        }
+
+        #[allow(unused_variables)]
+        async fn generate_with_options(
+            &self,
+            prompt: &str,
+            options: LLMOptions,
+        ) -> anyhow::Result<LLMResponse> {
+            Err(anyhow::anyhow!("provider unavailable"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fallback_to_second_provider() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let fallback = FallbackLLM::new(vec![
+            Arc::new(FailingLLM),
+            Arc::new(CountingLLM {
+                calls: calls.clone(),
+            }),
+        ]);
+
+        let response = fallback.generate("hello").await.unwrap();
+
+        assert_eq!(response.content, "answer 0");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_stops_on_non_retryable_error() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let fallback = FallbackLLM::new(vec![
+            Arc::new(FailingLLM),
+            Arc::new(CountingLLM {
+                calls: calls.clone(),
+            }),
+        ])
+        .with_retryable(|e| !e.to_string().contains("unavailable"));
+
+        let error = fallback.generate("hello").await.unwrap_err();
+
+        assert_eq!(error.to_string(), "provider unavailable");
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
 }
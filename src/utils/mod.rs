@@ -1,6 +1,16 @@
+pub mod backoff;
+pub mod bm25;
+pub mod concurrency;
+pub mod content_fetcher;
 pub mod embedding;
+pub mod hnsw_vector;
+pub mod kv_store;
 pub mod llm_wrapper;
+pub mod pg_vector;
+pub mod prompt_template;
+pub mod qdrant;
 pub mod text_chunking;
+pub mod translation;
 pub mod vector_db;
 pub mod viz_debug;
 pub mod web_search;
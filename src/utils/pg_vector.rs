@@ -0,0 +1,339 @@
+#![cfg(feature = "pgvector")]
+
+use crate::error::Error;
+use crate::utils::vector_db::{DEFAULT_MAX_K, DistanceMetric, ScrollPage, VectorDB, VectorRecord};
+use ::pgvector::Vector;
+use async_trait::async_trait;
+use serde_json::{Map, Value};
+use sqlx::postgres::{PgPool, PgPoolOptions, PgRow};
+use sqlx::{AssertSqlSafe, Row, types::Json};
+use tracing::{info, warn};
+
+/// A [`VectorDB`] kept in a Postgres table through the pgvector extension, one row
+/// per record: `id text primary key, embedding vector(dimension), metadata jsonb`.
+/// It behaves like `QdrantDB`: inserts upsert by id, searches are clamped to
+/// `max_k` results, and scores are the cosine similarity, the dot product or the
+/// euclidean distance, depending on the metric. Sparse vectors are not supported.
+pub struct PgVectorDB {
+    pool: PgPool,
+    table: String,
+    dimension: usize,
+    distance_metric: DistanceMetric,
+    max_k: usize,
+}
+
+impl PgVectorDB {
+    /// Connect to `database_url` and create the pgvector extension, `table` and an
+    /// HNSW index for `distance_metric` if they don't exist yet.
+    pub async fn new(
+        database_url: &str,
+        table: &str,
+        dimension: usize,
+        distance_metric: DistanceMetric,
+    ) -> anyhow::Result<Self> {
+        if !is_identifier(table) {
+            return Err(anyhow::anyhow!(
+                "Invalid table name '{}': use letters, digits and underscores only",
+                table
+            ));
+        }
+        let pool = PgPoolOptions::new()
+            .connect(database_url)
+            .await
+            .map_err(Error::from)?;
+
+        let db = Self {
+            pool,
+            table: table.to_string(),
+            dimension,
+            distance_metric,
+            max_k: DEFAULT_MAX_K,
+        };
+        db.create_table().await?;
+        Ok(db)
+    }
+
+    pub fn with_max_k(mut self, max_k: usize) -> Self {
+        self.max_k = max_k;
+        self
+    }
+
+    async fn create_table(&self) -> anyhow::Result<()> {
+        let statements = [
+            "CREATE EXTENSION IF NOT EXISTS vector".to_string(),
+            format!(
+                "CREATE TABLE IF NOT EXISTS \"{}\" (\
+                 id TEXT PRIMARY KEY, \
+                 embedding vector({}) NOT NULL, \
+                 metadata JSONB NOT NULL DEFAULT '{{}}')",
+                self.table, self.dimension
+            ),
+            format!(
+                "CREATE INDEX IF NOT EXISTS \"{}_embedding_idx\" ON \"{}\" USING hnsw (embedding {})",
+                self.table,
+                self.table,
+                operator_class(&self.distance_metric)
+            ),
+        ];
+        for statement in statements {
+            sqlx::query(AssertSqlSafe(statement))
+                .execute(&self.pool)
+                .await
+                .map_err(Error::from)?;
+        }
+        Ok(())
+    }
+
+    fn clamp_k(&self, k: usize) -> usize {
+        if k > self.max_k {
+            warn!("Clamping search k from {} to {}", k, self.max_k);
+            self.max_k
+        } else {
+            k
+        }
+    }
+}
+
+/// Whether `name` can be used as a table name without escaping.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn operator(metric: &DistanceMetric) -> &'static str {
+    match metric {
+        DistanceMetric::Cosine => "<=>",
+        DistanceMetric::Euclidean => "<->",
+        DistanceMetric::DotProduct => "<#>",
+    }
+}
+
+fn operator_class(metric: &DistanceMetric) -> &'static str {
+    match metric {
+        DistanceMetric::Cosine => "vector_cosine_ops",
+        DistanceMetric::Euclidean => "vector_l2_ops",
+        DistanceMetric::DotProduct => "vector_ip_ops",
+    }
+}
+
+/// The score Qdrant reports for a pgvector `distance`: `<=>` is one minus the
+/// cosine similarity and `<#>` the negated dot product.
+fn score(metric: &DistanceMetric, distance: f64) -> f32 {
+    let score = match metric {
+        DistanceMetric::Cosine => 1.0 - distance,
+        DistanceMetric::Euclidean => distance,
+        DistanceMetric::DotProduct => -distance,
+    };
+    score as f32
+}
+
+fn record_from_row(row: &PgRow) -> anyhow::Result<VectorRecord> {
+    let embedding: Vector = row.try_get("embedding").map_err(Error::from)?;
+    let metadata: Json<Map<String, Value>> = row.try_get("metadata").map_err(Error::from)?;
+    Ok(VectorRecord {
+        id: row.try_get("id").map_err(Error::from)?,
+        vector: embedding.to_vec(),
+        sparse_vector: None,
+        metadata: metadata.0,
+        score: None,
+    })
+}
+
+#[async_trait]
+impl VectorDB for PgVectorDB {
+    fn dimension(&self) -> Option<usize> {
+        Some(self.dimension)
+    }
+
+    fn distance_metric(&self) -> Option<DistanceMetric> {
+        Some(self.distance_metric.clone())
+    }
+
+    async fn insert(&self, records: Vec<VectorRecord>) -> anyhow::Result<()> {
+        let upsert = format!(
+            "INSERT INTO \"{}\" (id, embedding, metadata) VALUES ($1, $2, $3) \
+             ON CONFLICT (id) DO UPDATE SET embedding = EXCLUDED.embedding, metadata = EXCLUDED.metadata",
+            self.table
+        );
+
+        info!("Inserting {} rows into '{}'", records.len(), self.table);
+        let mut transaction = self.pool.begin().await.map_err(Error::from)?;
+        for record in records {
+            if record.sparse_vector.is_some() {
+                return Err(anyhow::anyhow!(
+                    "Record {} has a sparse vector, which table '{}' cannot store",
+                    record.id,
+                    self.table
+                ));
+            }
+            if record.vector.len() != self.dimension {
+                return Err(anyhow::anyhow!(
+                    "Record {} has {} dimensions, table '{}' expects {}",
+                    record.id,
+                    record.vector.len(),
+                    self.table,
+                    self.dimension
+                ));
+            }
+            sqlx::query(AssertSqlSafe(upsert.as_str()))
+                .bind(record.id)
+                .bind(Vector::from(record.vector))
+                .bind(Json(record.metadata))
+                .execute(&mut *transaction)
+                .await
+                .map_err(Error::from)?;
+        }
+        transaction.commit().await.map_err(Error::from)?;
+        Ok(())
+    }
+
+    async fn search(&self, query: Vec<f32>, k: usize) -> anyhow::Result<Vec<VectorRecord>> {
+        info!("Searching rows in '{}'", self.table);
+        let k = self.clamp_k(k);
+        let rows = sqlx::query(AssertSqlSafe(format!(
+            "SELECT id, embedding, metadata, embedding {} $1 AS distance FROM \"{}\" \
+             ORDER BY distance LIMIT $2",
+            operator(&self.distance_metric),
+            self.table
+        )))
+        .bind(Vector::from(query))
+        .bind(k as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::from)?;
+
+        let results = rows
+            .iter()
+            .map(|row| {
+                let mut record = record_from_row(row)?;
+                let distance: f64 = row.try_get("distance").map_err(Error::from)?;
+                record.score = Some(score(&self.distance_metric, distance));
+                Ok(record)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        info!("Retrieved results len: {:?}", results.len());
+
+        Ok(results)
+    }
+
+    async fn delete(&self, ids: Vec<String>) -> anyhow::Result<()> {
+        info!("Deleting rows from '{}'", self.table);
+        sqlx::query(AssertSqlSafe(format!(
+            "DELETE FROM \"{}\" WHERE id = ANY($1)",
+            self.table
+        )))
+        .bind(ids)
+        .execute(&self.pool)
+        .await
+        .map_err(Error::from)?;
+        Ok(())
+    }
+
+    /// Pages through the rows in id order; the offset is the id the next page
+    /// starts at.
+    async fn scroll(&self, offset: Option<String>, limit: usize) -> anyhow::Result<ScrollPage> {
+        info!("Scrolling rows in '{}'", self.table);
+        // One row more than the page says where the next one starts
+        let mut rows = sqlx::query(AssertSqlSafe(format!(
+            "SELECT id, embedding, metadata FROM \"{}\" \
+             WHERE $1::text IS NULL OR id >= $1 ORDER BY id LIMIT $2",
+            self.table
+        )))
+        .bind(offset)
+        .bind(limit as i64 + 1)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::from)?;
+
+        let next_offset = if rows.len() > limit {
+            rows.pop()
+                .map(|row| row.try_get("id"))
+                .transpose()
+                .map_err(Error::from)?
+        } else {
+            None
+        };
+        let records = rows
+            .iter()
+            .map(record_from_row)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(ScrollPage {
+            records,
+            next_offset,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_table_names_and_scores() {
+        assert!(is_identifier("documents"));
+        assert!(is_identifier("_chunks_v2"));
+        assert!(!is_identifier("2chunks"));
+        assert!(!is_identifier("chunks\"; DROP TABLE users; --"));
+        assert!(!is_identifier(""));
+
+        // Scores match Qdrant's: higher is closer, except for euclidean distance
+        assert_eq!(score(&DistanceMetric::Cosine, 0.25), 0.75);
+        assert_eq!(score(&DistanceMetric::DotProduct, -2.5), 2.5);
+        assert_eq!(score(&DistanceMetric::Euclidean, 1.5), 1.5);
+    }
+
+    #[tokio::test]
+    #[ignore = "E2E case, requires Postgres with pgvector at PGVECTOR_URL"]
+    async fn test_e2e_pgvector() {
+        let table = format!("pgvector_test_{}", std::process::id());
+        let db = PgVectorDB::new(
+            &std::env::var("PGVECTOR_URL").unwrap(),
+            &table,
+            2,
+            DistanceMetric::Cosine,
+        )
+        .await
+        .unwrap();
+        let record = |id: &str, vector: Vec<f32>| VectorRecord {
+            id: id.to_string(),
+            vector,
+            sparse_vector: None,
+            metadata: Map::from_iter([("text".to_string(), json!(id))]),
+            score: None,
+        };
+
+        db.insert(vec![
+            record("a", vec![1.0, 0.0]),
+            record("b", vec![0.0, 1.0]),
+        ])
+        .await
+        .unwrap();
+        // Upserting by id replaces the row
+        db.insert(vec![record("b", vec![0.6, 0.8])]).await.unwrap();
+
+        let results = db.search(vec![0.0, 1.0], 2).await.unwrap();
+        assert_eq!(results[0].id, "b");
+        assert_eq!(results[0].vector, vec![0.6, 0.8]);
+        assert!((results[0].score.unwrap() - 0.8).abs() < 1e-6);
+        assert_eq!(results[0].metadata["text"], json!("b"));
+
+        let page = db.scroll(None, 1).await.unwrap();
+        assert_eq!(page.records[0].id, "a");
+        assert_eq!(page.next_offset.as_deref(), Some("b"));
+
+        db.delete(vec!["a".to_string()]).await.unwrap();
+        let page = db.scroll(None, 10).await.unwrap();
+        assert_eq!(page.records.len(), 1);
+        assert_eq!(page.next_offset, None);
+
+        sqlx::query(AssertSqlSafe(format!("DROP TABLE \"{}\"", table)))
+            .execute(&db.pool)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,181 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// A prompt with `{{var}}` placeholders, filled in by [`PromptTemplate::render`].
+/// Whitespace inside the braces is ignored, so `{{ var }}` works too. Single braces
+/// are literal, as in JSON examples, and a backslash makes a brace literal, so
+/// `\{{var}}` renders as `{{var}}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptTemplate {
+    template: String,
+}
+
+/// A piece of a parsed template.
+enum Part<'a> {
+    Text(&'a str),
+    Var(&'a str),
+}
+
+impl PromptTemplate {
+    pub fn new(template: impl Into<String>) -> Self {
+        Self {
+            template: template.into(),
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let template = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read prompt template {:?}", path))?;
+        Ok(Self::new(template))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    /// The names of the template's variables, in order of first use.
+    pub fn variables(&self) -> Result<Vec<&str>> {
+        let mut names = Vec::new();
+        for part in self.parse()? {
+            if let Part::Var(name) = part
+                && !names.contains(&name)
+            {
+                names.push(name);
+            }
+        }
+        Ok(names)
+    }
+
+    /// The template with each placeholder replaced by its value in `vars`. Fails
+    /// listing every variable missing from `vars`, or on a placeholder that is
+    /// never closed or has no name. Values are inserted as is, so braces in them
+    /// are never taken for placeholders.
+    pub fn render(&self, vars: &HashMap<String, String>) -> Result<String> {
+        let parts = self.parse()?;
+        let mut missing: Vec<&str> = Vec::new();
+        let mut rendered = String::with_capacity(self.template.len());
+        for part in parts {
+            match part {
+                Part::Text(text) => rendered.push_str(text),
+                Part::Var(name) => match vars.get(name) {
+                    Some(value) => rendered.push_str(value),
+                    None if !missing.contains(&name) => missing.push(name),
+                    None => {}
+                },
+            }
+        }
+        if !missing.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Missing prompt template variables: {}",
+                missing.join(", ")
+            ));
+        }
+        Ok(rendered)
+    }
+
+    fn parse(&self) -> Result<Vec<Part<'_>>> {
+        let template = self.template.as_str();
+        let mut parts = Vec::new();
+        let mut text_start = 0;
+        let mut i = 0;
+        while i < template.len() {
+            let rest = &template[i..];
+            if rest.starts_with("\\{") || rest.starts_with("\\}") {
+                // Drop the backslash, keep the brace as text
+                parts.push(Part::Text(&template[text_start..i]));
+                text_start = i + 1;
+                i += 2;
+            } else if rest.starts_with("{{") {
+                let end = rest.find("}}").ok_or_else(|| {
+                    anyhow::anyhow!("Unclosed placeholder at byte {} of prompt template", i)
+                })?;
+                let name = rest[2..end].trim();
+                if name.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "Empty placeholder at byte {} of prompt template",
+                        i
+                    ));
+                }
+                parts.push(Part::Text(&template[text_start..i]));
+                parts.push(Part::Var(name));
+                i += end + 2;
+                text_start = i;
+            } else {
+                i += rest.chars().next().map_or(1, char::len_utf8);
+            }
+        }
+        parts.push(Part::Text(&template[text_start..]));
+        Ok(parts)
+    }
+}
+
+impl From<&str> for PromptTemplate {
+    fn from(template: &str) -> Self {
+        Self::new(template)
+    }
+}
+
+impl From<String> for PromptTemplate {
+    fn from(template: String) -> Self {
+        Self::new(template)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_render_substitutes_variables() {
+        let template = PromptTemplate::new("Answer {{question}} from {{ context }}. {{question}}?");
+
+        let rendered = template
+            .render(&vars(&[("question", "why"), ("context", "{{docs}}")]))
+            .unwrap();
+
+        assert_eq!(rendered, "Answer why from {{docs}}. why?");
+        assert_eq!(template.variables().unwrap(), vec!["question", "context"]);
+    }
+
+    #[test]
+    fn test_render_lists_missing_variables() {
+        let template = PromptTemplate::new("{{a}} {{b}} {{c}} {{a}}");
+
+        let error = template.render(&vars(&[("b", "1")])).unwrap_err();
+
+        assert_eq!(error.to_string(), "Missing prompt template variables: a, c");
+    }
+
+    #[test]
+    fn test_literal_braces() {
+        let template = PromptTemplate::new(r#"Reply {"query": "{{query}}"}, not \{{query}} or \}"#);
+
+        let rendered = template.render(&vars(&[("query", "pangu")])).unwrap();
+
+        assert_eq!(rendered, r#"Reply {"query": "pangu"}, not {{query}} or }"#);
+        assert_eq!(template.variables().unwrap(), vec!["query"]);
+    }
+
+    #[test]
+    fn test_malformed_placeholders() {
+        assert!(
+            PromptTemplate::new("Hello {{name")
+                .render(&vars(&[]))
+                .is_err()
+        );
+        assert!(
+            PromptTemplate::new("Hello {{ }}")
+                .render(&vars(&[]))
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,632 @@
+#![cfg(feature = "qdrant")]
+
+use crate::error::Error;
+use crate::utils::vector_db::{
+    DEFAULT_MAX_K, DistanceMetric, PayloadFieldType, ScrollPage, SparseVector, VectorDB,
+    VectorDBOptions, VectorRecord,
+};
+use async_trait::async_trait;
+use qdrant_client::Qdrant;
+use qdrant_client::qdrant::{
+    CreateCollectionBuilder, CreateFieldIndexCollectionBuilder, DeletePointsBuilder, Distance,
+    FieldType, Fusion, NamedVectors, PointId, PointStruct, PrefetchQueryBuilder, Query,
+    QueryPointsBuilder, RetrievedPoint, ScoredPoint, ScrollPointsBuilder, SearchPointsBuilder,
+    SparseVectorParamsBuilder, SparseVectorsConfigBuilder, UpsertPointsBuilder, Vector,
+    VectorInput, VectorParamsBuilder, Vectors, VectorsOutput, vector_output,
+    vectors_output::VectorsOptions,
+};
+use qdrant_client::qdrant::{Value as QdrantValue, value::Kind as QdrantKind};
+use serde_json::{Map as SerdeMap, Number as SerdeNumber, Value as SerdeValue};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use tracing::{info, warn};
+
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+impl From<PayloadFieldType> for FieldType {
+    fn from(field_type: PayloadFieldType) -> Self {
+        match field_type {
+            PayloadFieldType::Keyword => FieldType::Keyword,
+            PayloadFieldType::Integer => FieldType::Integer,
+            PayloadFieldType::Float => FieldType::Float,
+            PayloadFieldType::Bool => FieldType::Bool,
+            PayloadFieldType::Text => FieldType::Text,
+            PayloadFieldType::Datetime => FieldType::Datetime,
+        }
+    }
+}
+
+fn qdrant_value_to_serde_json(q_val: QdrantValue) -> SerdeValue {
+    match q_val.kind {
+        Some(QdrantKind::NullValue(_)) => SerdeValue::Null,
+        Some(QdrantKind::BoolValue(b)) => SerdeValue::Bool(b),
+        Some(QdrantKind::DoubleValue(d)) => {
+            SerdeNumber::from_f64(d).map_or(SerdeValue::Null, SerdeValue::Number)
+        }
+        Some(QdrantKind::IntegerValue(i)) => SerdeValue::Number(i.into()),
+        Some(QdrantKind::StringValue(s)) => SerdeValue::String(s),
+        Some(QdrantKind::ListValue(list_value)) => {
+            let serde_list: Vec<SerdeValue> = list_value
+                .values
+                .into_iter()
+                .map(qdrant_value_to_serde_json)
+                .collect();
+            SerdeValue::Array(serde_list)
+        }
+        Some(QdrantKind::StructValue(struct_value)) => {
+            let mut serde_map = SerdeMap::new();
+            for (key, val) in struct_value.fields {
+                serde_map.insert(key, qdrant_value_to_serde_json(val));
+            }
+            SerdeValue::Object(serde_map)
+        }
+        None => SerdeValue::Null, // Treat absence of kind as Null
+    }
+}
+
+impl VectorRecord {
+    pub fn from_scored_point(point: ScoredPoint) -> Option<Self> {
+        let mut record = Self::from_point_parts(point.id, point.vectors, point.payload)?;
+        record.score = Some(point.score);
+        Some(record)
+    }
+
+    pub fn from_retrieved_point(point: RetrievedPoint) -> Option<Self> {
+        Self::from_point_parts(point.id, point.vectors, point.payload)
+    }
+
+    fn from_point_parts(
+        id: Option<PointId>,
+        vectors: Option<VectorsOutput>,
+        payload: HashMap<String, QdrantValue>,
+    ) -> Option<Self> {
+        let id_str = match id {
+            Some(point_id) => match point_id.point_id_options {
+                Some(qdrant_client::qdrant::point_id::PointIdOptions::Num(n)) => n.to_string(),
+                Some(qdrant_client::qdrant::point_id::PointIdOptions::Uuid(s)) => s,
+                None => return None,
+            },
+            None => return None,
+        };
+        let mut vector_data = None;
+        let mut sparse_vector = None;
+        match vectors.and_then(|vectors| vectors.vectors_options)? {
+            VectorsOptions::Vector(v) => {
+                if let vector_output::Vector::Dense(dense) = v.into_vector() {
+                    vector_data = Some(dense.data);
+                }
+            }
+            // Collections with a sparse vector return the dense one under ""
+            VectorsOptions::Vectors(named) => {
+                for (_, v) in named.vectors {
+                    match v.into_vector() {
+                        vector_output::Vector::Dense(dense) => vector_data = Some(dense.data),
+                        vector_output::Vector::Sparse(sparse) => {
+                            sparse_vector = Some(SparseVector {
+                                indices: sparse.indices,
+                                values: sparse.values,
+                            })
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        let vector_data = vector_data?;
+        // 3. Convert Payload
+        let metadata_map: SerdeMap<String, SerdeValue> = payload
+            .into_iter()
+            .map(|(key, q_val)| (key, qdrant_value_to_serde_json(q_val)))
+            .collect();
+
+        Some(VectorRecord {
+            id: id_str,
+            vector: vector_data,
+            sparse_vector,
+            metadata: metadata_map,
+            score: None,
+        })
+    }
+}
+
+/// Searches are clamped to `max_k` results and fetched from Qdrant in pages of at
+/// most `page_size` points.
+pub struct QdrantDB {
+    client: Qdrant,
+    options: VectorDBOptions,
+    max_k: usize,
+    page_size: usize,
+}
+
+impl QdrantDB {
+    pub async fn new(
+        db_url: String,
+        api_key: Option<String>,
+        options: VectorDBOptions,
+    ) -> anyhow::Result<Self> {
+        let client = match api_key {
+            Some(api_key) => Qdrant::from_url(db_url.as_str()).api_key(api_key).build()?,
+            None => Qdrant::from_url(db_url.as_str()).build()?,
+        };
+
+        // Create collection if it doesn't exist
+        let collections = client.list_collections().await.map_err(Error::from)?;
+        if !collections
+            .collections
+            .iter()
+            .any(|c| c.name == options.collection_name)
+        {
+            let distance = match options.distance_metric {
+                DistanceMetric::Cosine => Distance::Cosine,
+                DistanceMetric::Euclidean => Distance::Euclid,
+                DistanceMetric::DotProduct => Distance::Dot,
+            };
+            let mut request = CreateCollectionBuilder::new(options.collection_name.clone())
+                .vectors_config(VectorParamsBuilder::new(options.dimension as u64, distance));
+            if let Some(name) = &options.sparse_vector_name {
+                let mut sparse_config = SparseVectorsConfigBuilder::default();
+                sparse_config
+                    .add_named_vector_params(name.clone(), SparseVectorParamsBuilder::default());
+                request = request.sparse_vectors_config(sparse_config);
+            }
+            client
+                .create_collection(request)
+                .await
+                .map_err(Error::from)?;
+        }
+
+        let db = Self {
+            client,
+            options,
+            max_k: DEFAULT_MAX_K,
+            page_size: DEFAULT_PAGE_SIZE,
+        };
+        db.ensure_payload_indexes().await?;
+        Ok(db)
+    }
+
+    /// Create the payload indexes in `options.payload_indexes` that the collection
+    /// does not have yet.
+    async fn ensure_payload_indexes(&self) -> anyhow::Result<()> {
+        if self.options.payload_indexes.is_empty() {
+            return Ok(());
+        }
+        let collection = &self.options.collection_name;
+        let info = self
+            .client
+            .collection_info(collection.as_str())
+            .await
+            .map_err(Error::from)?;
+        let existing = info
+            .result
+            .map(|info| info.payload_schema)
+            .unwrap_or_default();
+
+        for (field, field_type) in &self.options.payload_indexes {
+            if existing.contains_key(field) {
+                info!("Payload index on '{}' already exists, skipping", field);
+                continue;
+            }
+            info!("Creating {:?} payload index on '{}'", field_type, field);
+            self.client
+                .create_field_index(
+                    CreateFieldIndexCollectionBuilder::new(
+                        collection,
+                        field,
+                        FieldType::from(*field_type),
+                    )
+                    .wait(true),
+                )
+                .await
+                .map_err(Error::from)?;
+        }
+        Ok(())
+    }
+
+    pub fn with_max_k(mut self, max_k: usize) -> Self {
+        self.max_k = max_k;
+        self
+    }
+
+    pub fn with_page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    fn clamp_k(&self, k: usize) -> usize {
+        if k > self.max_k {
+            warn!("Clamping search k from {} to {}", k, self.max_k);
+            self.max_k
+        } else {
+            k
+        }
+    }
+
+    /// The dense vector alone, or named together with the sparse one.
+    fn point_vectors(&self, record: &mut VectorRecord) -> anyhow::Result<Vectors> {
+        let dense = std::mem::take(&mut record.vector);
+        match (
+            record.sparse_vector.take(),
+            &self.options.sparse_vector_name,
+        ) {
+            (None, _) => Ok(dense.into()),
+            (Some(sparse), Some(name)) => Ok(NamedVectors::default()
+                .add_vector("", Vector::new_dense(dense))
+                .add_vector(
+                    name.clone(),
+                    Vector::new_sparse(sparse.indices, sparse.values),
+                )
+                .into()),
+            (Some(_), None) => Err(anyhow::anyhow!(
+                "Record {} has a sparse vector, but collection '{}' has no sparse vector name",
+                record.id,
+                self.options.collection_name
+            )),
+        }
+    }
+}
+
+/// Collects up to `k` results with unique ids by calling `fetch(offset, limit)` for
+/// pages of at most `page_size`, stopping early once a page comes back short.
+async fn paged_search<F, Fut>(
+    k: usize,
+    page_size: usize,
+    mut fetch: F,
+) -> anyhow::Result<Vec<VectorRecord>>
+where
+    F: FnMut(usize, usize) -> Fut,
+    Fut: Future<Output = anyhow::Result<Vec<VectorRecord>>>,
+{
+    let mut seen = HashSet::new();
+    let mut results = Vec::with_capacity(k);
+    let mut offset = 0;
+    while results.len() < k {
+        let limit = page_size.min(k - results.len());
+        let page = fetch(offset, limit).await?;
+        let exhausted = page.len() < limit;
+        offset += page.len();
+        for record in page {
+            if results.len() < k && seen.insert(record.id.clone()) {
+                results.push(record);
+            }
+        }
+        if exhausted {
+            break;
+        }
+    }
+    Ok(results)
+}
+
+#[async_trait]
+impl VectorDB for QdrantDB {
+    fn dimension(&self) -> Option<usize> {
+        Some(self.options.dimension)
+    }
+
+    fn distance_metric(&self) -> Option<DistanceMetric> {
+        Some(self.options.distance_metric.clone())
+    }
+
+    async fn insert(&self, records: Vec<VectorRecord>) -> anyhow::Result<()> {
+        let points = records
+            .into_iter()
+            .map(|mut record| {
+                let vectors = self.point_vectors(&mut record)?;
+                Ok(PointStruct::new(record.id, vectors, record.metadata))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let points_request = UpsertPointsBuilder::new(&self.options.collection_name, points);
+
+        info!("Inserting points into Qdrant");
+        self.client
+            .upsert_points(points_request)
+            .await
+            .map_err(Error::from)?;
+        Ok(())
+    }
+
+    async fn search(&self, query: Vec<f32>, k: usize) -> anyhow::Result<Vec<VectorRecord>> {
+        info!(
+            "Searching points in Qdrant, collection: {}",
+            self.options.collection_name
+        );
+        let k = self.clamp_k(k);
+        let results = paged_search(k, self.page_size, |offset, limit| {
+            let request = SearchPointsBuilder::new(
+                &self.options.collection_name,
+                query.clone(),
+                limit as u64,
+            )
+            .offset(offset as u64)
+            .with_payload(true)
+            .with_vectors(true);
+            async move {
+                let response = self
+                    .client
+                    .search_points(request)
+                    .await
+                    .map_err(Error::from)?;
+                Ok(response
+                    .result
+                    .into_iter()
+                    .filter_map(VectorRecord::from_scored_point)
+                    .collect())
+            }
+        })
+        .await?;
+        info!("Retrieved results len: {:?}", results.len());
+
+        Ok(results)
+    }
+
+    /// Fuses the dense and sparse rankings with Qdrant's reciprocal rank fusion.
+    async fn search_hybrid(
+        &self,
+        dense: Vec<f32>,
+        sparse: SparseVector,
+        k: usize,
+    ) -> anyhow::Result<Vec<VectorRecord>> {
+        let Some(sparse_name) = &self.options.sparse_vector_name else {
+            return Err(anyhow::anyhow!(
+                "Collection '{}' has no sparse vector name for hybrid search",
+                self.options.collection_name
+            ));
+        };
+        info!(
+            "Hybrid searching points in Qdrant, collection: {}",
+            self.options.collection_name
+        );
+        let k = self.clamp_k(k) as u64;
+        let request = QueryPointsBuilder::new(&self.options.collection_name)
+            .add_prefetch(
+                PrefetchQueryBuilder::default()
+                    .query(Query::new_nearest(dense))
+                    .limit(k),
+            )
+            .add_prefetch(
+                PrefetchQueryBuilder::default()
+                    .query(Query::new_nearest(VectorInput::new_sparse(
+                        sparse.indices,
+                        sparse.values,
+                    )))
+                    .using(sparse_name.as_str())
+                    .limit(k),
+            )
+            .query(Query::new_fusion(Fusion::Rrf))
+            .limit(k)
+            .with_payload(true)
+            .with_vectors(true);
+        let response = self.client.query(request).await.map_err(Error::from)?;
+        let results: Vec<VectorRecord> = response
+            .result
+            .into_iter()
+            .filter_map(VectorRecord::from_scored_point)
+            .collect();
+        info!("Retrieved results len: {:?}", results.len());
+
+        Ok(results)
+    }
+
+    async fn delete(&self, ids: Vec<String>) -> anyhow::Result<()> {
+        info!("Deleting points from Qdrant");
+        self.client
+            .delete_points(DeletePointsBuilder::new(&self.options.collection_name).points(ids))
+            .await
+            .map_err(Error::from)?;
+        Ok(())
+    }
+
+    async fn scroll(&self, offset: Option<String>, limit: usize) -> anyhow::Result<ScrollPage> {
+        info!(
+            "Scrolling points in Qdrant, collection: {}",
+            self.options.collection_name
+        );
+        let mut request = ScrollPointsBuilder::new(&self.options.collection_name)
+            .limit(limit as u32)
+            .with_payload(true)
+            .with_vectors(true);
+        if let Some(offset) = offset {
+            let point_id = match offset.parse::<u64>() {
+                Ok(n) => PointId::from(n),
+                Err(_) => PointId::from(offset),
+            };
+            request = request.offset(point_id);
+        }
+
+        let response = self.client.scroll(request).await.map_err(Error::from)?;
+        let next_offset =
+            response
+                .next_page_offset
+                .and_then(|point_id| match point_id.point_id_options {
+                    Some(qdrant_client::qdrant::point_id::PointIdOptions::Num(n)) => {
+                        Some(n.to_string())
+                    }
+                    Some(qdrant_client::qdrant::point_id::PointIdOptions::Uuid(s)) => Some(s),
+                    None => None,
+                });
+        let records = response
+            .result
+            .into_iter()
+            .filter_map(VectorRecord::from_retrieved_point)
+            .collect();
+
+        Ok(ScrollPage {
+            records,
+            next_offset,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    fn record(id: &str, tenant: &str, vector: Vec<f32>) -> VectorRecord {
+        VectorRecord {
+            id: id.to_string(),
+            vector,
+            sparse_vector: None,
+            metadata: SerdeMap::from_iter(vec![("tenant".to_string(), json!(tenant))]),
+            score: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_paged_search_returns_k_unique_results() {
+        // Ranked results where each id appears twice in a row, as when points are
+        // re-upserted while paging
+        let ranked: Vec<VectorRecord> = (0..300)
+            .map(|i| record(&format!("p{}", i / 2), "a", vec![1.0]))
+            .collect();
+        let limits = Mutex::new(Vec::new());
+
+        let results = paged_search(120, 40, |offset, limit| {
+            limits.lock().unwrap().push(limit);
+            let page = ranked.iter().skip(offset).take(limit).cloned().collect();
+            async move { Ok(page) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(results.len(), 120);
+        let ids: HashSet<&str> = results.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids.len(), 120);
+        assert!(limits.lock().unwrap().iter().all(|&limit| limit <= 40));
+
+        // A collection smaller than k is returned whole
+        let results = paged_search(500, 40, |offset, limit| {
+            let page = ranked.iter().skip(offset).take(limit).cloned().collect();
+            async move { Ok(page) }
+        })
+        .await
+        .unwrap();
+        assert_eq!(results.len(), 150);
+    }
+
+    #[tokio::test]
+    #[ignore = "E2E case, requires a Qdrant server at QDRANT_URL"]
+    async fn test_e2e_payload_index() {
+        use qdrant_client::qdrant::{Condition, Filter};
+
+        let collection = format!("payload-index-test-{}", std::process::id());
+        let options = VectorDBOptions {
+            collection_name: collection.clone(),
+            dimension: 2,
+            distance_metric: DistanceMetric::Cosine,
+            payload_indexes: vec![
+                ("file_metadata.url".to_string(), PayloadFieldType::Keyword),
+                ("chunk_index".to_string(), PayloadFieldType::Integer),
+            ],
+            sparse_vector_name: None,
+        };
+        let url = std::env::var("QDRANT_URL").unwrap();
+        let db = QdrantDB::new(url.clone(), None, options.clone())
+            .await
+            .unwrap();
+        // Opening the collection again skips the existing indexes
+        QdrantDB::new(url, None, options).await.unwrap();
+
+        let info = db
+            .client
+            .collection_info(collection.as_str())
+            .await
+            .unwrap();
+        let schema = info.result.unwrap().payload_schema;
+        assert!(schema.contains_key("file_metadata.url"));
+        assert!(schema.contains_key("chunk_index"));
+
+        let mut a = record("a", "a", vec![1.0, 0.0]);
+        a.metadata
+            .insert("file_metadata".to_string(), json!({"url": "doc-a"}));
+        let mut b = record("b", "b", vec![0.9, 0.1]);
+        b.metadata
+            .insert("file_metadata".to_string(), json!({"url": "doc-b"}));
+        a.id = "00000000-0000-0000-0000-00000000000a".to_string();
+        b.id = "00000000-0000-0000-0000-00000000000b".to_string();
+        db.insert(vec![a, b]).await.unwrap();
+
+        // Upserts are applied asynchronously
+        let mut found = Vec::new();
+        for _ in 0..20 {
+            found = db
+                .client
+                .search_points(
+                    SearchPointsBuilder::new(&collection, vec![1.0, 0.0], 10).filter(Filter::must(
+                        [Condition::matches("file_metadata.url", "doc-b".to_string())],
+                    )),
+                )
+                .await
+                .unwrap()
+                .result;
+            if !found.is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        assert_eq!(found.len(), 1);
+
+        db.client.delete_collection(collection).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "E2E case, requires a Qdrant server at QDRANT_URL"]
+    async fn test_e2e_hybrid_search() {
+        let collection = format!("hybrid-search-test-{}", std::process::id());
+        let db = QdrantDB::new(
+            std::env::var("QDRANT_URL").unwrap(),
+            None,
+            VectorDBOptions {
+                collection_name: collection.clone(),
+                dimension: 2,
+                distance_metric: DistanceMetric::Cosine,
+                payload_indexes: Vec::new(),
+                sparse_vector_name: Some("sparse".to_string()),
+            },
+        )
+        .await
+        .unwrap();
+
+        let sparse = |indices: Vec<u32>, values: Vec<f32>| Some(SparseVector { indices, values });
+        let mut a = record("00000000-0000-0000-0000-00000000000a", "t", vec![1.0, 0.0]);
+        a.sparse_vector = sparse(vec![1], vec![1.0]);
+        let mut b = record("00000000-0000-0000-0000-00000000000b", "t", vec![0.9, 0.1]);
+        b.sparse_vector = sparse(vec![2], vec![1.0]);
+        let mut c = record("00000000-0000-0000-0000-00000000000c", "t", vec![0.0, 1.0]);
+        c.sparse_vector = sparse(vec![1], vec![0.5]);
+        db.insert(vec![a, b, c]).await.unwrap();
+
+        // Dense alone ranks a, b, c; the sparse query matches a and c, so fusing the
+        // two lifts c above b
+        let mut ids = Vec::new();
+        for _ in 0..20 {
+            let results = db
+                .search_hybrid(
+                    vec![1.0, 0.0],
+                    SparseVector {
+                        indices: vec![1],
+                        values: vec![1.0],
+                    },
+                    3,
+                )
+                .await
+                .unwrap();
+            ids = results.into_iter().map(|r| r.id).collect();
+            if ids.len() == 3 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        assert_eq!(
+            ids,
+            vec![
+                "00000000-0000-0000-0000-00000000000a",
+                "00000000-0000-0000-0000-00000000000c",
+                "00000000-0000-0000-0000-00000000000b",
+            ]
+        );
+
+        db.client.delete_collection(collection).await.unwrap();
+    }
+}
@@ -1,11 +1,19 @@
+use anyhow::Result;
 use regex::Regex;
-use tracing::info;
+use std::io::Read;
+use std::sync::OnceLock;
+use tracing::{info, warn};
 
 #[derive(Debug, Clone)]
 pub struct ChunkingOptions {
     pub chunk_size: usize,
     pub overlap: usize,
     pub strategy: ChunkingStrategy,
+    /// Regexes ending a sentence for the sentence strategy, see [`SeparatorSet`]. The
+    /// recursive strategy tries them one at a time, in order. Empty means the
+    /// strategy's default: [`SeparatorSet::english`] for sentences,
+    /// [`SeparatorSet::recursive`] for the recursive and Markdown strategies.
+    pub separators: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -13,6 +21,18 @@ pub enum ChunkingStrategy {
     FixedSize,
     Sentence,
     Paragraph,
+    /// Splits on the first of [`ChunkingOptions::separators`] found in the text,
+    /// then whitespace, then anywhere, merging the pieces back up to `chunk_size`
+    /// and splitting pieces still too big on the next separator. No chunk is longer
+    /// than `chunk_size`, overlap included. By default it tries paragraphs, then
+    /// lines, then sentences, then spaces.
+    Recursive,
+    /// Splits Markdown into sections at its headings and prefixes each chunk with
+    /// the trail of headings it is under, e.g. `# Title > ## Section`. Sections
+    /// longer than `chunk_size` are split between blocks, then recursively on
+    /// [`ChunkingOptions::separators`], but never inside a code fence, so a fence
+    /// longer than `chunk_size` becomes one oversized chunk. Chunks don't overlap.
+    Markdown,
 }
 
 impl Default for ChunkingOptions {
@@ -21,10 +41,101 @@ impl Default for ChunkingOptions {
             chunk_size: 1000,
             overlap: 100,
             strategy: ChunkingStrategy::FixedSize,
+            separators: Vec::new(),
         }
     }
 }
 
+/// Sentence separator presets for [`ChunkingOptions::separators`]. Separators are
+/// regexes and are dropped from the text, like the whitespace around sentences.
+pub struct SeparatorSet;
+
+impl SeparatorSet {
+    /// `.`, `!` and `?` followed by whitespace, so `3.14` stays whole.
+    pub fn english() -> Vec<String> {
+        vec![r"[.!?]+\s+".to_string()]
+    }
+
+    /// Full-width `。！？；`, which need no following whitespace, plus English ones.
+    pub fn cjk() -> Vec<String> {
+        vec![r"[。！？；]+\s*".to_string(), r"[.!?]+\s+".to_string()]
+    }
+
+    /// Paragraphs, then lines, then sentences, then spaces, the default for the
+    /// recursive strategy.
+    pub fn recursive() -> Vec<String> {
+        vec![
+            r"\n\n".to_string(),
+            r"\n".to_string(),
+            r"\. ".to_string(),
+            " ".to_string(),
+        ]
+    }
+
+    /// Blank lines and line breaks, so headings, list items and code lines are kept
+    /// apart, plus English sentence ends.
+    pub fn markdown() -> Vec<String> {
+        vec![
+            r"\n\s*\n".to_string(),
+            r"\n".to_string(),
+            r"[.!?]+\s+".to_string(),
+        ]
+    }
+}
+
+/// Approximate token count: each run of word characters and each punctuation mark
+/// counts as one token. Close enough to BPE tokenizers for budgeting and filtering.
+pub fn count_tokens(text: &str) -> usize {
+    static TOKEN_REGEX: OnceLock<Regex> = OnceLock::new();
+    TOKEN_REGEX
+        .get_or_init(|| Regex::new(r"\w+|[^\w\s]").unwrap())
+        .find_iter(text)
+        .count()
+}
+
+/// The end of the fixed-size chunk starting at `start`: the last whitespace within
+/// `chunk_size`, or `chunk_size` itself if there is none, snapped back to a character
+/// boundary. Unless `text` ends the input, it must extend past the chunk, so the
+/// break can be checked.
+fn fixed_size_end(text: &str, start: usize, chunk_size: usize) -> usize {
+    let text_size = text.len();
+    let end = start + prefix_within(&text[start..], chunk_size).len();
+
+    // Try to find a good breaking point (space or punctuation)
+    let mut actual_end = end;
+    if actual_end < text_size {
+        while actual_end > start && !text[actual_end..].starts_with(char::is_whitespace) {
+            actual_end = floor_char_boundary(text, actual_end - 1);
+        }
+        // If we couldn't find a good breaking point, force a break at the chunk size
+        if actual_end == start {
+            actual_end = end;
+        }
+    }
+    actual_end
+}
+
+/// Where the fixed-size chunk after `start..actual_end` starts, `overlap` back from
+/// its end and snapped back to a character boundary.
+fn next_fixed_size_start(text: &str, start: usize, actual_end: usize, overlap: usize) -> usize {
+    // Ensure we always advance by at least 1 character to prevent infinite loop
+    let new_start = floor_char_boundary(text, actual_end.saturating_sub(overlap));
+    if new_start <= start {
+        actual_end
+    } else {
+        new_start
+    }
+}
+
+/// The last character boundary of `text` at or before `index`.
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
 pub struct TextChunker {
     sentence_regex: Regex,
     paragraph_regex: Regex,
@@ -44,12 +155,51 @@ impl TextChunker {
         }
     }
 
+    /// The regex matching any of `separators`, or the English default if they are
+    /// empty or invalid.
+    fn separator_regex(&self, separators: &[String]) -> Regex {
+        if separators.is_empty() {
+            return self.sentence_regex.clone();
+        }
+        let pattern = separators
+            .iter()
+            .map(|separator| format!("(?:{})", separator))
+            .collect::<Vec<_>>()
+            .join("|");
+        Regex::new(&pattern).unwrap_or_else(|e| {
+            warn!(
+                "Invalid separators {:?}: {}, using the default",
+                separators, e
+            );
+            self.sentence_regex.clone()
+        })
+    }
+
+    /// Split `text` into trimmed sentences at `separators` (the English default if
+    /// empty). Unlike the sentence strategy, each sentence keeps its punctuation.
+    pub fn split_sentences(&self, text: &str, separators: &[String]) -> Vec<String> {
+        let mut sentences = Vec::new();
+        let mut start = 0;
+        for separator in self.separator_regex(separators).find_iter(text) {
+            sentences.push(text[start..separator.end()].trim().to_string());
+            start = separator.end();
+        }
+        sentences.push(text[start..].trim().to_string());
+        sentences.retain(|sentence| !sentence.is_empty());
+        sentences
+    }
+
     pub fn chunk_text(&self, text: &str, options: &ChunkingOptions) -> Vec<String> {
         info!("Chunking text with strategy: {:?}", options.strategy);
+        if text.trim().is_empty() {
+            return Vec::new();
+        }
         match options.strategy {
             ChunkingStrategy::FixedSize => self.chunk_by_size(text, options),
             ChunkingStrategy::Sentence => self.chunk_by_sentence(text, options),
             ChunkingStrategy::Paragraph => self.chunk_by_paragraph(text, options),
+            ChunkingStrategy::Recursive => self.chunk_recursively(text, options),
+            ChunkingStrategy::Markdown => self.chunk_markdown(text, options),
         }
     }
 
@@ -59,42 +209,28 @@ impl TextChunker {
         let text_size = text.len();
 
         while start < text_size {
-            let end = (start + options.chunk_size).min(text_size);
-
-            // Try to find a good breaking point (space or punctuation)
-            let mut actual_end = end;
-            if actual_end < text_size {
-                while actual_end > start && !text[actual_end..].starts_with(char::is_whitespace) {
-                    actual_end -= 1;
-                }
-                // If we couldn't find a good breaking point, force a break at the chunk size
-                if actual_end == start {
-                    actual_end = end;
-                }
-            }
+            let actual_end = fixed_size_end(text, start, options.chunk_size);
 
             let chunk = text[start..actual_end].trim().to_string();
             if !chunk.is_empty() {
                 chunks.push(chunk);
             }
-
-            // Ensure we always advance by at least 1 character to prevent infinite loop
-            let new_start = actual_end.saturating_sub(options.overlap);
-            if new_start <= start {
-                start = actual_end;
-            } else {
-                start = new_start;
+            // The rest of the text is covered, don't emit overlap-only tail chunks
+            if actual_end >= text_size {
+                break;
             }
+            start = next_fixed_size_start(text, start, actual_end, options.overlap);
         }
 
         chunks
     }
 
     fn chunk_by_sentence(&self, text: &str, options: &ChunkingOptions) -> Vec<String> {
+        let sentence_regex = self.separator_regex(&options.separators);
         let mut chunks = Vec::new();
         let mut current_chunk = String::new();
 
-        for sentence in self.sentence_regex.split(text) {
+        for sentence in sentence_regex.split(text) {
             let sentence = sentence.trim();
             if sentence.is_empty() {
                 continue;
@@ -127,8 +263,7 @@ impl TextChunker {
                 let current_chunk = &chunks[i];
 
                 // Find the last sentence in the previous chunk
-                let last_sentences: Vec<&str> = self
-                    .sentence_regex
+                let last_sentences: Vec<&str> = sentence_regex
                     .split(prev_chunk)
                     .filter(|s| !s.trim().is_empty())
                     .collect();
@@ -149,6 +284,218 @@ impl TextChunker {
         chunks
     }
 
+    /// `separators` ([`SeparatorSet::recursive`] if empty) compiled for recursive
+    /// splitting, whitespace last.
+    fn recursive_separators(&self, separators: &[String]) -> Vec<Regex> {
+        let defaults;
+        let separators = if separators.is_empty() {
+            defaults = SeparatorSet::recursive();
+            &defaults
+        } else {
+            separators
+        };
+        let mut separators: Vec<Regex> = separators
+            .iter()
+            .filter_map(|separator| match Regex::new(separator) {
+                Ok(regex) => Some(regex),
+                Err(e) => {
+                    warn!("Invalid separator {:?}: {}, skipping it", separator, e);
+                    None
+                }
+            })
+            .collect();
+        separators.push(Regex::new(r"\s+").unwrap());
+        separators
+    }
+
+    fn chunk_recursively(&self, text: &str, options: &ChunkingOptions) -> Vec<String> {
+        let separators = self.recursive_separators(&options.separators);
+
+        // Leave room for the overlap carried over from the previous chunk
+        let overlap = if options.overlap < options.chunk_size {
+            options.overlap
+        } else {
+            0
+        };
+        let mut chunks = Vec::new();
+        self.split_recursively(text, &separators, options.chunk_size - overlap, &mut chunks);
+
+        if overlap > 0 && chunks.len() > 1 {
+            for i in 1..chunks.len() {
+                let prev_chunk = &chunks[i - 1];
+                // The words in the last `overlap` bytes of the previous chunk
+                let mut tail_start = prev_chunk.len() - overlap.min(prev_chunk.len());
+                while !prev_chunk.is_char_boundary(tail_start) {
+                    tail_start += 1;
+                }
+                let tail = &prev_chunk[tail_start..];
+                let tail = match tail.find(char::is_whitespace) {
+                    Some(space) if tail_start > 0 => tail[space..].trim(),
+                    _ => tail.trim(),
+                };
+                if !tail.is_empty() && tail.len() + 1 + chunks[i].len() <= options.chunk_size {
+                    chunks[i] = format!("{} {}", tail, chunks[i]);
+                }
+            }
+        }
+
+        chunks
+    }
+
+    /// Push the chunks of `text` within `chunk_size` to `chunks`, splitting on the
+    /// first of `separators` that matches and recursing with the rest into pieces
+    /// that are still too big.
+    fn split_recursively(
+        &self,
+        text: &str,
+        separators: &[Regex],
+        chunk_size: usize,
+        chunks: &mut Vec<String>,
+    ) {
+        if text.trim().len() <= chunk_size {
+            if !text.trim().is_empty() {
+                chunks.push(text.trim().to_string());
+            }
+            return;
+        }
+        let split = separators
+            .iter()
+            .enumerate()
+            .find_map(|(i, regex)| Some((split_after(text, regex)?, &separators[i + 1..])));
+        let Some((pieces, finer)) = split else {
+            // No separator left, split anywhere
+            let mut rest = text.trim();
+            while !rest.is_empty() {
+                let chunk = prefix_within(rest, chunk_size);
+                chunks.push(chunk.to_string());
+                rest = &rest[chunk.len()..];
+            }
+            return;
+        };
+
+        let mut current = String::new();
+        for piece in pieces {
+            if piece.trim().len() > chunk_size {
+                if !current.trim().is_empty() {
+                    chunks.push(current.trim().to_string());
+                }
+                current.clear();
+                self.split_recursively(piece, finer, chunk_size, chunks);
+            } else if (current.clone() + piece).trim().len() <= chunk_size {
+                current.push_str(piece);
+            } else {
+                if !current.trim().is_empty() {
+                    chunks.push(current.trim().to_string());
+                }
+                current = piece.to_string();
+            }
+        }
+        if !current.trim().is_empty() {
+            chunks.push(current.trim().to_string());
+        }
+    }
+
+    fn chunk_markdown(&self, text: &str, options: &ChunkingOptions) -> Vec<String> {
+        let separators = self.recursive_separators(&options.separators);
+        let mut chunks = Vec::new();
+        // The headings above the current line, outermost first
+        let mut trail: Vec<(usize, &str)> = Vec::new();
+        let mut blocks: Vec<MarkdownBlock> = Vec::new();
+        let mut block = String::new();
+        // The marker of the code fence the current line is in
+        let mut fence: Option<&str> = None;
+
+        for line in text.lines() {
+            let trimmed = line.trim_start();
+            if let Some(marker) = fence {
+                block.push_str(line);
+                block.push('\n');
+                if trimmed.starts_with(marker) {
+                    fence = None;
+                    blocks.push(MarkdownBlock::new(&mut block, true));
+                }
+                continue;
+            }
+            if let Some(marker) = ["```", "~~~"]
+                .into_iter()
+                .find(|marker| trimmed.starts_with(marker))
+            {
+                blocks.push(MarkdownBlock::new(&mut block, false));
+                fence = Some(marker);
+                block.push_str(line);
+                block.push('\n');
+            } else if let Some(level) = heading_level(trimmed) {
+                blocks.push(MarkdownBlock::new(&mut block, false));
+                self.push_markdown_section(&trail, &blocks, &separators, options, &mut chunks);
+                blocks.clear();
+                trail.retain(|(outer, _)| *outer < level);
+                trail.push((level, trimmed.trim_end()));
+            } else if line.trim().is_empty() {
+                blocks.push(MarkdownBlock::new(&mut block, false));
+            } else {
+                block.push_str(line);
+                block.push('\n');
+            }
+        }
+        // An unclosed fence runs to the end of the text
+        blocks.push(MarkdownBlock::new(&mut block, fence.is_some()));
+        self.push_markdown_section(&trail, &blocks, &separators, options, &mut chunks);
+
+        chunks
+    }
+
+    /// Push the chunks of the section made of `blocks` under the headings in
+    /// `trail` to `chunks`.
+    fn push_markdown_section(
+        &self,
+        trail: &[(usize, &str)],
+        blocks: &[MarkdownBlock],
+        separators: &[Regex],
+        options: &ChunkingOptions,
+        chunks: &mut Vec<String>,
+    ) {
+        let prefix = trail
+            .iter()
+            .map(|(_, heading)| *heading)
+            .collect::<Vec<_>>()
+            .join(" > ");
+        // Leave room for the prefix and the blank line after it
+        let chunk_size = if prefix.is_empty() {
+            options.chunk_size
+        } else {
+            options.chunk_size.saturating_sub(prefix.len() + 2).max(1)
+        };
+
+        let mut pieces = Vec::new();
+        let mut current = String::new();
+        for block in blocks.iter().filter(|block| !block.text.is_empty()) {
+            if !current.is_empty() && current.len() + 2 + block.text.len() <= chunk_size {
+                current.push_str("\n\n");
+                current.push_str(&block.text);
+                continue;
+            }
+            if !current.is_empty() {
+                pieces.push(std::mem::take(&mut current));
+            }
+            if block.code || block.text.len() <= chunk_size {
+                current = block.text.clone();
+            } else {
+                self.split_recursively(&block.text, separators, chunk_size, &mut pieces);
+            }
+        }
+        if !current.is_empty() {
+            pieces.push(current);
+        }
+
+        chunks.extend(pieces.into_iter().map(|piece| {
+            if prefix.is_empty() {
+                piece
+            } else {
+                format!("{}\n\n{}", prefix, piece)
+            }
+        }));
+    }
+
     fn chunk_by_paragraph(&self, text: &str, options: &ChunkingOptions) -> Vec<String> {
         let mut chunks = Vec::new();
         let mut current_chunk = String::new();
@@ -209,10 +556,227 @@ impl TextChunker {
     }
 }
 
+/// A paragraph or code fence of a Markdown section.
+struct MarkdownBlock {
+    text: String,
+    /// Code fences are never split.
+    code: bool,
+}
+
+impl MarkdownBlock {
+    /// The block of the lines collected in `lines`, which is left empty.
+    fn new(lines: &mut String, code: bool) -> Self {
+        let text = std::mem::take(lines);
+        Self {
+            text: if code {
+                text.trim_end().to_string()
+            } else {
+                text.trim().to_string()
+            },
+            code,
+        }
+    }
+}
+
+/// The level of an ATX heading line (`#` to `######` followed by a space), or
+/// `None` if `line` isn't one.
+fn heading_level(line: &str) -> Option<usize> {
+    let level = line.len() - line.trim_start_matches('#').len();
+    let rest = &line[level..];
+    ((1..=6).contains(&level) && (rest.is_empty() || rest.starts_with([' ', '\t'])))
+        .then_some(level)
+}
+
+/// The longest prefix of `text` within `max_len` bytes that ends on a character
+/// boundary, but at least one character so splitting always advances.
+fn prefix_within(text: &str, max_len: usize) -> &str {
+    let mut end = max_len.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    if end == 0 {
+        end = text.chars().next().map_or(0, char::len_utf8);
+    }
+    &text[..end]
+}
+
+/// Split `text` after each match of `regex`, keeping the separators, or `None` if
+/// it doesn't match.
+fn split_after<'a>(text: &'a str, regex: &Regex) -> Option<Vec<&'a str>> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for separator in regex.find_iter(text) {
+        if separator.end() > start {
+            pieces.push(&text[start..separator.end()]);
+            start = separator.end();
+        }
+    }
+    if pieces.is_empty() {
+        return None;
+    }
+    pieces.push(&text[start..]);
+    Some(pieces)
+}
+
+/// Fixed-size chunks of text read from `reader` in reads of `buffer_size` bytes,
+/// yielding the same chunks as [`TextChunker::chunk_text`] on the whole text without
+/// holding it in memory. Between chunks only the text from the next chunk's start
+/// on is kept, so the `overlap` tail carries over however the reads split the text.
+pub struct StreamingChunker<R: Read> {
+    reader: R,
+    options: ChunkingOptions,
+    buffer_size: usize,
+    /// Read bytes not yet decoded, the start of a character split across reads
+    pending: Vec<u8>,
+    /// Decoded text from the next chunk's start on
+    text: String,
+    eof: bool,
+    done: bool,
+}
+
+impl<R: Read> StreamingChunker<R> {
+    /// Only the fixed-size strategy is supported, as sentence and paragraph overlap
+    /// depend on the previous chunk as a whole.
+    pub fn new(reader: R, options: ChunkingOptions, buffer_size: usize) -> Result<Self> {
+        if !matches!(options.strategy, ChunkingStrategy::FixedSize) {
+            return Err(anyhow::anyhow!(
+                "Streaming chunking only supports the fixed-size strategy, not {:?}",
+                options.strategy
+            ));
+        }
+        if options.overlap >= buffer_size {
+            return Err(anyhow::anyhow!(
+                "Overlap {} must be smaller than the buffer size {}",
+                options.overlap,
+                buffer_size
+            ));
+        }
+        Ok(Self {
+            reader,
+            options,
+            buffer_size,
+            pending: Vec::new(),
+            text: String::new(),
+            eof: false,
+            done: false,
+        })
+    }
+
+    /// Read until `text` holds at least `len` bytes or the input ends.
+    fn fill(&mut self, len: usize) -> Result<()> {
+        let mut buffer = vec![0; self.buffer_size];
+        while !self.eof && self.text.len() < len {
+            let read = self.reader.read(&mut buffer)?;
+            if read == 0 {
+                self.eof = true;
+                if !self.pending.is_empty() {
+                    return Err(anyhow::anyhow!("Input ends inside a UTF-8 character"));
+                }
+                break;
+            }
+            self.pending.extend_from_slice(&buffer[..read]);
+            let valid = match std::str::from_utf8(&self.pending) {
+                Ok(text) => text.len(),
+                // An incomplete character at the end is finished by the next read
+                Err(e) if e.error_len().is_none() => e.valid_up_to(),
+                Err(e) => return Err(anyhow::anyhow!("Input is not valid UTF-8: {}", e)),
+            };
+            let decoded: Vec<u8> = self.pending.drain(..valid).collect();
+            self.text.push_str(std::str::from_utf8(&decoded)?);
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for StreamingChunker<R> {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            // One byte past the chunk tells whether it ends on whitespace. A chunk
+            // smaller than a character still holds one, of up to 4 bytes.
+            if let Err(e) = self.fill(self.options.chunk_size.max(4) + 1) {
+                self.done = true;
+                return Some(Err(e));
+            }
+            if self.text.is_empty() {
+                self.done = true;
+                break;
+            }
+
+            let actual_end = fixed_size_end(&self.text, 0, self.options.chunk_size);
+            let chunk = self.text[..actual_end].trim().to_string();
+            if actual_end >= self.text.len() {
+                self.done = true;
+            } else {
+                let start = next_fixed_size_start(&self.text, 0, actual_end, self.options.overlap);
+                self.text.drain(..start);
+            }
+            if !chunk.is_empty() {
+                return Some(Ok(chunk));
+            }
+        }
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_count_tokens() {
+        assert_eq!(count_tokens(""), 0);
+        assert_eq!(count_tokens("Hello, world!"), 4);
+        assert_eq!(count_tokens("  it's   fine "), 4);
+    }
+
+    fn all_strategies() -> Vec<ChunkingOptions> {
+        [
+            ChunkingStrategy::FixedSize,
+            ChunkingStrategy::Sentence,
+            ChunkingStrategy::Paragraph,
+            ChunkingStrategy::Recursive,
+            ChunkingStrategy::Markdown,
+        ]
+        .into_iter()
+        .map(|strategy| ChunkingOptions {
+            chunk_size: 20,
+            overlap: 5,
+            strategy,
+            ..ChunkingOptions::default()
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_empty_and_whitespace_input() {
+        let chunker = TextChunker::new();
+        for options in all_strategies() {
+            for text in ["", " ", "\n\n \t\n"] {
+                assert!(
+                    chunker.chunk_text(text, &options).is_empty(),
+                    "{:?} on {:?}",
+                    options.strategy,
+                    text
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_single_word_input() {
+        let chunker = TextChunker::new();
+        for options in all_strategies() {
+            assert_eq!(
+                chunker.chunk_text("  hello ", &options),
+                vec!["hello"],
+                "{:?}",
+                options.strategy
+            );
+        }
+    }
+
     #[test]
     fn test_fixed_size_chunking() {
         let chunker = TextChunker::new();
@@ -221,15 +785,150 @@ mod tests {
             chunk_size: 20,
             overlap: 5,
             strategy: ChunkingStrategy::FixedSize,
+            ..ChunkingOptions::default()
         };
 
         let chunks = chunker.chunk_text(text, &options);
-        assert_eq!(chunks.len(), 5);
+        // No trailing chunk made only of overlap with the previous one
+        assert_eq!(chunks.len(), 4);
         for chunk in chunks {
             assert!(chunk.len() <= 20);
         }
     }
 
+    /// Hands out at most `read_size` bytes per read, like a slow stream.
+    struct TrickleReader<'a> {
+        data: &'a [u8],
+        read_size: usize,
+    }
+
+    impl Read for TrickleReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let read = self.read_size.min(buf.len()).min(self.data.len());
+            buf[..read].copy_from_slice(&self.data[..read]);
+            self.data = &self.data[read..];
+            Ok(read)
+        }
+    }
+
+    #[test]
+    fn test_streaming_matches_whole_text() {
+        let chunker = TextChunker::new();
+        let text = "Pangu stores every chunk three times.  Masters track chunk locations, \
+                    and chunkservers hold the data.\n\nA_very_long_identifier_without_any_breaks \
+                    forces a hard split. The end.";
+        for (chunk_size, overlap) in [(20, 5), (16, 0), (33, 12), (500, 50)] {
+            let options = ChunkingOptions {
+                chunk_size,
+                overlap,
+                strategy: ChunkingStrategy::FixedSize,
+                ..ChunkingOptions::default()
+            };
+            let expected = chunker.chunk_text(text, &options);
+            // Buffers just above the overlap, reads not lining up with them
+            for (buffer_size, read_size) in [(1, 1), (7, 7), (13, 3), (64, 64)] {
+                let buffer_size = overlap + buffer_size;
+                let reader = TrickleReader {
+                    data: text.as_bytes(),
+                    read_size,
+                };
+                let chunks: Vec<String> =
+                    StreamingChunker::new(reader, options.clone(), buffer_size)
+                        .unwrap()
+                        .collect::<Result<_>>()
+                        .unwrap();
+                assert_eq!(
+                    chunks, expected,
+                    "chunk size {}, overlap {}, buffer size {}",
+                    chunk_size, overlap, buffer_size
+                );
+            }
+        }
+
+        let chunker = StreamingChunker::new("".as_bytes(), ChunkingOptions::default(), 101);
+        assert_eq!(chunker.unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_fixed_size_cjk_with_overlap() {
+        let chunker = TextChunker::new();
+        let text = "盘古存储每个数据块三份。主服务器记录块的位置，块服务器保存数据 ok";
+        for (chunk_size, overlap) in [(4, 2), (7, 4), (10, 5), (2, 1)] {
+            let options = ChunkingOptions {
+                chunk_size,
+                overlap,
+                strategy: ChunkingStrategy::FixedSize,
+                ..ChunkingOptions::default()
+            };
+            let expected = chunker.chunk_text(text, &options);
+            assert!(
+                expected
+                    .iter()
+                    .all(|chunk| chunk.len() <= chunk_size.max(3))
+            );
+            assert!(expected.concat().contains("ok"));
+
+            let reader = TrickleReader {
+                data: text.as_bytes(),
+                read_size: 2,
+            };
+            let chunks: Vec<String> = StreamingChunker::new(reader, options.clone(), overlap + 3)
+                .unwrap()
+                .collect::<Result<_>>()
+                .unwrap();
+            assert_eq!(
+                chunks, expected,
+                "chunk size {}, overlap {}",
+                chunk_size, overlap
+            );
+        }
+    }
+
+    #[test]
+    fn test_streaming_rejects_invalid_options() {
+        let options = ChunkingOptions {
+            chunk_size: 20,
+            overlap: 8,
+            strategy: ChunkingStrategy::FixedSize,
+            ..ChunkingOptions::default()
+        };
+        let error = StreamingChunker::new("".as_bytes(), options.clone(), 8)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Overlap 8 must be smaller than the buffer size 8"
+        );
+
+        let sentence = ChunkingOptions {
+            strategy: ChunkingStrategy::Sentence,
+            ..options
+        };
+        assert!(StreamingChunker::new("".as_bytes(), sentence, 64).is_err());
+
+        // A character split across reads is decoded once complete, a truncated one fails
+        let pangu = "盘古 存储".as_bytes();
+        let reader = TrickleReader {
+            data: pangu,
+            read_size: 1,
+        };
+        let options = ChunkingOptions {
+            chunk_size: 100,
+            overlap: 0,
+            strategy: ChunkingStrategy::FixedSize,
+            ..ChunkingOptions::default()
+        };
+        let chunks: Vec<String> = StreamingChunker::new(reader, options.clone(), 4)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(chunks, vec!["盘古 存储"]);
+        let truncated = StreamingChunker::new(&pangu[..5], options, 4)
+            .unwrap()
+            .collect::<Result<Vec<String>>>();
+        assert!(truncated.is_err());
+    }
+
     #[test]
     fn test_sentence_chunking() {
         let chunker = TextChunker::new();
@@ -238,6 +937,7 @@ mod tests {
             chunk_size: 30,
             overlap: 10,
             strategy: ChunkingStrategy::Sentence,
+            ..ChunkingOptions::default()
         };
 
         let chunks = chunker.chunk_text(text, &options);
@@ -255,6 +955,7 @@ mod tests {
             chunk_size: 30,
             overlap: 10,
             strategy: ChunkingStrategy::Paragraph,
+            ..ChunkingOptions::default()
         };
 
         let chunks = chunker.chunk_text(text, &options);
@@ -263,4 +964,230 @@ mod tests {
         assert!(chunks[1].contains("This is another test"));
         assert!(chunks[2].contains("This is a third test"));
     }
+
+    #[test]
+    fn test_cjk_separators() {
+        let chunker = TextChunker::new();
+        let text = "盘古是阿里云的分布式存储系统。它支持海量数据！你了解它吗？Pangu is fast. 是的";
+        let options = ChunkingOptions {
+            chunk_size: 1,
+            overlap: 0,
+            strategy: ChunkingStrategy::Sentence,
+            separators: SeparatorSet::cjk(),
+        };
+
+        assert_eq!(
+            chunker.chunk_text(text, &options),
+            vec![
+                "盘古是阿里云的分布式存储系统",
+                "它支持海量数据",
+                "你了解它吗",
+                "Pangu is fast",
+                "是的"
+            ]
+        );
+
+        // The English default leaves CJK text as one sentence
+        let english = ChunkingOptions {
+            separators: SeparatorSet::english(),
+            ..options
+        };
+        assert_eq!(chunker.chunk_text(text, &english).len(), 2);
+    }
+
+    #[test]
+    fn test_split_sentences_keeps_punctuation() {
+        let chunker = TextChunker::new();
+
+        assert_eq!(
+            chunker.split_sentences("Pi is 3.14. Really?  Yes!\n", &[]),
+            vec!["Pi is 3.14.", "Really?", "Yes!"]
+        );
+        assert_eq!(
+            chunker.split_sentences("盘古很快。它可扩展", &SeparatorSet::cjk()),
+            vec!["盘古很快。", "它可扩展"]
+        );
+    }
+
+    #[test]
+    fn test_markdown_separators() {
+        let chunker = TextChunker::new();
+        let text = "# Pangu\n- replicas: 3\n- chunk size: 64MB\n\nIt is fast. It scales.";
+        let options = ChunkingOptions {
+            chunk_size: 1,
+            overlap: 0,
+            strategy: ChunkingStrategy::Sentence,
+            separators: SeparatorSet::markdown(),
+        };
+
+        assert_eq!(
+            chunker.chunk_text(text, &options),
+            vec![
+                "# Pangu",
+                "- replicas: 3",
+                "- chunk size: 64MB",
+                "It is fast",
+                "It scales."
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recursive_chunking_respects_boundaries() {
+        let chunker = TextChunker::new();
+        let text = "Pangu is a storage system.\nIt keeps three replicas.\n\n\
+                    Fuxi schedules jobs. It runs on every machine.";
+        let options = ChunkingOptions {
+            chunk_size: 40,
+            overlap: 0,
+            strategy: ChunkingStrategy::Recursive,
+            separators: SeparatorSet::markdown(),
+        };
+
+        assert_eq!(
+            chunker.chunk_text(text, &options),
+            vec![
+                "Pangu is a storage system.",
+                "It keeps three replicas.",
+                "Fuxi schedules jobs.",
+                "It runs on every machine."
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recursive_chunking_defaults_to_paragraphs_then_lines() {
+        let chunker = TextChunker::new();
+        let text = "Pangu keeps three replicas\nof every chunk\n\n\
+                    Fuxi schedules jobs\non every machine";
+        let options = ChunkingOptions {
+            chunk_size: 30,
+            overlap: 0,
+            strategy: ChunkingStrategy::Recursive,
+            ..ChunkingOptions::default()
+        };
+
+        assert_eq!(
+            chunker.chunk_text(text, &options),
+            vec![
+                "Pangu keeps three replicas",
+                "of every chunk",
+                "Fuxi schedules jobs",
+                "on every machine"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recursive_chunking_splits_oversized_word() {
+        let chunker = TextChunker::new();
+        let word = "pneumonoultramicroscopicsilicovolcanoconiosis";
+        let text = format!("A long word: {}. Short end.", word);
+        for overlap in [0, 4] {
+            let options = ChunkingOptions {
+                chunk_size: 10,
+                overlap,
+                strategy: ChunkingStrategy::Recursive,
+                separators: SeparatorSet::markdown(),
+            };
+
+            let chunks = chunker.chunk_text(&text, &options);
+            assert!(chunks.iter().all(|chunk| chunk.len() <= 10), "{:?}", chunks);
+            if overlap == 0 {
+                assert_eq!(chunks.concat().replace(' ', ""), text.replace(' ', ""));
+            }
+        }
+
+        let options = ChunkingOptions {
+            chunk_size: 4,
+            overlap: 0,
+            strategy: ChunkingStrategy::Recursive,
+            ..ChunkingOptions::default()
+        };
+        assert_eq!(
+            chunker.chunk_text("盘古存储", &options),
+            vec!["盘", "古", "存", "储"]
+        );
+    }
+
+    #[test]
+    fn test_markdown_chunks_carry_heading_trail() {
+        let chunker = TextChunker::new();
+        let text = "Preamble text.\n\n\
+                    # Pangu\n\
+                    Pangu is a storage system.\n\n\
+                    ## Replicas\n\
+                    Every chunk has three replicas.\n\n\
+                    ### Placement\n\
+                    Replicas go to different racks.\n\n\
+                    ## Masters\n\
+                    Masters track chunk locations.\n\
+                    #hashtag is not a heading.\n\n\
+                    # Fuxi\n\n\
+                    ## Scheduling\n\
+                    Fuxi schedules jobs.";
+        let options = ChunkingOptions {
+            chunk_size: 200,
+            overlap: 0,
+            strategy: ChunkingStrategy::Markdown,
+            separators: SeparatorSet::markdown(),
+        };
+
+        assert_eq!(
+            chunker.chunk_text(text, &options),
+            vec![
+                "Preamble text.",
+                "# Pangu\n\nPangu is a storage system.",
+                "# Pangu > ## Replicas\n\nEvery chunk has three replicas.",
+                "# Pangu > ## Replicas > ### Placement\n\nReplicas go to different racks.",
+                "# Pangu > ## Masters\n\nMasters track chunk locations.\n#hashtag is not a heading.",
+                "# Fuxi > ## Scheduling\n\nFuxi schedules jobs.",
+            ]
+        );
+
+        // Oversized sections are split further, each piece keeping the trail
+        let small = ChunkingOptions {
+            chunk_size: 50,
+            ..options
+        };
+        let chunks = chunker.chunk_text(
+            "# Pangu\n## Replicas\nEvery chunk has three replicas. They go to different racks.",
+            &small,
+        );
+        assert_eq!(
+            chunks,
+            vec![
+                "# Pangu > ## Replicas\n\nEvery chunk has three",
+                "# Pangu > ## Replicas\n\nreplicas.",
+                "# Pangu > ## Replicas\n\nThey go to different racks.",
+            ]
+        );
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 50));
+    }
+
+    #[test]
+    fn test_markdown_never_splits_code_fences() {
+        let chunker = TextChunker::new();
+        let code = "```rust\n# not a heading\nfn main() {\n\n    println!(\"hello\");\n}\n```";
+        let text = format!(
+            "# Usage\nRun the example:\n\n{}\n\nThen check the output.\n\n~~~\nunclosed\n\n# still code",
+            code
+        );
+        let options = ChunkingOptions {
+            chunk_size: 40,
+            overlap: 0,
+            strategy: ChunkingStrategy::Markdown,
+            separators: SeparatorSet::markdown(),
+        };
+
+        assert_eq!(
+            chunker.chunk_text(&text, &options),
+            vec![
+                "# Usage\n\nRun the example:".to_string(),
+                format!("# Usage\n\n{}", code),
+                "# Usage\n\nThen check the output.".to_string(),
+                "# Usage\n\n~~~\nunclosed\n\n# still code".to_string(),
+            ]
+        );
+    }
 }
@@ -0,0 +1,41 @@
+use anyhow::Result;
+use async_trait::async_trait;
+
+#[cfg(any(feature = "openai", feature = "anthropic"))]
+use crate::utils::llm_wrapper::{LLMOptions, LLMWrapper};
+#[cfg(any(feature = "openai", feature = "anthropic"))]
+use std::sync::Arc;
+
+#[async_trait]
+pub trait Translator: Send + Sync {
+    /// Translate `text` into `target_lang`, e.g. "English" or "en".
+    async fn translate(&self, text: &str, target_lang: &str) -> Result<String>;
+}
+
+/// A [`Translator`] that prompts an LLM at temperature 0.
+#[cfg(any(feature = "openai", feature = "anthropic"))]
+pub struct LLMTranslator {
+    client: Arc<dyn LLMWrapper>,
+}
+
+#[cfg(any(feature = "openai", feature = "anthropic"))]
+impl LLMTranslator {
+    pub fn new(client: Arc<dyn LLMWrapper>) -> Self {
+        Self { client }
+    }
+}
+
+#[cfg(any(feature = "openai", feature = "anthropic"))]
+#[async_trait]
+impl Translator for LLMTranslator {
+    async fn translate(&self, text: &str, target_lang: &str) -> Result<String> {
+        let prompt = format!(
+            "Translate the following text into {}. If it is already in {}, return it unchanged. \
+             Respond with ONLY the translated text.\n\nText:\n{}",
+            target_lang, target_lang, text
+        );
+        let options = LLMOptions::deterministic();
+        let response = self.client.generate_with_options(&prompt, options).await?;
+        Ok(response.content.trim().to_string())
+    }
+}
@@ -1,22 +1,42 @@
-#![cfg(feature = "qdrant")]
-
 use async_trait::async_trait;
-use qdrant_client::Qdrant;
-use qdrant_client::qdrant::{
-    CreateCollectionBuilder, DeletePointsBuilder, Distance, PointStruct, ScoredPoint,
-    SearchPointsBuilder, UpsertPointsBuilder, VectorParamsBuilder,
-};
-use qdrant_client::qdrant::{Value as QdrantValue, value::Kind as QdrantKind};
-
-use serde_json::{Map as SerdeMap, Number as SerdeNumber, Value as SerdeValue, json};
-
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tokio::task::JoinSet;
 use tracing::info;
 
+#[cfg(any(feature = "qdrant", feature = "pgvector"))]
+pub(crate) const DEFAULT_MAX_K: usize = 1000;
+#[cfg(feature = "hnsw")]
+pub use crate::utils::hnsw_vector::{HnswOptions, HnswVectorDB};
+#[cfg(feature = "pgvector")]
+pub use crate::utils::pg_vector::PgVectorDB;
+#[cfg(feature = "qdrant")]
+pub use crate::utils::qdrant::QdrantDB;
+
 #[derive(Debug, Clone)]
 pub struct VectorDBOptions {
     pub collection_name: String,
     pub dimension: usize,
     pub distance_metric: DistanceMetric,
+    /// Payload fields to index for fast filtered search, e.g.
+    /// `("file_metadata.url", PayloadFieldType::Keyword)`. Nested fields use dots.
+    pub payload_indexes: Vec<(String, PayloadFieldType)>,
+    /// Name of the sparse vector records may carry alongside the dense one, e.g.
+    /// `"sparse"` for SPLADE or BM42 weights; `None` for a dense-only collection.
+    pub sparse_vector_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadFieldType {
+    Keyword,
+    Integer,
+    Float,
+    Bool,
+    /// Full-text index
+    Text,
+    Datetime,
 }
 
 #[derive(Debug, Clone)]
@@ -26,11 +46,25 @@ pub enum DistanceMetric {
     DotProduct,
 }
 
-#[derive(Debug, Clone)]
+/// A sparse vector: the non-zero `values` at their `indices`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SparseVector {
+    pub indices: Vec<u32>,
+    pub values: Vec<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct VectorRecord {
     pub id: String,
     pub vector: Vec<f32>,
+    /// Stored only in collections with a `sparse_vector_name`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sparse_vector: Option<SparseVector>,
+    #[serde(default)]
     pub metadata: serde_json::Map<String, serde_json::Value>,
+    /// Similarity score, set on records returned by a search
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f32>,
 }
 
 impl VectorRecord {
@@ -45,168 +79,464 @@ impl VectorRecord {
             .map(|v| v.as_f64().unwrap() as f32)
             .collect();
         let metadata = value.get("metadata").unwrap().as_object().unwrap().clone();
+        let score = value
+            .get("score")
+            .and_then(|v| v.as_f64())
+            .map(|v| v as f32);
+        let sparse_vector = value
+            .get("sparse_vector")
+            .and_then(|v| serde_json::from_value(v.clone()).ok());
         Self {
             id,
             vector,
+            sparse_vector,
             metadata,
+            score,
         }
     }
 
     pub fn to_value(&self) -> serde_json::Value {
-        json!({
+        let mut value = json!({
             "id": self.id,
             "vector": self.vector,
-            "metadata": self.metadata
-        })
+            "metadata": self.metadata,
+            "score": self.score
+        });
+        if let Some(sparse_vector) = &self.sparse_vector {
+            value["sparse_vector"] = json!(sparse_vector);
+        }
+        value
     }
 }
 
-fn qdrant_value_to_serde_json(q_val: QdrantValue) -> SerdeValue {
-    match q_val.kind {
-        Some(QdrantKind::NullValue(_)) => SerdeValue::Null,
-        Some(QdrantKind::BoolValue(b)) => SerdeValue::Bool(b),
-        Some(QdrantKind::DoubleValue(d)) => {
-            SerdeNumber::from_f64(d).map_or(SerdeValue::Null, SerdeValue::Number)
-        }
-        Some(QdrantKind::IntegerValue(i)) => SerdeValue::Number(i.into()),
-        Some(QdrantKind::StringValue(s)) => SerdeValue::String(s),
-        Some(QdrantKind::ListValue(list_value)) => {
-            let serde_list: Vec<SerdeValue> = list_value
-                .values
-                .into_iter()
-                .map(qdrant_value_to_serde_json)
-                .collect();
-            SerdeValue::Array(serde_list)
-        }
-        Some(QdrantKind::StructValue(struct_value)) => {
-            let mut serde_map = SerdeMap::new();
-            for (key, val) in struct_value.fields {
-                serde_map.insert(key, qdrant_value_to_serde_json(val));
-            }
-            SerdeValue::Object(serde_map)
-        }
-        None => SerdeValue::Null, // Treat absence of kind as Null
+/// One page of a full-collection scan.
+#[derive(Debug, Clone)]
+pub struct ScrollPage {
+    pub records: Vec<VectorRecord>,
+    /// Pass as `offset` to fetch the next page; `None` once the scan is complete.
+    pub next_offset: Option<String>,
+}
+
+#[async_trait]
+pub trait VectorDB: Send + Sync {
+    async fn insert(&self, records: Vec<VectorRecord>) -> anyhow::Result<()>;
+    async fn search(&self, query: Vec<f32>, k: usize) -> anyhow::Result<Vec<VectorRecord>>;
+    async fn delete(&self, ids: Vec<String>) -> anyhow::Result<()>;
+
+    /// The number of dimensions the collection's vectors must have, if the db
+    /// knows it, so callers can check vectors before inserting them.
+    fn dimension(&self) -> Option<usize> {
+        None
+    }
+
+    /// The metric search scores are computed with, if the db knows it, so callers
+    /// can tell whether a higher score is closer (cosine, dot product) or farther
+    /// (euclidean distance).
+    fn distance_metric(&self) -> Option<DistanceMetric> {
+        None
+    }
+
+    /// Page through every record in the collection, starting from `offset`
+    /// (`None` for the first page).
+    #[allow(unused_variables)]
+    async fn scroll(&self, offset: Option<String>, limit: usize) -> anyhow::Result<ScrollPage> {
+        Err(anyhow::anyhow!("scroll is not supported by this vector db"))
+    }
+
+    /// Search with a dense and a sparse query at once, fusing the two rankings.
+    #[allow(unused_variables)]
+    async fn search_hybrid(
+        &self,
+        dense: Vec<f32>,
+        sparse: SparseVector,
+        k: usize,
+    ) -> anyhow::Result<Vec<VectorRecord>> {
+        Err(anyhow::anyhow!(
+            "hybrid search is not supported by this vector db"
+        ))
     }
 }
 
-impl VectorRecord {
-    pub fn from_scored_point(point: ScoredPoint) -> Option<Self> {
-        let id_str = match point.id {
-            Some(point_id) => match point_id.point_id_options {
-                Some(qdrant_client::qdrant::point_id::PointIdOptions::Num(n)) => n.to_string(),
-                Some(qdrant_client::qdrant::point_id::PointIdOptions::Uuid(s)) => s,
-                None => return None,
-            },
-            None => return None,
-        };
-        let vector_data = match point.vectors {
-            Some(vector) => match vector.vectors_options {
-                Some(qdrant_client::qdrant::vectors_output::VectorsOptions::Vector(v)) => v.data,
-                _ => return None,
-            },
-            None => return None,
-        };
-        // 3. Convert Payload
-        let metadata_map: SerdeMap<String, SerdeValue> = point
-            .payload
-            .into_iter()
-            .map(|(key, q_val)| (key, qdrant_value_to_serde_json(q_val)))
-            .collect();
+/// Picks the collection a record is inserted into.
+pub type CollectionSelector = Box<dyn Fn(&VectorRecord) -> String + Send + Sync>;
 
-        Some(VectorRecord {
-            id: id_str,
-            vector: vector_data,
-            metadata: metadata_map,
-        })
+/// Queries several collections as one. `search` fans out to every collection
+/// concurrently and merges results by normalized score; `insert` routes each record
+/// with the selector; `delete` is sent to every collection, as ids alone don't say
+/// where a record lives.
+pub struct MultiCollectionVectorDB {
+    collections: Vec<(String, Arc<dyn VectorDB>, DistanceMetric)>,
+    selector: CollectionSelector,
+}
+
+impl MultiCollectionVectorDB {
+    pub fn new(selector: CollectionSelector) -> Self {
+        Self {
+            collections: Vec::new(),
+            selector,
+        }
+    }
+
+    pub fn add_collection(&mut self, name: &str, db: Arc<dyn VectorDB>, metric: DistanceMetric) {
+        self.collections.push((name.to_string(), db, metric));
+    }
+
+    /// Map a raw score onto a common higher-is-better scale so results from
+    /// collections with different metrics can be ranked together.
+    fn normalize_score(score: f32, metric: &DistanceMetric) -> f32 {
+        match metric {
+            DistanceMetric::Cosine => (score + 1.0) / 2.0,
+            DistanceMetric::Euclidean => 1.0 / (1.0 + score),
+            DistanceMetric::DotProduct => score,
+        }
     }
 }
 
 #[async_trait]
-pub trait VectorDB {
-    async fn insert(&self, records: Vec<VectorRecord>) -> anyhow::Result<()>;
-    async fn search(&self, query: Vec<f32>, k: usize) -> anyhow::Result<Vec<VectorRecord>>;
-    async fn delete(&self, ids: Vec<String>) -> anyhow::Result<()>;
+impl VectorDB for MultiCollectionVectorDB {
+    async fn insert(&self, records: Vec<VectorRecord>) -> anyhow::Result<()> {
+        let mut routed: HashMap<String, Vec<VectorRecord>> = HashMap::new();
+        for record in records {
+            routed
+                .entry((self.selector)(&record))
+                .or_default()
+                .push(record);
+        }
+
+        for (name, records) in routed {
+            let (_, db, _) = self
+                .collections
+                .iter()
+                .find(|(collection, _, _)| *collection == name)
+                .ok_or_else(|| anyhow::anyhow!("Unknown collection: {}", name))?;
+            info!("Inserting {} records into {}", records.len(), name);
+            db.insert(records).await?;
+        }
+        Ok(())
+    }
+
+    async fn search(&self, query: Vec<f32>, k: usize) -> anyhow::Result<Vec<VectorRecord>> {
+        let mut searches = JoinSet::new();
+        for (_, db, metric) in &self.collections {
+            let db = db.clone();
+            let metric = metric.clone();
+            let query = query.clone();
+            searches.spawn(async move { db.search(query, k).await.map(|r| (metric, r)) });
+        }
+
+        let mut results = Vec::new();
+        while let Some(searched) = searches.join_next().await {
+            let (metric, records) = searched??;
+            results.extend(records.into_iter().map(|mut record| {
+                record.score = record
+                    .score
+                    .map(|score| Self::normalize_score(score, &metric));
+                record
+            }));
+        }
+
+        results.sort_by(|a, b| {
+            b.score
+                .unwrap_or(f32::NEG_INFINITY)
+                .total_cmp(&a.score.unwrap_or(f32::NEG_INFINITY))
+        });
+        results.truncate(k);
+        info!("Merged results len: {:?}", results.len());
+
+        Ok(results)
+    }
+
+    async fn delete(&self, ids: Vec<String>) -> anyhow::Result<()> {
+        for (_, db, _) in &self.collections {
+            db.delete(ids.clone()).await?;
+        }
+        Ok(())
+    }
 }
 
-pub struct QdrantDB {
-    client: Qdrant,
+/// A vector db held in memory, for tests and small demos that shouldn't need a
+/// running Qdrant. Searches are exact, scored with the options' distance metric the
+/// way Qdrant scores them: cosine similarity and dot product rank highest first,
+/// euclidean distance lowest first.
+pub struct InMemoryVectorDB {
     options: VectorDBOptions,
+    records: RwLock<Vec<VectorRecord>>,
 }
 
-impl QdrantDB {
-    pub async fn new(
-        db_url: String,
-        api_key: Option<String>,
-        options: VectorDBOptions,
-    ) -> anyhow::Result<Self> {
-        let client = match api_key {
-            Some(api_key) => Qdrant::from_url(db_url.as_str()).api_key(api_key).build()?,
-            None => Qdrant::from_url(db_url.as_str()).build()?,
-        };
+impl InMemoryVectorDB {
+    pub fn new(options: VectorDBOptions) -> Self {
+        Self {
+            options,
+            records: RwLock::new(Vec::new()),
+        }
+    }
 
-        // Create collection if it doesn't exist
-        let collections = client.list_collections().await?;
-        if !collections
-            .collections
-            .iter()
-            .any(|c| c.name == options.collection_name)
-        {
-            let distance = match options.distance_metric {
-                DistanceMetric::Cosine => Distance::Cosine,
-                DistanceMetric::Euclidean => Distance::Euclid,
-                DistanceMetric::DotProduct => Distance::Dot,
-            };
-            let request = CreateCollectionBuilder::new(options.collection_name.clone())
-                .vectors_config(VectorParamsBuilder::new(options.dimension as u64, distance));
-            client.create_collection(request).await?;
+    pub fn len(&self) -> usize {
+        self.records.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn score(&self, a: &[f32], b: &[f32]) -> f32 {
+        score(&self.options.distance_metric, a, b)
+    }
+
+    fn check_dimension(&self, vector: &[f32]) -> anyhow::Result<()> {
+        check_dimension(&self.options, vector)
+    }
+}
+
+/// The score of `a` against `b` under `metric`, the way Qdrant scores it.
+pub(crate) fn score(metric: &DistanceMetric, a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    match metric {
+        DistanceMetric::Cosine => {
+            let norms = a.iter().map(|x| x * x).sum::<f32>().sqrt()
+                * b.iter().map(|y| y * y).sum::<f32>().sqrt();
+            if norms == 0.0 { 0.0 } else { dot / norms }
         }
+        DistanceMetric::Euclidean => a
+            .iter()
+            .zip(b)
+            .map(|(x, y)| (x - y).powi(2))
+            .sum::<f32>()
+            .sqrt(),
+        DistanceMetric::DotProduct => dot,
+    }
+}
 
-        Ok(Self { client, options })
+pub(crate) fn check_dimension(options: &VectorDBOptions, vector: &[f32]) -> anyhow::Result<()> {
+    if vector.len() != options.dimension {
+        return Err(anyhow::anyhow!(
+            "Vector has {} dimensions, collection '{}' expects {}",
+            vector.len(),
+            options.collection_name,
+            options.dimension
+        ));
     }
+    Ok(())
 }
 
 #[async_trait]
-impl VectorDB for QdrantDB {
-    async fn insert(&self, records: Vec<VectorRecord>) -> anyhow::Result<()> {
-        let points: Vec<PointStruct> = records
-            .into_iter()
-            .map(|record| PointStruct::new(record.id, record.vector, record.metadata))
-            .collect();
-        let points_request = UpsertPointsBuilder::new(&self.options.collection_name, points);
+impl VectorDB for InMemoryVectorDB {
+    fn dimension(&self) -> Option<usize> {
+        Some(self.options.dimension)
+    }
+
+    fn distance_metric(&self) -> Option<DistanceMetric> {
+        Some(self.options.distance_metric.clone())
+    }
 
-        info!("Inserting points into Qdrant");
-        self.client.upsert_points(points_request).await?;
+    /// Records with an id that is already stored replace the stored record.
+    async fn insert(&self, records: Vec<VectorRecord>) -> anyhow::Result<()> {
+        for record in &records {
+            self.check_dimension(&record.vector)?;
+        }
+        let mut stored = self.records.write().unwrap();
+        for record in records {
+            match stored.iter_mut().find(|existing| existing.id == record.id) {
+                Some(existing) => *existing = record,
+                None => stored.push(record),
+            }
+        }
         Ok(())
     }
 
     async fn search(&self, query: Vec<f32>, k: usize) -> anyhow::Result<Vec<VectorRecord>> {
-        info!(
-            "Searching points in Qdrant, collection: {}",
-            self.options.collection_name
-        );
-        let response = self
-            .client
-            .search_points(
-                SearchPointsBuilder::new(&self.options.collection_name, query, k as u64)
-                    .with_payload(true)
-                    .with_vectors(true),
-            )
-            .await?;
-        let results = response
-            .result
-            .into_iter()
-            .filter_map(VectorRecord::from_scored_point)
-            .collect::<Vec<_>>();
-        info!("Retrieved results len: {:?}", results.len());
+        self.check_dimension(&query)?;
+        let mut results: Vec<VectorRecord> = self
+            .records
+            .read()
+            .unwrap()
+            .iter()
+            .map(|record| VectorRecord {
+                score: Some(self.score(&record.vector, &query)),
+                ..record.clone()
+            })
+            .collect();
 
+        let ascending = matches!(self.options.distance_metric, DistanceMetric::Euclidean);
+        results.sort_by(|a, b| {
+            let (a, b) = (a.score.unwrap(), b.score.unwrap());
+            if ascending {
+                a.total_cmp(&b)
+            } else {
+                b.total_cmp(&a)
+            }
+        });
+        results.truncate(k);
         Ok(results)
     }
 
     async fn delete(&self, ids: Vec<String>) -> anyhow::Result<()> {
-        info!("Deleting points from Qdrant");
-        self.client
-            .delete_points(DeletePointsBuilder::new(&self.options.collection_name).points(ids))
-            .await?;
+        self.records
+            .write()
+            .unwrap()
+            .retain(|record| !ids.contains(&record.id));
         Ok(())
     }
+
+    /// Pages in insertion order; offsets are positions in that order.
+    async fn scroll(&self, offset: Option<String>, limit: usize) -> anyhow::Result<ScrollPage> {
+        let start = match offset {
+            Some(offset) => offset
+                .parse::<usize>()
+                .map_err(|_| anyhow::anyhow!("Invalid scroll offset: {}", offset))?,
+            None => 0,
+        };
+        let stored = self.records.read().unwrap();
+        let end = start.saturating_add(limit).min(stored.len());
+        Ok(ScrollPage {
+            records: stored.get(start..end).unwrap_or_default().to_vec(),
+            next_offset: (end < stored.len()).then(|| end.to_string()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Map as SerdeMap;
+
+    fn record(id: &str, tenant: &str, vector: Vec<f32>) -> VectorRecord {
+        VectorRecord {
+            id: id.to_string(),
+            vector,
+            sparse_vector: None,
+            metadata: SerdeMap::from_iter(vec![("tenant".to_string(), json!(tenant))]),
+            score: None,
+        }
+    }
+
+    fn in_memory(distance_metric: DistanceMetric) -> InMemoryVectorDB {
+        InMemoryVectorDB::new(VectorDBOptions {
+            collection_name: "test".to_string(),
+            dimension: 2,
+            distance_metric,
+            payload_indexes: Vec::new(),
+            sparse_vector_name: None,
+        })
+    }
+
+    async fn search_ids(db: &InMemoryVectorDB, query: Vec<f32>, k: usize) -> Vec<String> {
+        db.search(query, k)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|record| record.id)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_search_respects_metric() {
+        let records = vec![
+            record("near", "a", vec![1.0, 0.1]),
+            record("long", "a", vec![10.0, 10.0]),
+            record("opposite", "a", vec![-1.0, 0.0]),
+        ];
+        let query = vec![1.0, 0.0];
+
+        let cosine = in_memory(DistanceMetric::Cosine);
+        cosine.insert(records.clone()).await.unwrap();
+        assert_eq!(
+            search_ids(&cosine, query.clone(), 3).await,
+            vec!["near", "long", "opposite"]
+        );
+        let top = cosine.search(query.clone(), 1).await.unwrap();
+        assert!((top[0].score.unwrap() - 0.995).abs() < 1e-3);
+
+        let dot = in_memory(DistanceMetric::DotProduct);
+        dot.insert(records.clone()).await.unwrap();
+        assert_eq!(
+            search_ids(&dot, query.clone(), 2).await,
+            vec!["long", "near"]
+        );
+
+        let euclidean = in_memory(DistanceMetric::Euclidean);
+        euclidean.insert(records).await.unwrap();
+        assert_eq!(
+            search_ids(&euclidean, query, 3).await,
+            vec!["near", "opposite", "long"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_insert_delete_and_scroll() {
+        let db = in_memory(DistanceMetric::Cosine);
+        db.insert(vec![
+            record("a", "t", vec![1.0, 0.0]),
+            record("b", "t", vec![0.0, 1.0]),
+            record("c", "t", vec![1.0, 1.0]),
+        ])
+        .await
+        .unwrap();
+        db.insert(vec![record("a", "t", vec![0.0, 2.0])])
+            .await
+            .unwrap();
+        assert_eq!(db.len(), 3);
+        assert_eq!(search_ids(&db, vec![0.0, 1.0], 2).await.len(), 2);
+        assert!(db.insert(vec![record("d", "t", vec![1.0])]).await.is_err());
+
+        db.delete(vec!["b".to_string()]).await.unwrap();
+        let first = db.scroll(None, 1).await.unwrap();
+        assert_eq!(first.records[0].vector, vec![0.0, 2.0]);
+        let second = db.scroll(first.next_offset, 1).await.unwrap();
+        assert_eq!(second.records[0].id, "c");
+        assert_eq!(second.next_offset, None);
+    }
+
+    #[tokio::test]
+    async fn test_multi_collection_search_merges_top_k() {
+        let db_a = Arc::new(in_memory(DistanceMetric::Cosine));
+        let db_b = Arc::new(in_memory(DistanceMetric::Cosine));
+        let mut db = MultiCollectionVectorDB::new(Box::new(|record: &VectorRecord| {
+            record.metadata["tenant"].as_str().unwrap().to_string()
+        }));
+        db.add_collection("a", db_a.clone(), DistanceMetric::Cosine);
+        db.add_collection("b", db_b.clone(), DistanceMetric::Cosine);
+
+        db.insert(vec![
+            record("a1", "a", vec![1.0, 0.0]),
+            record("a2", "a", vec![0.0, 1.0]),
+            record("a3", "a", vec![0.6, 0.8]),
+            record("b1", "b", vec![0.8, 0.6]),
+            record("b2", "b", vec![-1.0, 0.0]),
+        ])
+        .await
+        .unwrap();
+        assert_eq!(db_a.len(), 3);
+        assert_eq!(db_b.len(), 2);
+
+        let results = db.search(vec![1.0, 0.0], 3).await.unwrap();
+        let ids: Vec<&str> = results.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["a1", "b1", "a3"]);
+        assert_eq!(results[0].score, Some(1.0));
+
+        db.delete(vec!["a1".to_string(), "b1".to_string()])
+            .await
+            .unwrap();
+        let results = db.search(vec![1.0, 0.0], 1).await.unwrap();
+        assert_eq!(results[0].id, "a3");
+    }
+
+    #[test]
+    fn test_sparse_vector_round_trips_through_value() {
+        let mut with_sparse = record("a", "t", vec![1.0, 0.0]);
+        with_sparse.sparse_vector = Some(SparseVector {
+            indices: vec![3, 17],
+            values: vec![0.5, 1.25],
+        });
+        let value = with_sparse.to_value();
+        assert_eq!(value["sparse_vector"]["indices"], json!([3, 17]));
+        assert_eq!(VectorRecord::parse_by_value(&value), with_sparse);
+
+        let dense_only = record("b", "t", vec![0.0, 1.0]);
+        assert!(dense_only.to_value().get("sparse_vector").is_none());
+        assert_eq!(
+            VectorRecord::parse_by_value(&dense_only.to_value()),
+            dense_only
+        );
+    }
 }
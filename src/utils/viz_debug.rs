@@ -1,6 +1,8 @@
 #![cfg(feature = "debug")]
 use std::fmt::Debug;
 
+use crate::utils::vector_db::VectorRecord;
+
 pub trait DebugVisualizer {
     fn visualize<T: Debug>(&self, data: &T) -> String;
     fn visualize_flow(&self, flow_data: &[u8]) -> String;
@@ -34,3 +36,131 @@ impl DebugVisualizer for GraphDebugVisualizer {
         "Flow graph visualization not implemented".to_string()
     }
 }
+
+/// Renders retrieved `VectorRecord`s as a ranked table, highest score first.
+pub struct RecordsDebugVisualizer {
+    pub max_text_len: usize,
+}
+
+impl Default for RecordsDebugVisualizer {
+    fn default() -> Self {
+        Self { max_text_len: 80 }
+    }
+}
+
+impl RecordsDebugVisualizer {
+    pub fn visualize_records(&self, records: &[VectorRecord]) -> String {
+        let mut ranked: Vec<&VectorRecord> = records.iter().collect();
+        ranked.sort_by(|a, b| {
+            b.score
+                .unwrap_or(f32::NEG_INFINITY)
+                .total_cmp(&a.score.unwrap_or(f32::NEG_INFINITY))
+        });
+
+        let headers = ["rank", "score", "id", "text"].map(|h| h.to_string());
+        let rows: Vec<[String; 4]> = ranked
+            .iter()
+            .enumerate()
+            .map(|(i, record)| {
+                [
+                    (i + 1).to_string(),
+                    record
+                        .score
+                        .map_or("-".to_string(), |score| format!("{:.4}", score)),
+                    record.id.clone(),
+                    self.truncate(
+                        record
+                            .metadata
+                            .get("text")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or(""),
+                    ),
+                ]
+            })
+            .collect();
+
+        let mut widths = headers.clone().map(|h| h.chars().count());
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let format_row = |row: &[String; 4]| {
+            row.iter()
+                .zip(&widths)
+                .map(|(cell, w)| format!("{:<width$}", cell, width = w))
+                .collect::<Vec<_>>()
+                .join(" | ")
+                .trim_end()
+                .to_string()
+        };
+
+        let mut lines = vec![format_row(&headers)];
+        lines.push(
+            widths
+                .iter()
+                .map(|w| "-".repeat(*w))
+                .collect::<Vec<_>>()
+                .join("-+-"),
+        );
+        lines.extend(rows.iter().map(format_row));
+        lines.join("\n")
+    }
+
+    fn truncate(&self, text: &str) -> String {
+        let text = text.replace('\n', " ");
+        if text.chars().count() <= self.max_text_len {
+            text
+        } else {
+            let truncated: String = text.chars().take(self.max_text_len).collect();
+            format!("{}...", truncated)
+        }
+    }
+}
+
+impl DebugVisualizer for RecordsDebugVisualizer {
+    fn visualize<T: Debug>(&self, data: &T) -> String {
+        format!("{:?}", data)
+    }
+
+    #[allow(unused_variables)]
+    fn visualize_flow(&self, flow_data: &[u8]) -> String {
+        "Flow visualization not supported for records".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn record(id: &str, score: f32, text: &str) -> VectorRecord {
+        VectorRecord {
+            id: id.to_string(),
+            vector: vec![],
+            sparse_vector: None,
+            metadata: serde_json::Map::from_iter(vec![("text".to_string(), json!(text))]),
+            score: Some(score),
+        }
+    }
+
+    #[test]
+    fn test_visualize_records_ranked_and_truncated() {
+        let visualizer = RecordsDebugVisualizer { max_text_len: 10 };
+        let records = vec![
+            record("low", 0.25, "short"),
+            record("high", 0.9, "a rather long chunk of text"),
+            record("mid", 0.5, "medium"),
+        ];
+
+        let output = visualizer.visualize_records(&records);
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("rank"));
+        assert!(lines[2].starts_with("1    | 0.9000 | high | a rather l..."));
+        assert!(lines[3].starts_with("2    | 0.5000 | mid  | medium"));
+        assert!(lines[4].starts_with("3    | 0.2500 | low  | short"));
+    }
+}
@@ -1,8 +1,10 @@
 #![cfg(feature = "websearch")]
 
+use crate::error::Error;
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use tracing::info;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,7 +34,7 @@ pub struct SearchOptions {
 pub struct GoogleSearcher {
     api_key: String,
     search_engine_id: String,
-    client: Client,
+    client: Arc<Client>,
 }
 
 impl GoogleSearcher {
@@ -40,9 +42,15 @@ impl GoogleSearcher {
         Self {
             api_key,
             search_engine_id,
-            client: Client::new(),
+            client: Arc::new(Client::new()),
         }
     }
+
+    /// Send requests through `client` instead of a default one.
+    pub fn with_client(mut self, client: Arc<Client>) -> Self {
+        self.client = client;
+        self
+    }
 }
 
 #[async_trait]
@@ -73,8 +81,8 @@ impl WebSearcher for GoogleSearcher {
         }
 
         info!("Sending request to Google Search API");
-        let response = self.client.get(&url).send().await?;
-        let search_response: serde_json::Value = response.json().await?;
+        let response = self.client.get(&url).send().await.map_err(Error::from)?;
+        let search_response: serde_json::Value = response.json().await.map_err(Error::from)?;
         let default_val: Vec<serde_json::Value> = vec![];
         let items = search_response["items"].as_array().unwrap_or(&default_val);
         let results = items
@@ -90,10 +98,323 @@ impl WebSearcher for GoogleSearcher {
     }
 }
 
+pub struct BingSearcher {
+    api_key: String,
+    endpoint: String,
+    client: Arc<Client>,
+}
+
+impl BingSearcher {
+    pub fn new(api_key: String) -> Self {
+        Self {
+            api_key,
+            endpoint: "https://api.bing.microsoft.com/v7.0/search".to_string(),
+            client: Arc::new(Client::new()),
+        }
+    }
+
+    /// Send requests through `client` instead of a default one.
+    pub fn with_client(mut self, client: Arc<Client>) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Query `endpoint` instead of the public Bing Web Search API.
+    pub fn with_endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = endpoint.to_string();
+        self
+    }
+}
+
+#[async_trait]
+impl WebSearcher for BingSearcher {
+    async fn search(&self, query: &str) -> anyhow::Result<Vec<SearchResult>> {
+        self.search_with_options(query, SearchOptions::default())
+            .await
+    }
+
+    async fn search_with_options(
+        &self,
+        query: &str,
+        options: SearchOptions,
+    ) -> anyhow::Result<Vec<SearchResult>> {
+        let mut params = vec![("q", query.to_string())];
+        if let Some(max_results) = options.max_results {
+            params.push(("count", max_results.to_string()));
+        }
+        // A market needs both parts, e.g. "en-US"
+        if let (Some(lang), Some(region)) = (&options.language, &options.region) {
+            params.push(("mkt", format!("{}-{}", lang, region.to_uppercase())));
+        }
+        if let Some(lang) = options.language {
+            params.push(("setLang", lang));
+        }
+        if let Some(region) = options.region {
+            params.push(("cc", region));
+        }
+
+        info!("Sending request to Bing Web Search API");
+        let response = self
+            .client
+            .get(&self.endpoint)
+            .header("Ocp-Apim-Subscription-Key", &self.api_key)
+            .query(&params)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(Error::from)?;
+        let search_response: serde_json::Value = response.json().await.map_err(Error::from)?;
+        let default_val: Vec<serde_json::Value> = vec![];
+        let items = search_response["webPages"]["value"]
+            .as_array()
+            .unwrap_or(&default_val);
+        let results = items
+            .iter()
+            .map(|item| SearchResult {
+                title: item["name"].as_str().unwrap_or("").to_string(),
+                url: item["url"].as_str().unwrap_or("").to_string(),
+                snippet: item["snippet"].as_str().unwrap_or("").to_string(),
+            })
+            .collect();
+
+        Ok(results)
+    }
+}
+
+/// Searches with the DuckDuckGo Instant Answer API, which needs no key. It returns
+/// the topic's abstract and related topics rather than a full page of web results.
+pub struct DuckDuckGoSearcher {
+    endpoint: String,
+    client: Arc<Client>,
+}
+
+impl DuckDuckGoSearcher {
+    pub fn new() -> Self {
+        Self {
+            endpoint: "https://api.duckduckgo.com/".to_string(),
+            client: Arc::new(Client::new()),
+        }
+    }
+
+    /// Send requests through `client` instead of a default one.
+    pub fn with_client(mut self, client: Arc<Client>) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Query `endpoint` instead of the public Instant Answer API.
+    pub fn with_endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = endpoint.to_string();
+        self
+    }
+}
+
+impl Default for DuckDuckGoSearcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Related topics are either results or named groups of results.
+fn collect_topics(topics: &[serde_json::Value], results: &mut Vec<SearchResult>) {
+    for topic in topics {
+        if let Some(nested) = topic["Topics"].as_array() {
+            collect_topics(nested, results);
+            continue;
+        }
+        let (Some(text), Some(url)) = (topic["Text"].as_str(), topic["FirstURL"].as_str()) else {
+            continue;
+        };
+        // The text reads "Title - description" when the topic has one
+        let title = text.split_once(" - ").map_or(text, |(title, _)| title);
+        results.push(SearchResult {
+            title: title.to_string(),
+            url: url.to_string(),
+            snippet: text.to_string(),
+        });
+    }
+}
+
+#[async_trait]
+impl WebSearcher for DuckDuckGoSearcher {
+    async fn search(&self, query: &str) -> anyhow::Result<Vec<SearchResult>> {
+        self.search_with_options(query, SearchOptions::default())
+            .await
+    }
+
+    /// `max_results` truncates the results, as the API has no count. `region` and
+    /// `language` select the region, e.g. "us-en"; without a region they are ignored.
+    async fn search_with_options(
+        &self,
+        query: &str,
+        options: SearchOptions,
+    ) -> anyhow::Result<Vec<SearchResult>> {
+        let mut params = vec![
+            ("q", query.to_string()),
+            ("format", "json".to_string()),
+            ("no_html", "1".to_string()),
+            ("no_redirect", "1".to_string()),
+        ];
+        if let Some(region) = options.region {
+            let lang = options.language.as_deref().unwrap_or("en");
+            params.push(("kl", format!("{}-{}", region, lang).to_lowercase()));
+        }
+
+        info!("Sending request to DuckDuckGo Instant Answer API");
+        let response = self
+            .client
+            .get(&self.endpoint)
+            .query(&params)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(Error::from)?;
+        let search_response: serde_json::Value = response.json().await.map_err(Error::from)?;
+
+        let mut results = Vec::new();
+        let abstract_text = search_response["AbstractText"].as_str().unwrap_or("");
+        let abstract_url = search_response["AbstractURL"].as_str().unwrap_or("");
+        if !abstract_text.is_empty() && !abstract_url.is_empty() {
+            results.push(SearchResult {
+                title: search_response["Heading"]
+                    .as_str()
+                    .unwrap_or("")
+                    .to_string(),
+                url: abstract_url.to_string(),
+                snippet: abstract_text.to_string(),
+            });
+        }
+        if let Some(topics) = search_response["RelatedTopics"].as_array() {
+            collect_topics(topics, &mut results);
+        }
+        if let Some(max_results) = options.max_results {
+            results.truncate(max_results);
+        }
+
+        Ok(results)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::env;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::oneshot;
+
+    /// Serve `body` as JSON to one request, sending the request head back through
+    /// the returned receiver.
+    async fn serve_json(body: serde_json::Value) -> (String, oneshot::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = oneshot::channel();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 4096];
+            let n = socket.read(&mut buf).await.unwrap();
+            let _ = tx.send(String::from_utf8_lossy(&buf[..n]).to_string());
+            let body = body.to_string();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+        (format!("http://{}/search", addr), rx)
+    }
+
+    #[tokio::test]
+    async fn test_bing_searcher_sends_key_and_options() {
+        let (endpoint, request) = serve_json(serde_json::json!({
+            "webPages": {"value": [
+                {"name": "Pangu", "url": "https://example.com/pangu", "snippet": "A distributed file system"},
+                {"name": "Fuxi", "url": "https://example.com/fuxi", "snippet": "A scheduler"},
+            ]}
+        }))
+        .await;
+        let searcher = BingSearcher::new("bing-key".to_string()).with_endpoint(&endpoint);
+
+        let results = searcher
+            .search_with_options(
+                "pangu file system",
+                SearchOptions {
+                    max_results: Some(2),
+                    language: Some("en".to_string()),
+                    region: Some("us".to_string()),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].title, "Pangu");
+        assert_eq!(results[0].url, "https://example.com/pangu");
+        assert_eq!(results[1].snippet, "A scheduler");
+
+        let request = request.await.unwrap().to_lowercase();
+        assert!(request.contains("ocp-apim-subscription-key: bing-key"));
+        let request_line = request.lines().next().unwrap();
+        for param in [
+            "q=pangu+file+system",
+            "count=2",
+            "mkt=en-us",
+            "setlang=en",
+            "cc=us",
+        ] {
+            assert!(
+                request_line.contains(param),
+                "{} in {}",
+                param,
+                request_line
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_duckduckgo_searcher_flattens_topics() {
+        let (endpoint, request) = serve_json(serde_json::json!({
+            "Heading": "Pangu",
+            "AbstractText": "Pangu is a distributed file system.",
+            "AbstractURL": "https://example.com/pangu",
+            "RelatedTopics": [
+                {"Text": "Fuxi - A cluster scheduler", "FirstURL": "https://example.com/fuxi"},
+                {"Name": "Storage", "Topics": [
+                    {"Text": "HDFS - The Hadoop file system", "FirstURL": "https://example.com/hdfs"},
+                    {"Text": "GFS - The Google file system", "FirstURL": "https://example.com/gfs"},
+                ]},
+            ]
+        }))
+        .await;
+        let searcher = DuckDuckGoSearcher::new().with_endpoint(&endpoint);
+
+        let results = searcher
+            .search_with_options(
+                "pangu",
+                SearchOptions {
+                    max_results: Some(3),
+                    language: Some("zh".to_string()),
+                    region: Some("CN".to_string()),
+                },
+            )
+            .await
+            .unwrap();
+        let titles: Vec<&str> = results.iter().map(|r| r.title.as_str()).collect();
+        assert_eq!(titles, vec!["Pangu", "Fuxi", "HDFS"]);
+        assert_eq!(results[0].snippet, "Pangu is a distributed file system.");
+        assert_eq!(results[2].url, "https://example.com/hdfs");
+
+        let request = request.await.unwrap();
+        let request_line = request.lines().next().unwrap();
+        for param in ["q=pangu", "format=json", "kl=cn-zh"] {
+            assert!(
+                request_line.contains(param),
+                "{} in {}",
+                param,
+                request_line
+            );
+        }
+    }
 
     #[tokio::test]
     #[ignore = "E2E case, requires API keys"]